@@ -0,0 +1,13 @@
+//! Fuzz parsing of responses to the `CreateSession` command (i.e. the first
+//! message received from the HSM while opening a channel)
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yubihsm::{authentication, session::Handshake, Credentials};
+
+fuzz_target!(|response: &[u8]| {
+    // Use a static key: deriving one from a password is needlessly slow here
+    let credentials = Credentials::new(1, authentication::Key::new([0x42; 32]));
+    let _ = Handshake::new(&credentials).finish(response.to_vec());
+});
@@ -10,5 +10,8 @@ mod algorithm;
 pub(crate) mod commands;
 mod public_key;
 
-pub use self::{algorithm::Algorithm, public_key::PublicKey};
+pub use self::{
+    algorithm::Algorithm,
+    public_key::{PublicKey, TypedPublicKey},
+};
 pub use signature;
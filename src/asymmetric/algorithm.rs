@@ -1,6 +1,15 @@
 //! Asymmetric algorithm support
 
-use crate::algorithm;
+use crate::{algorithm, Capability};
+
+/// Capabilities for operations which are specific to a type of asymmetric key
+const KEY_OPERATIONS: Capability = Capability::SIGN_PKCS
+    .union(Capability::SIGN_PSS)
+    .union(Capability::DECRYPT_PKCS)
+    .union(Capability::DECRYPT_OAEP)
+    .union(Capability::SIGN_ECDSA)
+    .union(Capability::DERIVE_ECDH)
+    .union(Capability::SIGN_EDDSA);
 
 /// Asymmetric algorithms (RSA or ECC)
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -90,6 +99,21 @@ impl Algorithm {
         }
     }
 
+    /// Returns true if the algorithm is an elliptic curve used for ECDSA
+    pub fn is_ecc(self) -> bool {
+        matches!(
+            self,
+            Algorithm::EcP224
+                | Algorithm::EcP256
+                | Algorithm::EcK256
+                | Algorithm::EcP384
+                | Algorithm::EcP521
+                | Algorithm::EcBp256
+                | Algorithm::EcBp384
+                | Algorithm::EcBp512
+        )
+    }
+
     /// Returns true if the algorithm is RSA
     pub fn is_rsa(self) -> bool {
         matches!(
@@ -97,6 +121,28 @@ impl Algorithm {
             Algorithm::Rsa2048 | Algorithm::Rsa3072 | Algorithm::Rsa4096
         )
     }
+
+    /// Capabilities for the key-specific operations which can be performed
+    /// with keys of this algorithm (e.g. `SIGN_ECDSA` and `DERIVE_ECDH` for
+    /// EC keys)
+    pub fn operations(self) -> Capability {
+        if self.is_rsa() {
+            Capability::SIGN_PKCS
+                | Capability::SIGN_PSS
+                | Capability::DECRYPT_PKCS
+                | Capability::DECRYPT_OAEP
+        } else if self.is_ecc() {
+            Capability::SIGN_ECDSA | Capability::DERIVE_ECDH
+        } else {
+            Capability::SIGN_EDDSA
+        }
+    }
+
+    /// Get the capabilities among the given ones which are for operations
+    /// that can't be performed with keys of this algorithm
+    pub fn unsupported_capabilities(self, capabilities: Capability) -> Capability {
+        capabilities & KEY_OPERATIONS.difference(self.operations())
+    }
 }
 
 impl_algorithm_serializers!(Algorithm);
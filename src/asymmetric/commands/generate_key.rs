@@ -15,6 +15,10 @@ pub(crate) struct GenAsymmetricKeyCommand(pub(crate) generate::Params);
 
 impl Command for GenAsymmetricKeyCommand {
     type ResponseType = GenAsymmetricKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.0.key_id]
+    }
 }
 
 /// Response from `command::generate_asymmetric_key`
@@ -8,6 +8,7 @@ use crate::{
     response::Response,
 };
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 /// Request parameters for `command::put_asymmetric_key`
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,8 +20,18 @@ pub(crate) struct PutAsymmetricKeyCommand {
     pub data: Vec<u8>,
 }
 
+impl Drop for PutAsymmetricKeyCommand {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
 impl Command for PutAsymmetricKeyCommand {
     type ResponseType = PutAsymmetricKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.id]
+    }
 }
 
 /// Response from `command::put_asymmetric_key`
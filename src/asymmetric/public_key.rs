@@ -1,6 +1,11 @@
 //! Public keys for use with asymmetric cryptography / signatures
 
-use crate::{asymmetric, ecdsa::algorithm::CurveAlgorithm, ed25519};
+use crate::{
+    asymmetric,
+    client::{self, ErrorKind::ResponseError},
+    ecdsa::algorithm::CurveAlgorithm,
+    ed25519,
+};
 use ::ecdsa::elliptic_curve::{
     bigint::Integer, generic_array::GenericArray, point::PointCompression, sec1, FieldBytesSize,
     PrimeCurve,
@@ -90,6 +95,70 @@ impl PublicKey {
 
         RsaPublicKey::new(modulus, exp).ok()
     }
+
+    /// Parse this public key according to its algorithm
+    pub fn typed(&self) -> Result<TypedPublicKey, client::Error> {
+        let typed = if self.algorithm.is_rsa() {
+            self.rsa().map(|key| TypedPublicKey::Rsa {
+                algorithm: self.algorithm,
+                key,
+            })
+        } else if self.algorithm.is_ecc() {
+            (self.bytes.len() == self.algorithm.key_len() * 2).then(|| TypedPublicKey::Ec {
+                curve: self.algorithm,
+                point: [&[0x04], self.bytes.as_slice()].concat(),
+            })
+        } else {
+            self.ed25519().map(TypedPublicKey::Ed25519)
+        };
+
+        typed.ok_or_else(|| {
+            format_err!(
+                ResponseError,
+                "malformed {:?} public key ({} bytes)",
+                self.algorithm,
+                self.bytes.len()
+            )
+            .into()
+        })
+    }
+}
+
+/// Public keys parsed according to their algorithm (see [`PublicKey::typed`])
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypedPublicKey {
+    /// RSA public key
+    Rsa {
+        /// RSA key size
+        algorithm: asymmetric::Algorithm,
+
+        /// Public key (i.e. modulus and public exponent)
+        key: RsaPublicKey,
+    },
+
+    /// Elliptic curve public key
+    Ec {
+        /// Curve the point is on
+        curve: asymmetric::Algorithm,
+
+        /// Public point in uncompressed SEC1 encoding (i.e. with a leading
+        /// 0x04 tag byte)
+        point: Vec<u8>,
+    },
+
+    /// Ed25519 public key
+    Ed25519(ed25519::PublicKey),
+}
+
+impl TypedPublicKey {
+    /// Get the algorithm of this public key
+    pub fn algorithm(&self) -> asymmetric::Algorithm {
+        match self {
+            TypedPublicKey::Rsa { algorithm, .. } => *algorithm,
+            TypedPublicKey::Ec { curve, .. } => *curve,
+            TypedPublicKey::Ed25519(_) => asymmetric::Algorithm::Ed25519,
+        }
+    }
 }
 
 impl AsRef<[u8]> for PublicKey {
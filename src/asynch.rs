@@ -0,0 +1,15 @@
+//! Asynchronous client API (gated under the `async` cargo feature).
+//!
+//! [`Client`] mirrors the session-level commands of the synchronous
+//! [`Client`][crate::Client] (e.g. signing, HMAC and data wrapping), sending
+//! them over an [`AsyncConnector`][crate::connector::AsyncConnector] so
+//! services running on an async executor (i.e. tokio) can use the HSM without
+//! blocking worker threads.
+//!
+//! Commands which provision or otherwise modify the HSM (and the client-side
+//! journal which records them) are only available on the synchronous client.
+
+mod client;
+mod session;
+
+pub use self::{client::Client, session::Session};
@@ -0,0 +1,779 @@
+//! Asynchronous YubiHSM client
+
+use super::Session;
+use crate::{
+    asymmetric::{commands::*, PublicKey, TypedPublicKey},
+    authentication::Credentials,
+    client::{Error, ErrorKind},
+    command::Command,
+    connector::AsyncConnector,
+    device::{self, commands::*, StorageInfo},
+    ecdsa::commands::*,
+    ed25519::{self, commands::*},
+    hmac::{self, commands::*},
+    object::{self, commands::*},
+    rsa::{self, pkcs1::commands::*, pss::commands::*, SignatureAlgorithm},
+    session::{self, ChallengeRng, CryptoBackend, RustCryptoBackend},
+    uuid,
+    wrap::{self, commands::*},
+};
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{MappedMutexGuard, Mutex as AsyncMutex, MutexGuard};
+
+/// Asynchronous YubiHSM client: counterpart of [`Client`][crate::Client]
+/// which communicates with the HSM via an [`AsyncConnector`].
+///
+/// Unlike [`Client`][crate::Client], sessions aren't closed when dropped
+/// (that requires awaiting a response from the HSM), leaving them open until
+/// they time out. Call [`Client::close`] when done with the client to
+/// release its session on the HSM promptly.
+#[derive(Clone)]
+pub struct Client {
+    /// Connector for communicating with the HSM
+    connector: AsyncConnector,
+
+    /// Encrypted session with the HSM (if we have one open)
+    session: Arc<AsyncMutex<Option<Session>>>,
+
+    /// Cached `Credentials` for reconnecting closed sessions
+    credentials: Arc<Mutex<Option<Credentials>>>,
+
+    /// Reopen closed sessions using the cached credentials. If disabled, the
+    /// credentials are discarded once the first session has been opened.
+    reconnect: bool,
+
+    /// Reauthenticate and retry a command once if the HSM reports that the
+    /// session it was sent over has expired
+    reauthenticate: bool,
+
+    /// AES and AES-CMAC implementation used for secure channels
+    crypto_backend: Arc<dyn CryptoBackend>,
+
+    /// RNG used to generate host challenges when opening sessions
+    challenge_rng: Arc<dyn ChallengeRng>,
+}
+
+impl Client {
+    /// Open a connection via an [`AsyncConnector`] to a YubiHSM, returning
+    /// a `yubihsm::asynch::Client`.
+    pub async fn open(
+        connector: AsyncConnector,
+        credentials: Credentials,
+        reconnect: bool,
+    ) -> Result<Self, Error> {
+        let mut client = Self::create(connector, credentials);
+        client.reconnect = reconnect;
+        client.connect().await?;
+        Ok(client)
+    }
+
+    /// Create a `yubihsm::asynch::Client`, but defer connecting until
+    /// `connect()` is called (or the first command is sent).
+    pub fn create(connector: AsyncConnector, credentials: Credentials) -> Self {
+        Self {
+            connector,
+            session: Arc::new(AsyncMutex::new(None)),
+            credentials: Arc::new(Mutex::new(Some(credentials))),
+            reconnect: true,
+            reauthenticate: true,
+            crypto_backend: Arc::new(RustCryptoBackend),
+            challenge_rng: Arc::new(OsRng),
+        }
+    }
+
+    /// Borrow this client's YubiHSM connector (which is `Clone`able)
+    pub fn connector(&self) -> &AsyncConnector {
+        &self.connector
+    }
+
+    /// Use the given AES and AES-CMAC implementation for sessions opened by
+    /// this client (and any clones made after this call).
+    pub fn set_crypto_backend(&mut self, backend: impl CryptoBackend + 'static) {
+        self.crypto_backend = Arc::new(backend);
+    }
+
+    /// Generate host challenges for sessions opened by this client (and any
+    /// clones made after this call) using the given RNG instead of `OsRng`.
+    pub fn set_challenge_rng(&mut self, rng: impl ChallengeRng + 'static) {
+        self.challenge_rng = Arc::new(rng);
+    }
+
+    /// Enable or disable transparently reauthenticating when the HSM reports
+    /// that a session has expired (see
+    /// [`Client::set_reauthenticate`][crate::Client::set_reauthenticate]).
+    pub fn set_reauthenticate(&mut self, enabled: bool) {
+        self.reauthenticate = enabled;
+    }
+
+    /// Connect to the HSM (idempotently, i.e. returns success if we have
+    /// an open connection already)
+    pub async fn connect(&self) -> Result<(), Error> {
+        drop(self.session().await?);
+        Ok(())
+    }
+
+    /// Close the current session (if any), returning an error if the HSM
+    /// couldn't be told to close it. The next command opens a new session.
+    pub async fn close(&self) -> Result<(), Error> {
+        match self.session.lock().await.take() {
+            Some(session) => Ok(session.close().await?),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the current `Session` (either opening a new one or returning an
+    /// already open one). The session is locked until the guard is dropped.
+    pub async fn session(&self) -> Result<MappedMutexGuard<'_, Session>, Error> {
+        let mut session_guard = self.session.lock().await;
+
+        if let Some(session) = session_guard.as_ref() {
+            if session.is_open() && !session.needs_rekey() {
+                return Ok(MutexGuard::map(session_guard, |s| s.as_mut().unwrap()));
+            }
+        }
+
+        // Close sessions which are about to exhaust their message counter
+        // rather than letting them fail mid-workload
+        if let Some(session) = session_guard.take() {
+            if session.is_open() && session.needs_rekey() {
+                debug!("session {} due for rekeying", session.id().to_u8());
+
+                if let Err(e) = session.close().await {
+                    debug!("error closing session for rekeying: {}", e);
+                }
+            }
+        }
+
+        // The credentials lock can't be held across `.await` points
+        let credentials = self.credentials.lock().unwrap().clone().ok_or_else(|| {
+            Error::from(format_err!(
+                ErrorKind::AuthenticationError,
+                "session reconnection disabled"
+            ))
+        })?;
+
+        let session = Session::open(
+            self.connector.clone(),
+            &credentials,
+            session::Timeout::default(),
+            Arc::clone(&self.crypto_backend),
+            &*self.challenge_rng,
+        )
+        .await?;
+
+        // Clear credentials if reconnecting has been disabled
+        if !self.reconnect {
+            *self.credentials.lock().unwrap() = None;
+        }
+
+        *session_guard = Some(session);
+        Ok(MutexGuard::map(session_guard, |s| s.as_mut().unwrap()))
+    }
+
+    /// Ping the HSM, ensuring we have a live connection and returning the
+    /// end-to-end latency.
+    pub async fn ping(&self) -> Result<Duration, Error> {
+        let t = Instant::now();
+        let uuid = uuid::new_v4().to_string();
+        let response = self.echo(uuid.as_bytes()).await?;
+
+        ensure!(
+            uuid.as_bytes() == response.as_slice(),
+            ErrorKind::ResponseError,
+            "expected {}, got {}",
+            uuid,
+            String::from_utf8_lossy(&response)
+        );
+
+        Ok(Instant::now().duration_since(t))
+    }
+
+    /// Encrypt a command, send it to the HSM, then read and decrypt the
+    /// response, rekeying the session if needed.
+    async fn send_command<T: Command + Sync>(&self, command: T) -> Result<T::ResponseType, Error> {
+        let mut session = self.session().await?;
+
+        match session.send_command(&command).await {
+            Ok(response) => Ok(response),
+            Err(err) if *err.kind() == session::ErrorKind::CommandLimitExceeded => {
+                // The command was never sent: open a new session and retry
+                drop(session);
+                Ok(self.session().await?.send_command(&command).await?)
+            }
+            Err(err)
+                if *err.kind() == session::ErrorKind::ExpiredError
+                    && self.reauthenticate
+                    && self.reconnect =>
+            {
+                // The command was rejected without being executed: open a
+                // new session and retry
+                drop(session);
+                Ok(self.session().await?.send_command(&command).await?)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    //
+    // HSM Commands
+    // <https://developers.yubico.com/YubiHSM2/Commands/>
+    //
+
+    /// Blink the HSM's LEDs (to identify it) for the given number of seconds.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Blink_Device.html>
+    pub async fn blink_device(&self, num_seconds: u8) -> Result<(), Error> {
+        self.send_command(BlinkDeviceCommand { num_seconds })
+            .await?;
+        Ok(())
+    }
+
+    /// Get information about the HSM device.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Device_Info.html>
+    pub async fn device_info(&self) -> Result<device::Info, Error> {
+        Ok(self.send_command(DeviceInfoCommand {}).await?.into())
+    }
+
+    /// Echo a message sent to the HSM.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Echo.html>
+    pub async fn echo<M>(&self, msg: M) -> Result<Vec<u8>, Error>
+    where
+        M: Into<Vec<u8>>,
+    {
+        Ok(self
+            .send_command(EchoCommand {
+                message: msg.into(),
+            })
+            .await?
+            .0)
+    }
+
+    /// Get information about an object.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Object_Info.html>
+    pub async fn get_object_info(
+        &self,
+        object_id: object::Id,
+        object_type: object::Type,
+    ) -> Result<object::Info, Error> {
+        Ok(self
+            .send_command(GetObjectInfoCommand(object::Handle::new(
+                object_id,
+                object_type,
+            )))
+            .await?
+            .0)
+    }
+
+    /// Get some number of bytes of pseudo random data generated on the device.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Pseudo_Random.html>
+    pub async fn get_pseudo_random(&self, bytes: usize) -> Result<Vec<u8>, Error> {
+        ensure!(
+            bytes <= MAX_RAND_BYTES,
+            ErrorKind::ProtocolError,
+            "requested number of bytes too large: {} (max: {})",
+            bytes,
+            MAX_RAND_BYTES
+        );
+
+        Ok(self
+            .send_command(GetPseudoRandomCommand {
+                bytes: bytes as u16,
+            })
+            .await?
+            .bytes)
+    }
+
+    /// Get the public key for an asymmetric key stored on the device.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Public_Key.html>
+    pub async fn get_public_key(&self, key_id: object::Id) -> Result<PublicKey, Error> {
+        Ok(self
+            .send_command(GetPublicKeyCommand { key_id })
+            .await?
+            .into())
+    }
+
+    /// Get the public key for an asymmetric key stored on the device, parsed
+    /// according to its algorithm.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Public_Key.html>
+    pub async fn get_typed_public_key(&self, key_id: object::Id) -> Result<TypedPublicKey, Error> {
+        self.get_public_key(key_id).await?.typed()
+    }
+
+    /// Get storage info (i.e. currently free storage) from the HSM device.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Storage_Info.html>
+    pub async fn get_storage_info(&self) -> Result<StorageInfo, Error> {
+        Ok(self.send_command(GetStorageInfoCommand {}).await?.into())
+    }
+
+    /// List objects visible from the current session.
+    ///
+    /// Optionally apply a set of provided `filters` which select objects
+    /// based on their attributes.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/List_Objects.html>
+    pub async fn list_objects(
+        &self,
+        filters: &[object::Filter],
+    ) -> Result<Vec<object::Entry>, Error> {
+        let mut filter_bytes = vec![];
+
+        for filter in filters {
+            filter.serialize(&mut filter_bytes)?;
+        }
+
+        Ok(self.send_command(ListObjectsCommand(filter_bytes)).await?.0)
+    }
+
+    /// Compute an ECDSA signature of the given digest (i.e. a precomputed
+    /// SHA-2 digest) using the given key ID.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Ecdsa.html>
+    ///
+    /// This is a low-level ECDSA API, and if used incorrectly could
+    /// potentially result in forgeable signatures.
+    pub async fn sign_ecdsa_prehash_raw<T>(
+        &self,
+        key_id: object::Id,
+        digest: T,
+    ) -> Result<Vec<u8>, Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.send_command(SignEcdsaCommand {
+            key_id,
+            digest: digest.into(),
+        })
+        .await
+        .map(Into::into)
+    }
+
+    /// Compute an Ed25519 signature with the given key ID.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Eddsa.html>
+    pub async fn sign_ed25519<T>(
+        &self,
+        key_id: object::Id,
+        data: T,
+    ) -> Result<ed25519::Signature, Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.send_command(SignEddsaCommand {
+            key_id,
+            data: data.into(),
+        })
+        .await?
+        .signature()
+    }
+
+    /// Compute an HMAC tag of the given data with the given key ID.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Hmac.html>
+    pub async fn sign_hmac<M>(&self, key_id: object::Id, msg: M) -> Result<hmac::Tag, Error>
+    where
+        M: Into<Vec<u8>>,
+    {
+        Ok(self
+            .send_command(SignHmacCommand {
+                key_id,
+                data: msg.into(),
+            })
+            .await?
+            .into())
+    }
+
+    /// Compute an RSASSA-PKCS#1v1.5 signature of the SHA-256 hash of the given data.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pkcs1.html>
+    pub async fn sign_rsa_pkcs1v15_sha256(
+        &self,
+        key_id: object::Id,
+        data: &[u8],
+    ) -> Result<rsa::pkcs1::Signature, Error> {
+        Ok(self
+            .send_command(SignPkcs1Command {
+                key_id,
+                digest: Sha256::digest(data).as_slice().into(),
+            })
+            .await?
+            .into())
+    }
+
+    /// Compute an RSASSA-PSS signature of the SHA-256 hash of the given data with the given key ID.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pss.html>
+    pub async fn sign_rsa_pss_sha256(
+        &self,
+        key_id: object::Id,
+        data: &[u8],
+    ) -> Result<rsa::pss::Signature, Error> {
+        ensure!(
+            data.len() < rsa::pss::MAX_MESSAGE_SIZE,
+            ErrorKind::ProtocolError,
+            "message too large to be signed (max: {})",
+            rsa::pss::MAX_MESSAGE_SIZE
+        );
+
+        let digest = Sha256::digest(data);
+
+        Ok(self
+            .send_command(SignPssCommand {
+                key_id,
+                mgf1_hash_alg: Sha256::MGF_ALGORITHM,
+                salt_len: digest.as_slice().len() as u16,
+                digest: digest.as_slice().into(),
+            })
+            .await?
+            .into())
+    }
+
+    /// Decrypt data which was encrypted (using AES-CCM) under a wrap key.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Unwrap_Data.html>
+    pub async fn unwrap_data<M>(
+        &self,
+        wrap_key_id: object::Id,
+        wrap_message: M,
+    ) -> Result<Vec<u8>, Error>
+    where
+        M: Into<wrap::Message>,
+    {
+        let wrap::Message { nonce, ciphertext } = wrap_message.into();
+
+        Ok(self
+            .send_command(UnwrapDataCommand {
+                wrap_key_id,
+                nonce,
+                ciphertext,
+            })
+            .await?
+            .0)
+    }
+
+    /// Verify an HMAC tag of the given data with the given key ID.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Verify_Hmac.html>
+    pub async fn verify_hmac<M, T>(&self, key_id: object::Id, msg: M, tag: T) -> Result<(), Error>
+    where
+        M: Into<Vec<u8>>,
+        T: Into<hmac::Tag>,
+    {
+        let result = self
+            .send_command(VerifyHmacCommand {
+                key_id,
+                tag: tag.into(),
+                data: msg.into(),
+            })
+            .await?;
+
+        if result.0 == 0 {
+            fail!(ErrorKind::ResponseError, "HMAC verification failure")
+        }
+
+        Ok(())
+    }
+
+    /// Encrypt data (with AES-CCM) using the given wrap key.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Wrap_Data.html>
+    pub async fn wrap_data(
+        &self,
+        wrap_key_id: object::Id,
+        plaintext: Vec<u8>,
+    ) -> Result<wrap::Message, Error> {
+        Ok(self
+            .send_command(WrapDataCommand {
+                wrap_key_id,
+                plaintext,
+            })
+            .await?
+            .0)
+    }
+}
+
+#[cfg(all(test, feature = "mockhsm"))]
+mod tests {
+    use super::*;
+    use crate::{
+        asymmetric,
+        connector::{self, AsyncConnectable, AsyncConnection, BoxFuture, Connector, Message},
+        mockhsm::MockHsm,
+        Capability, Domain,
+    };
+    use ed25519_dalek::Verifier;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const KEY_ID: object::Id = 100;
+    const MESSAGE: &[u8] = b"The YubiHSM 2 is a simple, affordable, and secure HSM solution";
+
+    /// Connector which relays messages to a MockHsm, except while `stalled`
+    /// is set, when it never responds
+    #[derive(Clone)]
+    struct StallingConnector {
+        inner: AsyncConnector,
+        stalled: Arc<AtomicBool>,
+    }
+
+    impl AsyncConnectable for StallingConnector {
+        fn box_clone(&self) -> Box<dyn AsyncConnectable> {
+            Box::new(self.clone())
+        }
+
+        fn connect(&self) -> BoxFuture<'_, Result<Box<dyn AsyncConnection>, connector::Error>> {
+            let connection: Box<dyn AsyncConnection> = Box::new(self.clone());
+            Box::pin(async move { Ok(connection) })
+        }
+    }
+
+    impl AsyncConnection for StallingConnector {
+        fn send_message(
+            &self,
+            uuid: uuid::Uuid,
+            msg: Message,
+        ) -> BoxFuture<'_, Result<Message, connector::Error>> {
+            if self.stalled.load(Ordering::SeqCst) {
+                Box::pin(std::future::pending())
+            } else {
+                Box::pin(self.inner.send_message(uuid, msg))
+            }
+        }
+    }
+
+    /// Open an async client to the given MockHsm
+    async fn open_client(mockhsm: MockHsm) -> Client {
+        Client::open(
+            AsyncConnector::loopback(mockhsm),
+            Credentials::default(),
+            true,
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Open a synchronous client to the given MockHsm (e.g. to provision keys)
+    fn open_sync_client(mockhsm: &MockHsm) -> crate::Client {
+        crate::Client::open(
+            Connector::loopback(mockhsm.clone()),
+            Credentials::default(),
+            true,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cancelled_command_test() {
+        let stalled = Arc::new(AtomicBool::new(false));
+        let connector: Box<dyn AsyncConnectable> = Box::new(StallingConnector {
+            inner: AsyncConnector::mockhsm(),
+            stalled: Arc::clone(&stalled),
+        });
+
+        let client = Client::open(
+            AsyncConnector::from(connector),
+            Credentials::default(),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(client.echo(MESSAGE).await.unwrap(), MESSAGE);
+        let session_id = client.session().await.unwrap().id();
+
+        // Drop the command's future once it's waiting for the response
+        stalled.store(true, Ordering::SeqCst);
+        let mut echo = Box::pin(client.echo(MESSAGE));
+        tokio::select! {
+            biased;
+            _ = &mut echo => panic!("stalled command completed"),
+            _ = std::future::ready(()) => (),
+        }
+        drop(echo);
+        stalled.store(false, Ordering::SeqCst);
+
+        // The HSM never saw the command, so the old session's channel is
+        // out of step with it: a new session must be used instead
+        assert_eq!(client.echo(MESSAGE).await.unwrap(), MESSAGE);
+        assert_ne!(client.session().await.unwrap().id(), session_id);
+    }
+
+    #[tokio::test]
+    async fn device_test() {
+        let client = open_client(MockHsm::new()).await;
+
+        client.blink_device(1).await.unwrap();
+        client.ping().await.unwrap();
+
+        let info = client.device_info().await.unwrap();
+        assert!(info.serial_number.to_string().parse::<u32>().is_ok());
+
+        let storage_info = client.get_storage_info().await.unwrap();
+        assert!(storage_info.free_records <= storage_info.total_records);
+
+        assert_eq!(client.get_pseudo_random(32).await.unwrap().len(), 32);
+        assert!(client.get_pseudo_random(MAX_RAND_BYTES + 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn object_test() {
+        let mockhsm = MockHsm::new();
+        open_sync_client(&mockhsm)
+            .generate_asymmetric_key(
+                KEY_ID,
+                "async".into(),
+                Domain::DOM1,
+                Capability::SIGN_ECDSA,
+                asymmetric::Algorithm::EcP256,
+            )
+            .unwrap();
+
+        let client = open_client(mockhsm).await;
+
+        let entries = client
+            .list_objects(&[object::Filter::Type(object::Type::AsymmetricKey)])
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].object_id, KEY_ID);
+
+        let info = client
+            .get_object_info(KEY_ID, object::Type::AsymmetricKey)
+            .await
+            .unwrap();
+        assert_eq!(info.algorithm, asymmetric::Algorithm::EcP256.into());
+
+        assert!(client
+            .get_object_info(KEY_ID + 1, object::Type::AsymmetricKey)
+            .await
+            .is_err());
+
+        let public_key = client.get_typed_public_key(KEY_ID).await.unwrap();
+        assert_eq!(public_key.algorithm(), asymmetric::Algorithm::EcP256);
+    }
+
+    #[tokio::test]
+    async fn sign_ecdsa_test() {
+        use p256::ecdsa::{signature::hazmat::PrehashVerifier, DerSignature, VerifyingKey};
+
+        let mockhsm = MockHsm::new();
+        open_sync_client(&mockhsm)
+            .generate_asymmetric_key(
+                KEY_ID,
+                "async".into(),
+                Domain::DOM1,
+                Capability::SIGN_ECDSA,
+                asymmetric::Algorithm::EcP256,
+            )
+            .unwrap();
+
+        let client = open_client(mockhsm).await;
+        let digest = Sha256::digest(MESSAGE);
+        let signature = client
+            .sign_ecdsa_prehash_raw(KEY_ID, digest.as_slice())
+            .await
+            .unwrap();
+
+        let point = match client.get_typed_public_key(KEY_ID).await.unwrap() {
+            TypedPublicKey::Ec { point, .. } => point,
+            other => panic!("unexpected public key: {other:?}"),
+        };
+        let verifying_key = VerifyingKey::from_sec1_bytes(&point).unwrap();
+        let signature = DerSignature::try_from(signature.as_slice()).unwrap();
+        assert!(verifying_key.verify_prehash(&digest, &signature).is_ok());
+    }
+
+    #[tokio::test]
+    async fn hmac_test() {
+        let mockhsm = MockHsm::new();
+        open_sync_client(&mockhsm)
+            .generate_hmac_key(
+                KEY_ID,
+                "async".into(),
+                Domain::DOM1,
+                Capability::SIGN_HMAC | Capability::VERIFY_HMAC,
+                hmac::Algorithm::Sha256,
+            )
+            .unwrap();
+
+        let client = open_client(mockhsm).await;
+        let tag = client.sign_hmac(KEY_ID, MESSAGE).await.unwrap();
+        assert!(client
+            .verify_hmac(KEY_ID, MESSAGE, tag.clone())
+            .await
+            .is_ok());
+
+        let mut bad_tag = Vec::from(tag.as_ref());
+        bad_tag[0] ^= 1;
+        assert!(client.verify_hmac(KEY_ID, MESSAGE, bad_tag).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn close_test() {
+        let mockhsm = MockHsm::new();
+
+        // The HSM supports a limited number of sessions, which would be
+        // exhausted if closing clients leaked them
+        for _ in 0..20 {
+            let client = Client::open(
+                AsyncConnector::loopback(mockhsm.clone()),
+                Credentials::default(),
+                true,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(client.echo(MESSAGE).await.unwrap(), MESSAGE);
+            client.close().await.unwrap();
+            client.close().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn sign_ed25519_test() {
+        let mockhsm = MockHsm::new();
+
+        // Provision a key using the synchronous client
+        crate::Client::open(
+            Connector::loopback(mockhsm.clone()),
+            Credentials::default(),
+            true,
+        )
+        .unwrap()
+        .generate_asymmetric_key(
+            KEY_ID,
+            "async".into(),
+            Domain::DOM1,
+            Capability::SIGN_EDDSA,
+            asymmetric::Algorithm::Ed25519,
+        )
+        .unwrap();
+
+        let client = Client::open(
+            AsyncConnector::loopback(mockhsm),
+            Credentials::default(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(client.echo(MESSAGE).await.unwrap(), MESSAGE);
+
+        let public_key = client.get_public_key(KEY_ID).await.unwrap();
+        let verifying_key =
+            ed25519_dalek::VerifyingKey::from_bytes(public_key.as_slice().try_into().unwrap())
+                .unwrap();
+
+        let signature = client.sign_ed25519(KEY_ID, MESSAGE).await.unwrap();
+        assert!(verifying_key.verify(MESSAGE, &signature).is_ok());
+    }
+}
@@ -0,0 +1,319 @@
+//! Authenticated/encrypted sessions with the HSM over an asynchronous
+//! connector
+
+use crate::{
+    authentication::Credentials,
+    command::{self, Command},
+    connector::AsyncConnector,
+    device, response,
+    serialization::{deserialize, serialize},
+    session::{
+        commands::CloseSessionCommand,
+        securechannel::{Challenge, SecureChannel, MAX_COMMANDS_PER_SESSION},
+        ChallengeRng, CryptoBackend, Error, ErrorKind, Id, Timeout, REKEY_MARGIN,
+        TIMEOUT_FUZZ_FACTOR,
+    },
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Authenticated and encrypted (SCP03) `Session` with the HSM, established
+/// over an [`AsyncConnector`].
+///
+/// Unlike [`session::Session`][crate::session::Session], sessions can't be
+/// closed on the HSM when dropped (that requires I/O): use
+/// [`Session::close`] to release HSM session resources promptly.
+pub struct Session {
+    /// ID for this session
+    id: Id,
+
+    /// Connector which communicates with the HSM (HTTP or USB)
+    connector: AsyncConnector,
+
+    /// Encrypted channel (SCP03) to the HSM
+    secure_channel: Option<SecureChannel>,
+
+    /// Session creation timestamp
+    created_at: Instant,
+
+    /// Timestamp when this session was last active
+    last_active: Instant,
+
+    /// Inactivity timeout for this session
+    timeout: Timeout,
+}
+
+impl Session {
+    /// Connect to the HSM using the given configuration and credentials,
+    /// using the given crypto backend and host challenge RNG for the secure
+    /// channel
+    pub(super) async fn open(
+        connector: AsyncConnector,
+        credentials: &Credentials,
+        timeout: Timeout,
+        backend: Arc<dyn CryptoBackend>,
+        challenge_rng: &dyn ChallengeRng,
+    ) -> Result<Self, Error> {
+        ensure!(
+            timeout.duration() > TIMEOUT_FUZZ_FACTOR,
+            ErrorKind::CreateFailed,
+            "timeout too low: must be longer than {:?}",
+            TIMEOUT_FUZZ_FACTOR
+        );
+
+        let host_challenge = Challenge::from_rng(challenge_rng);
+        let command_message = SecureChannel::create_session_command(credentials, host_challenge);
+
+        let uuid = command_message.uuid;
+        let response_body = connector.send_message(uuid, command_message.into()).await?;
+        let response_message = response::Message::parse(response_body)?;
+
+        let channel = SecureChannel::from_create_session_response(
+            credentials,
+            host_challenge,
+            response_message,
+            backend,
+        )?;
+
+        let now = Instant::now();
+
+        let mut session = Session {
+            id: channel.id(),
+            connector,
+            secure_channel: Some(channel),
+            created_at: now,
+            last_active: now,
+            timeout,
+        };
+
+        session.authenticate(credentials).await?;
+
+        Ok(session)
+    }
+
+    /// Is this `Session` still open?
+    pub fn is_open(&self) -> bool {
+        self.secure_channel.is_some() && !self.is_timed_out()
+    }
+
+    /// Session ID value (1-16)
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// How long has this session been open?
+    pub fn duration(&self) -> Duration {
+        Instant::now().duration_since(self.created_at)
+    }
+
+    /// Number of messages sent during this session
+    pub fn messages_sent(&self) -> Result<usize, Error> {
+        self.secure_channel
+            .as_ref()
+            .ok_or_else(|| format_err!(ErrorKind::ClosedError, "session is already closed").into())
+            .map(SecureChannel::counter)
+    }
+
+    /// Is this session close to the limit on the number of messages which
+    /// can be sent under one set of session keys?
+    pub fn needs_rekey(&self) -> bool {
+        self.messages_sent()
+            .is_ok_and(|n| n + REKEY_MARGIN >= MAX_COMMANDS_PER_SESSION as usize)
+    }
+
+    /// Has this session timed out?
+    pub fn is_timed_out(&self) -> bool {
+        let idle_time = Instant::now().duration_since(self.last_active);
+        let timeout_with_fuzz = self.timeout.duration() - TIMEOUT_FUZZ_FACTOR;
+        idle_time >= timeout_with_fuzz
+    }
+
+    /// Close this session, consuming it in the process.
+    pub async fn close(mut self) -> Result<(), Error> {
+        // Only attempt to close the session if we have an active secure
+        // channel and our session hasn't already timed out
+        if self.secure_channel.is_none() || self.is_timed_out() {
+            return Ok(());
+        }
+
+        debug!("session={} closing session", self.id.to_u8());
+        self.send_command(&CloseSessionCommand {}).await?;
+        Ok(())
+    }
+
+    /// Abort this session, terminating it without closing it
+    fn abort(&mut self) {
+        self.secure_channel = None;
+    }
+
+    /// Encrypt a command, send it to the HSM, then read and decrypt the response
+    pub(super) async fn send_command<C: Command>(
+        &mut self,
+        command: &C,
+    ) -> Result<C::ResponseType, Error> {
+        let cmd_type = C::COMMAND_CODE;
+        let command_data = serialize(command)?;
+        SecureChannel::ensure_command_fits(cmd_type, &command_data)?;
+        let plaintext_cmd = command::Message::create(cmd_type, command_data)?;
+
+        let encrypted_cmd = self
+            .secure_channel()?
+            .encrypt_command(plaintext_cmd)
+            .map_err(|e| {
+                // Abort the session in the event of any cryptographic errors
+                self.abort();
+                e
+            })?;
+
+        let uuid = encrypted_cmd.uuid;
+        debug!(
+            "session={} n={} uuid={} cmd={:?}",
+            self.id.to_u8(),
+            self.messages_sent()?,
+            uuid,
+            cmd_type
+        );
+
+        // The channel has advanced past this command, so if this future is
+        // dropped before the response arrives the session can't be reused
+        let exchange = Exchange::start(self);
+        let encrypted_response = exchange.session.send_message(encrypted_cmd).await;
+        exchange.finish();
+        let encrypted_response = encrypted_response?;
+
+        let response = self
+            .secure_channel()?
+            .decrypt_response(encrypted_response)
+            .map_err(|e| {
+                // Abort the session in the event of any cryptographic errors
+                self.abort();
+                e
+            })?;
+
+        if response.is_err() {
+            if let Some(kind) = device::ErrorKind::from_response_message(&response) {
+                return Err(kind.into());
+            } else {
+                fail!(ErrorKind::ResponseError, "{:?} failed: HSM error", cmd_type);
+            }
+        }
+
+        if response.command() != Some(cmd_type) {
+            fail!(
+                ErrorKind::ResponseError,
+                "bad command type in response: {:?} (expected {:?})",
+                response.command(),
+                cmd_type,
+            );
+        }
+
+        deserialize(response.data.as_ref()).map_err(Into::into)
+    }
+
+    /// Send a command message to the HSM and parse the response
+    async fn send_message(&mut self, cmd: command::Message) -> Result<response::Message, Error> {
+        let uuid = cmd.uuid;
+        self.last_active = Instant::now();
+
+        let response = match self.connector.send_message(uuid, cmd.into()).await {
+            Ok(response_bytes) => response::Message::parse(response_bytes)?,
+            Err(e) => {
+                // Abort the session in the event of errors
+                self.abort();
+                return Err(e.into());
+            }
+        };
+
+        if response.is_err() {
+            error!(
+                "session={} uuid={} error={:?}",
+                self.id.to_u8(),
+                &uuid,
+                response.code
+            );
+
+            // The HSM no longer recognizes this session, so don't use it again
+            if device::ErrorKind::from_response_code(response.code)
+                == Some(device::ErrorKind::InvalidSession)
+            {
+                self.abort();
+                fail!(
+                    ErrorKind::ExpiredError,
+                    "session {} expired on HSM",
+                    self.id.to_u8()
+                );
+            }
+
+            fail!(
+                ErrorKind::ResponseError,
+                "HSM error (session: {})",
+                self.id.to_u8(),
+            );
+        }
+
+        Ok(response)
+    }
+
+    /// Authenticate the current session with the HSM
+    async fn authenticate(&mut self, credentials: &Credentials) -> Result<(), Error> {
+        let command = self.secure_channel()?.authenticate_session()?;
+        let response = self.send_message(command).await?;
+
+        self.secure_channel()?
+            .finish_authenticate_session(&response)?;
+
+        debug!(
+            "session={} auth=OK key={}",
+            self.id.to_u8(),
+            credentials.authentication_key_id
+        );
+        Ok(())
+    }
+
+    /// Get the underlying channel or return an error
+    fn secure_channel(&mut self) -> Result<&mut SecureChannel, Error> {
+        self.secure_channel
+            .as_mut()
+            .ok_or_else(|| format_err!(ErrorKind::ClosedError, "session is already closed").into())
+    }
+}
+
+/// Command/response exchange in progress on a session, which aborts the
+/// session if dropped before [`Exchange::finish`] is called (e.g. because
+/// the future sending the command was cancelled)
+struct Exchange<'a> {
+    /// Session the command was sent on
+    session: &'a mut Session,
+
+    /// Has a response (or an error) been received?
+    finished: bool,
+}
+
+impl<'a> Exchange<'a> {
+    /// Start an exchange on the given session
+    fn start(session: &'a mut Session) -> Self {
+        Self {
+            session,
+            finished: false,
+        }
+    }
+
+    /// Mark this exchange as complete, keeping the session usable
+    fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for Exchange<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            debug!(
+                "session={} aborted: command cancelled before its response arrived",
+                self.session.id.to_u8()
+            );
+            self.session.abort();
+        }
+    }
+}
@@ -6,8 +6,10 @@ use crate::{
     command::{self, Command},
     object,
     response::{self, Response},
+    serialization::serialize,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fmt::{self, Debug};
 
 /// Request parameters for `command::get_log_entries`
@@ -38,8 +40,31 @@ impl Response for LogEntries {
     const COMMAND_CODE: command::Code = command::Code::GetLogEntries;
 }
 
+impl LogEntries {
+    /// Check that each entry's digest chains from the previous entry's
+    /// digest, starting from the given digest of the entry preceding the
+    /// first (e.g. the last entry consumed by a previous call), if known.
+    ///
+    /// Returns the item number of the first entry which fails to verify.
+    pub fn verify(&self, previous: Option<&LogDigest>) -> Result<(), u16> {
+        let mut previous = previous;
+
+        for entry in &self.entries {
+            if let Some(digest) = previous {
+                if !entry.verify(digest) {
+                    return Err(entry.item);
+                }
+            }
+
+            previous = Some(&entry.digest);
+        }
+
+        Ok(())
+    }
+}
+
 /// Entry in the log response
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct LogEntry {
     /// Entry number
     pub item: u16,
@@ -69,11 +94,35 @@ pub struct LogEntry {
     pub digest: LogDigest,
 }
 
+impl LogEntry {
+    /// Compute the digest of this entry chained from the previous entry's
+    /// digest
+    pub fn compute_digest(&self, previous: &LogDigest) -> LogDigest {
+        let serialized = serialize(self).unwrap();
+        let fields = &serialized[..serialized.len() - LOG_DIGEST_SIZE];
+
+        let mut digest = [0u8; LOG_DIGEST_SIZE];
+        digest.copy_from_slice(
+            &Sha256::new()
+                .chain_update(fields)
+                .chain_update(previous)
+                .finalize()[..LOG_DIGEST_SIZE],
+        );
+
+        LogDigest(digest)
+    }
+
+    /// Does this entry's digest chain from the previous entry's digest?
+    pub fn verify(&self, previous: &LogDigest) -> bool {
+        self.compute_digest(previous) == self.digest
+    }
+}
+
 /// Size of a truncated digest in the log
 pub const LOG_DIGEST_SIZE: usize = 16;
 
 /// Truncated SHA-256 digest of a log entry and the previous log digest
-#[derive(Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
 pub struct LogDigest(pub [u8; LOG_DIGEST_SIZE]);
 
 impl AsRef<[u8]> for LogDigest {
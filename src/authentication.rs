@@ -2,6 +2,7 @@
 //! authenticate and establish a session with an HSM)
 
 mod algorithm;
+mod channel_keys;
 pub mod commands;
 mod credentials;
 mod error;
@@ -9,6 +10,7 @@ pub mod key;
 
 pub use self::{
     algorithm::Algorithm,
+    channel_keys::{ChannelKeys, StaticKey},
     credentials::*,
     error::{Error, ErrorKind},
     key::Key,
@@ -0,0 +1,31 @@
+//! Static SCP03 keys held outside of this process (e.g. in a TPM, OS keychain
+//! or another HSM)
+
+/// The two static keys which make up an authentication key
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StaticKey {
+    /// Static encryption key (from which S-ENC is derived)
+    Enc,
+
+    /// Static MAC key (from which S-MAC and S-RMAC are derived)
+    Mac,
+}
+
+/// Static encryption and MAC keys for establishing SCP03 channels which are
+/// held externally and never exposed as raw bytes.
+///
+/// The only operation performed with the static keys is the AES-CMAC used as
+/// the PRF of the SCP03 key derivation function, so that's all implementers
+/// need to provide. Session keys derived this way are held in memory for the
+/// lifetime of each session.
+///
+/// Use [`Key::from_channel_keys`][crate::authentication::Key::from_channel_keys]
+/// to authenticate with externally held keys.
+pub trait ChannelKeys: Send + Sync {
+    /// Size in bytes of each of the static keys: 16 for AES-128 or 32 for
+    /// AES-256
+    fn key_size(&self) -> usize;
+
+    /// Compute the AES-CMAC of the given message using the given static key
+    fn cmac(&self, key: StaticKey, message: &[u8]) -> [u8; 16];
+}
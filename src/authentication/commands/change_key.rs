@@ -0,0 +1,43 @@
+//! Replace the authentication key used to open the current session
+//!
+//! <https://developers.yubico.com/YubiHSM2/Commands/Change_Authentication_Key.html>
+
+use crate::{
+    authentication,
+    command::{self, Command},
+    object,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+/// Request parameters for `command::change_authentication_key`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ChangeAuthenticationKeyCommand {
+    /// ID of the authentication key used to open the current session
+    pub key_id: object::Id,
+
+    /// Authentication key algorithm
+    pub algorithm: authentication::Algorithm,
+
+    /// New authentication key
+    pub authentication_key: authentication::Key,
+}
+
+impl Command for ChangeAuthenticationKeyCommand {
+    type ResponseType = ChangeAuthenticationKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.key_id]
+    }
+}
+
+/// Response from `command::change_authentication_key`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ChangeAuthenticationKeyResponse {
+    /// ID of the key
+    pub key_id: object::Id,
+}
+
+impl Response for ChangeAuthenticationKeyResponse {
+    const COMMAND_CODE: command::Code = command::Code::ChangeAuthenticationKey;
+}
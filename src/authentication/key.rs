@@ -1,9 +1,15 @@
-//! `YubiHSM 2` authentication keys (2 * AES-128 symmetric PSK) from which session keys are derived
+//! `YubiHSM 2` authentication keys (2 * AES-128 or 2 * AES-256 symmetric PSK)
+//! from which session keys are derived
 
-use super::{Error, ErrorKind};
+use super::{ChannelKeys, Error, ErrorKind, StaticKey};
+use crate::session::CryptoBackend;
 use rand_core::{OsRng, RngCore};
-use std::fmt::{self, Debug};
-use zeroize::Zeroize;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt::{self, Debug},
+    sync::Arc,
+};
+use zeroize::{Zeroize, Zeroizing};
 
 #[cfg(feature = "pbkdf2")]
 use pbkdf2::pbkdf2_hmac;
@@ -14,6 +20,9 @@ use sha2::Sha256;
 /// Auth keys are 2 * AES-128 keys
 pub const SIZE: usize = 32;
 
+/// Size of auth keys consisting of 2 * AES-256 keys
+pub const AES256_SIZE: usize = 64;
+
 /// Password from which the default auth key is derived
 pub const DEFAULT_PASSWORD: &[u8] = b"password";
 
@@ -26,65 +35,148 @@ pub const PBKDF2_SALT: &[u8] = b"Yubico";
 /// This number of iterations matches what is performed by yubihsm-shell.
 pub const PBKDF2_ITERATIONS: u32 = 10_000;
 
-/// `YubiHSM 2` authentication keys (2 * AES-128 symmetric PSK) from which
-/// session keys are derived.c
+/// `YubiHSM 2` authentication keys (2 * AES-128 or 2 * AES-256 symmetric
+/// PSK) from which session keys are derived.
+///
+/// The YubiHSM 2 uses AES-128 keys ([`SIZE`] bytes). AES-256 keys
+/// ([`AES256_SIZE`] bytes) are also supported by the SCP03 implementation
+/// in this crate.
+///
+/// Key material is zeroized when a `Key` is dropped. Keys can also be held
+/// externally (see [`Key::from_channel_keys`]).
+#[derive(Clone)]
+pub struct Key(KeyMaterial);
+
+/// Where the static keys which make up a `Key` are held
 #[derive(Clone)]
-pub struct Key(pub(crate) [u8; SIZE]);
+enum KeyMaterial {
+    /// Raw key bytes (encryption key followed by MAC key)
+    Bytes(Vec<u8>),
+
+    /// Keys held outside of this process
+    External(Arc<dyn ChannelKeys>),
+}
 
 impl Key {
     /// Generate a random `Key` using `OsRng`.
     pub fn random() -> Self {
-        let mut challenge = [0u8; SIZE];
-        OsRng.fill_bytes(&mut challenge);
-        Key(challenge)
+        Self::random_with_size(SIZE)
+    }
+
+    /// Generate a random 2 * AES-256 `Key` using `OsRng`.
+    pub fn random_aes256() -> Self {
+        Self::random_with_size(AES256_SIZE)
     }
 
     /// Derive an auth key from a password (using PBKDF2 + static salt).
     /// This method is designed to be compatible with yubihsm-shell. Ensure
     /// you use a long, random password when using this method as the key
     /// derivation algorithm used does little to prevent brute force attacks.
+    ///
+    /// The key is derived using PBKDF2-HMAC-SHA256 with [`PBKDF2_SALT`] and
+    /// [`PBKDF2_ITERATIONS`]: the first 16 bytes of output are the encryption
+    /// key and the last 16 bytes the MAC key.
     #[cfg(feature = "passwords")]
     pub fn derive_from_password(password: &[u8]) -> Self {
-        let mut kdf_output = [0u8; SIZE];
+        let mut kdf_output = vec![0u8; SIZE];
         pbkdf2_hmac::<Sha256>(password, PBKDF2_SALT, PBKDF2_ITERATIONS, &mut kdf_output);
-        Self::new(kdf_output)
+        Key(KeyMaterial::Bytes(kdf_output))
     }
 
-    /// Create an `authentication::Key` from a 32-byte slice, returning an
-    /// error if the key is the wrong length
+    /// Create an `authentication::Key` from a 32-byte (AES-128) or 64-byte
+    /// (AES-256) slice, returning an error if the key is the wrong length
     pub fn from_slice(key_slice: &[u8]) -> Result<Self, Error> {
         ensure!(
-            key_slice.len() == SIZE,
+            key_slice.len() == SIZE || key_slice.len() == AES256_SIZE,
             ErrorKind::KeySizeInvalid,
-            "expected {}-byte key, got {}",
+            "expected {}-byte or {}-byte key, got {}",
             SIZE,
+            AES256_SIZE,
             key_slice.len()
         );
 
-        let mut key_bytes = [0u8; SIZE];
-        key_bytes.copy_from_slice(key_slice);
-
-        Ok(Key(key_bytes))
+        Ok(Key(KeyMaterial::Bytes(key_slice.into())))
     }
 
     /// Create a new Key from the given byte array
-    pub fn new(key_bytes: [u8; SIZE]) -> Self {
-        Key(key_bytes)
+    pub fn new(mut key_bytes: [u8; SIZE]) -> Self {
+        let key = Key(KeyMaterial::Bytes(key_bytes.into()));
+        key_bytes.zeroize();
+        key
+    }
+
+    /// Create a `Key` whose static keys are held externally (e.g. in a TPM,
+    /// OS keychain or another HSM) and accessed through [`ChannelKeys`].
+    ///
+    /// Such keys can be used to open sessions, but not serialized (e.g. to
+    /// put them into an HSM).
+    ///
+    /// Panics if the static keys aren't 16 (AES-128) or 32 (AES-256) bytes.
+    pub fn from_channel_keys(keys: impl ChannelKeys + 'static) -> Self {
+        let key_size = keys.key_size();
+        assert!(
+            key_size * 2 == SIZE || key_size * 2 == AES256_SIZE,
+            "16-byte or 32-byte static keys expected (got {key_size})"
+        );
+
+        Key(KeyMaterial::External(Arc::new(keys)))
+    }
+
+    /// Size of this key in bytes: [`SIZE`] for AES-128 keys, or
+    /// [`AES256_SIZE`] for AES-256 keys
+    pub fn size(&self) -> usize {
+        match &self.0 {
+            KeyMaterial::Bytes(bytes) => bytes.len(),
+            KeyMaterial::External(keys) => keys.key_size() * 2,
+        }
+    }
+
+    /// Is this key held externally (i.e. created with
+    /// [`Key::from_channel_keys`])?
+    pub fn is_external(&self) -> bool {
+        matches!(self.0, KeyMaterial::External(_))
     }
 
-    /// Borrow the secret authentication keys
-    pub fn as_secret_slice(&self) -> &[u8] {
-        &self.0
+    /// Borrow the secret authentication keys, or `None` if the key is held
+    /// externally (see [`Key::is_external`]).
+    pub fn as_secret_slice(&self) -> Option<&[u8]> {
+        match &self.0 {
+            KeyMaterial::Bytes(bytes) => Some(bytes),
+            KeyMaterial::External(_) => None,
+        }
     }
 
-    /// Obtain the encryption key portion of this auth key
-    pub(crate) fn enc_key(&self) -> &[u8] {
-        &self.0[..16]
+    /// Size of each of the static (encryption and MAC) keys in bytes
+    pub(crate) fn static_key_size(&self) -> usize {
+        self.size() / 2
     }
 
-    /// Obtain the MAC key portion of this auth key
-    pub(crate) fn mac_key(&self) -> &[u8] {
-        &self.0[16..]
+    /// Compute the AES-CMAC of the given message using one of the static
+    /// keys, with the given backend if the key is held in memory
+    pub(crate) fn cmac(
+        &self,
+        backend: &dyn CryptoBackend,
+        static_key: StaticKey,
+        message: &[u8],
+    ) -> [u8; 16] {
+        match &self.0 {
+            KeyMaterial::Bytes(bytes) => {
+                let (enc_key, mac_key) = bytes.split_at(bytes.len() / 2);
+
+                match static_key {
+                    StaticKey::Enc => backend.cmac(enc_key, message),
+                    StaticKey::Mac => backend.cmac(mac_key, message),
+                }
+            }
+            KeyMaterial::External(keys) => keys.cmac(static_key, message),
+        }
+    }
+
+    /// Generate a random `Key` of the given size using `OsRng`
+    fn random_with_size(size: usize) -> Self {
+        let mut key_bytes = vec![0u8; size];
+        OsRng.fill_bytes(&mut key_bytes);
+        Key(KeyMaterial::Bytes(key_bytes))
     }
 }
 
@@ -105,7 +197,9 @@ impl Default for Key {
 
 impl Drop for Key {
     fn drop(&mut self) {
-        self.0.zeroize();
+        if let KeyMaterial::Bytes(bytes) = &mut self.0 {
+            bytes.zeroize();
+        }
     }
 }
 
@@ -115,4 +209,45 @@ impl From<[u8; SIZE]> for Key {
     }
 }
 
-impl_array_serializers!(Key, SIZE);
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            KeyMaterial::Bytes(bytes) => bytes.serialize(serializer),
+            KeyMaterial::External(_) => Err(ser::Error::custom(
+                "externally held authentication keys can't be serialized",
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key_bytes = Zeroizing::new(Vec::<u8>::deserialize(deserializer)?);
+        Key::from_slice(&key_bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(all(test, feature = "passwords"))]
+mod tests {
+    use super::*;
+
+    /// Default authentication key as derived by yubihsm-shell (and documented
+    /// by Yubico) from the default password
+    const DEFAULT_ENC_KEY: [u8; 16] = [
+        0x09, 0x0b, 0x47, 0xdb, 0xed, 0x59, 0x56, 0x54, 0x90, 0x1d, 0xee, 0x1c, 0xc6, 0x55, 0xe4,
+        0x20,
+    ];
+    const DEFAULT_MAC_KEY: [u8; 16] = [
+        0x59, 0x2f, 0xd4, 0x83, 0xf7, 0x59, 0xe2, 0x99, 0x09, 0xa0, 0x4c, 0x45, 0x05, 0xd2, 0xce,
+        0x0a,
+    ];
+
+    #[test]
+    fn derive_default_key_from_password() {
+        let key = Key::derive_from_password(DEFAULT_PASSWORD);
+        assert_eq!(key.size(), SIZE);
+        let key_bytes = key.as_secret_slice().unwrap();
+        assert_eq!(&key_bytes[..16], DEFAULT_ENC_KEY);
+        assert_eq!(&key_bytes[16..], DEFAULT_MAC_KEY);
+    }
+}
@@ -9,9 +9,23 @@
 
 #[macro_use]
 mod error;
+mod builder;
+mod heartbeat;
+mod limiter;
+mod pool;
+mod retry;
+mod stats;
+
+pub use self::{
+    builder::ClientBuilder,
+    error::{Error, ErrorKind},
+    heartbeat::Heartbeat,
+    pool::{PooledClient, SessionPool, MAX_SESSIONS},
+    retry::RetryPolicy,
+    stats::{CommandStats, Stats},
+};
 
-pub use self::error::{Error, ErrorKind};
-
+use self::{limiter::CommandLimiter, stats::StatsRecorder};
 use crate::{
     asymmetric::{self, commands::*, PublicKey},
     attestation::{self, commands::*},
@@ -22,9 +36,11 @@ use crate::{
     connector::Connector,
     device::{self, commands::*, StorageInfo},
     domain::Domain,
+    ecdh::{self, commands::*},
     ecdsa::commands::*,
     ed25519::{self, commands::*},
     hmac::{self, commands::*},
+    journal::{self, Journal},
     object::{self, commands::*, generate},
     opaque::{self, commands::*},
     otp::{self, commands::*},
@@ -35,19 +51,24 @@ use crate::{
     uuid,
     wrap::{self, commands::*},
 };
-use sha2::Sha256;
+use rand_core::OsRng;
+use sha2::{Sha256, Sha384, Sha512};
+use spki::der::{asn1::AnyRef, Decode, Tag, Tagged};
 use std::{
-    sync::{Arc, Mutex},
+    collections::BTreeMap,
+    mem,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
     time::{Duration, Instant},
 };
+use zeroize::Zeroizing;
 
 #[cfg(feature = "passwords")]
-use std::{thread, time::SystemTime};
+use std::time::SystemTime;
 
 #[cfg(feature = "untested")]
 use crate::{
     algorithm::Algorithm,
-    ecdh::{self, commands::*},
     ssh::{self, commands::*},
 };
 
@@ -56,6 +77,17 @@ use crate::ecdsa;
 
 /// YubiHSM client: main API in this crate for accessing functions of the
 /// HSM hardware device.
+///
+/// Clients are `Send + Sync` and synchronize access to their session
+/// internally, so they can be shared between threads (e.g. in an `Arc`) and
+/// used concurrently without further locking. Commands sent concurrently
+/// over the same session are performed one at a time: use a
+/// [`SessionPool`] to perform them in parallel.
+///
+/// Cloning a `Client` is cheap: clones share its connection to the HSM, its
+/// sessions, credentials and statistics (via `Arc`s) rather than
+/// reconnecting, so a clone can be handed to each request handler. Settings
+/// changed with the `set_*` methods only apply to clones made afterwards.
 #[derive(Clone)]
 pub struct Client {
     /// Connector for communicating with the HSM
@@ -65,7 +97,63 @@ pub struct Client {
     session: Arc<Mutex<Option<Session>>>,
 
     /// Cached `Credentials` for reconnecting closed sessions
-    credentials: Option<Credentials>,
+    credentials: Arc<Mutex<Option<Credentials>>>,
+
+    /// Reopen closed sessions using the cached credentials. If disabled, the
+    /// credentials are discarded once the first session has been opened.
+    reconnect: bool,
+
+    /// Reauthenticate and retry a command once if the HSM reports that the
+    /// session it was sent over has expired
+    reauthenticate: bool,
+
+    /// Policy for resending commands which failed due to transient errors
+    /// (if any)
+    retry_policy: Option<Arc<RetryPolicy>>,
+
+    /// Limit on the number of commands in flight at once, shared with other
+    /// clients (if any)
+    command_limiter: Option<Arc<CommandLimiter>>,
+
+    /// How long to wait for the HSM to respond to commands (if overriding
+    /// the connector's timeout)
+    command_timeout: Option<Duration>,
+
+    /// Inactivity timeout for sessions opened by this client
+    session_timeout: session::Timeout,
+
+    /// Handler notified of session lifecycle events (if any)
+    session_event_handler: Option<session::EventHandler>,
+
+    /// Middleware called for each command sent over sessions
+    middleware: session::MiddlewareStack,
+
+    /// Latency and error statistics for commands sent by this client and
+    /// its clones
+    stats: Arc<Mutex<Stats>>,
+
+    /// Client-side journal of mutating operations (if enabled)
+    journal: Option<Arc<Mutex<Journal>>>,
+
+    /// AES and AES-CMAC implementation used for secure channels
+    crypto_backend: Arc<dyn session::CryptoBackend>,
+
+    /// RNG used to generate host challenges when opening sessions
+    challenge_rng: Arc<dyn session::ChallengeRng>,
+
+    /// Sessions (and their credentials) held by this client and its clones,
+    /// keyed by authentication key ID
+    key_sessions: Arc<Mutex<BTreeMap<object::Id, KeySession>>>,
+}
+
+/// Session state for a particular authentication key
+#[derive(Clone)]
+struct KeySession {
+    /// Encrypted session with the HSM (if we have one open)
+    session: Arc<Mutex<Option<Session>>>,
+
+    /// Cached `Credentials` for reconnecting closed sessions
+    credentials: Arc<Mutex<Option<Credentials>>>,
 }
 
 impl Client {
@@ -81,33 +169,288 @@ impl Client {
         credentials: Credentials,
         reconnect: bool,
     ) -> Result<Self, Error> {
-        let mut client = Self::create(connector, credentials)?;
+        let client = Self::open_lazy(connector, credentials, reconnect)?;
         client.connect()?;
+        Ok(client)
+    }
 
-        // Clear credentials if reconnecting has been disabled
-        if !reconnect {
-            client.credentials = None;
-        }
-
+    /// Create a `yubihsm::Client` like [`Client::open`], but defer connecting
+    /// to the HSM and opening a session until the first command is sent (or
+    /// `connect()` is called), e.g. for services which must start before the
+    /// HSM is reachable.
+    ///
+    /// If `reconnect` is disabled, the credentials are retained until the
+    /// first session has been opened.
+    pub fn open_lazy(
+        connector: Connector,
+        credentials: Credentials,
+        reconnect: bool,
+    ) -> Result<Self, Error> {
+        let mut client = Self::create(connector, credentials)?;
+        client.reconnect = reconnect;
         Ok(client)
     }
 
     /// Create a `yubihsm::Client`, but defer connecting until `connect()` is called.
     pub fn create(connector: Connector, credentials: Credentials) -> Result<Self, Error> {
+        let authentication_key_id = credentials.authentication_key_id;
+        let key_session = KeySession {
+            session: Arc::new(Mutex::new(None)),
+            credentials: Arc::new(Mutex::new(Some(credentials))),
+        };
+
+        let stats = Arc::new(Mutex::new(Stats::default()));
+
         let client = Self {
             connector,
-            session: Arc::new(Mutex::new(None)),
-            credentials: Some(credentials),
+            session: Arc::clone(&key_session.session),
+            credentials: Arc::clone(&key_session.credentials),
+            reconnect: true,
+            reauthenticate: true,
+            retry_policy: None,
+            command_limiter: None,
+            command_timeout: None,
+            session_timeout: session::Timeout::default(),
+            session_event_handler: None,
+            middleware: Arc::new([Arc::new(StatsRecorder(Arc::clone(&stats)))]),
+            stats,
+            journal: None,
+            crypto_backend: Arc::new(session::RustCryptoBackend),
+            challenge_rng: Arc::new(OsRng),
+            key_sessions: Arc::new(Mutex::new(BTreeMap::from([(
+                authentication_key_id,
+                key_session,
+            )]))),
         };
 
         Ok(client)
     }
 
+    /// Configure a `yubihsm::Client` using a [`ClientBuilder`]
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
     /// Borrow this client's YubiHSM connector (which is `Clone`able)
     pub fn connector(&self) -> &Connector {
         &self.connector
     }
 
+    /// Record all mutating operations performed by this client (and any
+    /// clones made after this call) in the given [`Journal`].
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(Arc::new(Mutex::new(journal)));
+    }
+
+    /// Number of operations performed by this client (and its clones) which
+    /// couldn't be recorded in the journal, e.g. due to I/O errors, or
+    /// `None` if no journal is set.
+    ///
+    /// Such failures don't cause the operations themselves to fail (the HSM
+    /// has already performed them), so this should be monitored to ensure
+    /// the journal is complete.
+    pub fn journal_failures(&self) -> Option<u64> {
+        self.journal
+            .as_deref()
+            .map(|journal| lock(journal).failures())
+    }
+
+    /// Use the given AES and AES-CMAC implementation for sessions opened by
+    /// this client (and any clones made after this call), e.g. to use a
+    /// validated cryptographic module. Sessions which are already open keep
+    /// using the previous backend.
+    pub fn set_crypto_backend(&mut self, backend: impl session::CryptoBackend + 'static) {
+        self.crypto_backend = Arc::new(backend);
+    }
+
+    /// Generate host challenges for sessions opened by this client (and any
+    /// clones made after this call) using the given RNG instead of `OsRng`.
+    pub fn set_challenge_rng(&mut self, rng: impl session::ChallengeRng + 'static) {
+        self.challenge_rng = Arc::new(rng);
+    }
+
+    /// Notify the given handler of changes in the state of sessions opened by
+    /// this client (and any clones made after this call), e.g. to emit
+    /// metrics or alerts. Sessions which are already open aren't affected.
+    ///
+    /// The handler is called while the session is locked, so it shouldn't
+    /// block or use the client.
+    pub fn set_session_event_handler(
+        &mut self,
+        handler: impl Fn(&session::Event) + Send + Sync + 'static,
+    ) {
+        self.session_event_handler = Some(Arc::new(handler));
+    }
+
+    /// Get a snapshot of the latency and error statistics for each type of
+    /// command sent by this client and its clones
+    pub fn stats(&self) -> Stats {
+        lock(&self.stats).clone()
+    }
+
+    /// Call the given middleware for each command sent by this client (and
+    /// any clones made after this call), after any middleware which was
+    /// previously added, e.g. to audit commands or enforce policies.
+    ///
+    /// The middleware is applied to each command as it's sent over the
+    /// client's session (like the command timeout), so no commands sent by
+    /// this client after this call bypass the middleware, even over a
+    /// session shared with (or reopened by) older clones, whose commands
+    /// don't pass through it.
+    pub fn add_middleware(&mut self, middleware: impl session::Middleware + 'static) {
+        let middleware: Arc<dyn session::Middleware> = Arc::new(middleware);
+        self.middleware = self
+            .middleware
+            .iter()
+            .cloned()
+            .chain(Some(middleware))
+            .collect();
+    }
+
+    /// Enable or disable transparently reauthenticating when the HSM reports
+    /// that a session has expired (enabled by default). If enabled, commands
+    /// sent over an expired session are retried once over a new session,
+    /// which requires `reconnect` to be enabled. Otherwise an error of kind
+    /// [`ErrorKind::ClosedSessionError`] is returned.
+    pub fn set_reauthenticate(&mut self, enabled: bool) {
+        self.reauthenticate = enabled;
+    }
+
+    /// Resend commands which are safe to perform more than once (e.g. reads
+    /// and signatures) if they fail due to transient errors, according to
+    /// the given policy (disabled by default). Commands which change the
+    /// state of the HSM are never resent.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry_policy = policy.map(Arc::new);
+    }
+
+    /// Allow at most `limit` commands sent by this client (and any clones
+    /// made after this call) to be in flight at once, or remove the limit if
+    /// `None`. Commands beyond the limit wait for others to complete.
+    ///
+    /// Clones of a client already share a session which serializes their
+    /// commands, so this is mainly useful for limiting clients with separate
+    /// sessions, e.g. via [`SessionPool::set_concurrency_limit`].
+    pub fn set_concurrency_limit(&mut self, limit: Option<usize>) -> Result<(), Error> {
+        if let Some(limit) = limit {
+            ensure!(
+                limit > 0,
+                ErrorKind::ConfigError,
+                "concurrency limit must be nonzero"
+            );
+        }
+
+        self.command_limiter = limit.map(|limit| Arc::new(CommandLimiter::new(limit)));
+        Ok(())
+    }
+
+    /// Maximum number of commands this client allows in flight at once (if
+    /// limited)
+    pub fn concurrency_limit(&self) -> Option<usize> {
+        self.command_limiter.as_deref().map(CommandLimiter::limit)
+    }
+
+    /// Share the given limit on commands in flight with other clients
+    pub(crate) fn set_command_limiter(&mut self, limiter: Arc<CommandLimiter>) {
+        self.command_limiter = Some(limiter);
+    }
+
+    /// Get a client which waits up to `timeout` for the HSM to respond to
+    /// each command, instead of the connector's configured timeout, e.g. for
+    /// slow operations like RSA key generation:
+    ///
+    /// ```ignore
+    /// client
+    ///     .with_timeout(Duration::from_secs(60))
+    ///     .generate_asymmetric_key(key_id, label, domains, capabilities, algorithm)?;
+    /// ```
+    ///
+    /// The returned client shares this client's sessions. The USB and HTTP
+    /// connectors support per-command timeouts: other connections use their
+    /// configured timeout.
+    pub fn with_timeout(&self, timeout: Duration) -> Client {
+        Client {
+            command_timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Open an additional session using the given credentials (e.g. for an
+    /// auditor alongside an operator), returning a client which sends
+    /// commands over it.
+    ///
+    /// The session is held alongside this client's own session, and shares
+    /// its connector and configuration. Clients for each session can be
+    /// obtained using [`Client::for_key`].
+    pub fn add_session(&self, credentials: Credentials) -> Result<Client, Error> {
+        let authentication_key_id = credentials.authentication_key_id;
+        let key_session = KeySession {
+            session: Arc::new(Mutex::new(None)),
+            credentials: Arc::new(Mutex::new(Some(credentials))),
+        };
+
+        let client = Client {
+            session: Arc::clone(&key_session.session),
+            credentials: Arc::clone(&key_session.credentials),
+            ..self.clone()
+        };
+
+        {
+            let mut key_sessions = lock(&self.key_sessions);
+
+            ensure!(
+                !key_sessions.contains_key(&authentication_key_id),
+                ErrorKind::CreateFailed,
+                "already have a session for authentication key {}",
+                authentication_key_id
+            );
+
+            key_sessions.insert(authentication_key_id, key_session);
+        }
+
+        if let Err(e) = client.connect() {
+            lock(&self.key_sessions).remove(&authentication_key_id);
+
+            return Err(e);
+        }
+
+        Ok(client)
+    }
+
+    /// Get a client which sends commands over the session for the given
+    /// authentication key (i.e. the one this client was created with, or one
+    /// added with [`Client::add_session`]).
+    pub fn for_key(&self, authentication_key_id: object::Id) -> Result<Client, Error> {
+        let key_sessions = lock(&self.key_sessions);
+        let key_session = key_sessions.get(&authentication_key_id).ok_or_else(|| {
+            format_err!(
+                ErrorKind::ClosedSessionError,
+                "no session for authentication key {}",
+                authentication_key_id
+            )
+        })?;
+
+        Ok(Client {
+            session: Arc::clone(&key_session.session),
+            credentials: Arc::clone(&key_session.credentials),
+            ..self.clone()
+        })
+    }
+
+    /// IDs of the authentication keys this client holds sessions for
+    pub fn authentication_key_ids(&self) -> Vec<object::Id> {
+        lock(&self.key_sessions).keys().copied().collect()
+    }
+
+    /// Ping the HSM over this client's session every `interval` from a
+    /// background thread until the returned [`Heartbeat`] is dropped, so
+    /// low-traffic clients don't have to reopen sessions which expired due
+    /// to inactivity. The interval should be shorter than the HSM's session
+    /// timeout (30 seconds).
+    pub fn start_heartbeat(&self, interval: Duration) -> Heartbeat {
+        Heartbeat::start(self.clone(), interval)
+    }
+
     /// Connect to the HSM (idempotently, i.e. returns success if we have
     /// an open connection already)
     pub fn connect(&self) -> Result<(), Error> {
@@ -115,32 +458,126 @@ impl Client {
         Ok(())
     }
 
+    /// Close the current session (if any), returning an error if the HSM
+    /// couldn't be told to close it. The next command opens a new session.
+    ///
+    /// Sessions are also closed on a best-effort basis when dropped, i.e.
+    /// when the last clone of the client sharing them is dropped.
+    pub fn close(&self) -> Result<(), Error> {
+        match lock(&self.session).take() {
+            Some(session) => Ok(session.close()?),
+            None => Ok(()),
+        }
+    }
+
     /// Get current `Session` (either opening a new one or returning an already
     /// open one).
     pub fn session(&self) -> Result<session::Guard<'_>, Error> {
-        // TODO(tarcieri): handle PoisonError better?
-        let mut session_mutex_guard = self.session.lock().unwrap();
+        let mut session_mutex_guard = lock(&self.session);
+
+        // A panic on another thread may have interrupted a command
+        if let Some(session) = session_mutex_guard.as_mut() {
+            if session.is_interrupted() {
+                session.abort();
+            }
+        }
 
         if let Some(session) = session_mutex_guard.as_ref() {
-            if session.is_open() {
-                return Ok(session::Guard::new(session_mutex_guard));
+            if session.is_open() && !session.needs_rekey() {
+                return Ok(self.session_guard(session_mutex_guard));
+            }
+        }
+
+        // Close sessions which are about to exhaust their message counter
+        // rather than letting them fail mid-workload, so the HSM's session
+        // slot is released before a new session is opened below
+        if let Some(session) = session_mutex_guard.take() {
+            if session.is_open() && session.needs_rekey() {
+                debug!("session {} due for rekeying", session.id().to_u8());
+
+                if let Err(e) = session.close() {
+                    debug!("error closing session for rekeying: {}", e);
+                }
+            } else {
+                session.discard();
             }
         }
 
         // If we don't have an open session, create a new one
-        let session = Session::open(
-            self.connector.clone(),
-            self.credentials.as_ref().ok_or_else(|| {
-                format_err!(
-                    ErrorKind::AuthenticationError,
-                    "session reconnection disabled"
-                )
-            })?,
-            session::Timeout::default(),
-        )?;
+        let mut credentials = lock(&self.credentials);
+        let session = self.open_session(credentials.as_ref().ok_or_else(|| {
+            format_err!(
+                ErrorKind::AuthenticationError,
+                "session reconnection disabled"
+            )
+        })?)?;
+
+        // Clear credentials if reconnecting has been disabled
+        if !self.reconnect {
+            *credentials = None;
+        }
 
         *session_mutex_guard = Some(session);
-        Ok(session::Guard::new(session_mutex_guard))
+        Ok(self.session_guard(session_mutex_guard))
+    }
+
+    /// Wrap a locked session, applying this client's command timeout to it
+    fn session_guard<'a>(
+        &self,
+        session_mutex_guard: MutexGuard<'a, Option<Session>>,
+    ) -> session::Guard<'a> {
+        let mut guard = session::Guard::new(session_mutex_guard);
+        guard.set_command_timeout(self.command_timeout);
+        guard.set_middleware(Arc::clone(&self.middleware));
+        guard
+    }
+
+    /// Open a new session, retrying according to the connector's reconnect
+    /// policy (if any) when connecting to the HSM fails
+    fn open_session(&self, credentials: &Credentials) -> Result<Session, Error> {
+        let policy = self.connector.reconnect_policy();
+        let mut attempt = 0;
+
+        loop {
+            match Session::open(
+                self.connector.clone(),
+                credentials,
+                self.session_timeout,
+                Arc::clone(&self.crypto_backend),
+                &*self.challenge_rng,
+            ) {
+                Err(e) if policy.is_some_and(|policy| policy.should_retry(attempt, &e)) => {
+                    let delay = policy.unwrap().backoff.delay(attempt);
+                    debug!("error opening session ({}), retrying in {:?}", e, delay);
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                Ok(mut session) => {
+                    session.set_event_handler(self.session_event_handler.clone());
+                    self.emit_session_event(session::Event::Opened {
+                        session_id: session.id(),
+                        authentication_key_id: credentials.authentication_key_id,
+                    });
+                    return Ok(session);
+                }
+                Err(e) => {
+                    if *e.kind() == session::ErrorKind::AuthenticationError {
+                        self.emit_session_event(session::Event::AuthenticationFailed {
+                            authentication_key_id: credentials.authentication_key_id,
+                        });
+                    }
+
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    /// Report a session event to the event handler (if any)
+    fn emit_session_event(&self, event: session::Event) {
+        if let Some(handler) = &self.session_event_handler {
+            handler(&event);
+        }
     }
 
     /// Ping the HSM, ensuring we have a live connection and returning the
@@ -163,9 +600,53 @@ impl Client {
 
     /// Encrypt a command, send it to the HSM, then read and decrypt the response.
     fn send_command<T: Command>(&self, command: T) -> Result<T::ResponseType, Error> {
+        let result = self.execute_command(&command);
+
+        if T::COMMAND_CODE.is_mutating() {
+            self.journal_operation(
+                T::COMMAND_CODE,
+                command.object_ids(),
+                result.as_ref().map(|_| ()),
+            );
+        }
+
+        result
+    }
+
+    /// Send a command over the current session, rekeying it if needed.
+    fn execute_command<T: Command>(&self, command: &T) -> Result<T::ResponseType, Error> {
+        let policy = match &self.retry_policy {
+            Some(policy) => policy,
+            None => return self.execute_command_once(command),
+        };
+
+        let mut attempt = 0;
+
+        loop {
+            match self.execute_command_once(command) {
+                Err(err) if policy.should_retry(T::COMMAND_CODE, attempt, &err) => {
+                    let delay = policy.backoff.delay(attempt);
+                    debug!(
+                        "{:?} failed ({}); retrying in {:?}",
+                        T::COMMAND_CODE,
+                        err,
+                        delay
+                    );
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a command over the current session (rekeying or reauthenticating
+    /// once if needed) without applying the retry policy
+    fn execute_command_once<T: Command>(&self, command: &T) -> Result<T::ResponseType, Error> {
+        let _permit = self.command_limiter.as_deref().map(CommandLimiter::acquire);
         let mut session = self.session()?;
 
-        match session.send_command(&command) {
+        match session.send_command(command) {
             Ok(response) => Ok(response),
             Err(err) if *err.kind() == session::ErrorKind::CommandLimitExceeded => {
                 // If we encounter this, we've exceeded the maximum number of
@@ -177,12 +658,123 @@ impl Client {
 
                 // Attempt to initiate a new session and retry the command.
                 // (the original command was never sent in this case)
-                Ok(self.session()?.send_command(&command)?)
+                Ok(self.session()?.send_command(command)?)
+            }
+            Err(err)
+                if *err.kind() == session::ErrorKind::ExpiredError
+                    && self.reauthenticate
+                    && self.reconnect =>
+            {
+                debug!("{}; reauthenticating", err);
+
+                // The HSM rejected the session before executing the command,
+                // so it's safe to retry it (once) over a new session
+                drop(session);
+                Ok(self.session()?.send_command(command)?)
             }
             Err(err) => Err(err.into()),
         }
     }
 
+    /// Send several read-only commands of the same type in a batch (see
+    /// `Session::send_commands`), returning their responses in order.
+    ///
+    /// Batches are split across sessions if they would exhaust the current
+    /// session's message counter. Like single commands, the remainder of a
+    /// batch is retried once over a new session if the session expires.
+    fn send_commands<T: Command>(&self, commands: &[T]) -> Result<Vec<T::ResponseType>, Error> {
+        debug_assert!(!T::COMMAND_CODE.is_mutating());
+
+        let _permit = self.command_limiter.as_deref().map(CommandLimiter::acquire);
+        let mut responses = Vec::with_capacity(commands.len());
+        let mut reauthenticated = false;
+
+        while responses.len() < commands.len() {
+            let mut session = self.session()?;
+            let pending = &commands[responses.len()..];
+            let batch = &pending[..pending.len().min(session.commands_until_rekey())];
+
+            match session.send_commands(batch, &mut responses) {
+                Ok(()) => (),
+                Err(err)
+                    if *err.kind() == session::ErrorKind::ExpiredError
+                        && self.reauthenticate
+                        && self.reconnect
+                        && !reauthenticated =>
+                {
+                    debug!("{}; reauthenticating", err);
+                    reauthenticated = true;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(responses)
+    }
+
+    /// Record a mutating operation in the journal (if enabled), signing the
+    /// journal head if a checkpoint is due.
+    ///
+    /// The HSM has already performed the operation, so failing to record it
+    /// doesn't replace the command's result: the failure is logged and
+    /// counted instead (see [`Client::journal_failures`]).
+    fn journal_operation(
+        &self,
+        command: command::Code,
+        object_ids: Vec<object::Id>,
+        result: Result<(), &Error>,
+    ) {
+        let journal = match &self.journal {
+            Some(journal) => journal,
+            None => return,
+        };
+
+        let outcome = match result {
+            Ok(()) => journal::Outcome::Success,
+            Err(err) => journal::Outcome::Failure(err.to_string()),
+        };
+
+        if let Err(e) = self.append_to_journal(
+            journal,
+            journal::Event::Operation {
+                command,
+                object_ids,
+                outcome,
+            },
+        ) {
+            error!("error recording {:?} in the journal: {}", command, e);
+            lock(journal).record_failure();
+        }
+    }
+
+    /// Append an event to the journal, signing the journal head if a
+    /// checkpoint is due
+    fn append_to_journal(
+        &self,
+        journal: &Mutex<Journal>,
+        event: journal::Event,
+    ) -> Result<(), journal::Error> {
+        let checkpoint = lock(journal).append(event)?;
+
+        // Sign the journal head without holding the journal lock, as signing
+        // requires sending a command to the HSM
+        if let Some((signer, head)) = checkpoint {
+            let signature = match signer {
+                journal::Signer::Ed25519(key_id) => self
+                    .sign_ed25519(key_id, head.as_ref())
+                    .map(|sig| sig.to_bytes().to_vec()),
+                journal::Signer::Ecdsa(key_id) => {
+                    self.sign_ecdsa_prehash_raw(key_id, head.as_ref())
+                }
+            }
+            .map_err(|e| journal::Error::from(journal::ErrorKind::SigningFailed.context(e)))?;
+
+            lock(journal).checkpoint(signer, head, signature)?;
+        }
+
+        Ok(())
+    }
+
     //
     // HSM Commands
     // <https://developers.yubico.com/YubiHSM2/Commands/>
@@ -196,6 +788,73 @@ impl Client {
         Ok(())
     }
 
+    /// Change the authentication key used to open this client's session,
+    /// e.g. to replace the default authentication key (`0x0001`), which must
+    /// have the `CHANGE_AUTHENTICATION_KEY` capability.
+    ///
+    /// The session remains open, and the client's cached credentials are
+    /// updated so it (and its clones) reopen sessions using the new key.
+    ///
+    /// Fails with a protocol error without contacting the HSM if the key's
+    /// size doesn't match the algorithm.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Change_Authentication_Key.html>
+    pub fn change_authentication_key<K>(
+        &self,
+        key_id: object::Id,
+        algorithm: authentication::Algorithm,
+        authentication_key: K,
+    ) -> Result<object::Id, Error>
+    where
+        K: Into<authentication::Key>,
+    {
+        let authentication_key = authentication_key.into();
+
+        ensure!(
+            authentication_key.size() == algorithm.key_len(),
+            ErrorKind::ProtocolError,
+            "invalid key length for {:?}: {} (expected {})",
+            algorithm,
+            authentication_key.size(),
+            algorithm.key_len()
+        );
+
+        let key_id = self
+            .send_command(ChangeAuthenticationKeyCommand {
+                key_id,
+                algorithm,
+                authentication_key: authentication_key.clone(),
+            })?
+            .key_id;
+
+        if let Some(credentials) = lock(&self.credentials).as_mut() {
+            if credentials.authentication_key_id == key_id {
+                credentials.authentication_key = authentication_key;
+            }
+        }
+
+        Ok(key_id)
+    }
+
+    /// Create an OTP AEAD from the AES key and private ID of a Yubico OTP
+    /// token, encrypted under the given OTP AEAD key.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Create_Otp_Aead.html>
+    pub fn create_otp_aead(
+        &self,
+        key_id: object::Id,
+        key: [u8; otp::KEY_SIZE],
+        private_id: [u8; otp::PRIVATE_ID_SIZE],
+    ) -> Result<otp::Aead, Error> {
+        Ok(self
+            .send_command(CreateOtpAeadCommand {
+                key_id,
+                key,
+                private_id,
+            })?
+            .0)
+    }
+
     /// Decrypt data encrypted with RSA-OAEP
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Decrypt_Oaep.html>
@@ -219,35 +878,108 @@ impl Client {
             .into())
     }
 
+    /// Decrypt data encrypted with RSA-OAEP using the given parameters
+    /// (MGF1 hash algorithm and label)
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Decrypt_Oaep.html>
+    pub fn decrypt_oaep_with_params<T>(
+        &self,
+        key_id: object::Id,
+        params: &rsa::oaep::Params,
+        data: T,
+    ) -> Result<rsa::oaep::DecryptedData, Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.decrypt_oaep(
+            key_id,
+            params.mgf1_algorithm,
+            data,
+            params.label_hash.clone(),
+        )
+    }
+
+    /// Decrypt a Yubico OTP using the AEAD of the token which generated it,
+    /// returning its counters and timestamp.
+    ///
+    /// Fails with a device error if the OTP wasn't generated by the token.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Decrypt_Otp.html>
+    pub fn decrypt_otp(
+        &self,
+        key_id: object::Id,
+        aead: &otp::Aead,
+        otp: [u8; otp::OTP_SIZE],
+    ) -> Result<otp::Counters, Error> {
+        Ok(self
+            .send_command(DecryptOtpCommand {
+                key_id,
+                aead: aead.clone(),
+                otp,
+            })?
+            .0)
+    }
+
+    /// Decrypt data encrypted with RSAES-PKCS#1v1.5
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Decrypt_Pkcs1.html>
+    pub fn decrypt_pkcs1<T>(
+        &self,
+        key_id: object::Id,
+        data: T,
+    ) -> Result<rsa::pkcs1::DecryptedData, Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        Ok(self
+            .send_command(DecryptPkcs1Command {
+                key_id,
+                data: data.into(),
+            })?
+            .into())
+    }
+
     /// Delete an object of the given ID and type.
     ///
+    /// Returns [`object::Deletion::NotFound`] rather than an error if there
+    /// is no such object (e.g. it was already deleted), so deleting objects
+    /// is idempotent. Other device errors, such as
+    /// [`device::ErrorKind::InsufficientPermissions`] if the authentication
+    /// key lacks the type's [`object::Type::delete_capability`], are returned
+    /// as errors.
+    ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Delete_Object.html>
     pub fn delete_object(
         &self,
         object_id: object::Id,
         object_type: object::Type,
-    ) -> Result<(), Error> {
-        self.send_command(DeleteObjectCommand {
+    ) -> Result<object::Deletion, Error> {
+        let result = self.send_command(DeleteObjectCommand {
             object_id,
             object_type,
-        })?;
-        Ok(())
+        });
+
+        match result {
+            Ok(_) => Ok(object::Deletion::Deleted),
+            Err(e) if e.device_error() == Some(device::ErrorKind::ObjectNotFound) => {
+                Ok(object::Deletion::NotFound)
+            }
+            Err(e) => Err(e),
+        }
     }
 
-    /// Elliptic Curve Diffie-Hellman: derive a shared secret via key exchange.
+    /// Elliptic Curve Diffie-Hellman: derive a shared secret via key exchange
+    /// between the given HSM-resident EC key and a peer's public key.
     ///
-    /// **WARNING**: This functionality has not been tested and has not yet been
-    /// confirmed to actually work! USE AT YOUR OWN RISK!
-    ///
-    /// You will need to enable the `untested` cargo feature to use it.
+    /// The shared secret is the x-coordinate of the shared point, and should
+    /// be passed through a key derivation function before use.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Derive_Ecdh.html>
-    #[cfg(feature = "untested")]
     pub fn derive_ecdh(
         &self,
         key_id: object::Id,
         public_key: ecdh::UncompressedPoint,
-    ) -> Result<ecdh::UncompressedPoint, Error> {
+    ) -> Result<ecdh::SharedSecret, Error> {
         Ok(self
             .send_command(DeriveEcdhCommand { key_id, public_key })?
             .into())
@@ -274,6 +1006,65 @@ impl Client {
             .0)
     }
 
+    /// Send a command this crate doesn't implement (e.g. one added in newer
+    /// firmware) over the current session, returning the data of the HSM's
+    /// response. The command data must already be serialized in the format
+    /// the HSM expects.
+    ///
+    /// Raw commands bypass the client's retry policy. Mutating commands
+    /// (e.g. `DeleteObject`) are recorded in the client's journal (if any),
+    /// although without the IDs of the objects they operate on, as the
+    /// command data is opaque to the client. Commands which manage the
+    /// session itself (e.g. `CloseSession`) are rejected.
+    pub fn send_raw_command(
+        &self,
+        command_type: command::Code,
+        command_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        ensure!(
+            !matches!(
+                command_type,
+                command::Code::CreateSession
+                    | command::Code::AuthenticateSession
+                    | command::Code::SessionMessage
+                    | command::Code::CloseSession
+            ),
+            ErrorKind::ProtocolError,
+            "{:?} can't be sent as a raw command",
+            command_type
+        );
+
+        let result = self.execute_raw_command(command_type, command_data);
+
+        if command_type.is_mutating() {
+            self.journal_operation(command_type, vec![], result.as_ref().map(|_| ()));
+        }
+
+        result
+    }
+
+    /// Send a raw command over the current session, rekeying it if needed.
+    fn execute_raw_command(
+        &self,
+        command_type: command::Code,
+        command_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let _permit = self.command_limiter.as_deref().map(CommandLimiter::acquire);
+        let mut session = self.session()?;
+
+        match session.send_raw_command(command_type, command_data) {
+            Err(err) if *err.kind() == session::ErrorKind::CommandLimitExceeded => {
+                // The command was never sent, so rekey and send it over a
+                // new session (see `execute_command_once`)
+                drop(session);
+                Ok(self
+                    .session()?
+                    .send_raw_command(command_type, command_data)?)
+            }
+            result => Ok(result?),
+        }
+    }
+
     /// Export an encrypted object from the HSM using the given key-wrapping key.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Export_Wrapped.html>
@@ -294,6 +1085,10 @@ impl Client {
 
     /// Generate a new asymmetric key within the HSM.
     ///
+    /// Fails with a protocol error without contacting the HSM if any of the
+    /// given capabilities are for operations which keys of the given
+    /// algorithm can't perform (e.g. `SIGN_ECDSA` for an RSA key).
+    ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Generate_Asymmetric_Key.html>
     pub fn generate_asymmetric_key(
         &self,
@@ -303,6 +1098,16 @@ impl Client {
         capabilities: Capability,
         algorithm: asymmetric::Algorithm,
     ) -> Result<object::Id, Error> {
+        let unsupported = algorithm.unsupported_capabilities(capabilities);
+
+        ensure!(
+            unsupported.is_empty(),
+            ErrorKind::ProtocolError,
+            "capabilities not supported by {:?} keys: {:?}",
+            algorithm,
+            unsupported
+        );
+
         Ok(self
             .send_command(GenAsymmetricKeyCommand(generate::Params {
                 key_id,
@@ -336,6 +1141,35 @@ impl Client {
             .key_id)
     }
 
+    /// Generate a new OTP AEAD key within the HSM.
+    ///
+    /// The nonce ID is included in the nonces of the AEADs encrypted with the
+    /// key, and should be unique for each OTP AEAD key.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Generate_Otp_Aead_Key.html>
+    pub fn generate_otp_aead_key(
+        &self,
+        key_id: object::Id,
+        label: object::Label,
+        domains: Domain,
+        capabilities: Capability,
+        algorithm: otp::Algorithm,
+        nonce_id: u32,
+    ) -> Result<object::Id, Error> {
+        Ok(self
+            .send_command(GenOtpAeadKeyCommand {
+                params: generate::Params {
+                    key_id,
+                    label,
+                    domains,
+                    capabilities,
+                    algorithm: algorithm.into(),
+                },
+                nonce_id,
+            })?
+            .key_id)
+    }
+
     /// Generate a new wrap key within the HSM.
     ///
     /// Delegated capabilities are the set of `Capability` bits that an object is allowed to have
@@ -372,6 +1206,22 @@ impl Client {
         self.send_command(GetLogEntriesCommand {})
     }
 
+    /// Get the unconsumed audit log entries from the HSM, then mark them as
+    /// consumed (with [`Client::set_log_index`]) so the HSM can reuse their
+    /// space, e.g. to periodically drain the log into external storage.
+    ///
+    /// Use [`LogEntries::verify`] with the digest of the last entry from the
+    /// previous call to check that no entries were dropped or altered.
+    pub fn consume_log_entries(&self) -> Result<LogEntries, Error> {
+        let log_entries = self.get_log_entries()?;
+
+        if let Some(last) = log_entries.entries.last() {
+            self.set_log_index(last.item)?;
+        }
+
+        Ok(log_entries)
+    }
+
     /// Get information about an object.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Object_Info.html>
@@ -388,6 +1238,27 @@ impl Client {
             .0)
     }
 
+    /// Get an X.509 certificate (in DER format) stored in the HSM as an
+    /// opaque object, e.g. by [`Client::put_certificate`].
+    ///
+    /// Fails with a protocol error if the opaque object isn't an X.509
+    /// certificate.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Opaque.html>
+    pub fn get_certificate(&self, object_id: object::Id) -> Result<Vec<u8>, Error> {
+        let info = self.get_object_info(object_id, object::Type::Opaque)?;
+
+        ensure!(
+            info.algorithm.opaque() == Some(opaque::Algorithm::X509Certificate),
+            ErrorKind::ProtocolError,
+            "opaque object 0x{:04x} is not an X.509 certificate: {:?}",
+            object_id,
+            info.algorithm
+        );
+
+        self.get_opaque(object_id)
+    }
+
     /// Get an opaque object stored in the HSM.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Opaque.html>
@@ -485,6 +1356,17 @@ impl Client {
         Ok(self.send_command(GetPublicKeyCommand { key_id })?.into())
     }
 
+    /// Get the public key for an asymmetric key stored on the device, parsed
+    /// according to its algorithm.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Public_Key.html>
+    pub fn get_typed_public_key(
+        &self,
+        key_id: object::Id,
+    ) -> Result<asymmetric::TypedPublicKey, Error> {
+        self.get_public_key(key_id)?.typed()
+    }
+
     /// Get storage info (i.e. currently free storage) from the HSM device.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Get_Storage_Info.html>
@@ -542,6 +1424,10 @@ impl Client {
 
     /// Put an existing asymmetric key into the HSM.
     ///
+    /// The key bytes are the primes `p` and `q` of RSA keys, the secret scalar
+    /// of EC keys, or the seed of Ed25519 keys. They're validated before being
+    /// sent, and zeroized once the command has been sent (or rejected).
+    ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Put_Asymmetric.html>
     pub fn put_asymmetric_key<K>(
         &self,
@@ -555,7 +1441,7 @@ impl Client {
     where
         K: Into<Vec<u8>>,
     {
-        let data = key_bytes.into();
+        let mut data = Zeroizing::new(key_bytes.into());
 
         if data.len() != algorithm.key_len() {
             fail!(
@@ -567,6 +1453,38 @@ impl Client {
             );
         }
 
+        ensure!(
+            data.iter().any(|&byte| byte != 0),
+            ErrorKind::ProtocolError,
+            "invalid {:?} key: all bytes are zero",
+            algorithm
+        );
+
+        if algorithm.is_rsa() {
+            let (p, q) = data.split_at(data.len() / 2);
+
+            // Both primes must be odd and exactly half the size of the modulus
+            ensure!(
+                [p, q]
+                    .iter()
+                    .all(|prime| prime[0] & 0x80 != 0 && prime[prime.len() - 1] & 1 != 0),
+                ErrorKind::ProtocolError,
+                "invalid {:?} key: p and q must be odd {}-bit primes",
+                algorithm,
+                p.len() * 8
+            );
+        }
+
+        let unsupported = algorithm.unsupported_capabilities(capabilities);
+
+        ensure!(
+            unsupported.is_empty(),
+            ErrorKind::ProtocolError,
+            "capabilities not supported by {:?} keys: {:?}",
+            algorithm,
+            unsupported
+        );
+
         Ok(self
             .send_command(PutAsymmetricKeyCommand {
                 params: object::put::Params {
@@ -576,13 +1494,17 @@ impl Client {
                     capabilities,
                     algorithm: algorithm.into(),
                 },
-                data,
+                data: mem::take(&mut *data),
             })?
             .key_id)
     }
 
     /// Put an existing `authentication::Key` into the HSM.
     ///
+    /// Fails with a protocol error without contacting the HSM if the key's
+    /// size doesn't match the algorithm (i.e. AES-256 keys can't be put into
+    /// a YubiHSM 2, which only supports `YubicoAes` AES-128 keys).
+    ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Put_Authentication_Key.html>
     pub fn put_authentication_key<K>(
         &self,
@@ -597,6 +1519,17 @@ impl Client {
     where
         K: Into<authentication::Key>,
     {
+        let authentication_key = authentication_key.into();
+
+        ensure!(
+            authentication_key.size() == algorithm.key_len(),
+            ErrorKind::ProtocolError,
+            "invalid key length for {:?}: {} (expected {})",
+            algorithm,
+            authentication_key.size(),
+            algorithm.key_len()
+        );
+
         Ok(self
             .send_command(PutAuthenticationKeyCommand {
                 params: object::put::Params {
@@ -607,11 +1540,56 @@ impl Client {
                     algorithm: algorithm.into(),
                 },
                 delegated_capabilities,
-                authentication_key: authentication_key.into(),
+                authentication_key,
             })?
             .key_id)
     }
 
+    /// Put an X.509 certificate (in DER format) into the HSM as an opaque
+    /// object, e.g. to store the certificate for an asymmetric key alongside
+    /// it (optionally under the same object ID).
+    ///
+    /// Fails with a protocol error without contacting the HSM if the
+    /// certificate isn't a single DER-encoded `SEQUENCE`.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Put_Opaque.html>
+    pub fn put_certificate<C>(
+        &self,
+        object_id: object::Id,
+        label: object::Label,
+        domains: Domain,
+        capabilities: Capability,
+        certificate: C,
+    ) -> Result<object::Id, Error>
+    where
+        C: Into<Vec<u8>>,
+    {
+        let certificate = certificate.into();
+
+        match AnyRef::from_der(&certificate) {
+            Ok(any) if any.tag() == Tag::Sequence => (),
+            Ok(any) => fail!(
+                ErrorKind::ProtocolError,
+                "expected DER SEQUENCE for X.509 certificate, got {}",
+                any.tag()
+            ),
+            Err(e) => fail!(
+                ErrorKind::ProtocolError,
+                "malformed X.509 certificate: {}",
+                e
+            ),
+        }
+
+        self.put_opaque(
+            object_id,
+            label,
+            domains,
+            capabilities,
+            opaque::Algorithm::X509Certificate,
+            certificate,
+        )
+    }
+
     /// Put an existing HMAC key into the HSM.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Put_Hmac_Key.html>
@@ -685,6 +1663,9 @@ impl Client {
 
     /// Put an existing OTP AEAD key into the HSM.
     ///
+    /// The nonce ID is included in the nonces of the AEADs encrypted with the
+    /// key, and should be unique for each OTP AEAD key.
+    ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Put_Otp_Aead_Key.html>
     pub fn put_otp_aead_key<K>(
         &self,
@@ -693,6 +1674,7 @@ impl Client {
         domains: Domain,
         capabilities: Capability,
         algorithm: otp::Algorithm,
+        nonce_id: u32,
         key_bytes: K,
     ) -> Result<object::Id, Error>
     where
@@ -719,6 +1701,7 @@ impl Client {
                     capabilities,
                     algorithm: algorithm.into(),
                 },
+                nonce_id,
                 data,
             })?
             .key_id)
@@ -799,6 +1782,14 @@ impl Client {
             .object_id)
     }
 
+    /// Create an OTP AEAD from a random key and private ID, encrypted under
+    /// the given OTP AEAD key (e.g. to provision a new Yubico OTP token).
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Randomize_Otp_Aead.html>
+    pub fn randomize_otp_aead(&self, key_id: object::Id) -> Result<otp::Aead, Error> {
+        Ok(self.send_command(RandomizeOtpAeadCommand { key_id })?.0)
+    }
+
     /// Reset the HSM to a factory default state and reboot, clearing all
     /// stored objects and restoring the default auth key.
     ///
@@ -816,6 +1807,9 @@ impl Client {
 
         // Resetting the HSM invalidates our session
         session.abort();
+        drop(session);
+
+        self.journal_operation(command::Code::ResetDevice, vec![], Ok(()));
         Ok(())
     }
 
@@ -848,7 +1842,7 @@ impl Client {
         self.reset_device()?;
 
         // Configure default credentials
-        self.credentials = Some(Credentials::default());
+        *lock(&self.credentials) = Some(Credentials::default());
 
         let deadline = SystemTime::now() + timeout;
 
@@ -880,6 +1874,25 @@ impl Client {
         }
     }
 
+    /// Re-encrypt an OTP AEAD from one OTP AEAD key to another (e.g. when
+    /// rotating OTP AEAD keys).
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Rewrap_Otp_Aead.html>
+    pub fn rewrap_otp_aead(
+        &self,
+        from_key_id: object::Id,
+        to_key_id: object::Id,
+        aead: &otp::Aead,
+    ) -> Result<otp::Aead, Error> {
+        Ok(self
+            .send_command(RewrapOtpAeadCommand {
+                from_key_id,
+                to_key_id,
+                aead: aead.clone(),
+            })?
+            .0)
+    }
+
     /// Configure the audit policy settings for a particular command, e.g. auditing
     /// should be `On`, `Off`, or `Fix` (i.e. fixed permanently on).
     ///
@@ -985,6 +1998,42 @@ impl Client {
         .map(Into::into)
     }
 
+    /// Compute an ECDSA signature of the given digest, returning it in the
+    /// fixed-size `r || s` encoding for the given curve (i.e. twice the
+    /// curve's [`asymmetric::Algorithm::key_len`]) rather than ASN.1 DER.
+    ///
+    /// This is useful for curves which aren't supported by [`ecdsa::Signer`]
+    /// (e.g. P-521 and the brainpool curves).
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Ecdsa.html>
+    ///
+    /// # Security Warning
+    ///
+    /// This is a low-level ECDSA API with the same caveats as
+    /// [`Client::sign_ecdsa_prehash_raw`].
+    pub fn sign_ecdsa_prehash_fixed<T>(
+        &self,
+        key_id: object::Id,
+        curve: asymmetric::Algorithm,
+        digest: T,
+    ) -> Result<Vec<u8>, Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        ensure!(
+            curve.is_ecc(),
+            ErrorKind::ProtocolError,
+            "not an ECDSA curve: {:?}",
+            curve
+        );
+
+        self.send_command(SignEcdsaCommand {
+            key_id,
+            digest: digest.into(),
+        })?
+        .to_fixed(curve)
+    }
+
     /// Compute an Ed25519 signature with the given key ID.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Eddsa.html>
@@ -999,6 +2048,31 @@ impl Client {
         .signature()
     }
 
+    /// Compute Ed25519 signatures of several messages with the given key ID,
+    /// sending the commands as a batch over a single session to reduce
+    /// per-command overhead (see [`Client::sign_ed25519`]).
+    pub fn sign_ed25519_batch<T>(
+        &self,
+        key_id: object::Id,
+        messages: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<ed25519::Signature>, Error>
+    where
+        T: Into<Vec<u8>>,
+    {
+        let commands = messages
+            .into_iter()
+            .map(|data| SignEddsaCommand {
+                key_id,
+                data: data.into(),
+            })
+            .collect::<Vec<_>>();
+
+        self.send_commands(&commands)?
+            .into_iter()
+            .map(|response| response.signature())
+            .collect()
+    }
+
     /// Compute an HMAC tag of the given data with the given key ID.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Hmac.html>
@@ -1014,7 +2088,10 @@ impl Client {
             .into())
     }
 
-    /// Compute an RSASSA-PKCS#1v1.5 signature of the SHA-256 hash of the given data.
+    /// Compute an RSASSA-PKCS#1v1.5 signature of the `S` hash of the given data.
+    ///
+    /// The digest is sent to the HSM, which prepends the `DigestInfo` for the
+    /// hash function indicated by the digest's length.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pkcs1.html>
     pub(crate) fn sign_rsa_pkcs1v15<S: SignatureAlgorithm>(
@@ -1041,6 +2118,28 @@ impl Client {
         self.sign_rsa_pkcs1v15::<Sha256>(key_id, data)
     }
 
+    /// Compute an RSASSA-PKCS#1v1.5 signature of the SHA-384 hash of the given data.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pkcs1.html>
+    pub fn sign_rsa_pkcs1v15_sha384(
+        &self,
+        key_id: object::Id,
+        data: &[u8],
+    ) -> Result<rsa::pkcs1::Signature, Error> {
+        self.sign_rsa_pkcs1v15::<Sha384>(key_id, data)
+    }
+
+    /// Compute an RSASSA-PKCS#1v1.5 signature of the SHA-512 hash of the given data.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pkcs1.html>
+    pub fn sign_rsa_pkcs1v15_sha512(
+        &self,
+        key_id: object::Id,
+        data: &[u8],
+    ) -> Result<rsa::pkcs1::Signature, Error> {
+        self.sign_rsa_pkcs1v15::<Sha512>(key_id, data)
+    }
+
     /// Compute an RSASSA-PSS signature of the SHA-256 hash of the given data with the given key ID.
     ///
     /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pss.html>
@@ -1056,16 +2155,29 @@ impl Client {
             rsa::pss::MAX_MESSAGE_SIZE
         );
 
-        let mut hasher = S::new();
-        hasher.update(data);
-        let digest = hasher.finalize();
+        self.sign_rsa_pss_prehash(
+            key_id,
+            rsa::pss::Params::for_digest::<S>(),
+            S::digest(data).as_slice(),
+        )
+    }
 
+    /// Compute an RSASSA-PSS signature of the given digest (i.e. a precomputed
+    /// SHA-1/SHA-2 digest) with the given MGF1 hash algorithm and salt length.
+    ///
+    /// <https://developers.yubico.com/YubiHSM2/Commands/Sign_Pss.html>
+    pub fn sign_rsa_pss_prehash(
+        &self,
+        key_id: object::Id,
+        params: rsa::pss::Params,
+        digest: &[u8],
+    ) -> Result<rsa::pss::Signature, Error> {
         Ok(self
             .send_command(SignPssCommand {
                 key_id,
-                mgf1_hash_alg: S::MGF_ALGORITHM,
-                salt_len: digest.as_slice().len() as u16,
-                digest: digest.as_slice().into(),
+                mgf1_hash_alg: params.mgf1_algorithm,
+                salt_len: params.salt_len,
+                digest: digest.into(),
             })?
             .into())
     }
@@ -1169,3 +2281,569 @@ impl Client {
             .0)
     }
 }
+
+/// Lock one of a client's mutexes, recovering it if a thread panicked while
+/// holding the lock, so one panicking thread doesn't make a shared client
+/// unusable for all the others
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Clients are intended to be shared between threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Client>();
+};
+
+#[cfg(all(test, feature = "mockhsm"))]
+mod tests {
+    use super::*;
+    use crate::{
+        connector::{self, Backoff, Connectable, Connection},
+        mockhsm::MockHsm,
+        test_util::{Buffer, FailingWriter, FixedRng},
+    };
+    use ::uuid::Uuid;
+
+    const MESSAGE: &[u8] = b"sessions expire after 30 seconds of inactivity";
+
+    /// Connector to a `MockHsm` which records the timeouts messages are sent
+    /// with
+    #[derive(Clone)]
+    struct TimeoutRecorder(MockHsm, Arc<Mutex<Vec<Option<Duration>>>>);
+
+    impl Connectable for TimeoutRecorder {
+        fn box_clone(&self) -> Box<dyn Connectable> {
+            Box::new(self.clone())
+        }
+
+        fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl Connection for TimeoutRecorder {
+        fn send_message(
+            &self,
+            uuid: Uuid,
+            msg: connector::Message,
+        ) -> Result<connector::Message, connector::Error> {
+            self.1.lock().unwrap().push(None);
+            self.0.connect()?.send_message(uuid, msg)
+        }
+
+        fn send_message_with_timeout(
+            &self,
+            uuid: Uuid,
+            msg: connector::Message,
+            timeout: Duration,
+        ) -> Result<connector::Message, connector::Error> {
+            self.1.lock().unwrap().push(Some(timeout));
+            self.0.connect()?.send_message(uuid, msg)
+        }
+    }
+
+    /// Connector to a `MockHsm` which fails to send the given number of
+    /// session messages with an I/O error
+    #[derive(Clone)]
+    struct FlakyConnector(MockHsm, Arc<Mutex<usize>>);
+
+    impl Connectable for FlakyConnector {
+        fn box_clone(&self) -> Box<dyn Connectable> {
+            Box::new(self.clone())
+        }
+
+        fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl Connection for FlakyConnector {
+        fn send_message(
+            &self,
+            uuid: Uuid,
+            msg: connector::Message,
+        ) -> Result<connector::Message, connector::Error> {
+            let mut failures = self.1.lock().unwrap();
+
+            if *failures > 0 && msg.as_ref()[0] == command::Code::SessionMessage.to_u8() {
+                *failures -= 1;
+                fail!(connector::ErrorKind::IoError, "simulated I/O error");
+            }
+
+            self.0.connect()?.send_message(uuid, msg)
+        }
+    }
+
+    #[test]
+    fn reauthenticate_test() {
+        let mockhsm = MockHsm::new();
+        let mut client = Client::open(
+            Connector::loopback(mockhsm.clone()),
+            Credentials::default(),
+            true,
+        )
+        .unwrap();
+
+        mockhsm.expire_sessions();
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+
+        client.set_reauthenticate(false);
+        mockhsm.expire_sessions();
+
+        let err = client.echo(MESSAGE).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ClosedSessionError);
+
+        // The expired session is discarded, so the next command opens a new one
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn retry_policy_test() {
+        let failures = Arc::new(Mutex::new(0));
+        let connectable: Box<dyn Connectable> =
+            Box::new(FlakyConnector(MockHsm::new(), Arc::clone(&failures)));
+        let mut client = Client::open(connectable.into(), Credentials::default(), true).unwrap();
+
+        *failures.lock().unwrap() = 1;
+        let err = client.echo(MESSAGE).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ProtocolError);
+
+        client.set_retry_policy(Some(RetryPolicy {
+            backoff: Backoff {
+                initial_backoff_ms: 1,
+                ..RetryPolicy::default().backoff
+            },
+            ..Default::default()
+        }));
+
+        *failures.lock().unwrap() = 2;
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+
+        // Commands which change the state of the HSM are never resent
+        *failures.lock().unwrap() = 1;
+        assert!(client
+            .put_opaque(
+                1,
+                "opaque".into(),
+                Domain::DOM1,
+                Capability::empty(),
+                opaque::Algorithm::Data,
+                MESSAGE,
+            )
+            .is_err());
+        assert_eq!(*failures.lock().unwrap(), 0);
+
+        // Retries are limited by the policy's maximum number of attempts
+        *failures.lock().unwrap() = 3;
+        assert!(client.echo(MESSAGE).is_err());
+    }
+
+    #[test]
+    fn close_on_drop_test() {
+        let mockhsm = MockHsm::new();
+        let events = Arc::new(Mutex::new(vec![]));
+
+        // The HSM supports a limited number of sessions, which would be
+        // exhausted if dropping clients leaked them
+        for _ in 0..20 {
+            let mut client =
+                Client::create(Connector::loopback(mockhsm.clone()), Credentials::default())
+                    .unwrap();
+
+            let recorded = Arc::clone(&events);
+            client.set_session_event_handler(move |event| recorded.lock().unwrap().push(*event));
+            assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+        }
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 40);
+        assert!(matches!(events[1], session::Event::Closed { .. }));
+
+        let client =
+            Client::open(Connector::loopback(mockhsm), Credentials::default(), true).unwrap();
+        client.close().unwrap();
+        client.close().unwrap();
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn raw_command_test() {
+        let client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+
+        assert_eq!(
+            client
+                .send_raw_command(command::Code::Echo, MESSAGE)
+                .unwrap(),
+            MESSAGE
+        );
+
+        let err = client
+            .send_raw_command(command::Code::CloseSession, &[])
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ProtocolError);
+        assert!(client.session().unwrap().is_open());
+    }
+
+    #[test]
+    fn replay_encrypted_session_test() {
+        let recording = Buffer::default();
+        let connector = Connector::record(Connector::mockhsm(), recording.clone());
+        let mut client = Client::create(connector, Credentials::default()).unwrap();
+        client.set_challenge_rng(FixedRng);
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+
+        // With the same host challenges, the replayed session derives the
+        // same session keys as the recorded one
+        let connector = Connector::replay(recording.contents().as_slice()).unwrap();
+        let mut client = Client::create(connector, Credentials::default()).unwrap();
+        client.set_challenge_rng(FixedRng);
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn raw_command_journal_test() {
+        let mut client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        let journal = Buffer::default();
+        client.set_journal(Journal::new(journal.clone()));
+
+        client
+            .send_raw_command(command::Code::Echo, MESSAGE)
+            .unwrap();
+
+        // Delete the (nonexistent) asymmetric key 0x0064
+        assert!(client
+            .send_raw_command(command::Code::DeleteObject, &[0x00, 0x64, 0x03])
+            .is_err());
+
+        let journal = String::from_utf8(journal.contents()).unwrap();
+        assert_eq!(journal.lines().count(), 1);
+        assert!(journal.contains("DeleteObject\t\terror"));
+    }
+
+    /// Middleware which rejects `SignEddsa` commands and records the commands
+    /// it sees
+    struct NoSigning(Arc<Mutex<Vec<(command::Code, bool)>>>);
+
+    impl session::Middleware for NoSigning {
+        fn before_command(
+            &self,
+            command: &session::CommandInfo,
+        ) -> Result<(), crate::error::BoxError> {
+            if command.command_type == command::Code::SignEddsa {
+                return Err("signing is disabled".into());
+            }
+
+            Ok(())
+        }
+
+        fn after_command(&self, command: &session::CommandInfo, outcome: &session::CommandOutcome) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((command.command_type, outcome.error.is_none()));
+        }
+    }
+
+    #[test]
+    fn middleware_test() {
+        let mut client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        let commands = Arc::new(Mutex::new(vec![]));
+        client.add_middleware(NoSigning(Arc::clone(&commands)));
+
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+
+        let err = client.sign_ed25519(1, MESSAGE).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::RejectedError);
+
+        assert!(client
+            .get_object_info(1, object::Type::AsymmetricKey)
+            .is_err());
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                (command::Code::Echo, true),
+                (command::Code::GetObjectInfo, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn journal_failure_test() {
+        let mut client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        assert_eq!(client.journal_failures(), None);
+
+        client.set_journal(Journal::new(FailingWriter));
+
+        // The HSM performed the command, so its result is returned regardless
+        let key_id = client
+            .generate_hmac_key(
+                200,
+                "journal test".into(),
+                Domain::DOM1,
+                Capability::SIGN_HMAC,
+                hmac::Algorithm::Sha256,
+            )
+            .unwrap();
+
+        assert_eq!(key_id, 200);
+        assert_eq!(client.journal_failures(), Some(1));
+    }
+
+    #[test]
+    fn middleware_clone_test() {
+        let old_client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        let mut client = old_client.clone();
+        let commands = Arc::new(Mutex::new(vec![]));
+        client.add_middleware(NoSigning(Arc::clone(&commands)));
+
+        // Reopen the shared session from the clone made before the middleware
+        // was added
+        old_client.close().unwrap();
+        assert_eq!(old_client.echo(MESSAGE).unwrap(), MESSAGE);
+
+        let err = client.sign_ed25519(1, MESSAGE).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::RejectedError);
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+
+        // The older clone's commands don't pass through the middleware
+        assert!(old_client
+            .get_object_info(1, object::Type::AsymmetricKey)
+            .is_err());
+
+        assert_eq!(*commands.lock().unwrap(), [(command::Code::Echo, true)]);
+    }
+
+    #[test]
+    fn clone_test() {
+        let mut client = Client::create(Connector::mockhsm(), Credentials::default()).unwrap();
+        let opened = Arc::new(Mutex::new(0));
+        let recorded = Arc::clone(&opened);
+        client.set_session_event_handler(move |event| {
+            if let session::Event::Opened { .. } = event {
+                *recorded.lock().unwrap() += 1;
+            }
+        });
+
+        let clones = (0..8).map(|_| client.clone()).collect::<Vec<_>>();
+
+        thread::scope(|scope| {
+            for clone in &clones {
+                scope.spawn(move || assert_eq!(clone.echo(MESSAGE).unwrap(), MESSAGE));
+            }
+        });
+
+        let session_id = client.session().unwrap().id();
+        assert!(clones
+            .iter()
+            .all(|clone| clone.session().unwrap().id() == session_id));
+        assert_eq!(*opened.lock().unwrap(), 1);
+        assert_eq!(client.stats().get(command::Code::Echo).unwrap().count(), 8);
+    }
+
+    #[test]
+    fn force_audit_test() {
+        let client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        client.set_force_audit_option(AuditOption::On).unwrap();
+
+        // Fill the log until audited commands are refused
+        let err = (0..100)
+            .find_map(|_| client.blink_device(1).err())
+            .expect("audit log never filled up");
+        assert_eq!(err.device_error(), Some(device::ErrorKind::LogFull));
+
+        let log_entries = client.consume_log_entries().unwrap();
+        assert!(log_entries.verify(None).is_ok());
+        client.blink_device(1).unwrap();
+    }
+
+    #[test]
+    fn multiple_sessions_test() {
+        const AUDITOR_KEY_ID: object::Id = 2;
+
+        let operator = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        let auditor_key = authentication::Key::derive_from_password(b"auditor");
+
+        operator
+            .put_authentication_key(
+                AUDITOR_KEY_ID,
+                "auditor".into(),
+                Domain::DOM1,
+                Capability::GET_LOG_ENTRIES,
+                Capability::empty(),
+                authentication::Algorithm::YubicoAes,
+                auditor_key.clone(),
+            )
+            .unwrap();
+
+        let auditor = operator
+            .add_session(Credentials::new(AUDITOR_KEY_ID, auditor_key.clone()))
+            .unwrap();
+
+        assert!(operator
+            .add_session(Credentials::new(AUDITOR_KEY_ID, auditor_key))
+            .is_err());
+
+        assert_eq!(
+            operator.authentication_key_ids(),
+            [
+                authentication::DEFAULT_AUTHENTICATION_KEY_ID,
+                AUDITOR_KEY_ID
+            ]
+        );
+
+        let operator_session_id = operator.session().unwrap().id();
+        let auditor_session_id = auditor.session().unwrap().id();
+        assert_ne!(operator_session_id, auditor_session_id);
+
+        let routed = operator.for_key(AUDITOR_KEY_ID).unwrap();
+        assert_eq!(routed.session().unwrap().id(), auditor_session_id);
+        assert_eq!(routed.echo(MESSAGE).unwrap(), MESSAGE);
+
+        assert!(operator.for_key(3).is_err());
+    }
+
+    #[test]
+    fn with_timeout_test() {
+        let timeouts = Arc::new(Mutex::new(vec![]));
+        let connectable: Box<dyn Connectable> =
+            Box::new(TimeoutRecorder(MockHsm::new(), Arc::clone(&timeouts)));
+
+        let client = Client::open(connectable.into(), Credentials::default(), true).unwrap();
+        let timeout = Duration::from_secs(60);
+
+        client.with_timeout(timeout).echo(MESSAGE).unwrap();
+        client.echo(MESSAGE).unwrap();
+
+        // CreateSession, AuthenticateSession, then the two echo commands
+        assert_eq!(*timeouts.lock().unwrap(), [None, None, Some(timeout), None]);
+    }
+
+    #[test]
+    fn builder_test() {
+        let err = Client::builder().build().err().unwrap();
+        assert_eq!(*err.kind(), ErrorKind::ConfigError);
+
+        let err = Client::builder()
+            .connector(Connector::mockhsm())
+            .session_timeout(session::Timeout::from_secs(60))
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(*err.kind(), ErrorKind::ConfigError);
+
+        let client = Client::builder()
+            .connector(Connector::mockhsm())
+            .credentials(Credentials::default())
+            .command_timeout(Duration::from_secs(10))
+            .session_timeout(session::Timeout::from_secs(20))
+            .reconnect(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+        assert!(client.credentials.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn session_event_test() {
+        let mockhsm = MockHsm::new();
+        let events = Arc::new(Mutex::new(vec![]));
+
+        let record_events = |client: &mut Client| {
+            let events = Arc::clone(&events);
+            client.set_session_event_handler(move |event| events.lock().unwrap().push(*event));
+        };
+
+        let mut client = Client::open_lazy(
+            Connector::loopback(mockhsm.clone()),
+            Credentials::default(),
+            true,
+        )
+        .unwrap();
+        record_events(&mut client);
+        client.set_reauthenticate(false);
+
+        client.connect().unwrap();
+        let session_id = client.session().unwrap().id();
+
+        mockhsm.expire_sessions();
+        assert!(client.echo(MESSAGE).is_err());
+
+        let mut bad_client = Client::create(
+            Connector::loopback(mockhsm),
+            Credentials::new(
+                authentication::DEFAULT_AUTHENTICATION_KEY_ID,
+                authentication::Key::derive_from_password(b"wrong password"),
+            ),
+        )
+        .unwrap();
+        record_events(&mut bad_client);
+        assert!(bad_client.connect().is_err());
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                session::Event::Opened {
+                    session_id,
+                    authentication_key_id: authentication::DEFAULT_AUTHENTICATION_KEY_ID,
+                },
+                session::Event::Terminated { session_id },
+                session::Event::AuthenticationFailed {
+                    authentication_key_id: authentication::DEFAULT_AUTHENTICATION_KEY_ID,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_client_test() {
+        let client =
+            Arc::new(Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap());
+
+        let threads = (0..8u8)
+            .map(|i| {
+                let client = Arc::clone(&client);
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        assert_eq!(client.echo(vec![i; 16]).unwrap(), vec![i; 16]);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // A thread panicking while holding the session doesn't break the client
+        let panicking_client = Arc::clone(&client);
+        assert!(thread::spawn(move || {
+            let _session = panicking_client.session().unwrap();
+            panic!("oops");
+        })
+        .join()
+        .is_err());
+
+        assert_eq!(client.echo(MESSAGE).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn heartbeat_test() {
+        let client = Client::open(Connector::mockhsm(), Credentials::default(), true).unwrap();
+        let messages_sent = || client.session().unwrap().messages_sent().unwrap();
+        let before = messages_sent();
+
+        let heartbeat = client.start_heartbeat(Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(100));
+        drop(heartbeat);
+
+        let after = messages_sent();
+        assert!(after > before);
+
+        // No more pings are sent once the heartbeat has stopped
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(messages_sent(), after);
+    }
+}
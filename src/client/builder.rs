@@ -0,0 +1,239 @@
+//! Builder for configuring and opening clients
+
+use super::{Client, Error, ErrorKind, RetryPolicy};
+use crate::{
+    authentication::Credentials,
+    connector::{Backoff, Connector, ConnectorConfig, ReconnectPolicy},
+    session::{self, TIMEOUT_FUZZ_FACTOR},
+};
+use std::time::Duration;
+
+/// Builder for a [`Client`], which validates its configuration before
+/// opening a session.
+///
+/// ```ignore
+/// let client = Client::builder()
+///     .connector_config(&ConnectorConfig::Usb(UsbConfig::default()))
+///     .credentials(Credentials::from_password(auth_key_id, password))
+///     .command_timeout(Duration::from_secs(10))
+///     .reconnect_policy(ReconnectPolicy::default())
+///     .build()?;
+/// ```
+pub struct ClientBuilder {
+    /// Connector for communicating with the HSM
+    connector: Option<Connector>,
+
+    /// Credentials for authenticating sessions (default if unset)
+    credentials: Option<Credentials>,
+
+    /// How long to wait for the HSM to respond to each command
+    command_timeout: Option<Duration>,
+
+    /// Inactivity timeout for sessions
+    session_timeout: Option<session::Timeout>,
+
+    /// Policy for retrying opening sessions when connecting fails
+    reconnect_policy: Option<ReconnectPolicy>,
+
+    /// Policy for resending commands which failed due to transient errors
+    retry_policy: Option<RetryPolicy>,
+
+    /// Maximum number of commands in flight at once
+    concurrency_limit: Option<usize>,
+
+    /// Reopen closed sessions using the cached credentials
+    reconnect: bool,
+
+    /// Reauthenticate when the HSM reports an expired session
+    reauthenticate: bool,
+
+    /// Defer opening a session until the first command is sent
+    lazy: bool,
+}
+
+impl ClientBuilder {
+    /// Create a new builder with the default configuration
+    pub fn new() -> Self {
+        Self {
+            connector: None,
+            credentials: None,
+            command_timeout: None,
+            session_timeout: None,
+            reconnect_policy: None,
+            retry_policy: None,
+            concurrency_limit: None,
+            reconnect: true,
+            reauthenticate: true,
+            lazy: false,
+        }
+    }
+
+    /// Communicate with the HSM using the given connector
+    pub fn connector(mut self, connector: Connector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Communicate with the HSM using a connector of the type selected by the
+    /// given configuration
+    pub fn connector_config(self, config: &ConnectorConfig) -> Self {
+        self.connector(Connector::from_config(config))
+    }
+
+    /// Authenticate sessions using the given credentials (the default
+    /// authentication key if unset and the `passwords` feature is enabled,
+    /// otherwise they must be given)
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Wait up to `timeout` for the HSM to respond to each command, instead
+    /// of the connector's configured timeout (see [`Client::with_timeout`])
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = Some(timeout);
+        self
+    }
+
+    /// Consider sessions expired after being idle for this long. Must not be
+    /// longer than the HSM's inactivity timeout (30 seconds, the default).
+    pub fn session_timeout(mut self, timeout: session::Timeout) -> Self {
+        self.session_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry opening sessions according to the given policy when connecting
+    /// to the HSM fails (overriding the connector's policy, if any)
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// Resend commands which are safe to perform more than once if they fail
+    /// due to transient errors (see [`Client::set_retry_policy`])
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Allow at most `limit` commands to be in flight at once (see
+    /// [`Client::set_concurrency_limit`])
+    pub fn concurrency_limit(mut self, limit: usize) -> Self {
+        self.concurrency_limit = Some(limit);
+        self
+    }
+
+    /// Reopen closed sessions using the same credentials (enabled by
+    /// default). If disabled, the credentials are discarded once the first
+    /// session has been opened.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Reauthenticate and retry commands when the HSM reports that a session
+    /// has expired (enabled by default, see [`Client::set_reauthenticate`])
+    pub fn reauthenticate(mut self, reauthenticate: bool) -> Self {
+        self.reauthenticate = reauthenticate;
+        self
+    }
+
+    /// Defer connecting to the HSM until the first command is sent (see
+    /// [`Client::open_lazy`])
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Validate the configuration and create the client, opening a session
+    /// with the HSM unless `lazy` is set
+    pub fn build(self) -> Result<Client, Error> {
+        let mut connector = self
+            .connector
+            .ok_or_else(|| format_err!(ErrorKind::ConfigError, "no connector configured"))?;
+
+        if let Some(timeout) = self.command_timeout {
+            ensure!(
+                !timeout.is_zero(),
+                ErrorKind::ConfigError,
+                "command timeout must be nonzero"
+            );
+        }
+
+        let max_session_timeout = session::Timeout::default();
+
+        if let Some(timeout) = self.session_timeout {
+            ensure!(
+                timeout.duration() > TIMEOUT_FUZZ_FACTOR
+                    && timeout.duration() <= max_session_timeout.duration(),
+                ErrorKind::ConfigError,
+                "session timeout must be longer than {:?} and at most {:?} (got {:?})",
+                TIMEOUT_FUZZ_FACTOR,
+                max_session_timeout.duration(),
+                timeout.duration()
+            );
+        }
+
+        if let Some(policy) = self.reconnect_policy {
+            check_backoff("reconnect policy", &policy.backoff)?;
+            connector = connector.with_reconnect_policy(Some(policy));
+        }
+
+        if let Some(policy) = &self.retry_policy {
+            check_backoff("retry policy", &policy.backoff)?;
+        }
+
+        #[cfg(feature = "passwords")]
+        let credentials = self.credentials.unwrap_or_default();
+
+        #[cfg(not(feature = "passwords"))]
+        let credentials = self.credentials.ok_or_else(|| {
+            format_err!(
+                ErrorKind::ConfigError,
+                "no credentials given (the default credentials need the `passwords` feature)"
+            )
+        })?;
+
+        let mut client = Client::create(connector, credentials)?;
+        client.reconnect = self.reconnect;
+        client.reauthenticate = self.reauthenticate;
+        client.set_retry_policy(self.retry_policy);
+        client.set_concurrency_limit(self.concurrency_limit)?;
+        client.command_timeout = self.command_timeout;
+        client.session_timeout = self.session_timeout.unwrap_or(max_session_timeout);
+
+        if !self.lazy {
+            client.connect()?;
+        }
+
+        Ok(client)
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check that the backoff of the named policy allows at least one attempt
+/// and that its initial delay doesn't exceed its maximum
+fn check_backoff(policy: &str, backoff: &Backoff) -> Result<(), Error> {
+    ensure!(
+        backoff.max_attempts > 0,
+        ErrorKind::ConfigError,
+        "{} must allow at least one attempt",
+        policy
+    );
+
+    ensure!(
+        backoff.initial_backoff_ms <= backoff.max_backoff_ms,
+        ErrorKind::ConfigError,
+        "{}'s initial backoff ({}ms) exceeds its maximum ({}ms)",
+        policy,
+        backoff.initial_backoff_ms,
+        backoff.max_backoff_ms
+    );
+
+    Ok(())
+}
@@ -3,7 +3,7 @@
 use crate::{
     connector, device,
     error::{BoxError, Context},
-    serialization, session,
+    journal, serialization, session,
 };
 use std::io;
 use thiserror::Error;
@@ -26,6 +26,10 @@ pub enum ErrorKind {
     #[error("connector error")]
     ConnectorError,
 
+    /// Invalid client configuration
+    #[error("invalid configuration")]
+    ConfigError,
+
     /// Couldn't create session
     #[error("couldn't create session")]
     CreateFailed,
@@ -34,10 +38,22 @@ pub enum ErrorKind {
     #[error("HSM error")]
     DeviceError,
 
+    /// Error recording an operation in the client-side journal
+    #[error("journal error")]
+    JournalError,
+
+    /// Command is too long to be sent to the HSM
+    #[error("message too long")]
+    MessageTooLong,
+
     /// Protocol error occurred
     #[error("protocol error")]
     ProtocolError,
 
+    /// Command was rejected by middleware before being sent
+    #[error("command rejected")]
+    RejectedError,
+
     /// Error response from HSM we can't further specify
     #[error("HSM response error")]
     ResponseError,
@@ -69,17 +85,28 @@ impl From<connector::Error> for Error {
     }
 }
 
+impl From<journal::Error> for Error {
+    fn from(err: journal::Error) -> Self {
+        ErrorKind::JournalError.context(err).into()
+    }
+}
+
 impl From<session::Error> for Error {
     fn from(err: session::Error) -> Self {
         let kind = match err.kind() {
             session::ErrorKind::AuthenticationError => ErrorKind::AuthenticationError,
-            session::ErrorKind::ClosedError => ErrorKind::ClosedSessionError,
+            session::ErrorKind::ClosedError | session::ErrorKind::ExpiredError => {
+                ErrorKind::ClosedSessionError
+            }
             session::ErrorKind::CreateFailed => ErrorKind::CreateFailed,
             session::ErrorKind::DeviceError => ErrorKind::DeviceError,
+            session::ErrorKind::MessageTooLong => ErrorKind::MessageTooLong,
             session::ErrorKind::ProtocolError
+            | session::ErrorKind::DesyncError
             | session::ErrorKind::CommandLimitExceeded
             | session::ErrorKind::MismatchError
             | session::ErrorKind::VerifyFailed => ErrorKind::ProtocolError,
+            session::ErrorKind::RejectedError => ErrorKind::RejectedError,
             session::ErrorKind::ResponseError => ErrorKind::ResponseError,
         };
 
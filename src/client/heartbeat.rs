@@ -0,0 +1,52 @@
+//! Background keep-alive for client sessions
+
+use super::Client;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// Handle to a background thread which periodically pings the HSM over a
+/// [`Client`]'s session, so it doesn't expire while the client is idle.
+///
+/// Created by [`Client::start_heartbeat`]. The heartbeat stops when this
+/// handle is dropped.
+pub struct Heartbeat {
+    /// Dropped to signal the heartbeat thread to stop
+    stop: Option<mpsc::Sender<()>>,
+
+    /// Heartbeat thread
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// Spawn a thread which pings the HSM using the given client every
+    /// `interval`
+    pub(super) fn start(client: Client, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if let Err(e) = client.ping() {
+                    debug!("heartbeat failed: {}", e);
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
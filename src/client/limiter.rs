@@ -0,0 +1,72 @@
+//! Limits on the number of commands which are in flight at once
+
+use std::sync::{Condvar, Mutex, PoisonError};
+
+/// Counting semaphore shared by clients sending commands to the same HSM,
+/// which makes commands beyond the limit queue until others complete rather
+/// than piling up (and timing out) on the device.
+pub(crate) struct CommandLimiter {
+    /// Number of commands currently in flight
+    in_flight: Mutex<usize>,
+
+    /// Signalled when a command completes
+    completed: Condvar,
+
+    /// Maximum number of commands in flight at once
+    limit: usize,
+}
+
+impl CommandLimiter {
+    /// Create a limiter allowing up to `limit` commands in flight at once
+    pub(crate) fn new(limit: usize) -> Self {
+        debug_assert!(limit > 0);
+
+        Self {
+            in_flight: Mutex::new(0),
+            completed: Condvar::new(),
+            limit,
+        }
+    }
+
+    /// Maximum number of commands in flight at once
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Wait until a command can be sent, returning a permit which allows
+    /// sending it until dropped
+    pub(crate) fn acquire(&self) -> Permit<'_> {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        while *in_flight >= self.limit {
+            in_flight = self
+                .completed
+                .wait(in_flight)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+
+        *in_flight += 1;
+        Permit { limiter: self }
+    }
+}
+
+/// Permission to have a command in flight, released when dropped
+pub(crate) struct Permit<'a> {
+    /// Limiter this permit was acquired from
+    limiter: &'a CommandLimiter,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self
+            .limiter
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) -= 1;
+
+        self.limiter.completed.notify_one();
+    }
+}
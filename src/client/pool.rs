@@ -0,0 +1,253 @@
+//! Pools of clients with independent sessions, for executing commands
+//! concurrently
+
+use super::{limiter::CommandLimiter, Client, Error, ErrorKind};
+use crate::{authentication::Credentials, connector::Connector};
+use std::{
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// Maximum number of concurrent sessions supported by the YubiHSM 2
+pub const MAX_SESSIONS: usize = 16;
+
+/// Pool of [`Client`]s, each with its own authenticated session using the
+/// same credentials.
+///
+/// A single `Client` serializes commands over its session. Checking a
+/// client out of the pool for each command instead lets multi-threaded
+/// callers keep several commands in flight at once (subject to how
+/// concurrently the connector and HSM can process them).
+pub struct SessionPool {
+    /// Clients which aren't checked out
+    idle: Mutex<Vec<Client>>,
+
+    /// Signalled when a client is returned to the pool
+    returned: Condvar,
+
+    /// Total number of clients in the pool
+    size: usize,
+}
+
+impl SessionPool {
+    /// Open `size` sessions to the HSM with the given credentials.
+    ///
+    /// Each session occupies one of the HSM's [`MAX_SESSIONS`] session slots.
+    pub fn open(
+        connector: Connector,
+        credentials: Credentials,
+        size: usize,
+    ) -> Result<Self, Error> {
+        ensure!(
+            (1..=MAX_SESSIONS).contains(&size),
+            ErrorKind::CreateFailed,
+            "session pool size must be between 1 and {} (got {})",
+            MAX_SESSIONS,
+            size
+        );
+
+        let clients = (0..size)
+            .map(|_| Client::open(connector.clone(), credentials.clone(), true))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::from_clients(clients))
+    }
+
+    /// Create a pool from the given clients (e.g. ones configured with a
+    /// custom crypto backend).
+    ///
+    /// Clones of a `Client` share its session, so each client should have
+    /// been opened or created separately.
+    pub fn from_clients(clients: Vec<Client>) -> Self {
+        Self {
+            size: clients.len(),
+            idle: Mutex::new(clients),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Total number of clients in the pool
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Allow at most `limit` commands to be in flight across all of the
+    /// pool's clients at once, so bursts of commands queue on the host
+    /// rather than on the HSM. Commands beyond the limit wait for others to
+    /// complete.
+    pub fn set_concurrency_limit(&mut self, limit: usize) -> Result<(), Error> {
+        ensure!(
+            limit > 0,
+            ErrorKind::ConfigError,
+            "concurrency limit must be nonzero"
+        );
+
+        let limiter = Arc::new(CommandLimiter::new(limit));
+
+        // No clients are checked out, as they borrow the pool
+        for client in self.idle.get_mut().unwrap().iter_mut() {
+            client.set_command_limiter(Arc::clone(&limiter));
+        }
+
+        Ok(())
+    }
+
+    /// Check out a client, blocking until one is available
+    pub fn get(&self) -> PooledClient<'_> {
+        let mut idle = self.idle.lock().unwrap();
+
+        loop {
+            if let Some(client) = idle.pop() {
+                return PooledClient {
+                    pool: self,
+                    client: Some(client),
+                };
+            }
+
+            idle = self.returned.wait(idle).unwrap();
+        }
+    }
+
+    /// Check out a client if one is available without blocking
+    pub fn try_get(&self) -> Option<PooledClient<'_>> {
+        let client = self.idle.lock().unwrap().pop()?;
+
+        Some(PooledClient {
+            pool: self,
+            client: Some(client),
+        })
+    }
+}
+
+/// Client checked out of a [`SessionPool`], which is returned to the pool
+/// when dropped
+pub struct PooledClient<'pool> {
+    /// Pool this client was checked out of
+    pool: &'pool SessionPool,
+
+    /// Checked out client (taken when returned to the pool)
+    client: Option<Client>,
+}
+
+impl<'pool> Deref for PooledClient<'pool> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl<'pool> Drop for PooledClient<'pool> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool.idle.lock().unwrap().push(client);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mockhsm"))]
+mod tests {
+    use super::*;
+    use crate::{
+        command,
+        connector::{self, Connectable, Connection},
+        mockhsm::MockHsm,
+    };
+    use ::uuid::Uuid;
+    use std::{collections::HashSet, thread, time::Duration};
+
+    /// Connector to a `MockHsm` which records the maximum number of session
+    /// messages in flight at once
+    #[derive(Clone)]
+    struct InFlightRecorder(MockHsm, Arc<Mutex<(usize, usize)>>);
+
+    impl Connectable for InFlightRecorder {
+        fn box_clone(&self) -> Box<dyn Connectable> {
+            Box::new(self.clone())
+        }
+
+        fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl Connection for InFlightRecorder {
+        fn send_message(
+            &self,
+            uuid: Uuid,
+            msg: connector::Message,
+        ) -> Result<connector::Message, connector::Error> {
+            if msg.as_ref()[0] != command::Code::SessionMessage.to_u8() {
+                return self.0.connect()?.send_message(uuid, msg);
+            }
+
+            {
+                let mut counts = self.1.lock().unwrap();
+                counts.0 += 1;
+                counts.1 = counts.1.max(counts.0);
+            }
+
+            thread::sleep(Duration::from_millis(5));
+            let response = self.0.connect()?.send_message(uuid, msg);
+            self.1.lock().unwrap().0 -= 1;
+            response
+        }
+    }
+
+    #[test]
+    fn concurrent_commands_test() {
+        let pool = SessionPool::open(Connector::mockhsm(), Credentials::default(), 4).unwrap();
+        assert_eq!(pool.size(), 4);
+
+        // Distinct clients have distinct sessions
+        let session_ids = {
+            let clients = (0..4).map(|_| pool.try_get().unwrap()).collect::<Vec<_>>();
+            assert!(pool.try_get().is_none());
+
+            clients
+                .iter()
+                .map(|client| client.session().unwrap().id())
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(session_ids.len(), 4);
+
+        thread::scope(|scope| {
+            for i in 0..16u8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let message = vec![i; 32];
+                    assert_eq!(pool.get().echo(message.clone()).unwrap(), message);
+                });
+            }
+        });
+
+        assert!(SessionPool::open(Connector::mockhsm(), Credentials::default(), 0).is_err());
+    }
+
+    #[test]
+    fn concurrency_limit_test() {
+        let counts = Arc::new(Mutex::new((0, 0)));
+        let connectable: Box<dyn Connectable> =
+            Box::new(InFlightRecorder(MockHsm::new(), Arc::clone(&counts)));
+        let mut pool = SessionPool::open(connectable.into(), Credentials::default(), 4).unwrap();
+
+        assert!(pool.set_concurrency_limit(0).is_err());
+        pool.set_concurrency_limit(2).unwrap();
+        assert_eq!(pool.get().concurrency_limit(), Some(2));
+
+        thread::scope(|scope| {
+            for i in 0..16u8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let message = vec![i; 32];
+                    assert_eq!(pool.get().echo(message.clone()).unwrap(), message);
+                });
+            }
+        });
+
+        let (in_flight, max_in_flight) = *counts.lock().unwrap();
+        assert_eq!(in_flight, 0);
+        assert!((1..=2).contains(&max_in_flight));
+    }
+}
@@ -0,0 +1,105 @@
+//! Policy for automatically resending commands which failed due to transient
+//! errors
+
+use super::{Error, ErrorKind};
+use crate::{
+    command,
+    connector::{self, Backoff},
+};
+use serde::{Deserialize, Serialize};
+
+/// Policy consulted by the `Client` when a command fails due to a transient
+/// error, e.g. the connection to the HSM being interrupted, the session
+/// being closed, or a retryable device error such as all session slots
+/// being in use (see [`crate::device::ErrorKind::is_retryable`]).
+///
+/// Only commands which are safe to perform more than once are retried (see
+/// [`command::Code::is_retryable`]): reads like listing objects or fetching
+/// public keys, and computations like signing or decrypting. Commands which
+/// change the state of the HSM (e.g. generating or deleting objects) are
+/// never resent, as the HSM may have already performed them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts to send a command (including the first) and the delay
+    /// between them
+    pub backoff: Backoff,
+
+    /// Kinds of connector errors which are retried
+    pub error_kinds: Vec<connector::ErrorKind>,
+
+    /// Retry commands sent over sessions which were closed (or expired)
+    pub closed_sessions: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: Backoff {
+                max_attempts: 3,
+                initial_backoff_ms: 50,
+                max_backoff_ms: 1000,
+                jitter: false,
+            },
+            error_kinds: vec![
+                connector::ErrorKind::ConnectionFailed,
+                connector::ErrorKind::IoError,
+            ],
+            closed_sessions: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Should the given failed attempt (zero-indexed) to send a command with
+    /// the given code be retried?
+    ///
+    /// This is the case if the command is retryable, the error is transient,
+    /// and attempts remain.
+    pub fn should_retry(&self, code: command::Code, attempt: u32, error: &Error) -> bool {
+        self.backoff.attempts_remain(attempt) && code.is_retryable() && self.is_transient(error)
+    }
+
+    /// Was the given error caused by a transient failure?
+    fn is_transient(&self, error: &Error) -> bool {
+        if *error.kind() == ErrorKind::ClosedSessionError {
+            return self.closed_sessions;
+        }
+
+        if let Some(kind) = error.device_error() {
+            return kind.is_retryable();
+        }
+
+        connector::connector_error_kind(error).is_some_and(|kind| self.error_kinds.contains(&kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::{client, command, connector, device, session};
+
+    #[test]
+    fn retries_transient_errors_for_retryable_commands() {
+        let policy = RetryPolicy::default();
+
+        let connection_failed: client::Error = client::ErrorKind::ConnectorError
+            .context(connector::Error::from(connector::ErrorKind::IoError))
+            .into();
+
+        assert!(policy.should_retry(command::Code::SignEddsa, 0, &connection_failed));
+        assert!(policy.should_retry(command::Code::ListObjects, 1, &connection_failed));
+        assert!(!policy.should_retry(command::Code::SignEddsa, 2, &connection_failed));
+        assert!(!policy.should_retry(command::Code::GenerateAsymmetricKey, 0, &connection_failed));
+
+        let device_error: client::Error = client::ErrorKind::DeviceError
+            .context(device::ErrorKind::ObjectNotFound)
+            .into();
+
+        assert!(!policy.should_retry(command::Code::SignEddsa, 0, &device_error));
+
+        let sessions_full: client::Error =
+            session::Error::from(device::ErrorKind::SessionsFull).into();
+        assert!(policy.should_retry(command::Code::SignEddsa, 0, &sessions_full));
+    }
+}
@@ -0,0 +1,162 @@
+//! Per-command latency and error statistics
+
+use crate::{command, session};
+use std::{
+    collections::{btree_map, BTreeMap},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Number of latency histogram buckets. Bucket `i` counts latencies of less
+/// than `2^i` microseconds (and at least `2^(i-1)`), with the last bucket
+/// counting all longer latencies.
+const HISTOGRAM_BUCKETS: usize = 32;
+
+/// Snapshot of the latencies and errors of the commands sent by a client
+/// (and its clones), returned by [`Client::stats`].
+///
+/// [`Client::stats`]: crate::Client::stats
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Statistics for each type of command which has been sent
+    commands: BTreeMap<command::Code, CommandStats>,
+}
+
+impl Stats {
+    /// Get the statistics for the given type of command, if any have been
+    /// sent
+    pub fn get(&self, command_type: command::Code) -> Option<&CommandStats> {
+        self.commands.get(&command_type)
+    }
+
+    /// Iterate over the statistics for each type of command which has been
+    /// sent
+    pub fn iter(&self) -> btree_map::Iter<'_, command::Code, CommandStats> {
+        self.commands.iter()
+    }
+
+    /// Record the outcome of a command
+    fn record(&mut self, command_type: command::Code, outcome: &session::CommandOutcome) {
+        let stats = self.commands.entry(command_type).or_default();
+        let micros = u64::try_from(outcome.latency.as_micros()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+
+        stats.count += 1;
+        stats.total_micros = stats.total_micros.saturating_add(micros);
+        stats.max_micros = stats.max_micros.max(micros);
+        stats.histogram[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+
+        if outcome.error.is_some() {
+            stats.errors += 1;
+        }
+    }
+}
+
+/// Latency histogram and error count for one type of command
+#[derive(Clone, Debug, Default)]
+pub struct CommandStats {
+    /// Number of commands sent
+    count: u64,
+
+    /// Number of commands which failed
+    errors: u64,
+
+    /// Sum of all latencies in microseconds
+    total_micros: u64,
+
+    /// Longest latency in microseconds
+    max_micros: u64,
+
+    /// Number of commands in each latency bucket (see [`HISTOGRAM_BUCKETS`])
+    histogram: [u64; HISTOGRAM_BUCKETS],
+}
+
+impl CommandStats {
+    /// Number of commands of this type which were sent
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Number of commands of this type which failed
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Mean latency
+    pub fn mean(&self) -> Duration {
+        Duration::from_micros(self.total_micros.checked_div(self.count).unwrap_or(0))
+    }
+
+    /// Longest latency
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_micros)
+    }
+
+    /// Estimate the given latency percentile (e.g. `99.0` for p99).
+    ///
+    /// Latencies are recorded in power-of-two buckets, so this is an upper
+    /// bound which is at most twice the actual percentile.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+
+        for (bucket, &count) in self.histogram.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank.max(1) {
+                let upper_bound = 1u64.checked_shl(bucket as u32).unwrap_or(u64::MAX);
+                return Duration::from_micros(upper_bound.min(self.max_micros));
+            }
+        }
+
+        self.max()
+    }
+}
+
+/// Middleware which records statistics for each command
+pub(crate) struct StatsRecorder(pub(crate) Arc<Mutex<Stats>>);
+
+impl session::Middleware for StatsRecorder {
+    fn after_command(&self, command: &session::CommandInfo, outcome: &session::CommandOutcome) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(command.command_type, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use crate::{command, session};
+    use std::time::Duration;
+
+    fn outcome(latency_micros: u64, failed: bool) -> session::CommandOutcome {
+        session::CommandOutcome {
+            latency: Duration::from_micros(latency_micros),
+            response_length: None,
+            error: failed.then_some(session::ErrorKind::DeviceError),
+        }
+    }
+
+    #[test]
+    fn records_latency_histogram() {
+        let mut stats = Stats::default();
+
+        for latency in 1..=100 {
+            stats.record(
+                command::Code::SignEcdsa,
+                &outcome(latency * 100, latency == 100),
+            );
+        }
+
+        let sign = stats.get(command::Code::SignEcdsa).unwrap();
+        assert_eq!(sign.count(), 100);
+        assert_eq!(sign.errors(), 1);
+        assert_eq!(sign.mean(), Duration::from_micros(5050));
+        assert_eq!(sign.max(), Duration::from_millis(10));
+        assert_eq!(sign.percentile(50.0), Duration::from_micros(8192));
+        assert_eq!(sign.percentile(99.0), Duration::from_millis(10));
+        assert!(stats.get(command::Code::Echo).is_none());
+    }
+}
@@ -11,12 +11,21 @@ pub use self::{
 };
 
 pub(crate) use self::message::Message;
-use crate::{response::Response, serialization::serialize};
+use crate::{object, response::Response, serialization::serialize};
 use serde::{de::DeserializeOwned, ser::Serialize};
 
 /// Maximum size of a message sent to/from the YubiHSM
 pub const MAX_MSG_SIZE: usize = 2048;
 
+/// Maximum size of the data in a command sent over an encrypted session.
+///
+/// Commands are wrapped in a `SessionMessage` (3-byte header, session ID and
+/// 8-byte MAC) whose payload is the command (with its 3-byte header)
+/// encrypted in AES-CBC mode, padded with at least one byte to a multiple of
+/// the 16-byte block size, all of which must fit within [`MAX_MSG_SIZE`].
+/// The protocol has no way to split a command across several messages.
+pub const MAX_SESSION_COMMAND_DATA_SIZE: usize = (MAX_MSG_SIZE - 12) / 16 * 16 - 1 - 3;
+
 /// Structured command (i.e. requests) which are encrypted and then sent to
 /// the HSM. Every command has a corresponding `ResponseType`.
 ///
@@ -28,6 +37,11 @@ pub(crate) trait Command: Serialize + DeserializeOwned + Sized {
 
     /// Command ID for this command
     const COMMAND_CODE: Code = Self::ResponseType::COMMAND_CODE;
+
+    /// IDs of the objects this command operates on (recorded in the journal)
+    fn object_ids(&self) -> Vec<object::Id> {
+        Vec::new()
+    }
 }
 
 impl<'c, C: Command> From<&'c C> for Message {
@@ -134,6 +134,77 @@ impl Code {
     pub fn to_u8(self) -> u8 {
         self as u8
     }
+
+    /// Can this command be safely resent if delivery of the original is in
+    /// doubt? (i.e. it is sent outside of an encrypted session and has no
+    /// side effects)
+    pub fn is_idempotent(self) -> bool {
+        matches!(self, Code::Echo | Code::DeviceInfo)
+    }
+
+    /// Does this command modify the state of the HSM (e.g. by creating or
+    /// deleting objects or changing device options)?
+    pub fn is_mutating(self) -> bool {
+        matches!(
+            self,
+            Code::ResetDevice
+                | Code::PutOpaqueObject
+                | Code::PutAuthenticationKey
+                | Code::PutAsymmetricKey
+                | Code::GenerateAsymmetricKey
+                | Code::ImportWrapped
+                | Code::PutWrapKey
+                | Code::SetOption
+                | Code::PutHmacKey
+                | Code::DeleteObject
+                | Code::GenerateHmacKey
+                | Code::GenerateWrapKey
+                | Code::PutTemplate
+                | Code::PutOtpAead
+                | Code::GenerateOtpAead
+                | Code::SetLogIndex
+                | Code::ChangeAuthenticationKey
+        )
+    }
+
+    /// Can this command be resent over a new session if it's unknown whether
+    /// the HSM performed it? (i.e. it only reads state or computes a result,
+    /// such as a signature, so performing it twice is harmless)
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Code::Echo
+                | Code::DeviceInfo
+                | Code::GetStorageInfo
+                | Code::GetOpaqueObject
+                | Code::SignPkcs1
+                | Code::ListObjects
+                | Code::DecryptPkcs1
+                | Code::ExportWrapped
+                | Code::GetLogEntries
+                | Code::GetObjectInfo
+                | Code::GetOption
+                | Code::GetPseudoRandom
+                | Code::SignHmac
+                | Code::GetPublicKey
+                | Code::SignPss
+                | Code::SignEcdsa
+                | Code::DeriveEcdh
+                | Code::DecryptOaep
+                | Code::VerifyHmac
+                | Code::SignSshCertificate
+                | Code::GetTemplate
+                | Code::DecryptOtp
+                | Code::CreateOtpAead
+                | Code::RandomizeOtpAead
+                | Code::RewrapOtpAead
+                | Code::SignAttestationCertificate
+                | Code::WrapData
+                | Code::UnwrapData
+                | Code::SignEddsa
+                | Code::BlinkDevice
+        )
+    }
 }
 
 impl Serialize for Code {
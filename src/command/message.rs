@@ -14,6 +14,7 @@ use crate::{
     session::{self, securechannel::Mac, ErrorKind::ProtocolError},
     uuid::{self, Uuid},
 };
+use zeroize::Zeroize;
 
 /// A command sent from the host to the `YubiHSM 2`. May or may not be
 /// authenticated using SCP03's chained/evolving MAC protocol.
@@ -182,7 +183,10 @@ impl Message {
             result.push(session_id.to_u8());
         }
 
-        result.append(&mut self.data);
+        // Copy the data rather than moving it so the original buffer can be
+        // zeroized, as it may contain sensitive data (e.g. key material)
+        result.extend_from_slice(&self.data);
+        self.data.zeroize();
 
         if let Some(mac) = self.mac {
             result.extend_from_slice(mac.as_slice());
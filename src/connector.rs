@@ -3,7 +3,18 @@
 //! - [HTTP][http-connector]: communicate with YubiHSM via the `yubihsm-connector`
 //!   process from the Yubico SDK.
 //! - [USB][usb-connector]: communicate directly with the YubiHSM over USB using
-//!   the [rusb] crate.
+//!   the [rusb] crate (or the pure Rust [nusb] crate via the `usb-nusb` cargo
+//!   feature).
+//!
+//! Asynchronous (tokio-based) variants of these connectors are available via
+//! [`AsyncConnector`] when the `async` cargo feature is enabled (e.g. the
+//! `http-async` feature provides an asynchronous HTTP connector, and the
+//! `usb-async` feature an asynchronous USB connector).
+//!
+//! Asynchronous USB transfers are implemented with nusb, so `usb-async` also
+//! enables the `usb-nusb` feature. This doesn't change the backend used by
+//! synchronous USB connections when the `usb` feature is enabled as well:
+//! they keep using libusb.
 //!
 //! Additionally, this crate includes an optional development-only [mockhsm]
 //! (gated under a `mockhsm` cargo feature) which can be used as a drop-in
@@ -12,35 +23,69 @@
 //! [http-connector]: https://docs.rs/yubihsm/latest/yubihsm/connector/struct.Connector.html#method.http
 //! [usb-connector]: https://docs.rs/yubihsm/latest/yubihsm/connector/struct.Connector.html#method.usb
 //! [rusb]: https://github.com/a1ien/rusb
+//! [nusb]: https://github.com/kevinmehall/nusb
 //! [mockhsm]: https://docs.rs/yubihsm/latest/yubihsm/connector/struct.Connector.html#method.mockhsm
 
 #[macro_use]
 mod error;
 
+#[cfg(feature = "async")]
+mod async_connector;
+mod backoff;
+mod config;
 mod connectable;
 mod connection;
+mod failover;
+mod health;
 #[cfg(feature = "http")]
 pub mod http;
 mod message;
-#[cfg(feature = "usb")]
+mod pool;
+mod reconnect;
+mod recording;
+mod throttle;
+#[cfg(any(feature = "usb", feature = "usb-nusb"))]
 pub mod usb;
 
-pub use self::connection::Connection;
 pub use self::error::*;
+pub use self::{
+    backoff::Backoff,
+    config::ConnectorConfig,
+    connection::Connection,
+    pool::{PoolConfig, PoolConnector, PoolStrategy},
+    reconnect::ReconnectPolicy,
+    throttle::ThrottleConfig,
+};
+
+pub(crate) use self::reconnect::connector_error_kind;
 
 pub(crate) use self::{connectable::Connectable, message::Message};
-use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "async")]
+pub(crate) use self::async_connector::AsyncConnectable;
+#[cfg(feature = "async")]
+pub use self::async_connector::{AsyncConnection, AsyncConnector, BoxFuture};
+use self::{
+    failover::FailoverConnector,
+    recording::{RecordingConnector, ReplayConnector},
+    throttle::ThrottledConnector,
+};
+use std::{
+    io::{BufRead, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use uuid::Uuid;
 
 #[cfg(feature = "http")]
-pub use self::http::HttpConfig;
+pub use self::http::{HttpConfig, HttpTransport};
 
 #[cfg(feature = "http")]
 use self::http::HttpConnector;
 
-#[cfg(feature = "usb")]
+#[cfg(any(feature = "usb", feature = "usb-nusb"))]
 pub use self::usb::UsbConfig;
-#[cfg(feature = "usb")]
+#[cfg(any(feature = "usb", feature = "usb-nusb"))]
 use self::usb::UsbConnector;
 
 #[cfg(feature = "mockhsm")]
@@ -49,17 +94,53 @@ use crate::mockhsm::MockHsm;
 /// Abstract interface to multiple types of YubiHSM 2 connections
 pub struct Connector {
     /// Currently active connection (if any)
-    connection: Arc<Mutex<Option<Box<dyn Connection>>>>,
+    connection: Arc<Mutex<Option<Arc<dyn Connection>>>>,
+
+    /// Backend connector driver (shared by clones)
+    driver: Arc<dyn Connectable>,
 
-    /// Backend connector driver
-    driver: Box<dyn Connectable>,
+    /// Policy for reopening sessions when connecting fails (if any)
+    reconnect_policy: Option<Arc<ReconnectPolicy>>,
 }
 
 impl Connector {
+    /// Create a connector of the type selected by the given configuration,
+    /// for applications which choose how to connect to the HSM at runtime
+    pub fn from_config(config: &ConnectorConfig) -> Self {
+        match config {
+            #[cfg(feature = "http")]
+            ConnectorConfig::Http(config) => Self::http(config),
+            #[cfg(any(feature = "usb", feature = "usb-nusb"))]
+            ConnectorConfig::Usb(config) => Self::usb(config),
+            #[cfg(feature = "mockhsm")]
+            ConnectorConfig::MockHsm => Self::mockhsm(),
+            ConnectorConfig::Failover { connectors } => {
+                Self::failover(connectors.iter().map(Self::from_config))
+            }
+        }
+    }
+
     /// Create a new HTTP connector
     #[cfg(feature = "http")]
     pub fn http(config: &HttpConfig) -> Self {
         Self::from(HttpConnector::create(config))
+            .with_reconnect_policy(config.reconnect_policy.clone())
+    }
+
+    /// Create a new HTTP connector which makes requests to
+    /// `yubihsm-connector` using a custom [`HttpTransport`] instead of the
+    /// built-in HTTP client. Settings in the config which apply to the
+    /// transport itself (e.g. TLS or proxies) are left to the transport.
+    ///
+    /// To check the connector's health through the same transport, pass an
+    /// `Arc` of it here and to [`http::HealthCheck::spawn_with_transport`].
+    #[cfg(feature = "http")]
+    pub fn http_with_transport(
+        config: &HttpConfig,
+        transport: impl HttpTransport + 'static,
+    ) -> Self {
+        Self::from(HttpConnector::with_transport(config, Arc::new(transport)))
+            .with_reconnect_policy(config.reconnect_policy.clone())
     }
 
     /// Create a new USB connector. For more advanced usage including
@@ -67,33 +148,169 @@ impl Connector {
     /// the same computer, please see the [yubihsm::connector::usb] module.
     ///
     /// [yubihsm::connector::usb]: https://docs.rs/yubihsm/latest/yubihsm/connector/usb/index.html
-    #[cfg(feature = "usb")]
+    #[cfg(any(feature = "usb", feature = "usb-nusb"))]
     pub fn usb(config: &UsbConfig) -> Self {
         Self::from(UsbConnector::create(config))
+            .with_reconnect_policy(config.reconnect_policy.clone())
+    }
+
+    /// Create a connector which connects using the first of the given
+    /// connectors (in order of preference, e.g. USB then HTTP) which
+    /// succeeds, failing over to the others when a connection errors.
+    ///
+    /// The command which encountered the error still fails, but subsequent
+    /// commands (and the session re-established for them) are sent using
+    /// the remaining connectors. Connectors which failed (to connect, or
+    /// while sending) are only tried after the others for the next 30
+    /// seconds, after which the preferred connector is tried first again.
+    ///
+    /// Sessions are reopened according to the reconnect policy of the first
+    /// of the connectors which has one.
+    pub fn failover(connectors: impl IntoIterator<Item = Connector>) -> Self {
+        let connectors: Vec<_> = connectors.into_iter().collect();
+        let reconnect_policy = connectors
+            .iter()
+            .find_map(|connector| connector.reconnect_policy.clone());
+
+        Self::from(FailoverConnector::create(
+            connectors
+                .iter()
+                .map(|connector| connector.driver.box_clone())
+                .collect(),
+        ))
+        .with_shared_reconnect_policy(reconnect_policy)
+    }
+
+    /// Create a connector which limits the rate and concurrency of commands
+    /// sent using the given connector, e.g. so a noisy client can't starve
+    /// other services sharing the HSM. Commands wait until they're permitted
+    /// by the limits rather than failing.
+    ///
+    /// Returns an error if either limit is zero (which would block every
+    /// command forever). The given connector's reconnect policy is kept.
+    pub fn throttle(connector: Connector, config: &ThrottleConfig) -> Result<Self, Error> {
+        Ok(Self::from(ThrottledConnector::create(
+            connector.driver.box_clone(),
+            config,
+        )?)
+        .with_shared_reconnect_policy(connector.reconnect_policy))
+    }
+
+    /// Create a connector which records every command sent using the given
+    /// connector, along with its response, to the given writer (e.g. a file)
+    /// so it can later be served by [`Connector::replay`]. The given
+    /// connector's reconnect policy is kept.
+    pub fn record(connector: Connector, writer: impl Write + Send + 'static) -> Self {
+        Self::from(RecordingConnector::create(
+            connector.driver.box_clone(),
+            Box::new(writer),
+        ))
+        .with_shared_reconnect_policy(connector.reconnect_policy)
+    }
+
+    /// Create a connector which serves the responses from a recording made
+    /// with [`Connector::record`] in the order they were recorded, returning
+    /// an error if a command doesn't match the recorded one.
+    ///
+    /// Encrypted sessions are established using random host challenges, so
+    /// by default only exchanges which don't depend on randomness (e.g.
+    /// commands sent outside of a session such as `Echo`) can be replayed.
+    /// To replay encrypted sessions, record and replay them with clients
+    /// whose challenges come from the same fixed RNG (see
+    /// `Client::set_challenge_rng`), which must never be used with a real
+    /// HSM as it makes the session keys predictable.
+    pub fn replay(reader: impl BufRead) -> Result<Self, Error> {
+        Ok(Self::from(ReplayConnector::create(reader)?))
     }
 
     /// Create a mock HSM connector (useful for testing)
     #[cfg(feature = "mockhsm")]
     pub fn mockhsm() -> Self {
-        let mockhsm: Box<dyn Connectable> = MockHsm::new().into();
+        Self::loopback(MockHsm::new())
+    }
+
+    /// Create a connector which sends commands directly to the given mock HSM
+    /// in-process, without any sockets.
+    ///
+    /// Unlike [`Connector::mockhsm`], the caller keeps a handle to the mock
+    /// HSM, so several connectors (and clients) can share its state, e.g. to
+    /// test reconnecting to the same simulated device.
+    #[cfg(feature = "mockhsm")]
+    pub fn loopback(mockhsm: MockHsm) -> Self {
+        let mockhsm: Box<dyn Connectable> = mockhsm.into();
         Self::from(mockhsm)
     }
 
+    /// Set the policy the `Client` uses to retry opening sessions when
+    /// connecting to the HSM fails (none by default, unless configured in the
+    /// connector's configuration)
+    pub fn with_reconnect_policy(mut self, policy: Option<ReconnectPolicy>) -> Self {
+        self.reconnect_policy = policy.map(Arc::new);
+        self
+    }
+
+    /// Get the policy for reopening sessions when connecting fails (if any)
+    pub fn reconnect_policy(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect_policy.as_deref()
+    }
+
+    /// Use the reconnect policy of a connector this one wraps
+    fn with_shared_reconnect_policy(mut self, policy: Option<Arc<ReconnectPolicy>>) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
     /// Send a command message to the HSM, then read and return the response
     pub fn send_message(&self, uuid: Uuid, msg: Message) -> Result<Message, Error> {
-        let mut connection = self.connection.lock().unwrap();
+        self.send_message_with(uuid, msg, None)
+    }
 
-        if connection.is_none() {
-            *connection = Some(self.driver.connect()?);
-        }
+    /// Send a command message to the HSM, waiting up to `timeout` for the
+    /// response instead of the connector's configured timeout (if supported
+    /// by the underlying connection, e.g. USB and HTTP)
+    pub fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, Error> {
+        self.send_message_with(uuid, msg, Some(timeout))
+    }
 
+    /// Send a command message to the HSM, overriding the connection's
+    /// timeout if one is given
+    pub(crate) fn send_message_with(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Option<Duration>,
+    ) -> Result<Message, Error> {
+        let connection = {
+            let mut connection = self.connection.lock().unwrap();
+
+            if connection.is_none() {
+                *connection = Some(Arc::from(self.driver.connect()?));
+            }
+
+            Arc::clone(connection.as_ref().unwrap())
+        };
+
+        // Don't hold the lock while the message is in flight, so connections
+        // which support it can service several requests concurrently
         connection
-            .as_ref()
-            .unwrap()
-            .send_message(uuid, msg)
+            .send_message_with(uuid, msg, timeout)
             .map_err(|e| {
                 // In the event of an error, mark this connection as invalid
-                *connection = None;
+                let mut current = self.connection.lock().unwrap();
+
+                if current
+                    .as_ref()
+                    .map(|c| Arc::ptr_eq(c, &connection))
+                    .unwrap_or(false)
+                {
+                    *current = None;
+                }
+
                 e
             })
     }
@@ -103,7 +320,8 @@ impl Clone for Connector {
     fn clone(&self) -> Self {
         Connector {
             connection: self.connection.clone(),
-            driver: self.driver.box_clone(),
+            driver: Arc::clone(&self.driver),
+            reconnect_policy: self.reconnect_policy.clone(),
         }
     }
 }
@@ -112,7 +330,8 @@ impl From<Box<dyn Connectable>> for Connector {
     fn from(driver: Box<dyn Connectable>) -> Connector {
         Connector {
             connection: Arc::new(Mutex::new(None)),
-            driver,
+            driver: Arc::from(driver),
+            reconnect_policy: None,
         }
     }
 }
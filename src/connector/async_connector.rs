@@ -0,0 +1,118 @@
+//! Asynchronous connections to the YubiHSM2 (gated under the `async` feature)
+
+use crate::connector::{self, Message};
+use std::{future::Future, pin::Pin, sync::Arc};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+#[cfg(feature = "http-async")]
+use crate::connector::http::{AsyncHttpConnector, HttpConfig};
+#[cfg(feature = "usb-async")]
+use crate::connector::usb::{AsyncUsbConnector, UsbConfig};
+#[cfg(feature = "mockhsm")]
+use crate::mockhsm::MockHsm;
+
+/// Boxed future returned by asynchronous connections
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Asynchronous connections to the HSM
+pub trait AsyncConnection: Send + Sync {
+    /// Send a command message to the HSM, then read and return the response
+    fn send_message(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+    ) -> BoxFuture<'_, Result<Message, connector::Error>>;
+}
+
+/// Connectors which asynchronously create `AsyncConnection` objects to the HSM
+pub trait AsyncConnectable: Send + Sync {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn AsyncConnectable>;
+
+    /// Open a connection to the HSM using this connector
+    fn connect(&self) -> BoxFuture<'_, Result<Box<dyn AsyncConnection>, connector::Error>>;
+}
+
+/// Asynchronous counterpart of [`Connector`][`connector::Connector`] which
+/// performs I/O without blocking the executor.
+pub struct AsyncConnector {
+    /// Currently active connection (if any)
+    connection: Arc<Mutex<Option<Box<dyn AsyncConnection>>>>,
+
+    /// Backend connector driver
+    driver: Box<dyn AsyncConnectable>,
+}
+
+impl AsyncConnector {
+    /// Create a new asynchronous HTTP connector.
+    ///
+    /// Only plain TCP connections are supported: connecting fails with an
+    /// error if the config enables TLS, a proxy or a Unix domain socket.
+    #[cfg(feature = "http-async")]
+    pub fn http(config: &HttpConfig) -> Self {
+        Self::from(AsyncHttpConnector::create(config))
+    }
+
+    /// Create a new asynchronous USB connector
+    #[cfg(feature = "usb-async")]
+    pub fn usb(config: &UsbConfig) -> Self {
+        Self::from(AsyncUsbConnector::create(config))
+    }
+
+    /// Create an asynchronous mock HSM connector (useful for testing)
+    #[cfg(feature = "mockhsm")]
+    pub fn mockhsm() -> Self {
+        Self::loopback(MockHsm::new())
+    }
+
+    /// Create an asynchronous connector which sends commands directly to the
+    /// given mock HSM in-process (see [`Connector::loopback`]).
+    ///
+    /// [`Connector::loopback`]: crate::connector::Connector::loopback
+    #[cfg(feature = "mockhsm")]
+    pub fn loopback(mockhsm: MockHsm) -> Self {
+        let mockhsm: Box<dyn AsyncConnectable> = Box::new(mockhsm);
+        Self::from(mockhsm)
+    }
+
+    /// Send a command message to the HSM, then read and return the response
+    pub async fn send_message(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+    ) -> Result<Message, connector::Error> {
+        let mut connection = self.connection.lock().await;
+
+        if connection.is_none() {
+            *connection = Some(self.driver.connect().await?);
+        }
+
+        let result = connection.as_ref().unwrap().send_message(uuid, msg).await;
+
+        if result.is_err() {
+            // In the event of an error, mark this connection as invalid
+            *connection = None;
+        }
+
+        result
+    }
+}
+
+impl Clone for AsyncConnector {
+    fn clone(&self) -> Self {
+        AsyncConnector {
+            connection: self.connection.clone(),
+            driver: self.driver.box_clone(),
+        }
+    }
+}
+
+impl From<Box<dyn AsyncConnectable>> for AsyncConnector {
+    fn from(driver: Box<dyn AsyncConnectable>) -> AsyncConnector {
+        AsyncConnector {
+            connection: Arc::new(Mutex::new(None)),
+            driver,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Exponential backoff between attempts to retry failed operations
+
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Number of attempts and exponential backoff between them, used when
+/// retrying HTTP requests (`HttpConfig::retry`), reopening sessions (see
+/// [`ReconnectPolicy`][`super::ReconnectPolicy`]) and resending commands
+/// (see `client::RetryPolicy`)
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Backoff {
+    /// Maximum number of attempts (including the first)
+    pub max_attempts: u32,
+
+    /// Delay before the first retry in milliseconds (doubled on each retry)
+    pub initial_backoff_ms: u64,
+
+    /// Maximum delay between retries in milliseconds
+    pub max_backoff_ms: u64,
+
+    /// Randomize each delay to between half and all of its computed value
+    pub jitter: bool,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 2000,
+            jitter: true,
+        }
+    }
+}
+
+impl Backoff {
+    /// Compute the delay before the given retry (zero-indexed)
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay_ms = self
+            .initial_backoff_ms
+            .saturating_mul(1 << retry.min(32))
+            .min(self.max_backoff_ms);
+
+        if self.jitter && delay_ms > 0 {
+            let half = delay_ms / 2;
+            Duration::from_millis(half + OsRng.next_u64() % (delay_ms - half + 1))
+        } else {
+            Duration::from_millis(delay_ms)
+        }
+    }
+
+    /// Do attempts remain after the given failed attempt (zero-indexed)?
+    pub fn attempts_remain(&self, attempt: u32) -> bool {
+        attempt.saturating_add(1) < self.max_attempts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Backoff;
+    use std::time::Duration;
+
+    #[test]
+    fn exponential_backoff() {
+        let backoff = Backoff {
+            jitter: false,
+            ..Default::default()
+        };
+
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(3), Duration::from_millis(800));
+        assert_eq!(backoff.delay(10), Duration::from_millis(2000));
+
+        assert!(backoff.attempts_remain(1));
+        assert!(!backoff.attempts_remain(2));
+    }
+
+    #[test]
+    fn jittered_backoff() {
+        let backoff = Backoff::default();
+
+        for retry in 0..8 {
+            let delay = backoff.delay(retry);
+            let max = Duration::from_millis((100 << retry).min(2000));
+            assert!(delay >= max / 2 && delay <= max);
+        }
+    }
+}
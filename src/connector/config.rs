@@ -0,0 +1,63 @@
+//! Configuration for selecting a connector at runtime
+
+#[cfg(feature = "http")]
+use super::HttpConfig;
+#[cfg(any(feature = "usb", feature = "usb-nusb"))]
+use super::UsbConfig;
+use serde::{Deserialize, Serialize};
+
+/// Configuration for a [`Connector`][`super::Connector`] whose type is chosen
+/// at runtime, e.g. from a configuration file.
+///
+/// The type of connector is selected by the `type` field, with the remaining
+/// fields being the configuration for that type of connector.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+#[allow(clippy::large_enum_variant)]
+pub enum ConnectorConfig {
+    /// Connect via HTTP using `yubihsm-connector`
+    #[cfg(feature = "http")]
+    Http(HttpConfig),
+
+    /// Connect via USB
+    #[cfg(any(feature = "usb", feature = "usb-nusb"))]
+    Usb(UsbConfig),
+
+    /// Connect to a mock HSM (useful for testing)
+    #[cfg(feature = "mockhsm")]
+    MockHsm,
+
+    /// Connect using the first of the given connectors which succeeds,
+    /// failing over to the others when a connection errors
+    Failover {
+        /// Connectors in order of preference
+        connectors: Vec<ConnectorConfig>,
+    },
+}
+
+#[cfg(all(test, feature = "http", feature = "setup"))]
+mod tests {
+    use super::ConnectorConfig;
+
+    #[test]
+    fn deserialize_failover() {
+        let config: ConnectorConfig = serde_json::from_str(
+            r#"{
+                "type": "failover",
+                "connectors": [{ "type": "http", "addr": "10.0.0.1", "port": 12345 }]
+            }"#,
+        )
+        .unwrap();
+
+        match config {
+            ConnectorConfig::Failover { connectors } => match connectors.as_slice() {
+                [ConnectorConfig::Http(http)] => {
+                    assert_eq!(http.addr, "10.0.0.1");
+                    assert_eq!(http.port, 12345);
+                }
+                other => panic!("unexpected connectors: {other:?}"),
+            },
+            other => panic!("unexpected config: {other:?}"),
+        }
+    }
+}
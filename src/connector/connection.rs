@@ -1,6 +1,7 @@
 //! Trait shared across all methods for connecting to the YubiHSM2
 
 use crate::connector;
+use std::time::Duration;
 use uuid::Uuid;
 
 /// Connections to the HSM
@@ -11,4 +12,36 @@ pub trait Connection: Send + Sync {
         uuid: Uuid,
         msg: connector::Message,
     ) -> Result<connector::Message, connector::Error>;
+
+    /// Send a command message to the HSM like [`Connection::send_message`],
+    /// but wait up to `timeout` for the response instead of the connection's
+    /// configured timeout.
+    ///
+    /// Connections which don't support per-message timeouts use their
+    /// configured timeout.
+    fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        msg: connector::Message,
+        timeout: Duration,
+    ) -> Result<connector::Message, connector::Error> {
+        let _ = timeout;
+        self.send_message(uuid, msg)
+    }
+}
+
+impl dyn Connection {
+    /// Send a command message to the HSM, overriding the connection's
+    /// timeout if one is given
+    pub(crate) fn send_message_with(
+        &self,
+        uuid: Uuid,
+        msg: connector::Message,
+        timeout: Option<Duration>,
+    ) -> Result<connector::Message, connector::Error> {
+        match timeout {
+            Some(timeout) => self.send_message_with_timeout(uuid, msg, timeout),
+            None => self.send_message(uuid, msg),
+        }
+    }
 }
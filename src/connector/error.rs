@@ -1,6 +1,7 @@
 //! Error types for `yubihsm-connector`
 
 use crate::error::{BoxError, Context};
+use serde::{Deserialize, Serialize};
 use std::{fmt, io, num::ParseIntError, str::Utf8Error};
 use thiserror::Error;
 
@@ -11,12 +12,17 @@ use super::http;
 pub type Error = crate::Error<ErrorKind>;
 
 /// `yubihsm-connector` related error kinds
-#[derive(Copy, Clone, Debug, Eq, Error, PartialEq)]
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Error, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ErrorKind {
     /// Address provided was not valid
     #[error("invalid address")]
     AddrInvalid,
 
+    /// Connector configuration was not valid
+    #[error("invalid configuration")]
+    ConfigInvalid,
+
     /// Access denied
     #[error("access denied")]
     AccessDenied,
@@ -41,8 +47,19 @@ pub enum ErrorKind {
     #[error("bad response from connector")]
     ResponseError,
 
+    /// `yubihsm-connector` (or a proxy in front of it) returned an
+    /// unsuccessful HTTP status (e.g. 503 if it's overloaded). The body of
+    /// the response is available as the error's source, as an
+    /// [`HttpResponseError`][`http::HttpResponseError`].
+    #[cfg(feature = "http")]
+    #[error("HTTP error response (status {code})")]
+    HttpResponseError {
+        /// HTTP status code
+        code: u16,
+    },
+
     /// USB operation failed
-    #[cfg(feature = "usb")]
+    #[cfg(any(feature = "usb", feature = "usb-nusb"))]
     #[error("USB error")]
     UsbError,
 }
@@ -69,13 +86,22 @@ impl From<io::Error> for Error {
 #[cfg(feature = "http")]
 impl From<http::client::Error> for Error {
     fn from(err: http::client::Error) -> Error {
+        if let Some(response) = err.http_response() {
+            let kind = ErrorKind::HttpResponseError {
+                code: response.code,
+            };
+
+            return kind.context(response.clone()).into();
+        }
+
         let kind = match err.kind() {
             http::client::ErrorKind::AddrInvalid => ErrorKind::AddrInvalid,
             http::client::ErrorKind::IoError => ErrorKind::IoError,
-            http::client::ErrorKind::ParseError | http::client::ErrorKind::ResponseError => {
-                ErrorKind::ResponseError
-            }
+            http::client::ErrorKind::ParseError
+            | http::client::ErrorKind::ResponseError
+            | http::client::ErrorKind::StatusError => ErrorKind::ResponseError,
             http::client::ErrorKind::RequestError => ErrorKind::RequestError,
+            http::client::ErrorKind::TlsError => ErrorKind::ConnectionFailed,
         };
 
         kind.context(err).into()
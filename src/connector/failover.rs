@@ -0,0 +1,156 @@
+//! Connector which fails over between an ordered list of connectors
+
+use super::health::HealthTracker;
+use crate::connector::{self, Connectable, Connection};
+use std::{sync::Arc, time::Duration};
+
+/// How long a connector which failed is tried after the others (unless they
+/// fail too) before it's preferred again
+pub(crate) const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Connects using the first of an ordered list of connectors (e.g. USB, then
+/// HTTP) which succeeds.
+///
+/// When one of the connectors fails to connect, or a connection made by it
+/// errors, it's tried after the others for the next [`RETRY_INTERVAL`], so
+/// subsequent commands (and the session re-established for them) use a
+/// healthy path. Once the interval has elapsed, connectors earlier in the
+/// list are preferred again, and are only marked as healthy again once they
+/// connect successfully. Connectors aren't probed in the background, so a
+/// connector which recovered is only noticed the next time a connection is
+/// opened after the interval.
+pub(crate) struct FailoverConnector {
+    /// Connectors in order of preference
+    connectors: Arc<Vec<Box<dyn Connectable>>>,
+
+    /// Which connectors recently failed
+    health: Arc<HealthTracker>,
+}
+
+impl FailoverConnector {
+    /// Create a new `FailoverConnector` from the given connectors, in order
+    /// of preference
+    pub fn create(connectors: Vec<Box<dyn Connectable>>) -> Box<dyn Connectable> {
+        Self::with_retry_interval(connectors, RETRY_INTERVAL)
+    }
+
+    /// Create a new `FailoverConnector` which tries failed connectors after
+    /// the others for the given interval
+    pub(crate) fn with_retry_interval(
+        connectors: Vec<Box<dyn Connectable>>,
+        retry_interval: Duration,
+    ) -> Box<dyn Connectable> {
+        Box::new(FailoverConnector {
+            health: Arc::new(HealthTracker::new(
+                "failover",
+                connectors.len(),
+                retry_interval,
+            )),
+            connectors: Arc::new(connectors),
+        })
+    }
+}
+
+impl Connectable for FailoverConnector {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn Connectable> {
+        Box::new(FailoverConnector {
+            connectors: Arc::clone(&self.connectors),
+            health: Arc::clone(&self.health),
+        })
+    }
+
+    /// Open a connection using the first connector which succeeds, trying
+    /// recently failed connectors last
+    fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+        let order = self.health.healthy_first(0..self.connectors.len());
+        Ok(Box::new(self.health.connect(&self.connectors, order)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FailoverConnector;
+    use crate::{
+        connector::{self, Connectable},
+        test_util::TestConnector,
+    };
+    use std::{sync::atomic::Ordering, thread, time::Duration};
+    use uuid::Uuid;
+
+    /// Retry interval used in tests
+    const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Connect with the given connector and send a message
+    fn connect_and_send(connector: &dyn Connectable) -> Result<Vec<u8>, connector::Error> {
+        connector
+            .connect()?
+            .send_message(Uuid::nil(), vec![].into())
+            .map(|response| response.as_ref().to_vec())
+    }
+
+    /// Create a failover connector for a primary and backup test connector
+    fn failover(primary: &TestConnector) -> Box<dyn Connectable> {
+        let backup = TestConnector {
+            id: 1,
+            ..Default::default()
+        };
+
+        FailoverConnector::with_retry_interval(
+            vec![Box::new(primary.clone()), Box::new(backup)],
+            RETRY_INTERVAL,
+        )
+    }
+
+    #[test]
+    fn fails_over_and_back() {
+        let primary = TestConnector::default();
+        let failover = failover(&primary);
+
+        assert_eq!(connect_and_send(&*failover).unwrap(), [0]);
+
+        primary.broken.store(true, Ordering::Relaxed);
+        assert!(connect_and_send(&*failover).is_err());
+        assert_eq!(connect_and_send(&*failover).unwrap(), [1]);
+
+        // The primary is preferred again once the retry interval has elapsed
+        primary.broken.store(false, Ordering::Relaxed);
+        assert_eq!(connect_and_send(&*failover).unwrap(), [1]);
+        thread::sleep(RETRY_INTERVAL);
+        assert_eq!(connect_and_send(&*failover).unwrap(), [0]);
+    }
+
+    #[test]
+    fn stays_failed_over_while_primary_is_down() {
+        let primary = TestConnector::default();
+        let failover = failover(&primary);
+
+        primary.broken.store(true, Ordering::Relaxed);
+        assert!(connect_and_send(&*failover).is_err());
+
+        for _ in 0..3 {
+            assert_eq!(connect_and_send(&*failover).unwrap(), [1]);
+        }
+
+        // The primary is retried after the interval, and is tried last again
+        // once it fails
+        thread::sleep(RETRY_INTERVAL);
+        assert!(connect_and_send(&*failover).is_err());
+        assert_eq!(connect_and_send(&*failover).unwrap(), [1]);
+    }
+
+    #[test]
+    fn records_connect_failures() {
+        let primary = TestConnector::default();
+        let failover = failover(&primary);
+
+        primary.down.store(true, Ordering::Relaxed);
+        assert_eq!(connect_and_send(&*failover).unwrap(), [1]);
+        assert_eq!(connect_and_send(&*failover).unwrap(), [1]);
+        assert_eq!(primary.connects.load(Ordering::Relaxed), 1);
+
+        primary.down.store(false, Ordering::Relaxed);
+        thread::sleep(RETRY_INTERVAL);
+        assert_eq!(connect_and_send(&*failover).unwrap(), [0]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Tracking which of several connectors recently failed, shared by the
+//! failover and pool connectors
+
+use crate::connector::{self, Connectable, Connection, ErrorKind::ConnectionFailed, Message};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Tracks when each of a list of connectors last failed, either to connect
+/// or while sending a message over a connection made by it.
+///
+/// Connectors which failed are considered unhealthy for the retry interval,
+/// and tried after the healthy ones. There's no active probing: once the
+/// interval has elapsed, a connector is only marked as healthy again when
+/// connecting with it succeeds.
+pub(crate) struct HealthTracker {
+    /// Prefix for debug messages (e.g. the name of the connector)
+    label: &'static str,
+
+    /// When each connector last failed (if it hasn't recovered since)
+    failures: Mutex<Vec<Option<Instant>>>,
+
+    /// How long connectors are considered unhealthy after failing
+    retry_interval: Duration,
+}
+
+impl HealthTracker {
+    /// Create a tracker for the given number of connectors, all of which are
+    /// initially healthy
+    pub fn new(label: &'static str, len: usize, retry_interval: Duration) -> Self {
+        Self {
+            label,
+            failures: Mutex::new(vec![None; len]),
+            retry_interval,
+        }
+    }
+
+    /// Is each connector currently healthy?
+    pub fn healthy(&self) -> Vec<bool> {
+        self.failures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|failure| !failure.is_some_and(|at| at.elapsed() < self.retry_interval))
+            .collect()
+    }
+
+    /// Reorder the given connector indexes so healthy connectors come
+    /// first, otherwise keeping their order
+    pub fn healthy_first(&self, order: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        let healthy = self.healthy();
+        let mut order: Vec<usize> = order.into_iter().collect();
+        order.sort_by_key(|&index| !healthy[index]);
+        order
+    }
+
+    /// Record that the given connector failed
+    pub fn mark_failed(&self, index: usize) {
+        self.failures.lock().unwrap()[index] = Some(Instant::now());
+    }
+
+    /// Connect with the first of the given connectors which succeeds, trying
+    /// them in the given order and recording which fail
+    pub fn connect(
+        self: &Arc<Self>,
+        connectors: &[Box<dyn Connectable>],
+        order: impl IntoIterator<Item = usize>,
+    ) -> Result<TrackedConnection, connector::Error> {
+        let mut last_error = None;
+
+        for index in order {
+            match connectors[index].connect() {
+                Ok(connection) => {
+                    self.failures.lock().unwrap()[index] = None;
+
+                    return Ok(TrackedConnection {
+                        connection,
+                        index,
+                        health: Arc::clone(self),
+                    });
+                }
+                Err(e) => {
+                    debug!(
+                        "{}: error connecting with connector #{}: {}",
+                        self.label, index, e
+                    );
+                    self.mark_failed(index);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            format_err!(ConnectionFailed, "{}: no connectors configured", self.label).into()
+        }))
+    }
+}
+
+/// Connection made by [`HealthTracker::connect`], which records that its
+/// connector failed if sending a message errors
+pub(crate) struct TrackedConnection {
+    /// Underlying connection
+    connection: Box<dyn Connection>,
+
+    /// Index of the connector which made this connection
+    index: usize,
+
+    /// Health of the connector which made this connection
+    health: Arc<HealthTracker>,
+}
+
+impl TrackedConnection {
+    /// Index of the connector which made this connection
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Send a message using the underlying connection, recording that its
+    /// connector failed if it errors
+    fn send(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Option<Duration>,
+    ) -> Result<Message, connector::Error> {
+        self.connection
+            .send_message_with(uuid, msg, timeout)
+            .map_err(|e| {
+                debug!(
+                    "{}: connector #{} failed: {}",
+                    self.health.label, self.index, e
+                );
+                self.health.mark_failed(self.index);
+                e
+            })
+    }
+}
+
+impl Connection for TrackedConnection {
+    fn send_message(&self, uuid: Uuid, msg: Message) -> Result<Message, connector::Error> {
+        self.send(uuid, msg, None)
+    }
+
+    fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, connector::Error> {
+        self.send(uuid, msg, Some(timeout))
+    }
+}
@@ -2,18 +2,35 @@
 //!
 //! <https://developers.yubico.com/YubiHSM2/Component_Reference/yubihsm-connector/>
 
+#[cfg(feature = "http-async")]
+mod async_connection;
 pub(super) mod client;
 mod config;
 mod connection;
+mod health;
+mod pool;
+mod retry;
 #[cfg(feature = "http-server")]
 mod server;
+mod transport;
 
-pub use self::config::HttpConfig;
 #[cfg(feature = "http-server")]
 pub use self::server::Server;
+pub use self::{
+    client::HttpResponseError,
+    config::{HttpConfig, ProxyConfig, ProxyProtocol, DEFAULT_MAX_RESPONSE_SIZE},
+    health::{check_status, check_transport_status, HealthCheck},
+    transport::HttpTransport,
+};
 
 use self::connection::HttpConnection;
 use crate::connector::{self, Connectable, Connection};
+use std::sync::Arc;
+
+#[cfg(feature = "http-async")]
+use self::async_connection::AsyncHttpConnection;
+#[cfg(feature = "http-async")]
+use crate::connector::{AsyncConnectable, AsyncConnection, BoxFuture};
 
 /// Connect to the HSM via HTTP(S) using `yubihsm-connector`.
 ///
@@ -29,25 +46,51 @@ use crate::connector::{self, Connectable, Connection};
 /// <https://developers.yubico.com/YubiHSM2/Component_Reference/yubihsm-connector/>
 ///
 /// [Yubico SDK]: https://developers.yubico.com/YubiHSM2/Releases/
-#[derive(Clone, Default, Debug)]
-pub(crate) struct HttpConnector(HttpConfig);
+#[derive(Clone)]
+pub(crate) struct HttpConnector {
+    /// Configuration for connecting to `yubihsm-connector`
+    config: HttpConfig,
+
+    /// Custom transport to make requests with (if any)
+    transport: Option<Arc<dyn HttpTransport>>,
+}
 
 impl HttpConnector {
     /// Create a new `HttpConnector` with the given configuration
     pub fn create(config: &HttpConfig) -> Box<dyn Connectable> {
-        Box::new(HttpConnector(config.clone()))
+        Box::new(HttpConnector {
+            config: config.clone(),
+            transport: None,
+        })
+    }
+
+    /// Create a new `HttpConnector` which makes requests using the given
+    /// transport rather than the built-in HTTP client
+    pub fn with_transport(
+        config: &HttpConfig,
+        transport: Arc<dyn HttpTransport>,
+    ) -> Box<dyn Connectable> {
+        Box::new(HttpConnector {
+            config: config.clone(),
+            transport: Some(transport),
+        })
     }
 }
 
 impl Connectable for HttpConnector {
     /// Make a clone of this connectable as boxed trait object
     fn box_clone(&self) -> Box<dyn Connectable> {
-        Box::new(HttpConnector(self.0.clone()))
+        Box::new(self.clone())
     }
 
     /// Open a connection to `yubihsm-connector`
     fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
-        Ok(Box::new(HttpConnection::open(&self.0)?))
+        let connection = match &self.transport {
+            Some(transport) => HttpConnection::with_transport(&self.config, transport.clone()),
+            None => HttpConnection::open(&self.config)?,
+        };
+
+        Ok(Box::new(connection))
     }
 }
 
@@ -56,3 +99,36 @@ impl Into<Box<dyn Connectable>> for HttpConnector {
         Box::new(self)
     }
 }
+
+/// Connect to the HSM asynchronously via HTTP using `yubihsm-connector`.
+///
+/// `AsyncHttpConnector` is available when the `http-async` cargo feature is
+/// enabled.
+#[cfg(feature = "http-async")]
+#[derive(Clone, Default, Debug)]
+pub(crate) struct AsyncHttpConnector(HttpConfig);
+
+#[cfg(feature = "http-async")]
+impl AsyncHttpConnector {
+    /// Create a new `AsyncHttpConnector` with the given configuration
+    pub fn create(config: &HttpConfig) -> Box<dyn AsyncConnectable> {
+        Box::new(AsyncHttpConnector(config.clone()))
+    }
+}
+
+#[cfg(feature = "http-async")]
+impl AsyncConnectable for AsyncHttpConnector {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn AsyncConnectable> {
+        Box::new(AsyncHttpConnector(self.0.clone()))
+    }
+
+    /// Open an asynchronous connection to `yubihsm-connector`
+    fn connect(&self) -> BoxFuture<'_, Result<Box<dyn AsyncConnection>, connector::Error>> {
+        Box::pin(async move {
+            let connection: Box<dyn AsyncConnection> =
+                Box::new(AsyncHttpConnection::open(&self.0).await?);
+            Ok(connection)
+        })
+    }
+}
@@ -0,0 +1,41 @@
+//! Asynchronous HTTP connection to `yubihsm-connector`
+
+use super::{client, config::HttpConfig};
+use crate::connector::{self, AsyncConnection, BoxFuture};
+use uuid::Uuid;
+
+/// Asynchronous connection to YubiHSM via HTTP requests to `yubihsm-connector`.
+///
+/// This is the asynchronous counterpart of `HttpConnection`, built on tokio.
+pub struct AsyncHttpConnection {
+    /// HTTP connection
+    connection: client::AsyncConnection,
+}
+
+impl AsyncHttpConnection {
+    /// Open a connection to a `yubihsm-connector` service
+    pub(crate) async fn open(config: &HttpConfig) -> Result<Self, connector::Error> {
+        let opts = config.connection_options();
+        let connection = client::AsyncConnection::open(&config.addr, config.port, &opts).await?;
+
+        Ok(AsyncHttpConnection { connection })
+    }
+}
+
+impl AsyncConnection for AsyncHttpConnection {
+    /// `POST /connector/api` with a given command message
+    fn send_message(
+        &self,
+        _uuid: Uuid,
+        cmd: connector::Message,
+    ) -> BoxFuture<'_, Result<connector::Message, connector::Error>> {
+        Box::pin(async move {
+            Ok(self
+                .connection
+                .post("/connector/api", &client::request::Body::new(cmd.as_ref()))
+                .await?
+                .into_vec()
+                .into())
+        })
+    }
+}
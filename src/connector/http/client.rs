@@ -4,11 +4,19 @@
 #[macro_use]
 pub mod error;
 
+#[cfg(feature = "http-async")]
+pub mod async_connection;
 pub mod connection;
 pub mod path;
+mod proxy;
 pub mod request;
 pub mod response;
+mod socks5;
+#[cfg(any(feature = "https", feature = "tls-rustls"))]
+mod tls;
 
+#[cfg(feature = "http-async")]
+pub use self::async_connection::AsyncConnection;
 pub use self::{connection::*, error::*};
 
 /// HTTP version.
@@ -16,3 +24,22 @@ pub const HTTP_VERSION: &str = "HTTP/1.1";
 
 /// `User-Agent` string.
 pub const USER_AGENT: &str = concat!("yubihsm.rs ", env!("CARGO_PKG_VERSION"));
+
+/// Strip the brackets from a bracketed IPv6 literal (e.g. `[::1]`)
+pub fn unbracket(addr: &str) -> &str {
+    addr.strip_prefix('[')
+        .and_then(|addr| addr.strip_suffix(']'))
+        .unwrap_or(addr)
+}
+
+/// Format an address and port as an authority suitable for the `Host`
+/// header, bracketing IPv6 literals (e.g. `[::1]:12345`)
+pub fn authority(addr: &str, port: u16) -> String {
+    let addr = unbracket(addr);
+
+    if addr.contains(':') {
+        format!("[{addr}]:{port}")
+    } else {
+        format!("{addr}:{port}")
+    }
+}
@@ -0,0 +1,172 @@
+//! Asynchronous connections to HTTP servers (using tokio)
+
+use super::{error::Error, path::PathBuf as HttpPath, request, response, ConnectionOptions};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Mutex,
+    time,
+};
+
+/// Asynchronous HTTP connection to a remote host.
+///
+/// If a request fails, times out or is cancelled (i.e. its future is
+/// dropped), the socket may be left partway through an exchange, so it's
+/// discarded and the next request opens a new one.
+pub struct AsyncConnection {
+    /// Address of the remote host
+    addr: String,
+
+    /// Port of the remote host
+    port: u16,
+
+    /// Host header to send in HTTP requests
+    host: String,
+
+    /// Socket to the remote host, or `None` if it must be reopened
+    socket: Mutex<Option<BufReader<TcpStream>>>,
+
+    /// Options used when (re)opening the socket and sending requests
+    opts: ConnectionOptions,
+}
+
+impl AsyncConnection {
+    /// Create a new asynchronous connection to an HTTP server
+    pub async fn open(addr: &str, port: u16, opts: &ConnectionOptions) -> Result<Self, Error> {
+        if opts.tls {
+            fail!(
+                TlsError,
+                "TLS is not yet supported by asynchronous connections"
+            );
+        }
+
+        ensure!(
+            opts.proxy.is_none() && opts.unix_socket.is_none(),
+            RequestError,
+            "proxies and Unix domain sockets are not yet supported by asynchronous connections"
+        );
+
+        let addr = super::unbracket(addr);
+        let host = super::authority(addr, port);
+        request::validate_headers(&opts.headers)?;
+
+        let connection = Self {
+            addr: addr.to_owned(),
+            port,
+            host,
+            socket: Mutex::new(None),
+            opts: opts.clone(),
+        };
+
+        let socket = connection.connect().await?;
+        *connection.socket.lock().await = Some(socket);
+        Ok(connection)
+    }
+
+    /// Make an HTTP POST request to the given path
+    pub async fn post<P: Into<HttpPath>>(
+        &self,
+        into_path: P,
+        body: &request::Body,
+    ) -> Result<response::Body, Error> {
+        let request = request::post(&self.host, &into_path.into(), &self.opts.headers, body)?;
+        let mut state = self.socket.lock().await;
+
+        // The socket is only put back once the exchange completes
+        let mut socket = match state.take() {
+            Some(socket) => socket,
+            None => self.connect().await?,
+        };
+
+        time::timeout(
+            self.opts.write_timeout,
+            socket.get_mut().write_all(&request),
+        )
+        .await
+        .map_err(|_| err!(IoError, "timed out sending request to {}", self.host))??;
+
+        let response = time::timeout(
+            self.opts.read_timeout,
+            response::read_async(&mut socket, self.opts.max_response_size),
+        )
+        .await
+        .map_err(|_| err!(IoError, "timed out waiting for response from {}", self.host))??;
+
+        *state = Some(socket);
+        Ok(response)
+    }
+
+    /// Open a socket to the remote host
+    async fn connect(&self) -> Result<BufReader<TcpStream>, Error> {
+        let socket = time::timeout(
+            self.opts.connect_timeout,
+            TcpStream::connect((self.addr.as_str(), self.port)),
+        )
+        .await
+        .map_err(|_| err!(IoError, "timed out connecting to {}", self.host))??;
+
+        super::connection::set_tcp_options(&socket, &self.opts)?;
+        Ok(BufReader::new(socket))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader as StdBufReader, Read, Write},
+        net::TcpListener,
+        thread,
+        time::Duration,
+    };
+
+    /// Read an HTTP request with a 4-byte body from the given socket
+    fn read_request(reader: &mut StdBufReader<std::net::TcpStream>) {
+        let mut line = String::new();
+
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        let mut body = [0u8; 4];
+        reader.read_exact(&mut body).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconnect_after_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            // Never answer the request sent over the first connection
+            let (first, _) = listener.accept().unwrap();
+            read_request(&mut StdBufReader::new(first.try_clone().unwrap()));
+
+            let (second, _) = listener.accept().unwrap();
+            let mut reader = StdBufReader::new(second);
+            read_request(&mut reader);
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\npong")
+                .unwrap();
+            drop(first);
+        });
+
+        let opts = ConnectionOptions {
+            read_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+
+        let connection = AsyncConnection::open("127.0.0.1", port, &opts)
+            .await
+            .unwrap();
+        let body = request::Body::new(b"ping");
+
+        assert!(connection.post("/connector/api", &body).await.is_err());
+
+        let response = connection.post("/connector/api", &body).await.unwrap();
+        assert_eq!(response.into_vec(), b"pong");
+        server.join().unwrap();
+    }
+}
@@ -1,90 +1,444 @@
 //! Connections to HTTP servers
 
 use std::{
-    fmt::Write as FmtWrite,
-    io::Write,
-    net::{TcpStream, ToSocketAddrs},
+    fmt::{self, Debug},
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
     ops::DerefMut,
+    path::{Path, PathBuf},
     string::String,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
     time::Duration,
-    vec::Vec,
 };
 
-use super::{error::Error, path::PathBuf, request, response, HTTP_VERSION, USER_AGENT};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use super::{error::Error, path::PathBuf as HttpPath, proxy, request, response};
+use crate::connector::http::{ProxyConfig, DEFAULT_MAX_RESPONSE_SIZE};
+use socket2::{SockRef, TcpKeepalive};
 
 /// Default timeout in milliseconds (20 seconds)
 const DEFAULT_TIMEOUT_MS: u64 = 20000;
 
+/// Counter used to select the first address to try when `round_robin` is set
+static NEXT_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+/// Bidirectional byte streams which HTTP requests can be sent over
+pub trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
 /// Options when building a `Connection`
+#[derive(Clone)]
 pub struct ConnectionOptions {
-    timeout: Duration,
+    /// Timeout for connecting
+    pub connect_timeout: Duration,
+
+    /// Timeout for reading responses
+    pub read_timeout: Duration,
+
+    /// Timeout for writing requests
+    pub write_timeout: Duration,
+
+    /// Negotiate TLS with the remote host (i.e. HTTPS)
+    pub tls: bool,
+
+    /// PEM-encoded CA certificates to trust in addition to the default roots
+    /// of the TLS implementation (see the `tls` module)
+    pub ca_bundle: Option<PathBuf>,
+
+    /// PEM-encoded client certificate (chain) to authenticate with
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM-encoded PKCS#8 private key for the client certificate
+    pub client_key: Option<PathBuf>,
+
+    /// Proxy to tunnel the connection through
+    pub proxy: Option<ProxyConfig>,
+
+    /// Maximum size of a response (headers and body) in bytes
+    pub max_response_size: usize,
+
+    /// Connect over the Unix domain socket at the given path instead of TCP
+    pub unix_socket: Option<PathBuf>,
+
+    /// Rotate which resolved address is tried first on each connection,
+    /// spreading connections across all of a host's addresses
+    pub round_robin: bool,
+
+    /// Additional headers to send with every request
+    pub headers: Vec<(String, String)>,
+
+    /// Disable Nagle's algorithm on TCP connections (`TCP_NODELAY`)
+    pub tcp_nodelay: bool,
+
+    /// Idle time (and interval) before sending TCP keepalive probes
+    pub tcp_keepalive: Option<Duration>,
 }
 
 impl Default for ConnectionOptions {
     fn default() -> Self {
         Self {
-            timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            connect_timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            read_timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            write_timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            tls: false,
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+            proxy: None,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            unix_socket: None,
+            round_robin: false,
+            headers: vec![],
+            tcp_nodelay: false,
+            tcp_keepalive: None,
         }
     }
 }
 
+impl Debug for ConnectionOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid leaking header values (e.g. bearer tokens) in debug messages
+        let headers: Vec<_> = self.headers.iter().map(|(name, _)| (name, "...")).collect();
+
+        f.debug_struct("ConnectionOptions")
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("write_timeout", &self.write_timeout)
+            .field("tls", &self.tls)
+            .field("ca_bundle", &self.ca_bundle)
+            .field("client_cert", &self.client_cert)
+            .field("client_key", &self.client_key)
+            .field("proxy", &self.proxy)
+            .field("max_response_size", &self.max_response_size)
+            .field("unix_socket", &self.unix_socket)
+            .field("round_robin", &self.round_robin)
+            .field("headers", &headers)
+            .field("tcp_nodelay", &self.tcp_nodelay)
+            .field("tcp_keepalive", &self.tcp_keepalive)
+            .finish()
+    }
+}
+
 /// HTTP connection to a remote host
 pub struct Connection {
     /// Host header to send in HTTP requests
     host: String,
 
-    /// Open TCP socket to remote host
-    socket: Mutex<TcpStream>,
+    /// Open socket to remote host
+    socket: Mutex<Box<dyn Stream>>,
+
+    /// Handle to the underlying socket (for adjusting its read timeout)
+    raw_socket: RawSocket,
+
+    /// Default timeout for reading responses
+    read_timeout: Duration,
+
+    /// Additional headers to send with every request
+    headers: Vec<(String, String)>,
+
+    /// Maximum size of a response in bytes
+    max_response_size: usize,
 }
 
 impl Connection {
     /// Create a new connection to an HTTP server
     pub fn open(addr: &str, port: u16, opts: &ConnectionOptions) -> Result<Self, Error> {
-        let host = format!("{addr}:{port}");
+        let addr = super::unbracket(addr);
+        let host = super::authority(addr, port);
+        request::validate_headers(&opts.headers)?;
+
+        ensure!(
+            opts.tls
+                || (opts.ca_bundle.is_none()
+                    && opts.client_cert.is_none()
+                    && opts.client_key.is_none()),
+            TlsError,
+            "TLS options given for a plaintext HTTP connection"
+        );
 
-        let socketaddr = &host.to_socket_addrs()?.next().ok_or_else(|| {
-            err!(
-                AddrInvalid,
-                "couldn't resolve DNS for {}",
-                host.split(':').next().unwrap()
-            )
-        })?;
+        if let Some(path) = &opts.unix_socket {
+            ensure!(
+                !opts.tls && opts.proxy.is_none(),
+                RequestError,
+                "TLS and proxies are not supported over Unix domain sockets"
+            );
+
+            let (socket, raw_socket) = Self::connect_unix(path, opts)?;
+
+            return Ok(Self {
+                host,
+                socket: Mutex::new(socket),
+                raw_socket,
+                read_timeout: opts.read_timeout,
+                headers: opts.headers.clone(),
+                max_response_size: opts.max_response_size,
+            });
+        }
 
-        // TODO: better timeout handling?
-        let socket = TcpStream::connect_timeout(socketaddr, opts.timeout)?;
-        socket.set_read_timeout(Some(opts.timeout))?;
-        socket.set_write_timeout(Some(opts.timeout))?;
+        let mut socket = match &opts.proxy {
+            Some(proxy) => Self::connect_tcp(&proxy.addr, proxy.port, opts)?,
+            None => Self::connect_tcp(addr, port, opts)?,
+        };
+
+        if let Some(proxy) = &opts.proxy {
+            proxy::connect_tunnel(&mut socket, addr, port, proxy)?;
+        }
+
+        let raw_socket = RawSocket::Tcp(socket.try_clone()?);
+
+        let stream = if opts.tls {
+            Self::negotiate_tls(addr, socket, opts)?
+        } else {
+            Box::new(socket)
+        };
 
         Ok(Self {
             host,
-            socket: Mutex::new(socket),
+            socket: Mutex::new(stream),
+            raw_socket,
+            read_timeout: opts.read_timeout,
+            headers: opts.headers.clone(),
+            max_response_size: opts.max_response_size,
         })
     }
 
+    /// Open a TCP socket to the given host, trying each address it resolves
+    /// to in turn until a connection succeeds
+    fn connect_tcp(addr: &str, port: u16, opts: &ConnectionOptions) -> Result<TcpStream, Error> {
+        let addr = super::unbracket(addr);
+        let mut socketaddrs: Vec<SocketAddr> = (addr, port).to_socket_addrs()?.collect();
+
+        ensure!(
+            !socketaddrs.is_empty(),
+            AddrInvalid,
+            "couldn't resolve DNS for {}",
+            addr
+        );
+
+        if opts.round_robin {
+            let offset = NEXT_ADDR.fetch_add(1, Ordering::Relaxed) % socketaddrs.len();
+            socketaddrs.rotate_left(offset);
+        }
+
+        let mut last_error = None;
+
+        for socketaddr in &socketaddrs {
+            match TcpStream::connect_timeout(socketaddr, opts.connect_timeout) {
+                Ok(socket) => {
+                    socket.set_read_timeout(Some(opts.read_timeout))?;
+                    socket.set_write_timeout(Some(opts.write_timeout))?;
+                    set_tcp_options(&socket, opts)?;
+                    return Ok(socket);
+                }
+                Err(e) => {
+                    debug!("error connecting to {} ({}): {}", addr, socketaddr, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap().into())
+    }
+
+    /// Open a Unix domain socket at the given path
+    #[cfg(unix)]
+    fn connect_unix(
+        path: &Path,
+        opts: &ConnectionOptions,
+    ) -> Result<(Box<dyn Stream>, RawSocket), Error> {
+        let socket = UnixStream::connect(path)?;
+        socket.set_read_timeout(Some(opts.read_timeout))?;
+        socket.set_write_timeout(Some(opts.write_timeout))?;
+        let raw_socket = RawSocket::Unix(socket.try_clone()?);
+        Ok((Box::new(socket), raw_socket))
+    }
+
+    /// Open a Unix domain socket at the given path
+    #[cfg(not(unix))]
+    fn connect_unix(
+        path: &Path,
+        _opts: &ConnectionOptions,
+    ) -> Result<(Box<dyn Stream>, RawSocket), Error> {
+        Err(err!(
+            AddrInvalid,
+            "Unix domain sockets are unsupported on this platform: {}",
+            path.display()
+        ))
+    }
+
+    /// Negotiate TLS over the given socket
+    #[cfg(any(feature = "https", feature = "tls-rustls"))]
+    fn negotiate_tls(
+        addr: &str,
+        socket: TcpStream,
+        opts: &ConnectionOptions,
+    ) -> Result<Box<dyn Stream>, Error> {
+        super::tls::connect(addr, socket, opts)
+    }
+
+    /// Negotiate TLS over the given socket
+    #[cfg(not(any(feature = "https", feature = "tls-rustls")))]
+    fn negotiate_tls(
+        _addr: &str,
+        _socket: TcpStream,
+        _opts: &ConnectionOptions,
+    ) -> Result<Box<dyn Stream>, Error> {
+        Err(err!(
+            TlsError,
+            "TLS support unavailable (enable the `https` or `tls-rustls` cargo feature)"
+        ))
+    }
+
+    /// Make an HTTP GET request to the given path
+    pub fn get<P: Into<HttpPath>>(&self, into_path: P) -> Result<response::Body, Error> {
+        let request = request::get(&self.host, &into_path.into(), &self.headers)?;
+        self.send(&request, None)
+    }
+
     /// Make an HTTP POST request to the given path
-    pub fn post<P: Into<PathBuf>>(
+    pub fn post<P: Into<HttpPath>>(
         &self,
         into_path: P,
         body: &request::Body,
     ) -> Result<response::Body, Error> {
-        let path = into_path.into();
-        let mut headers = String::new();
-
-        writeln!(headers, "POST {path} {HTTP_VERSION}\r")?;
-        writeln!(headers, "Host: {}\r", self.host)?;
-        writeln!(headers, "User-Agent: {USER_AGENT}\r")?;
-        writeln!(headers, "Content-Length: {}\r", body.0.len())?;
-        writeln!(headers, "\r")?;
+        let request = request::post(&self.host, &into_path.into(), &self.headers, body)?;
+        self.send(&request, None)
+    }
 
-        // Make a Nagle-friendly request by combining headers and body
-        let mut request: Vec<u8> = headers.into();
-        request.extend_from_slice(body.0.as_slice());
+    /// Make an HTTP POST request to the given path, waiting up to `timeout`
+    /// for the response instead of the configured read timeout
+    pub fn post_with_timeout<P: Into<HttpPath>>(
+        &self,
+        into_path: P,
+        body: &request::Body,
+        timeout: Duration,
+    ) -> Result<response::Body, Error> {
+        let request = request::post(&self.host, &into_path.into(), &self.headers, body)?;
+        self.send(&request, Some(timeout))
+    }
 
+    /// Send a serialized request and read the response, overriding the read
+    /// timeout if one is given
+    fn send(&self, request: &[u8], timeout: Option<Duration>) -> Result<response::Body, Error> {
         let mut socket = self.socket.lock().unwrap();
-        socket.write_all(&request)?;
+        socket.write_all(request)?;
+
+        if let Some(timeout) = timeout {
+            self.raw_socket.set_read_timeout(timeout)?;
+        }
+
+        let result = response::Reader::new(socket.deref_mut(), self.max_response_size)
+            .map(response::Reader::into_body);
+
+        if timeout.is_some() {
+            self.raw_socket.set_read_timeout(self.read_timeout)?;
+        }
+
+        result
+    }
+}
+
+/// Handle to the socket underlying a connection, which remains accessible
+/// when the stream is wrapped (e.g. in TLS)
+enum RawSocket {
+    /// TCP socket
+    Tcp(TcpStream),
+
+    /// Unix domain socket
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl RawSocket {
+    /// Set the read timeout for the socket
+    fn set_read_timeout(&self, timeout: Duration) -> Result<(), Error> {
+        match self {
+            RawSocket::Tcp(socket) => socket.set_read_timeout(Some(timeout))?,
+            #[cfg(unix)]
+            RawSocket::Unix(socket) => socket.set_read_timeout(Some(timeout))?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Apply the TCP socket options in the given `ConnectionOptions`
+pub(super) fn set_tcp_options<S>(socket: &S, opts: &ConnectionOptions) -> Result<(), Error>
+where
+    for<'a> SockRef<'a>: From<&'a S>,
+{
+    let socket = SockRef::from(socket);
+    socket.set_tcp_nodelay(opts.tcp_nodelay)?;
+
+    if let Some(keepalive) = opts.tcp_keepalive {
+        let params = TcpKeepalive::new().with_time(keepalive);
+
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            windows
+        ))]
+        let params = params.with_interval(keepalive);
+
+        socket.set_tcp_keepalive(&params)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{io::BufRead, io::BufReader, os::unix::net::UnixListener, thread};
+
+    #[test]
+    fn post_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("yubihsm-rs-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket);
+            let mut line = String::new();
+
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+
+            let mut body = [0u8; 4];
+            reader.read_exact(&mut body).unwrap();
+            assert_eq!(&body, b"ping");
+
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\npong")
+                .unwrap();
+        });
+
+        let opts = ConnectionOptions {
+            unix_socket: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let connection = Connection::open("localhost", 12345, &opts).unwrap();
+        let response = connection
+            .post("/connector/api", &request::Body::new(b"ping"))
+            .unwrap();
 
-        let response_body = response::Reader::new(socket.deref_mut())?.into_body();
-        Ok(response_body)
+        assert_eq!(response.into_vec(), b"pong");
+        server.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
     }
 }
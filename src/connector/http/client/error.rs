@@ -16,6 +16,9 @@ pub struct Error {
 
     /// Optional description
     description: Option<String>,
+
+    /// Response from the server, if it returned an unsuccessful status
+    response: Option<HttpResponseError>,
 }
 
 impl Error {
@@ -27,15 +30,30 @@ impl Error {
         err
     }
 
+    /// Create an error for an unsuccessful response from the server
+    pub fn response(code: u16, body: Vec<u8>) -> Self {
+        let mut err = Self::from(ErrorKind::StatusError);
+        err.response = Some(HttpResponseError { code, body });
+        err
+    }
+
     /// Obtain the inner `ErrorKind` for this `Error`
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    /// Obtain the unsuccessful response from the server (if applicable)
+    pub fn http_response(&self) -> Option<&HttpResponseError> {
+        self.response.as_ref()
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.kind.fmt(f)
+        match &self.response {
+            Some(response) => response.fmt(f),
+            None => self.kind.fmt(f),
+        }
     }
 }
 
@@ -46,6 +64,7 @@ impl From<ErrorKind> for Error {
         Error {
             kind,
             description: None,
+            response: None,
         }
     }
 }
@@ -67,6 +86,12 @@ pub enum ErrorKind {
 
     /// Error reading response
     ResponseError,
+
+    /// Server returned an unsuccessful (i.e. non-200) status
+    StatusError,
+
+    /// Error negotiating TLS
+    TlsError,
 }
 
 impl fmt::Display for ErrorKind {
@@ -77,12 +102,41 @@ impl fmt::Display for ErrorKind {
             ErrorKind::ParseError => "parse error",
             ErrorKind::RequestError => "request error",
             ErrorKind::ResponseError => "error reading response",
+            ErrorKind::StatusError => "unsuccessful HTTP response status",
+            ErrorKind::TlsError => "TLS error",
         };
 
         write!(f, "{description}")
     }
 }
 
+/// Unsuccessful (i.e. non-200) response from an HTTP server
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HttpResponseError {
+    /// HTTP status code (e.g. 503)
+    pub code: u16,
+
+    /// Body of the response
+    pub body: Vec<u8>,
+}
+
+impl fmt::Display for HttpResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP status {}", self.code)?;
+
+        let body = String::from_utf8_lossy(&self.body);
+        let body = body.trim();
+
+        if !body.is_empty() {
+            write!(f, ": {body}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpResponseError {}
+
 /// Create a new error (of a given enum variant) with a formatted message
 macro_rules! err {
     ($variant:ident, $msg:expr) => {
@@ -114,7 +168,7 @@ macro_rules! ensure {
         }
     };
     ($condition: expr, $variant:ident, $fmt:expr, $($arg:tt)+) => {
-        ensure!($variant, $fmt, $($arg)+);
+        ensure!($condition, $variant, &format!($fmt, $($arg)+));
     };
 }
 
@@ -0,0 +1,188 @@
+//! Tunneling connections through proxies
+
+use super::{error::Error, socks5, HTTP_VERSION, USER_AGENT};
+use crate::connector::http::{ProxyConfig, ProxyProtocol};
+use base64ct::{Base64, Encoding};
+use std::{
+    fmt::Write as FmtWrite,
+    io::{Read, Write},
+    net::TcpStream,
+    str,
+};
+
+/// Delimiter which ends the proxy's response headers
+const HEADER_DELIMITER: &[u8] = b"\r\n\r\n";
+
+/// Maximum size of the proxy's response to a `CONNECT` request
+const MAX_RESPONSE_SIZE: usize = 8192;
+
+/// Ask the proxy on the other end of `socket` to open a tunnel to the given
+/// address and port
+pub(super) fn connect_tunnel(
+    socket: &mut TcpStream,
+    addr: &str,
+    port: u16,
+    proxy: &ProxyConfig,
+) -> Result<(), Error> {
+    match proxy.protocol {
+        ProxyProtocol::Http => http_connect(socket, &super::authority(addr, port), proxy),
+        ProxyProtocol::Socks5 => socks5::connect(socket, addr, port, proxy),
+    }
+}
+
+/// Open a tunnel to `host` through an HTTP proxy using the `CONNECT` method
+fn http_connect(socket: &mut TcpStream, host: &str, proxy: &ProxyConfig) -> Result<(), Error> {
+    let mut request = String::new();
+
+    writeln!(request, "CONNECT {host} {HTTP_VERSION}\r")?;
+    writeln!(request, "Host: {host}\r")?;
+    writeln!(request, "User-Agent: {USER_AGENT}\r")?;
+
+    if let Some(username) = &proxy.username {
+        let credentials = format!(
+            "{}:{}",
+            username,
+            proxy.password.as_deref().unwrap_or_default()
+        );
+
+        writeln!(
+            request,
+            "Proxy-Authorization: Basic {}\r",
+            Base64::encode_string(credentials.as_bytes())
+        )?;
+    }
+
+    writeln!(request, "\r")?;
+    socket.write_all(request.as_bytes())?;
+
+    let response = read_response(socket)?;
+    let status_line = response.split("\r\n").next().unwrap_or_default();
+
+    // e.g. `HTTP/1.1 200 Connection established`
+    match status_line.split(' ').nth(1) {
+        Some("200") => Ok(()),
+        Some("407") => fail!(
+            RequestError,
+            "proxy {}:{} requires authentication: \"{}\"",
+            proxy.addr,
+            proxy.port,
+            status_line
+        ),
+        _ => fail!(
+            ResponseError,
+            "proxy {}:{} refused tunnel to {}: \"{}\"",
+            proxy.addr,
+            proxy.port,
+            host,
+            status_line
+        ),
+    }
+}
+
+/// Read the proxy's response headers.
+///
+/// This reads a byte at a time to avoid consuming any data sent through the
+/// tunnel after the headers.
+fn read_response(socket: &mut TcpStream) -> Result<String, Error> {
+    let mut response = Vec::new();
+    let mut byte = [0u8];
+
+    while !response.ends_with(HEADER_DELIMITER) {
+        ensure!(
+            response.len() < MAX_RESPONSE_SIZE,
+            ResponseError,
+            "proxy response exceeded maximum size"
+        );
+
+        if socket.read(&mut byte)? == 0 {
+            fail!(ResponseError, "proxy closed connection during CONNECT");
+        }
+
+        response.push(byte[0]);
+    }
+
+    Ok(str::from_utf8(&response)?.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::http::client::error::ErrorKind;
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    /// Run an HTTP proxy which accepts one `CONNECT` request, answers it with
+    /// the given response, and returns the request's lines
+    fn serve_proxy(response: &'static str) -> (ProxyConfig, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket);
+            let mut lines = vec![];
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+
+                if line == "\r\n" {
+                    break;
+                }
+
+                lines.push(line.trim_end().to_owned());
+            }
+
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            lines
+        });
+
+        let proxy = ProxyConfig {
+            protocol: ProxyProtocol::Http,
+            addr: "127.0.0.1".to_owned(),
+            port,
+            username: Some("bob".to_owned()),
+            password: Some("pw!".to_owned()),
+        };
+
+        (proxy, server)
+    }
+
+    #[test]
+    fn connect_with_password() {
+        let (proxy, server) = serve_proxy("HTTP/1.1 200 Connection established\r\n\r\ntunneled");
+
+        let mut socket = TcpStream::connect(("127.0.0.1", proxy.port)).unwrap();
+        connect_tunnel(&mut socket, "hsm.example.com", 12345, &proxy).unwrap();
+
+        let request = server.join().unwrap();
+        assert_eq!(request[0], "CONNECT hsm.example.com:12345 HTTP/1.1");
+        assert!(request.contains(&"Host: hsm.example.com:12345".to_owned()));
+        assert!(request.contains(&"Proxy-Authorization: Basic Ym9iOnB3IQ==".to_owned()));
+
+        // Data sent through the tunnel after the response isn't consumed
+        let mut tunneled = String::new();
+        socket.read_to_string(&mut tunneled).unwrap();
+        assert_eq!(tunneled, "tunneled");
+    }
+
+    #[test]
+    fn connect_refused() {
+        let (proxy, server) = serve_proxy("HTTP/1.1 403 Forbidden\r\n\r\n");
+
+        let mut socket = TcpStream::connect(("127.0.0.1", proxy.port)).unwrap();
+        let err = connect_tunnel(&mut socket, "hsm.example.com", 12345, &proxy).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+        server.join().unwrap();
+
+        let (proxy, server) = serve_proxy("HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+
+        let mut socket = TcpStream::connect(("127.0.0.1", proxy.port)).unwrap();
+        let err = connect_tunnel(&mut socket, "hsm.example.com", 12345, &proxy).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::RequestError);
+        server.join().unwrap();
+    }
+}
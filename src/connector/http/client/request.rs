@@ -1,5 +1,8 @@
 //! HTTP request types
 
+use super::{error::Error, path::PathBuf, HTTP_VERSION, USER_AGENT};
+use std::fmt::Write;
+
 /// Request bodies
 #[derive(Debug, Default)]
 pub struct Body(pub(crate) Vec<u8>);
@@ -16,3 +19,75 @@ impl From<Vec<u8>> for Body {
         Body(bytes)
     }
 }
+
+/// Serialize a `GET` request for the given host and path
+pub(super) fn get(
+    host: &str,
+    path: &PathBuf,
+    extra_headers: &[(String, String)],
+) -> Result<Vec<u8>, Error> {
+    Ok(headers("GET", host, path, extra_headers, None)?.into())
+}
+
+/// Serialize a `POST` request for the given host, path, and body
+pub(super) fn post(
+    host: &str,
+    path: &PathBuf,
+    extra_headers: &[(String, String)],
+    body: &Body,
+) -> Result<Vec<u8>, Error> {
+    let headers = headers("POST", host, path, extra_headers, Some(body.0.len()))?;
+
+    // Make a Nagle-friendly request by combining headers and body
+    let mut request: Vec<u8> = headers.into();
+    request.extend_from_slice(body.0.as_slice());
+    Ok(request)
+}
+
+/// Serialize the request line and headers for a request
+fn headers(
+    method: &str,
+    host: &str,
+    path: &PathBuf,
+    extra_headers: &[(String, String)],
+    content_length: Option<usize>,
+) -> Result<String, Error> {
+    let mut headers = String::new();
+
+    writeln!(headers, "{method} {path} {HTTP_VERSION}\r")?;
+    writeln!(headers, "Host: {host}\r")?;
+    writeln!(headers, "User-Agent: {USER_AGENT}\r")?;
+
+    for (name, value) in extra_headers {
+        writeln!(headers, "{name}: {value}\r")?;
+    }
+
+    if let Some(content_length) = content_length {
+        writeln!(headers, "Content-Length: {content_length}\r")?;
+    }
+
+    writeln!(headers, "\r")?;
+    Ok(headers)
+}
+
+/// Ensure additional request headers are well-formed and can't be used to
+/// inject additional headers or requests
+pub(super) fn validate_headers(headers: &[(String, String)]) -> Result<(), Error> {
+    for (name, value) in headers {
+        ensure!(
+            !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic() && b != b':'),
+            RequestError,
+            "invalid HTTP header name: {:?}",
+            name
+        );
+
+        ensure!(
+            !value.contains(['\r', '\n']),
+            RequestError,
+            "invalid value for HTTP header: {}",
+            name
+        );
+    }
+
+    Ok(())
+}
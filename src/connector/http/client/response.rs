@@ -1,6 +1,10 @@
 //! HTTP response handling
 
+#[cfg(feature = "http-async")]
+mod async_reader;
 mod body;
 mod reader;
 
+#[cfg(feature = "http-async")]
+pub(crate) use self::async_reader::read_async;
 pub use self::{body::Body, reader::Reader};
@@ -0,0 +1,189 @@
+//! Read HTTP responses from a `tokio::io::AsyncBufRead`
+
+use super::{
+    reader::{
+        parse_status, CHUNKED_ENCODING, CONTENT_LENGTH_HEADER, HTTP_SUCCESS_CODE,
+        TRANSFER_ENCODING_HEADER,
+    },
+    Body,
+};
+use crate::connector::http::client::Error;
+use std::vec::Vec;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// Read an HTTP response, returning its body
+pub(crate) async fn read_async<R>(readable: &mut R, max_size: usize) -> Result<Body, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    AsyncReader {
+        readable,
+        remaining: max_size,
+        max_size,
+    }
+    .read_response()
+    .await
+}
+
+/// Reads HTTP responses without exceeding a maximum size
+struct AsyncReader<'a, R> {
+    /// Source of the response
+    readable: &'a mut R,
+
+    /// Number of bytes remaining before we exceed the maximum size
+    remaining: usize,
+
+    /// Maximum size of the response in bytes
+    max_size: usize,
+}
+
+impl<R> AsyncReader<'_, R>
+where
+    R: AsyncBufRead + Unpin,
+{
+    /// Read the response headers and body
+    async fn read_response(mut self) -> Result<Body, Error> {
+        let status_code = parse_status(&self.read_line().await?)?;
+
+        let mut content_length = 0;
+        let mut chunked = false;
+
+        loop {
+            let header = self.read_line().await?;
+
+            if header.is_empty() {
+                break;
+            } else if let Some(value) = header.strip_prefix(CONTENT_LENGTH_HEADER) {
+                content_length = value.parse()?;
+            } else if let Some(transfer_encoding) = header.strip_prefix(TRANSFER_ENCODING_HEADER) {
+                if !transfer_encoding.eq_ignore_ascii_case(CHUNKED_ENCODING) {
+                    fail!(
+                        ResponseError,
+                        "connection sent unsupported transfer encoding: {}",
+                        transfer_encoding
+                    );
+                }
+
+                chunked = true;
+            }
+        }
+
+        let body = if chunked {
+            self.read_chunked_body().await?
+        } else {
+            self.read_exact(content_length).await?
+        };
+
+        if status_code != HTTP_SUCCESS_CODE {
+            return Err(Error::response(status_code, body));
+        }
+
+        Ok(Body(body))
+    }
+
+    /// Read and decode a body sent with chunked transfer encoding
+    async fn read_chunked_body(&mut self) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+
+        loop {
+            let size_line = self.read_line().await?;
+
+            // Ignore any chunk extensions, e.g. `1a;name=value`
+            let size_str = size_line.split(';').next().unwrap().trim();
+            let chunk_size = usize::from_str_radix(size_str, 16)?;
+
+            if chunk_size == 0 {
+                break;
+            }
+
+            body.extend_from_slice(&self.read_exact(chunk_size).await?);
+
+            ensure!(
+                self.read_line().await?.is_empty(),
+                ResponseError,
+                "malformed chunk in response body"
+            );
+        }
+
+        // Discard any trailers until we reach the final empty line
+        while !self.read_line().await?.is_empty() {}
+
+        Ok(body)
+    }
+
+    /// Read a CRLF-terminated line (returned without the CRLF)
+    async fn read_line(&mut self) -> Result<String, Error> {
+        let mut line = Vec::new();
+
+        let nbytes = (&mut *self.readable)
+            .take(self.remaining as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+
+        if nbytes == 0 {
+            fail!(
+                IoError,
+                "read {} bytes, the remote connection was likely shutdown",
+                nbytes
+            );
+        }
+
+        self.remaining -= nbytes;
+
+        if !line.ends_with(b"\r\n") {
+            ensure!(
+                self.remaining > 0,
+                ResponseError,
+                "exceeded {}-byte response limit",
+                self.max_size
+            );
+
+            fail!(ResponseError, "malformed line in HTTP response");
+        }
+
+        line.truncate(line.len() - 2);
+        Ok(String::from_utf8(line)?)
+    }
+
+    /// Read exactly the given number of bytes
+    async fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        ensure!(
+            len <= self.remaining,
+            ResponseError,
+            "response body length too large for buffer ({} bytes)",
+            len
+        );
+
+        let mut bytes = vec![0u8; len];
+        self.readable.read_exact(&mut bytes).await?;
+        self.remaining -= len;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(response: &[u8], max_size: usize) -> Result<Vec<u8>, Error> {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(read_async(&mut &response[..], max_size))
+            .map(Body::into_vec)
+    }
+
+    #[test]
+    fn read_content_length_body() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        assert_eq!(read(response, 1024).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn read_chunked_body() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        assert_eq!(read(response, 1024).unwrap(), b"hello, world");
+        assert!(read(response, 64).is_err());
+    }
+}
@@ -4,22 +4,28 @@
 
 use super::Body;
 use crate::connector::http::client::Error;
+#[cfg(test)]
+use crate::connector::http::client::ErrorKind;
 use std::{io::Read, str, vec::Vec};
 
-const TRANSFER_ENCODING_HEADER: &str = "Transfer-Encoding: ";
+pub(super) const TRANSFER_ENCODING_HEADER: &str = "Transfer-Encoding: ";
 const HEADER_DELIMITER: &[u8] = b"\r\n\r\n";
-const HTTP_SUCCESS_STATUS: &str = "HTTP/1.1 200 OK";
-const CONTENT_LENGTH_HEADER: &str = "Content-Length: ";
+const LINE_DELIMITER: &[u8] = b"\r\n";
+pub(super) const CHUNKED_ENCODING: &str = "chunked";
+pub(super) const HTTP_SUCCESS_CODE: u16 = 200;
+pub(super) const CONTENT_LENGTH_HEADER: &str = "Content-Length: ";
 
-/// Maximum response size we can parse.
-// TODO: we shouldn't have a max, or at least one this small
-const MAX_RESPONSE_SIZE: usize = 65536;
+/// Initial size of the response buffer (grown as needed)
+const INITIAL_BUFFER_SIZE: usize = 4096;
 
 /// Read HTTP responses from the server
 pub struct Reader {
     /// Internal buffer
     buffer: Vec<u8>,
 
+    /// Maximum size the internal buffer is allowed to grow to
+    max_size: usize,
+
     /// Position within the response
     pos: usize,
 
@@ -28,23 +34,36 @@ pub struct Reader {
 
     /// Total length of the response content
     content_length: usize,
+
+    /// Is the response body sent with chunked transfer encoding?
+    chunked: bool,
+
+    /// HTTP status code of the response
+    status_code: u16,
 }
 
 impl Reader {
     /// Create a new `response::Reader` that consumes a response body from a socket
     #[allow(clippy::new_ret_no_self)]
-    pub(crate) fn new(readable: &mut dyn Read) -> Result<Self, Error> {
-        // TODO: better buffering
+    pub(crate) fn new(readable: &mut dyn Read, max_size: usize) -> Result<Self, Error> {
         let mut buffer = Self {
-            buffer: vec![0u8; MAX_RESPONSE_SIZE],
+            buffer: vec![0u8; INITIAL_BUFFER_SIZE.min(max_size)],
+            max_size,
             pos: 0,
             body_offset: None,
             content_length: 0,
+            chunked: false,
+            status_code: 0,
         };
 
         buffer.read_headers(readable)?;
         buffer.read_body(readable)?;
 
+        if buffer.status_code != HTTP_SUCCESS_CODE {
+            let code = buffer.status_code;
+            return Err(Error::response(code, buffer.into_body().into_vec()));
+        }
+
         Ok(buffer)
     }
 
@@ -59,7 +78,11 @@ impl Reader {
 
     /// Fill the internal buffer with data from the socket
     fn fill_buffer(&mut self, readable: &mut dyn Read) -> Result<usize, Error> {
-        let nbytes = readable.read(self.buffer.as_mut())?;
+        if self.pos >= self.buffer.len() {
+            self.grow_buffer(self.buffer.len() * 2)?;
+        }
+
+        let nbytes = readable.read(&mut self.buffer[self.pos..])?;
         self.pos += nbytes;
 
         // See: https://doc.rust-lang.org/src/std/io/mod.rs.html#571
@@ -67,7 +90,7 @@ impl Reader {
         // where returning zero indicates the connection was shut down correctly
         if nbytes == 0 {
             fail!(
-                ResponseError,
+                IoError,
                 "read {} bytes, the remote connection was likely shutdown",
                 nbytes
             );
@@ -76,6 +99,19 @@ impl Reader {
         Ok(nbytes)
     }
 
+    /// Grow the internal buffer to (at most) the given size
+    fn grow_buffer(&mut self, size: usize) -> Result<(), Error> {
+        ensure!(
+            self.buffer.len() < self.max_size,
+            ResponseError,
+            "exceeded {}-byte response limit",
+            self.max_size
+        );
+
+        self.buffer.resize(size.min(self.max_size), 0);
+        Ok(())
+    }
+
     /// Read the response headers
     fn read_headers(&mut self, readable: &mut dyn Read) -> Result<(), Error> {
         assert!(self.body_offset.is_none(), "already read headers!");
@@ -86,7 +122,7 @@ impl Reader {
             // Scan for the header delimiter
             // TODO: real parser
             let mut offset = 0;
-            while self.buffer[offset..].len() > HEADER_DELIMITER.len() {
+            while self.buffer[offset..self.pos].len() >= HEADER_DELIMITER.len() {
                 if self.buffer[offset..].starts_with(HEADER_DELIMITER) {
                     self.body_offset = Some(offset + HEADER_DELIMITER.len());
                     break;
@@ -97,12 +133,6 @@ impl Reader {
 
             if self.body_offset.is_some() {
                 break;
-            } else if self.pos + 1 >= MAX_RESPONSE_SIZE {
-                fail!(
-                    ResponseError,
-                    "exceeded {}-byte response limit reading headers",
-                    MAX_RESPONSE_SIZE
-                );
             }
         }
 
@@ -116,12 +146,7 @@ impl Reader {
         let mut header_iter = header_str.split("\r\n");
 
         match header_iter.next() {
-            Some(HTTP_SUCCESS_STATUS) => (),
-            Some(status) => fail!(
-                ResponseError,
-                "unexpected HTTP response status: \"{}\"",
-                status
-            ),
+            Some(status) => self.status_code = parse_status(status)?,
             None => fail!(ResponseError, "HTTP response status line missing!"),
         }
 
@@ -129,7 +154,7 @@ impl Reader {
             if header.starts_with(CONTENT_LENGTH_HEADER) {
                 let content_length: usize = header[CONTENT_LENGTH_HEADER.len()..].parse()?;
 
-                if MAX_RESPONSE_SIZE - body_offset < content_length {
+                if self.max_size - body_offset < content_length {
                     fail!(
                         ResponseError,
                         "response body length too large for buffer ({} bytes)",
@@ -140,19 +165,33 @@ impl Reader {
                 self.content_length = content_length;
             } else if header.starts_with(TRANSFER_ENCODING_HEADER) {
                 let transfer_encoding = &header[TRANSFER_ENCODING_HEADER.len()..];
-                fail!(
-                    ResponseError,
-                    "connection sent unsupported transfer encoding: {}",
-                    transfer_encoding
-                );
+
+                if !transfer_encoding.eq_ignore_ascii_case(CHUNKED_ENCODING) {
+                    fail!(
+                        ResponseError,
+                        "connection sent unsupported transfer encoding: {}",
+                        transfer_encoding
+                    );
+                }
+
+                self.chunked = true;
             }
         }
 
+        // Make room for the entire body up front if we know its length
+        if self.buffer.len() < body_offset + self.content_length {
+            self.buffer.resize(body_offset + self.content_length, 0);
+        }
+
         Ok(())
     }
 
     /// Read the response body into the internal buffer
     fn read_body(&mut self, readable: &mut dyn Read) -> Result<(), Error> {
+        if self.chunked {
+            return self.read_chunked_body(readable);
+        }
+
         let body_end =
             self.content_length + self.body_offset.expect("not ready to read the body yet");
 
@@ -162,4 +201,144 @@ impl Reader {
 
         Ok(())
     }
+
+    /// Read and decode a response body sent with chunked transfer encoding.
+    ///
+    /// The decoded body replaces the encoded one in the internal buffer.
+    fn read_chunked_body(&mut self, readable: &mut dyn Read) -> Result<(), Error> {
+        let body_offset = self.body_offset.expect("not ready to read the body yet");
+        let mut body = Vec::new();
+        let mut offset = body_offset;
+
+        loop {
+            let size_line = self.read_line(readable, &mut offset)?;
+
+            // Ignore any chunk extensions, e.g. `1a;name=value`
+            let size_str = size_line.split(';').next().unwrap().trim();
+            let chunk_size = usize::from_str_radix(size_str, 16)?;
+
+            if chunk_size == 0 {
+                break;
+            }
+
+            // The chunk size comes from the server, so make sure the chunk
+            // fits within the response limit before buffering it
+            let chunk_end = match offset.checked_add(chunk_size) {
+                Some(end) if end + LINE_DELIMITER.len() <= self.max_size => end,
+                _ => fail!(
+                    ResponseError,
+                    "response chunk too large for {}-byte response limit ({} bytes)",
+                    self.max_size,
+                    chunk_size
+                ),
+            };
+
+            while self.pos < chunk_end + LINE_DELIMITER.len() {
+                self.fill_buffer(readable)?;
+            }
+
+            ensure!(
+                &self.buffer[chunk_end..chunk_end + LINE_DELIMITER.len()] == LINE_DELIMITER,
+                ResponseError,
+                "malformed chunk in response body"
+            );
+
+            body.extend_from_slice(&self.buffer[offset..chunk_end]);
+            offset = chunk_end + LINE_DELIMITER.len();
+        }
+
+        // Discard any trailers until we reach the final empty line
+        while !self.read_line(readable, &mut offset)?.is_empty() {}
+
+        self.buffer.truncate(body_offset);
+        self.buffer.extend_from_slice(&body);
+        self.pos = self.buffer.len();
+
+        Ok(())
+    }
+
+    /// Read a CRLF-terminated line starting at the given offset, advancing
+    /// the offset past the end of the line
+    fn read_line(&mut self, readable: &mut dyn Read, offset: &mut usize) -> Result<String, Error> {
+        loop {
+            if let Some(len) = self.buffer[*offset..self.pos]
+                .windows(LINE_DELIMITER.len())
+                .position(|window| window == LINE_DELIMITER)
+            {
+                let line = str::from_utf8(&self.buffer[*offset..*offset + len])?.to_owned();
+                *offset += len + LINE_DELIMITER.len();
+                return Ok(line);
+            }
+
+            self.fill_buffer(readable)?;
+        }
+    }
+}
+
+/// Parse the status code from a status line, e.g. `HTTP/1.1 200 OK`
+pub(super) fn parse_status(status_line: &str) -> Result<u16, Error> {
+    let mut parts = status_line.splitn(3, ' ');
+
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/1.") && code.len() == 3 => {
+            Ok(code.parse()?)
+        }
+        _ => fail!(
+            ResponseError,
+            "malformed HTTP response status: \"{}\"",
+            status_line
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_content_length_body() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let body = Reader::new(&mut &response[..], 1024).unwrap().into_body();
+        assert_eq!(body.into_vec(), b"hello");
+    }
+
+    #[test]
+    fn read_chunked_body() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        let body = Reader::new(&mut &response[..], 1024).unwrap().into_body();
+        assert_eq!(body.into_vec(), b"hello, world");
+    }
+
+    #[test]
+    fn read_error_response() {
+        let response = b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\n\r\nbusy";
+        let err = Reader::new(&mut &response[..], 1024).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::StatusError);
+
+        let http_response = err.http_response().unwrap();
+        assert_eq!(http_response.code, 503);
+        assert_eq!(http_response.body, b"busy");
+    }
+
+    #[test]
+    fn response_size_limit() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            20\r\n0123456789abcdef0123456789abcdef\r\n0\r\n\r\n";
+        assert!(Reader::new(&mut &response[..], 64).is_err());
+        assert!(Reader::new(&mut &response[..], 128).is_ok());
+    }
+
+    #[test]
+    fn oversized_chunk() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            ffffffffffffffff\r\nhello\r\n0\r\n\r\n";
+        let err = Reader::new(&mut &response[..], 1024).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            400\r\nhello\r\n0\r\n\r\n";
+        let err = Reader::new(&mut &response[..], 1024).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+    }
 }
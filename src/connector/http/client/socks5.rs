@@ -0,0 +1,239 @@
+//! Tunneling connections through SOCKS5 proxies ([RFC 1928]), with optional
+//! username/password authentication ([RFC 1929])
+//!
+//! [RFC 1928]: https://www.rfc-editor.org/rfc/rfc1928
+//! [RFC 1929]: https://www.rfc-editor.org/rfc/rfc1929
+
+use super::error::Error;
+use crate::connector::http::ProxyConfig;
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, TcpStream},
+};
+
+/// SOCKS protocol version
+const SOCKS_VERSION: u8 = 0x05;
+
+/// Username/password authentication subnegotiation version
+const AUTH_VERSION: u8 = 0x01;
+
+/// Authentication method: no authentication required
+const METHOD_NONE: u8 = 0x00;
+
+/// Authentication method: username/password
+const METHOD_PASSWORD: u8 = 0x02;
+
+/// Authentication method: no acceptable methods
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+
+/// `CONNECT` command
+const CMD_CONNECT: u8 = 0x01;
+
+/// Address type: IPv4 address
+const ATYP_IPV4: u8 = 0x01;
+
+/// Address type: domain name
+const ATYP_DOMAIN: u8 = 0x03;
+
+/// Address type: IPv6 address
+const ATYP_IPV6: u8 = 0x04;
+
+/// Ask the SOCKS5 proxy on the other end of `socket` to connect to the
+/// given address and port. Domain names are resolved by the proxy.
+pub(super) fn connect(
+    socket: &mut TcpStream,
+    addr: &str,
+    port: u16,
+    proxy: &ProxyConfig,
+) -> Result<(), Error> {
+    authenticate(socket, proxy)?;
+
+    let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0x00];
+
+    match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            ensure!(
+                addr.len() <= u8::MAX as usize,
+                AddrInvalid,
+                "hostname too long for SOCKS5: {}",
+                addr
+            );
+
+            request.push(ATYP_DOMAIN);
+            request.push(addr.len() as u8);
+            request.extend_from_slice(addr.as_bytes());
+        }
+    }
+
+    request.extend_from_slice(&port.to_be_bytes());
+    socket.write_all(&request)?;
+
+    let mut reply = [0u8; 4];
+    socket.read_exact(&mut reply)?;
+    ensure!(
+        reply[0] == SOCKS_VERSION,
+        ResponseError,
+        "invalid SOCKS5 reply version: {}",
+        reply[0]
+    );
+
+    if reply[1] != 0x00 {
+        fail!(
+            RequestError,
+            "SOCKS5 proxy {}:{} couldn't connect to {}:{}: {}",
+            proxy.addr,
+            proxy.port,
+            addr,
+            port,
+            reply_message(reply[1])
+        );
+    }
+
+    // Discard the bound address and port
+    let bound_addr_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8];
+            socket.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        atyp => fail!(ResponseError, "invalid SOCKS5 address type: {}", atyp),
+    };
+
+    let mut bound_addr = vec![0u8; bound_addr_len + 2];
+    socket.read_exact(&mut bound_addr)?;
+
+    Ok(())
+}
+
+/// Negotiate an authentication method and authenticate (if required)
+fn authenticate(socket: &mut TcpStream, proxy: &ProxyConfig) -> Result<(), Error> {
+    if proxy.username.is_some() {
+        socket.write_all(&[SOCKS_VERSION, 2, METHOD_NONE, METHOD_PASSWORD])?;
+    } else {
+        socket.write_all(&[SOCKS_VERSION, 1, METHOD_NONE])?;
+    }
+
+    let mut reply = [0u8; 2];
+    socket.read_exact(&mut reply)?;
+    ensure!(
+        reply[0] == SOCKS_VERSION,
+        ResponseError,
+        "invalid SOCKS5 reply version: {}",
+        reply[0]
+    );
+
+    match reply[1] {
+        METHOD_NONE => Ok(()),
+        METHOD_PASSWORD if proxy.username.is_some() => {
+            let username = proxy.username.as_deref().unwrap_or_default();
+            let password = proxy.password.as_deref().unwrap_or_default();
+
+            ensure!(
+                username.len() <= u8::MAX as usize && password.len() <= u8::MAX as usize,
+                RequestError,
+                "SOCKS5 username or password too long"
+            );
+
+            let mut request = vec![AUTH_VERSION, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            socket.write_all(&request)?;
+
+            let mut status = [0u8; 2];
+            socket.read_exact(&mut status)?;
+            ensure!(
+                status[1] == 0x00,
+                RequestError,
+                "SOCKS5 proxy {}:{} rejected credentials",
+                proxy.addr,
+                proxy.port
+            );
+
+            Ok(())
+        }
+        METHOD_UNACCEPTABLE => fail!(
+            RequestError,
+            "SOCKS5 proxy {}:{} requires an unsupported authentication method",
+            proxy.addr,
+            proxy.port
+        ),
+        method => fail!(
+            ResponseError,
+            "SOCKS5 proxy selected unexpected authentication method: {}",
+            method
+        ),
+    }
+}
+
+/// Describe a SOCKS5 reply code
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general SOCKS server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::http::ProxyProtocol;
+    use std::{net::TcpListener, thread};
+
+    #[test]
+    fn connect_with_password() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+
+            let mut greeting = [0u8; 4];
+            socket.read_exact(&mut greeting).unwrap();
+            assert_eq!(greeting, [5, 2, METHOD_NONE, METHOD_PASSWORD]);
+            socket.write_all(&[5, METHOD_PASSWORD]).unwrap();
+
+            let mut auth = [0u8; 9];
+            socket.read_exact(&mut auth).unwrap();
+            assert_eq!(&auth, b"\x01\x03bob\x03pw!");
+            socket.write_all(&[AUTH_VERSION, 0]).unwrap();
+
+            let mut request = [0u8; 20];
+            socket.read_exact(&mut request).unwrap();
+            assert_eq!(&request[..5], &[5, CMD_CONNECT, 0, ATYP_DOMAIN, 13]);
+            assert_eq!(&request[5..18], b"hsm.example.c");
+            socket
+                .write_all(&[5, 0, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+                .unwrap();
+        });
+
+        let proxy = ProxyConfig {
+            protocol: ProxyProtocol::Socks5,
+            addr: "127.0.0.1".to_owned(),
+            port: proxy_port,
+            username: Some("bob".to_owned()),
+            password: Some("pw!".to_owned()),
+        };
+
+        let mut socket = TcpStream::connect(("127.0.0.1", proxy_port)).unwrap();
+        connect(&mut socket, "hsm.example.c", 12345, &proxy).unwrap();
+        server.join().unwrap();
+    }
+}
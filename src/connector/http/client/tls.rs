@@ -0,0 +1,193 @@
+//! TLS support for HTTPS connections.
+//!
+//! Two implementations are available:
+//!
+//! - `https` cargo feature: the platform's TLS library (via `native-tls`),
+//!   trusting the system's trust store
+//! - `tls-rustls` cargo feature: a pure Rust implementation (via `rustls`)
+//!   for environments which can't link against OpenSSL, trusting Mozilla's
+//!   root certificates (via `webpki-roots`) rather than the system's trust
+//!   store. If both features are enabled, `rustls` is used.
+
+#[cfg(all(feature = "https", not(feature = "tls-rustls")))]
+mod native_tls;
+#[cfg(feature = "tls-rustls")]
+mod rustls;
+
+#[cfg(all(feature = "https", not(feature = "tls-rustls")))]
+pub(super) use self::native_tls::connect;
+#[cfg(feature = "tls-rustls")]
+pub(super) use self::rustls::connect;
+
+#[cfg(all(test, any(feature = "https", feature = "tls-rustls")))]
+mod tests {
+    use super::super::{
+        connection::{Connection, ConnectionOptions},
+        error::ErrorKind,
+    };
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        path::PathBuf,
+        thread::{self, JoinHandle},
+    };
+
+    /// Path to a test certificate or key (issued by the test CA in `ca.pem`)
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "tls", name]
+            .iter()
+            .collect()
+    }
+
+    /// Accept a TLS session on the given socket using a certificate for
+    /// `localhost` issued by the test CA. The handshake is completed by the
+    /// first read, which fails if the client rejects the certificate.
+    #[cfg(feature = "tls-rustls")]
+    fn accept(socket: TcpStream) -> Option<impl Read + Write> {
+        use rustls::{
+            pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+            ServerConfig, ServerConnection, StreamOwned,
+        };
+        use std::sync::Arc;
+
+        let certs = CertificateDer::pem_file_iter(fixture("server.pem"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_file(fixture("server-key.pem")).unwrap();
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .unwrap();
+
+        let connection = ServerConnection::new(Arc::new(config)).unwrap();
+        Some(StreamOwned::new(connection, socket))
+    }
+
+    /// Accept a TLS session on the given socket using a certificate for
+    /// `localhost` issued by the test CA, or `None` if the client aborts the
+    /// handshake (e.g. because it rejected the certificate)
+    #[cfg(all(feature = "https", not(feature = "tls-rustls")))]
+    fn accept(socket: TcpStream) -> Option<impl Read + Write> {
+        use native_tls::{Identity, TlsAcceptor};
+        use std::fs;
+
+        let cert = fs::read(fixture("server.pem")).unwrap();
+        let key = fs::read(fixture("server-key.pem")).unwrap();
+        let identity = Identity::from_pkcs8(&cert, &key).unwrap();
+
+        TlsAcceptor::new(identity).unwrap().accept(socket).ok()
+    }
+
+    /// Serve a single HTTPS response with the given body on a local port
+    fn serve_https(body: &'static str) -> (u16, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let Some(stream) = accept(socket) else {
+                return;
+            };
+
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+
+            // Clients which reject the certificate abort the handshake
+            while line != "\r\n" {
+                line.clear();
+
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+
+            let stream = reader.get_mut();
+            stream.write_all(response.as_bytes()).unwrap();
+            stream.flush().unwrap();
+        });
+
+        (port, server)
+    }
+
+    #[test]
+    fn get_with_ca_bundle() {
+        let (port, server) = serve_https("status=OK\n");
+
+        let opts = ConnectionOptions {
+            tls: true,
+            ca_bundle: Some(fixture("ca.pem")),
+            ..Default::default()
+        };
+
+        let connection = Connection::open("localhost", port, &opts).unwrap();
+        let response = connection.get("/connector/status").unwrap();
+        assert_eq!(response.into_vec(), b"status=OK\n");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn untrusted_certificate_rejected() {
+        let (port, server) = serve_https("status=OK\n");
+
+        let opts = ConnectionOptions {
+            tls: true,
+            ..Default::default()
+        };
+
+        let err = Connection::open("localhost", port, &opts).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TlsError);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_with_plaintext_server_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request);
+            let _ = socket.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n");
+        });
+
+        let opts = ConnectionOptions {
+            tls: true,
+            ca_bundle: Some(fixture("ca.pem")),
+            ..Default::default()
+        };
+
+        let err = Connection::open("localhost", port, &opts).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TlsError);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn ca_bundle_without_certificates_rejected() {
+        let opts = ConnectionOptions {
+            tls: true,
+            ca_bundle: Some(fixture("server-key.pem")),
+            ..Default::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let err = Connection::open("localhost", port, &opts).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TlsError);
+    }
+}
@@ -0,0 +1,89 @@
+//! TLS support for HTTPS connections (using `native-tls`)
+
+use super::super::{
+    connection::{ConnectionOptions, Stream},
+    error::Error,
+};
+use native_tls::{Certificate, Identity, TlsConnector};
+use std::{fs, net::TcpStream, path::Path};
+
+/// PEM header which begins each certificate in a CA bundle
+const PEM_CERTIFICATE_HEADER: &str = "-----BEGIN CERTIFICATE-----";
+
+/// Negotiate a TLS session with the remote host over the given socket
+pub(in super::super) fn connect(
+    domain: &str,
+    socket: TcpStream,
+    opts: &ConnectionOptions,
+) -> Result<Box<dyn Stream>, Error> {
+    let mut builder = TlsConnector::builder();
+
+    if let Some(path) = &opts.ca_bundle {
+        for certificate in read_ca_bundle(path)? {
+            builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&opts.client_cert, &opts.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            builder.identity(read_identity(cert_path, key_path)?);
+        }
+        (None, None) => (),
+        _ => fail!(
+            TlsError,
+            "both a client certificate and key are required for client authentication"
+        ),
+    }
+
+    let connector = builder
+        .build()
+        .map_err(|e| err!(TlsError, "error initializing TLS: {}", e))?;
+
+    let stream = connector
+        .connect(domain, socket)
+        .map_err(|e| err!(TlsError, "TLS handshake with {} failed: {}", domain, e))?;
+
+    Ok(Box::new(stream))
+}
+
+/// Load a PEM-encoded client certificate (chain) and PKCS#8 private key
+fn read_identity(cert_path: &Path, key_path: &Path) -> Result<Identity, Error> {
+    let cert = fs::read(cert_path)?;
+    let key = fs::read(key_path)?;
+
+    Identity::from_pkcs8(&cert, &key).map_err(|e| {
+        err!(
+            TlsError,
+            "invalid client certificate {} or key {}: {}",
+            cert_path.display(),
+            key_path.display(),
+            e
+        )
+    })
+}
+
+/// Parse the certificates in a PEM-encoded CA bundle
+fn read_ca_bundle(path: &Path) -> Result<Vec<Certificate>, Error> {
+    let pem = fs::read_to_string(path)?;
+    let mut certificates = vec![];
+
+    for pem_cert in pem.split(PEM_CERTIFICATE_HEADER).skip(1) {
+        let pem_cert = format!("{PEM_CERTIFICATE_HEADER}{pem_cert}");
+        certificates.push(Certificate::from_pem(pem_cert.as_bytes()).map_err(|e| {
+            err!(
+                TlsError,
+                "invalid certificate in CA bundle {}: {}",
+                path.display(),
+                e
+            )
+        })?);
+    }
+
+    ensure!(
+        !certificates.is_empty(),
+        TlsError,
+        &format!("no certificates found in CA bundle: {}", path.display())
+    );
+
+    Ok(certificates)
+}
@@ -0,0 +1,88 @@
+//! TLS support for HTTPS connections (using `rustls`)
+
+use super::super::{
+    connection::{ConnectionOptions, Stream},
+    error::Error,
+};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+};
+use std::{net::TcpStream, path::Path, sync::Arc};
+
+/// Negotiate a TLS session with the remote host over the given socket
+pub(in super::super) fn connect(
+    domain: &str,
+    mut socket: TcpStream,
+    opts: &ConnectionOptions,
+) -> Result<Box<dyn Stream>, Error> {
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+
+    if let Some(path) = &opts.ca_bundle {
+        for certificate in read_certificates(path)? {
+            roots.add(certificate).map_err(|e| {
+                err!(
+                    TlsError,
+                    "invalid certificate in CA bundle {}: {}",
+                    path.display(),
+                    e
+                )
+            })?;
+        }
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| err!(TlsError, "error initializing TLS: {}", e))?
+        .with_root_certificates(roots);
+
+    let config = match (&opts.client_cert, &opts.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let key = PrivateKeyDer::from_pem_file(key_path)
+                .map_err(|e| err!(TlsError, "invalid client key {}: {}", key_path.display(), e))?;
+
+            builder
+                .with_client_auth_cert(read_certificates(cert_path)?, key)
+                .map_err(|e| err!(TlsError, "invalid client certificate: {}", e))?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => fail!(
+            TlsError,
+            "both a client certificate and key are required for client authentication"
+        ),
+    };
+
+    let server_name = ServerName::try_from(domain.to_owned())
+        .map_err(|e| err!(TlsError, "invalid TLS server name {}: {}", domain, e))?;
+
+    let mut connection = ClientConnection::new(Arc::new(config), server_name)
+        .map_err(|e| err!(TlsError, "error initializing TLS: {}", e))?;
+
+    // Complete the handshake up front so errors are reported when connecting
+    while connection.is_handshaking() {
+        connection
+            .complete_io(&mut socket)
+            .map_err(|e| err!(TlsError, "TLS handshake with {} failed: {}", domain, e))?;
+    }
+
+    Ok(Box::new(StreamOwned::new(connection, socket)))
+}
+
+/// Read the certificates in a PEM file
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| err!(TlsError, "error reading {}: {}", path.display(), e))?;
+
+    ensure!(
+        !certificates.is_empty(),
+        TlsError,
+        "no certificates found in {}",
+        path.display()
+    );
+
+    Ok(certificates)
+}
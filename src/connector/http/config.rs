@@ -1,13 +1,35 @@
 //! yubihsm-connector HTTP configuration
 
+use super::client::{self, ConnectionOptions};
+use crate::connector::{self, Backoff, ErrorKind, ReconnectPolicy};
 use serde::{Deserialize, Serialize};
-use std::fmt::{self, Display};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Display},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 
-/// Default timeouts for reading and writing (5 seconds)
-pub const DEFAULT_TIMEOUT_MILLIS: u64 = 5000;
+/// Default timeout for connecting (5 seconds)
+pub const DEFAULT_CONNECT_TIMEOUT_MILLIS: u64 = 5000;
+
+/// Default timeout for reading responses (30 seconds). This is longer than
+/// the other timeouts as some operations (e.g. RSA key generation) can take
+/// a while to complete.
+pub const DEFAULT_READ_TIMEOUT_MILLIS: u64 = 30000;
+
+/// Default timeout for writing requests (5 seconds)
+pub const DEFAULT_WRITE_TIMEOUT_MILLIS: u64 = 5000;
+
+/// Default maximum size of a response from `yubihsm-connector` (1 MiB)
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// Default maximum number of concurrent connections to `yubihsm-connector`
+pub const DEFAULT_POOL_SIZE: usize = 1;
 
 /// Configuration options for the HTTP (i.e. `yubihsm-connector`) connection
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct HttpConfig {
     /// Address of `yubihsm-connector` (IP address or DNS name)
     pub addr: String,
@@ -15,11 +37,100 @@ pub struct HttpConfig {
     /// Port `yubihsm-connector` process is listening on
     pub port: u16,
 
-    /// Timeout for connecting, reading, and writing in milliseconds
+    /// Timeout for connecting in milliseconds
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+
+    /// Timeout for reading responses in milliseconds
+    #[serde(default = "default_read_timeout_ms")]
+    pub read_timeout_ms: u64,
+
+    /// Timeout for writing requests in milliseconds
+    #[serde(default = "default_write_timeout_ms")]
+    pub write_timeout_ms: u64,
+
+    /// Timeout for connecting, reading, and writing in milliseconds (for
+    /// configurations predating the individual timeouts above). When
+    /// nonzero, this overrides `connect_timeout_ms`, `read_timeout_ms`, and
+    /// `write_timeout_ms`; when zero (the default), they're used instead.
+    #[deprecated(note = "use `connect_timeout_ms`, `read_timeout_ms`, and `write_timeout_ms`")]
+    #[serde(default, skip_serializing_if = "is_zero")]
     pub timeout_ms: u64,
+
+    /// Connect to `yubihsm-connector` using HTTPS (requires the `https` or
+    /// `tls-rustls` cargo feature)
+    #[serde(default)]
+    pub tls: bool,
+
+    /// Path to a PEM-encoded bundle of CA certificates to trust when
+    /// verifying the connector's TLS certificate, in addition to the default
+    /// roots: the system's trust store with the `https` cargo feature, or
+    /// Mozilla's root certificates (via `webpki-roots`, not the system's
+    /// trust store) with the `tls-rustls` cargo feature
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Path to a PEM-encoded client certificate (chain) to authenticate to
+    /// the connector with (i.e. mutual TLS). Requires `client_key`.
+    #[serde(default)]
+    pub client_cert: Option<PathBuf>,
+
+    /// Path to the PEM-encoded PKCS#8 private key for `client_cert`
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+
+    /// Proxy to tunnel connections to `yubihsm-connector` through (either
+    /// an HTTP proxy supporting `CONNECT`, or a SOCKS5 proxy)
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+
+    /// Maximum size of a response (headers and body) in bytes
+    #[serde(default = "default_max_response_size")]
+    pub max_response_size: usize,
+
+    /// Path to a Unix domain socket `yubihsm-connector` is listening on.
+    /// When set, this is used instead of connecting to `addr` and `port`
+    /// over TCP (Unix platforms only).
+    #[serde(default)]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Spread connections across all of the addresses `addr` resolves to,
+    /// rather than always trying them in the order they were resolved
+    #[serde(default)]
+    pub round_robin: bool,
+
+    /// Retry policy for transient connection errors (e.g. the connector
+    /// being restarted). Only opening connections and idempotent requests
+    /// sent outside of encrypted sessions are retried.
+    #[serde(default)]
+    pub retry: Option<Backoff>,
+
+    /// Policy for reopening sessions when connecting to the HSM fails
+    #[serde(default)]
+    pub reconnect_policy: Option<ReconnectPolicy>,
+
+    /// Additional headers to send with every request (e.g. an
+    /// `Authorization: Bearer ...` header required by a reverse proxy)
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// Maximum number of concurrent connections to `yubihsm-connector`
+    #[serde(default = "default_pool_size")]
+    pub pool_size: usize,
+
+    /// Disable Nagle's algorithm on TCP connections (`TCP_NODELAY`)
+    #[serde(default)]
+    pub tcp_nodelay: bool,
+
+    /// Enable TCP keepalive, sending probes after connections have been idle
+    /// for this many milliseconds (and at this interval thereafter). Useful
+    /// for keeping idle connections open through firewalls.
+    #[serde(default)]
+    pub tcp_keepalive_ms: Option<u64>,
 }
 
 impl Default for HttpConfig {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             // Default `yubihsm-connector` address
@@ -29,14 +140,381 @@ impl Default for HttpConfig {
             port: 12345,
 
             // 5 seconds
-            timeout_ms: DEFAULT_TIMEOUT_MILLIS,
+            connect_timeout_ms: DEFAULT_CONNECT_TIMEOUT_MILLIS,
+
+            // 30 seconds
+            read_timeout_ms: DEFAULT_READ_TIMEOUT_MILLIS,
+
+            // 5 seconds
+            write_timeout_ms: DEFAULT_WRITE_TIMEOUT_MILLIS,
+
+            // Use the individual timeouts above
+            timeout_ms: 0,
+
+            // Plaintext HTTP
+            tls: false,
+
+            // Default roots only
+            ca_bundle: None,
+
+            // No client authentication
+            client_cert: None,
+            client_key: None,
+
+            // Direct connection
+            proxy: None,
+
+            // 1 MiB
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+
+            // TCP
+            unix_socket: None,
+
+            // Try addresses in the order they resolve
+            round_robin: false,
+
+            // Fail immediately
+            retry: None,
+            reconnect_policy: None,
+
+            // No additional headers
+            headers: BTreeMap::new(),
+
+            // Single connection
+            pool_size: DEFAULT_POOL_SIZE,
+
+            // Use Nagle's algorithm
+            tcp_nodelay: false,
+
+            // No TCP keepalive
+            tcp_keepalive_ms: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Options for opening HTTP client connections with this configuration
+    pub(super) fn connection_options(&self) -> ConnectionOptions {
+        #[allow(deprecated)]
+        let timeout = |timeout_ms| {
+            Duration::from_millis(match self.timeout_ms {
+                0 => timeout_ms,
+                legacy_timeout_ms => legacy_timeout_ms,
+            })
+        };
+
+        ConnectionOptions {
+            connect_timeout: timeout(self.connect_timeout_ms),
+            read_timeout: timeout(self.read_timeout_ms),
+            write_timeout: timeout(self.write_timeout_ms),
+            tls: self.tls,
+            ca_bundle: self.ca_bundle.clone(),
+            client_cert: self.client_cert.clone(),
+            client_key: self.client_key.clone(),
+            proxy: self.proxy.clone(),
+            max_response_size: self.max_response_size,
+            unix_socket: self.unix_socket.clone(),
+            round_robin: self.round_robin,
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            tcp_nodelay: self.tcp_nodelay,
+            tcp_keepalive: self.tcp_keepalive_ms.map(Duration::from_millis),
         }
     }
 }
 
+impl Debug for HttpConfig {
+    #[allow(deprecated)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid leaking header values (e.g. bearer tokens) in debug messages
+        let headers: BTreeMap<_, _> = self.headers.keys().map(|name| (name, "...")).collect();
+
+        f.debug_struct("HttpConfig")
+            .field("addr", &self.addr)
+            .field("port", &self.port)
+            .field("connect_timeout_ms", &self.connect_timeout_ms)
+            .field("read_timeout_ms", &self.read_timeout_ms)
+            .field("write_timeout_ms", &self.write_timeout_ms)
+            .field("timeout_ms", &self.timeout_ms)
+            .field("tls", &self.tls)
+            .field("ca_bundle", &self.ca_bundle)
+            .field("client_cert", &self.client_cert)
+            .field("client_key", &self.client_key)
+            .field("proxy", &self.proxy)
+            .field("max_response_size", &self.max_response_size)
+            .field("unix_socket", &self.unix_socket)
+            .field("round_robin", &self.round_robin)
+            .field("retry", &self.retry)
+            .field("reconnect_policy", &self.reconnect_policy)
+            .field("headers", &headers)
+            .field("pool_size", &self.pool_size)
+            .field("tcp_nodelay", &self.tcp_nodelay)
+            .field("tcp_keepalive_ms", &self.tcp_keepalive_ms)
+            .finish()
+    }
+}
+
 impl Display for HttpConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // TODO: HTTPS support
-        write!(f, "http://{}:{}", self.addr, self.port)
+        if let Some(path) = &self.unix_socket {
+            return write!(f, "http+unix://{}", path.display());
+        }
+
+        let scheme = if self.tls { "https" } else { "http" };
+        write!(
+            f,
+            "{}://{}",
+            scheme,
+            client::authority(&self.addr, self.port)
+        )
+    }
+}
+
+impl FromStr for HttpConfig {
+    type Err = connector::Error;
+
+    /// Parse a connector URL, e.g. `http://127.0.0.1:12345` or
+    /// `https://[::1]:12345`, using the default port if none is given
+    fn from_str(url: &str) -> Result<Self, connector::Error> {
+        let mut config = Self::default();
+
+        let authority = if let Some(rest) = url.strip_prefix("https://") {
+            config.tls = true;
+            rest
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            rest
+        } else {
+            ensure!(
+                !url.contains("://"),
+                ErrorKind::AddrInvalid,
+                "unsupported URL scheme: {}",
+                url
+            );
+            url
+        };
+
+        let authority = authority.strip_suffix('/').unwrap_or(authority);
+
+        let (addr, port) = if let Some(rest) = authority.strip_prefix('[') {
+            // Bracketed IPv6 literal, e.g. `[::1]:12345`
+            let (addr, rest) = rest.split_once(']').ok_or_else(|| {
+                format_err!(ErrorKind::AddrInvalid, "unterminated IPv6 literal: {}", url)
+            })?;
+
+            match rest {
+                "" => (addr, None),
+                _ => match rest.strip_prefix(':') {
+                    Some(port) => (addr, Some(port)),
+                    None => fail!(ErrorKind::AddrInvalid, "invalid connector URL: {}", url),
+                },
+            }
+        } else if authority.matches(':').count() > 1 {
+            // Unbracketed IPv6 literal (no port)
+            (authority, None)
+        } else {
+            match authority.split_once(':') {
+                Some((addr, port)) => (addr, Some(port)),
+                None => (authority, None),
+            }
+        };
+
+        ensure!(
+            !addr.is_empty() && !addr.contains('/'),
+            ErrorKind::AddrInvalid,
+            "invalid connector URL: {}",
+            url
+        );
+
+        config.addr = addr.to_owned();
+
+        if let Some(port) = port {
+            config.port = port.parse().map_err(|_| {
+                format_err!(
+                    ErrorKind::AddrInvalid,
+                    "invalid port in connector URL: {}",
+                    url
+                )
+            })?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Default for `HttpConfig::connect_timeout_ms` when deserializing
+fn default_connect_timeout_ms() -> u64 {
+    DEFAULT_CONNECT_TIMEOUT_MILLIS
+}
+
+/// Default for `HttpConfig::read_timeout_ms` when deserializing
+fn default_read_timeout_ms() -> u64 {
+    DEFAULT_READ_TIMEOUT_MILLIS
+}
+
+/// Default for `HttpConfig::write_timeout_ms` when deserializing
+fn default_write_timeout_ms() -> u64 {
+    DEFAULT_WRITE_TIMEOUT_MILLIS
+}
+
+/// Is the given (legacy) `HttpConfig::timeout_ms` unset?
+fn is_zero(timeout_ms: &u64) -> bool {
+    *timeout_ms == 0
+}
+
+/// Default for `HttpConfig::max_response_size` when deserializing
+fn default_max_response_size() -> usize {
+    DEFAULT_MAX_RESPONSE_SIZE
+}
+
+/// Default for `HttpConfig::pool_size` when deserializing
+fn default_pool_size() -> usize {
+    DEFAULT_POOL_SIZE
+}
+
+/// Configuration for tunneling connections through a proxy
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ProxyConfig {
+    /// Protocol spoken by the proxy
+    #[serde(default)]
+    pub protocol: ProxyProtocol,
+
+    /// Address of the proxy (IP address or DNS name)
+    pub addr: String,
+
+    /// Port the proxy is listening on
+    pub port: u16,
+
+    /// Username to authenticate to the proxy with (HTTP Basic auth, or
+    /// SOCKS5 username/password authentication)
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Password to authenticate to the proxy with
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid leaking the password in debug messages
+        f.debug_struct("ProxyConfig")
+            .field("protocol", &self.protocol)
+            .field("addr", &self.addr)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "..."))
+            .finish()
+    }
+}
+
+/// Protocols supported for proxying connections
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyProtocol {
+    /// HTTP proxy (tunneling using the `CONNECT` method)
+    #[default]
+    Http,
+
+    /// SOCKS5 proxy (e.g. `ssh -D`). Hostnames are resolved by the proxy.
+    Socks5,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_connector_urls() {
+        let config: HttpConfig = "https://[::1]:4443".parse().unwrap();
+        assert!(config.tls);
+        assert_eq!(config.addr, "::1");
+        assert_eq!(config.port, 4443);
+        assert_eq!(config.to_string(), "https://[::1]:4443");
+
+        let config: HttpConfig = "connector.example.com:8080/".parse().unwrap();
+        assert_eq!(config.addr, "connector.example.com");
+        assert_eq!(config.port, 8080);
+
+        let config: HttpConfig = "fe80::1".parse().unwrap();
+        assert_eq!(config.addr, "fe80::1");
+        assert_eq!(config.port, 12345);
+
+        assert!("ftp://127.0.0.1".parse::<HttpConfig>().is_err());
+        assert!("[::1".parse::<HttpConfig>().is_err());
+        assert!("127.0.0.1:http".parse::<HttpConfig>().is_err());
+    }
+
+    #[test]
+    fn proxy_password_redacted() {
+        let proxy = ProxyConfig {
+            protocol: ProxyProtocol::Http,
+            addr: "proxy.example.com".to_owned(),
+            port: 3128,
+            username: Some("user".to_owned()),
+            password: Some("hunter2".to_owned()),
+        };
+
+        let debug = format!(
+            "{:?}",
+            HttpConfig {
+                proxy: Some(proxy),
+                ..Default::default()
+            }
+        );
+
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn header_values_redacted() {
+        let mut config = HttpConfig::default();
+        config
+            .headers
+            .insert("Authorization".to_owned(), "Bearer hunter2".to_owned());
+
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("Authorization"));
+        assert!(!debug.contains("hunter2"));
+
+        let debug = format!("{:?}", config.connection_options());
+        assert!(debug.contains("Authorization"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn legacy_timeout() {
+        let config: HttpConfig =
+            serde_json::from_str(r#"{"addr": "127.0.0.1", "port": 12345, "timeout_ms": 10000}"#)
+                .unwrap();
+
+        let opts = config.connection_options();
+        assert_eq!(opts.connect_timeout, Duration::from_secs(10));
+        assert_eq!(opts.read_timeout, Duration::from_secs(10));
+        assert_eq!(opts.write_timeout, Duration::from_secs(10));
+
+        let config: HttpConfig =
+            serde_json::from_str(r#"{"addr": "127.0.0.1", "port": 12345}"#).unwrap();
+
+        let opts = config.connection_options();
+        assert_eq!(opts.connect_timeout, Duration::from_secs(5));
+        assert_eq!(opts.read_timeout, Duration::from_secs(30));
+        assert_eq!(opts.write_timeout, Duration::from_secs(5));
+
+        // Struct literals predating the individual timeouts keep compiling
+        #[allow(deprecated)]
+        let config = HttpConfig {
+            timeout_ms: 10000,
+            ..Default::default()
+        };
+
+        let opts = config.connection_options();
+        assert_eq!(opts.read_timeout, Duration::from_secs(10));
+        assert!(!serde_json::to_string(&HttpConfig::default())
+            .unwrap()
+            .contains("\"timeout_ms\""));
     }
 }
@@ -1,7 +1,8 @@
 //! Persistent HTTP connection to `yubihsm-connector`
 
-use super::{client, config::HttpConfig};
-use crate::connector::{self, Connection};
+use super::{config::HttpConfig, pool::Pool, retry, HttpTransport};
+use crate::connector::{self, Backoff, Connection};
+use std::{sync::Arc, time::Duration};
 use uuid::Uuid;
 
 /// Connection to YubiHSM via HTTP requests to `yubihsm-connector`.
@@ -13,17 +14,31 @@ use uuid::Uuid;
 /// more information on `yubihsm-connector`, see:
 ///
 /// <https://developers.yubico.com/YubiHSM2/Component_Reference/yubihsm-connector/>
+///
+/// Requests are made using an [`HttpTransport`]. By default this is a pool
+/// of up to `HttpConfig::pool_size` connections made using the built-in
+/// HTTP client, allowing several requests to be in flight concurrently.
 pub struct HttpConnection {
-    /// HTTP connection
-    connection: client::Connection,
+    /// Retry policy for transient errors
+    retry: Option<Backoff>,
+
+    /// Transport used to make HTTP requests
+    transport: Arc<dyn HttpTransport>,
 }
 
 impl HttpConnection {
     /// Open a connection to a `yubihsm-connector` service
     pub(crate) fn open(config: &HttpConfig) -> Result<Self, connector::Error> {
-        let connection = client::Connection::open(&config.addr, config.port, &Default::default())?;
+        let pool = retry::with_retry(config.retry.as_ref(), |_| Ok(Pool::open(config)?))?;
+        Ok(Self::with_transport(config, Arc::new(pool)))
+    }
 
-        Ok(HttpConnection { connection })
+    /// Create a connection which makes requests with the given transport
+    pub(crate) fn with_transport(config: &HttpConfig, transport: Arc<dyn HttpTransport>) -> Self {
+        HttpConnection {
+            retry: config.retry.clone(),
+            transport,
+        }
     }
 
     /// Make an HTTP POST request to a `yubihsm-connector` service
@@ -32,12 +47,31 @@ impl HttpConnection {
         path: &str,
         _uuid: Uuid,
         body: &[u8],
+        idempotent: bool,
+        timeout: Option<Duration>,
     ) -> Result<Vec<u8>, connector::Error> {
-        // TODO: send UUID as `X-Request-ID` header, zero copy body creation
-        Ok(self
-            .connection
-            .post(path, &client::request::Body::new(body))?
-            .into_vec())
+        // TODO: send UUID as `X-Request-ID` header
+        let policy = self.retry.as_ref().filter(|_| idempotent);
+        retry::with_retry(policy, |_| match timeout {
+            Some(timeout) => self.transport.post_with_timeout(path, body, timeout),
+            None => self.transport.post(path, body),
+        })
+    }
+
+    /// `POST /connector/api` with a given command message
+    fn send(
+        &self,
+        uuid: Uuid,
+        cmd: connector::Message,
+        timeout: Option<Duration>,
+    ) -> Result<connector::Message, connector::Error> {
+        let idempotent = cmd
+            .command_code()
+            .map(|code| code.is_idempotent())
+            .unwrap_or(false);
+
+        self.post("/connector/api", uuid, cmd.as_ref(), idempotent, timeout)
+            .map(Into::into)
     }
 }
 
@@ -48,7 +82,17 @@ impl Connection for HttpConnection {
         uuid: Uuid,
         cmd: connector::Message,
     ) -> Result<connector::Message, connector::Error> {
-        self.post("/connector/api", uuid, cmd.as_ref())
-            .map(Into::into)
+        self.send(uuid, cmd, None)
+    }
+
+    /// `POST /connector/api` with a given command message, waiting up to
+    /// `timeout` for the response
+    fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        cmd: connector::Message,
+        timeout: Duration,
+    ) -> Result<connector::Message, connector::Error> {
+        self.send(uuid, cmd, Some(timeout))
     }
 }
@@ -0,0 +1,265 @@
+//! Background health checking of `yubihsm-connector`
+
+use super::{config::HttpConfig, pool::Pool, HttpTransport};
+use crate::connector::{self, ErrorKind};
+use std::{
+    str,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Path to the `yubihsm-connector` status page
+const STATUS_PATH: &str = "/connector/status";
+
+/// Check whether `yubihsm-connector` is reachable and connected to a
+/// YubiHSM 2 by requesting its status page (`GET /connector/status`).
+///
+/// The request is made with the built-in HTTP client, configured the same
+/// way as [`Connector::http`][`connector::Connector::http`] (i.e. using the
+/// config's TLS, proxy and header settings). Use [`check_transport_status`]
+/// for connectors created with a custom [`HttpTransport`].
+pub fn check_status(config: &HttpConfig) -> Result<(), connector::Error> {
+    check_transport_status(&Pool::open(config)?)
+}
+
+/// Check whether `yubihsm-connector` is reachable and connected to a
+/// YubiHSM 2 by requesting its status page using the given transport.
+pub fn check_transport_status(transport: &dyn HttpTransport) -> Result<(), connector::Error> {
+    let body = transport.get(STATUS_PATH)?;
+    let status = str::from_utf8(&body)?
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("status="))
+        .unwrap_or_default();
+
+    ensure!(
+        status == "OK",
+        ErrorKind::ResponseError,
+        "connector status: {}",
+        if status.is_empty() { "unknown" } else { status }
+    );
+
+    Ok(())
+}
+
+/// Periodically checks the health of `yubihsm-connector` from a background
+/// thread, allowing services to notice the connector (or HSM) becoming
+/// unavailable without waiting for a request to fail.
+///
+/// The check stops when the `HealthCheck` is dropped.
+pub struct HealthCheck {
+    /// State shared with the background thread
+    shared: Arc<Shared>,
+
+    /// Background thread performing the checks
+    thread: Option<JoinHandle<()>>,
+}
+
+/// State shared between a `HealthCheck` and its background thread
+struct Shared {
+    /// Was the connector healthy when last checked?
+    healthy: AtomicBool,
+
+    /// Has the check been stopped?
+    stopped: Mutex<bool>,
+
+    /// Used to wake the background thread when stopping
+    wakeup: Condvar,
+}
+
+impl HealthCheck {
+    /// Check the health of the connector every `interval`, invoking the
+    /// given callback with the result of the first check and whenever the
+    /// connector's health changes thereafter.
+    pub fn spawn<F>(config: &HttpConfig, interval: Duration, callback: F) -> Self
+    where
+        F: FnMut(bool) + Send + 'static,
+    {
+        let config = config.clone();
+        Self::spawn_with(move || check_status(&config), interval, callback)
+    }
+
+    /// Check the health of the connector every `interval` like
+    /// [`HealthCheck::spawn`], making requests using the given transport
+    /// (e.g. the one given to
+    /// [`Connector::http_with_transport`][`connector::Connector::http_with_transport`]).
+    pub fn spawn_with_transport<F>(
+        transport: Arc<dyn HttpTransport>,
+        interval: Duration,
+        callback: F,
+    ) -> Self
+    where
+        F: FnMut(bool) + Send + 'static,
+    {
+        Self::spawn_with(
+            move || check_transport_status(&*transport),
+            interval,
+            callback,
+        )
+    }
+
+    /// Run the given check every `interval` from a background thread
+    fn spawn_with<C, F>(mut check: C, interval: Duration, mut callback: F) -> Self
+    where
+        C: FnMut() -> Result<(), connector::Error> + Send + 'static,
+        F: FnMut(bool) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            healthy: AtomicBool::new(false),
+            stopped: Mutex::new(false),
+            wakeup: Condvar::new(),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::spawn(move || {
+            let mut last = None;
+
+            loop {
+                let healthy = match check() {
+                    Ok(()) => true,
+                    Err(e) => {
+                        debug!("connector health check failed: {}", e);
+                        false
+                    }
+                };
+
+                thread_shared.healthy.store(healthy, Ordering::SeqCst);
+
+                if last != Some(healthy) {
+                    callback(healthy);
+                    last = Some(healthy);
+                }
+
+                let stopped = thread_shared.stopped.lock().unwrap();
+                let (stopped, _) = thread_shared
+                    .wakeup
+                    .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                    .unwrap();
+
+                if *stopped {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Was the connector healthy when last checked? (`false` until the first
+    /// check completes)
+    pub fn healthy(&self) -> bool {
+        self.shared.healthy.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for HealthCheck {
+    fn drop(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.wakeup.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    /// Serve a single status page response with the given body
+    fn serve_status(body: &'static str) -> (u16, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let nbytes = socket.read(&mut request).unwrap();
+            assert!(request[..nbytes].starts_with(b"GET /connector/status "));
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).unwrap();
+        });
+
+        (port, server)
+    }
+
+    #[test]
+    fn status_ok() {
+        let (port, server) = serve_status("status=OK\nserial=*\n");
+        let config = HttpConfig {
+            port,
+            ..Default::default()
+        };
+
+        check_status(&config).unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn status_no_device() {
+        let (port, server) = serve_status("status=NO_DEVICE\nserial=*\n");
+        let config = HttpConfig {
+            port,
+            ..Default::default()
+        };
+
+        assert!(check_status(&config).is_err());
+        server.join().unwrap();
+    }
+
+    /// Transport which serves a fixed status page
+    struct StatusTransport(&'static str);
+
+    impl HttpTransport for StatusTransport {
+        fn post(&self, path: &str, _body: &[u8]) -> Result<Vec<u8>, connector::Error> {
+            fail!(ErrorKind::RequestError, "unexpected POST {}", path)
+        }
+
+        fn get(&self, path: &str) -> Result<Vec<u8>, connector::Error> {
+            assert_eq!(path, STATUS_PATH);
+            Ok(self.0.as_bytes().to_vec())
+        }
+    }
+
+    /// Transport which only supports `POST` requests
+    struct PostOnlyTransport;
+
+    impl HttpTransport for PostOnlyTransport {
+        fn post(&self, path: &str, _body: &[u8]) -> Result<Vec<u8>, connector::Error> {
+            fail!(ErrorKind::RequestError, "unexpected POST {}", path)
+        }
+    }
+
+    #[test]
+    fn transport_status() {
+        check_transport_status(&StatusTransport("status=OK\n")).unwrap();
+        assert!(check_transport_status(&StatusTransport("status=NO_DEVICE\n")).is_err());
+        assert!(check_transport_status(&PostOnlyTransport).is_err());
+
+        let (sender, receiver) = mpsc::channel();
+        let _check = HealthCheck::spawn_with_transport(
+            Arc::new(StatusTransport("status=OK\n")),
+            Duration::from_secs(60),
+            move |healthy| sender.send(healthy).unwrap(),
+        );
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+    }
+}
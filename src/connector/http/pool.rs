@@ -0,0 +1,192 @@
+//! Pool of HTTP connections to `yubihsm-connector`, allowing several
+//! requests to be in flight at the same time
+
+use super::{client, config::HttpConfig, HttpTransport};
+use crate::connector;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+/// Pool of HTTP client connections: the default `HttpTransport`
+pub(super) struct Pool {
+    /// Configuration used to open new connections
+    config: HttpConfig,
+
+    /// Idle connections and the number of connections open
+    state: Mutex<State>,
+
+    /// Notified when a connection is returned to the pool (or closed)
+    available: Condvar,
+}
+
+/// Internal state of the pool
+struct State {
+    /// Connections which aren't presently in use
+    idle: Vec<client::Connection>,
+
+    /// Total number of connections open (idle or in use)
+    open: usize,
+}
+
+impl Pool {
+    /// Create a pool, opening its first connection
+    pub fn open(config: &HttpConfig) -> Result<Self, client::Error> {
+        let connection = Self::connect(config)?;
+
+        Ok(Self {
+            config: config.clone(),
+            state: Mutex::new(State {
+                idle: vec![connection],
+                open: 1,
+            }),
+            available: Condvar::new(),
+        })
+    }
+
+    /// Open a new HTTP client connection
+    fn connect(config: &HttpConfig) -> Result<client::Connection, client::Error> {
+        let opts = config.connection_options();
+        client::Connection::open(&config.addr, config.port, &opts)
+    }
+
+    /// Check out a connection from the pool, opening a new one if none are
+    /// idle, or waiting for one to be returned if the pool is full
+    pub fn checkout(&self) -> Result<PooledConnection<'_>, client::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(connection) = state.idle.pop() {
+                return Ok(PooledConnection::new(self, connection));
+            }
+
+            if state.open < self.config.pool_size.max(1) {
+                state.open += 1;
+                drop(state);
+
+                return match Self::connect(&self.config) {
+                    Ok(connection) => Ok(PooledConnection::new(self, connection)),
+                    Err(e) => {
+                        self.release(None);
+                        Err(e)
+                    }
+                };
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// Return a connection to the pool, or close it if `None`
+    fn release(&self, connection: Option<client::Connection>) {
+        let mut state = self.state.lock().unwrap();
+
+        match connection {
+            Some(connection) => state.idle.push(connection),
+            None => state.open -= 1,
+        }
+
+        self.available.notify_one();
+    }
+}
+
+impl HttpTransport for Pool {
+    fn post(&self, path: &str, body: &[u8]) -> Result<Vec<u8>, connector::Error> {
+        self.send(path, body, None)
+    }
+
+    fn post_with_timeout(
+        &self,
+        path: &str,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>, connector::Error> {
+        self.send(path, body, Some(timeout))
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>, connector::Error> {
+        let connection = self.checkout()?;
+
+        match connection.get(path) {
+            Ok(response) => Ok(response.into_vec()),
+            Err(e) => {
+                connection.discard();
+                Err(e.into())
+            }
+        }
+    }
+}
+
+impl Pool {
+    /// Make a `POST` request using a pooled connection, overriding its read
+    /// timeout if one is given
+    fn send(
+        &self,
+        path: &str,
+        body: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, connector::Error> {
+        // TODO: zero copy body creation
+        let body = client::request::Body::new(body);
+        let connection = self.checkout()?;
+
+        let result = match timeout {
+            Some(timeout) => connection.post_with_timeout(path, &body, timeout),
+            None => connection.post(path, &body),
+        };
+
+        match result {
+            Ok(response) => Ok(response.into_vec()),
+            Err(e) => {
+                // Don't return a potentially broken connection to the pool
+                connection.discard();
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Connection checked out from a `Pool`, which is returned when dropped
+pub(super) struct PooledConnection<'a> {
+    /// Pool this connection belongs to
+    pool: &'a Pool,
+
+    /// Underlying connection (`None` if discarded)
+    connection: Option<client::Connection>,
+}
+
+impl<'a> PooledConnection<'a> {
+    /// Wrap a connection checked out from the given pool
+    fn new(pool: &'a Pool, connection: client::Connection) -> Self {
+        Self {
+            pool,
+            connection: Some(connection),
+        }
+    }
+
+    /// Close this connection rather than returning it to the pool
+    pub fn discard(mut self) {
+        self.connection = None;
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = client::Connection;
+
+    fn deref(&self) -> &client::Connection {
+        self.connection.as_ref().expect("connection discarded")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut client::Connection {
+        self.connection.as_mut().expect("connection discarded")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        self.pool.release(self.connection.take());
+    }
+}
@@ -0,0 +1,52 @@
+//! Retrying requests to `yubihsm-connector` which fail due to transient
+//! connection errors (e.g. the connector being restarted)
+
+use crate::connector::{self, Backoff};
+use std::thread;
+
+/// Run the given operation, retrying transient errors according to the
+/// given policy (if any). The operation is passed the attempt number.
+pub(super) fn with_retry<T>(
+    policy: Option<&Backoff>,
+    mut op: impl FnMut(u32) -> Result<T, connector::Error>,
+) -> Result<T, connector::Error> {
+    let mut attempt = 0;
+
+    loop {
+        match op(attempt) {
+            Err(e) if *e.kind() == connector::ErrorKind::IoError => match policy {
+                Some(policy) if policy.attempts_remain(attempt) => {
+                    let delay = policy.delay(attempt);
+                    debug!("transient error ({}), retrying in {:?}", e, delay);
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                _ => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_transient_errors() {
+        let policy = Backoff {
+            initial_backoff_ms: 0,
+            ..Default::default()
+        };
+
+        let result = with_retry(Some(&policy), |attempt| match attempt {
+            0 | 1 => Err(connector::ErrorKind::IoError.into()),
+            _ => Ok(attempt),
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        let result: Result<(), _> =
+            with_retry(Some(&policy), |_| Err(connector::ErrorKind::IoError.into()));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,73 @@
+//! Pluggable transports for making HTTP requests to `yubihsm-connector`
+
+use crate::connector::{self, ErrorKind};
+use std::{sync::Arc, time::Duration};
+
+/// Transports which can make HTTP requests to `yubihsm-connector`.
+///
+/// By default, the HTTP connector uses a small built-in HTTP client, however
+/// applications can substitute their own (e.g. one built on `hyper`, or one
+/// which proxies requests out of an enclave) using
+/// [`Connector::http_with_transport`][`connector::Connector::http_with_transport`].
+///
+/// Transports must be safe to call concurrently. To share a transport between
+/// a connector and a [`HealthCheck`][`super::HealthCheck`], wrap it in an
+/// [`Arc`].
+pub trait HttpTransport: Send + Sync {
+    /// Make a `POST` request to the given path (e.g. `/connector/api`) with
+    /// the given body, returning the body of the response.
+    ///
+    /// Non-200 responses should be reported as errors. Errors which are
+    /// likely transient (e.g. the connection was reset) should use
+    /// [`ErrorKind::IoError`][`connector::ErrorKind::IoError`] so they can
+    /// be retried according to `HttpConfig::retry`.
+    fn post(&self, path: &str, body: &[u8]) -> Result<Vec<u8>, connector::Error>;
+
+    /// Make a `POST` request like [`HttpTransport::post`], but wait up to
+    /// `timeout` for the response instead of the transport's configured
+    /// timeout.
+    ///
+    /// Transports which don't support per-request timeouts use their
+    /// configured timeout.
+    fn post_with_timeout(
+        &self,
+        path: &str,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>, connector::Error> {
+        let _ = timeout;
+        self.post(path, body)
+    }
+
+    /// Make a `GET` request to the given path (e.g. `/connector/status`),
+    /// returning the body of the response. Used for health checks (see
+    /// [`check_transport_status`][`super::check_transport_status`]).
+    ///
+    /// Transports which don't support `GET` requests return an error.
+    fn get(&self, path: &str) -> Result<Vec<u8>, connector::Error> {
+        fail!(
+            ErrorKind::RequestError,
+            "GET {} unsupported by this HTTP transport",
+            path
+        )
+    }
+}
+
+impl<T: HttpTransport + ?Sized> HttpTransport for Arc<T> {
+    fn post(&self, path: &str, body: &[u8]) -> Result<Vec<u8>, connector::Error> {
+        (**self).post(path, body)
+    }
+
+    fn post_with_timeout(
+        &self,
+        path: &str,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>, connector::Error> {
+        (**self).post_with_timeout(path, body, timeout)
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>, connector::Error> {
+        (**self).get(path)
+    }
+}
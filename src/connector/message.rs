@@ -1,7 +1,9 @@
 //! Wrapper type around messages sent to/from the HSM
 
+#[cfg(any(feature = "http", feature = "mockhsm"))]
+use crate::command;
 #[cfg(any(feature = "http-server", feature = "mockhsm"))]
-use crate::{command, session};
+use crate::session;
 
 /// Messages sent to/from the HSM
 #[derive(Clone, Debug)]
@@ -26,6 +28,14 @@ impl From<Message> for Vec<u8> {
 }
 
 impl Message {
+    /// Get the command code this message begins with (if valid)
+    #[cfg(feature = "http")]
+    pub(crate) fn command_code(&self) -> Option<command::Code> {
+        self.0
+            .first()
+            .and_then(|&byte| command::Code::from_u8(byte).ok())
+    }
+
     /// Parse a `command::Message` from this `connector::Message`
     #[cfg(any(feature = "http-server", feature = "mockhsm"))]
     pub(crate) fn parse(self) -> Result<command::Message, session::Error> {
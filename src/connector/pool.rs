@@ -0,0 +1,247 @@
+//! Connector which balances connections across several HSMs
+
+use super::health::{HealthTracker, TrackedConnection};
+use crate::connector::{self, Connectable, Connection, Connector, Message};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// Configuration for a [`PoolConnector`]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Strategy for choosing which member to connect to
+    pub strategy: PoolStrategy,
+
+    /// How long a member is considered dead after an error in milliseconds
+    /// (default 30s)
+    pub retry_interval_ms: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            strategy: PoolStrategy::default(),
+            retry_interval_ms: 30_000,
+        }
+    }
+}
+
+/// Strategies for choosing which member of a [`PoolConnector`] to connect to
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolStrategy {
+    /// Choose each member in turn
+    #[default]
+    RoundRobin,
+
+    /// Choose the member with the fewest open connections
+    LeastBusy,
+}
+
+/// Balances connections across several HSMs with identical keys, e.g. for
+/// high-throughput signing.
+///
+/// Commands sent within a session must all be sent to the HSM the session
+/// was opened with, so rather than balancing individual commands, each
+/// [`Connector`] created with [`PoolConnector::connector`] connects to a
+/// single member chosen according to the pool's [`PoolStrategy`]. Create a
+/// `Client` for each connector to spread commands across the pool.
+///
+/// Members whose connections error are considered dead and skipped for the
+/// configured retry interval, after which connecting to them is attempted
+/// again.
+/// If every member is dead, all of them are tried. Members aren't probed in
+/// the background, so a member which recovered is only noticed the next time
+/// a connection is opened after its retry interval.
+#[derive(Clone)]
+pub struct PoolConnector(Arc<Pool>);
+
+impl PoolConnector {
+    /// Create a pool from the given connectors using the given configuration
+    pub fn new(connectors: impl IntoIterator<Item = Connector>, config: &PoolConfig) -> Self {
+        let members: Vec<_> = connectors
+            .into_iter()
+            .map(|connector| connector.driver.box_clone())
+            .collect();
+
+        PoolConnector(Arc::new(Pool {
+            connections: members.iter().map(|_| AtomicUsize::new(0)).collect(),
+            health: Arc::new(HealthTracker::new(
+                "pool",
+                members.len(),
+                Duration::from_millis(config.retry_interval_ms),
+            )),
+            members,
+            strategy: config.strategy,
+            next: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Create a new connector which connects to a member of this pool
+    pub fn connector(&self) -> Connector {
+        let driver: Box<dyn Connectable> = Box::new(PoolConnectable(Arc::clone(&self.0)));
+        Connector::from(driver)
+    }
+
+    /// Number of members in this pool
+    pub fn len(&self) -> usize {
+        self.0.members.len()
+    }
+
+    /// Does this pool have no members?
+    pub fn is_empty(&self) -> bool {
+        self.0.members.is_empty()
+    }
+
+    /// Number of members which are not currently considered dead
+    pub fn healthy_members(&self) -> usize {
+        self.0
+            .health
+            .healthy()
+            .into_iter()
+            .filter(|&healthy| healthy)
+            .count()
+    }
+}
+
+/// State shared by a pool and the connectors created from it
+struct Pool {
+    /// Connectors for the members of the pool
+    members: Vec<Box<dyn Connectable>>,
+
+    /// Number of open connections to each member
+    connections: Vec<AtomicUsize>,
+
+    /// Which members are considered dead
+    health: Arc<HealthTracker>,
+
+    /// Strategy for choosing members
+    strategy: PoolStrategy,
+
+    /// Index of the next member to choose when using round-robin
+    next: AtomicUsize,
+}
+
+impl Pool {
+    /// Indexes of the members in the order they should be tried: healthy
+    /// members chosen according to the strategy, followed by dead ones
+    fn candidates(&self) -> Vec<usize> {
+        let len = self.members.len();
+
+        let order: Vec<usize> = match self.strategy {
+            PoolStrategy::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..len)
+                    .map(|offset| (start + offset) % len.max(1))
+                    .collect()
+            }
+            PoolStrategy::LeastBusy => {
+                let mut order: Vec<usize> = (0..len).collect();
+                order.sort_by_key(|&index| self.connections[index].load(Ordering::Relaxed));
+                order
+            }
+        };
+
+        self.health.healthy_first(order)
+    }
+}
+
+/// Driver for connectors created by [`PoolConnector::connector`]
+struct PoolConnectable(Arc<Pool>);
+
+impl Connectable for PoolConnectable {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn Connectable> {
+        Box::new(PoolConnectable(Arc::clone(&self.0)))
+    }
+
+    /// Open a connection to the first member which can be connected to
+    fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+        let connection = self
+            .0
+            .health
+            .connect(&self.0.members, self.0.candidates())?;
+        self.0.connections[connection.index()].fetch_add(1, Ordering::Relaxed);
+
+        Ok(Box::new(PoolConnection {
+            connection,
+            pool: Arc::clone(&self.0),
+        }))
+    }
+}
+
+/// Connection to a member of a pool
+struct PoolConnection {
+    /// Underlying connection, which marks the member dead if it errors
+    connection: TrackedConnection,
+
+    /// Pool the member belongs to
+    pool: Arc<Pool>,
+}
+
+impl Connection for PoolConnection {
+    fn send_message(&self, uuid: Uuid, msg: Message) -> Result<Message, connector::Error> {
+        self.connection.send_message(uuid, msg)
+    }
+
+    fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, connector::Error> {
+        self.connection
+            .send_message_with_timeout(uuid, msg, timeout)
+    }
+}
+
+impl Drop for PoolConnection {
+    fn drop(&mut self) {
+        self.pool.connections[self.connection.index()].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PoolConfig, PoolConnector};
+    use crate::{
+        connector::{Connectable, Connector},
+        test_util::TestConnector,
+    };
+    use std::sync::{atomic::AtomicBool, Arc};
+    use uuid::Uuid;
+
+    #[test]
+    fn round_robin_skips_dead_members() {
+        let members = [true, false].iter().enumerate().map(|(id, &broken)| {
+            let driver: Box<dyn Connectable> = Box::new(TestConnector {
+                id: id as u8,
+                broken: Arc::new(AtomicBool::new(broken)),
+                ..Default::default()
+            });
+            Connector::from(driver)
+        });
+
+        let pool = PoolConnector::new(members, &PoolConfig::default());
+        let send = |connector: &Connector| {
+            connector
+                .send_message(Uuid::nil(), vec![].into())
+                .map(|response| response.as_ref().to_vec())
+        };
+
+        // The first member is broken, so it's marked dead after erroring
+        assert!(send(&pool.connector()).is_err());
+        assert_eq!(pool.healthy_members(), 1);
+
+        for _ in 0..3 {
+            assert_eq!(send(&pool.connector()).unwrap(), [1]);
+        }
+    }
+}
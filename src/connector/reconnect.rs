@@ -0,0 +1,124 @@
+//! Policy for automatically reopening sessions when the connection to the
+//! HSM fails
+
+use crate::connector::{self, Backoff};
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+
+/// Policy consulted by the `Client` when opening a session fails due to an
+/// error connecting to the HSM, e.g. while the HSM or `yubihsm-connector` is
+/// being restarted.
+///
+/// Only opening sessions is retried: commands which fail are only resent
+/// according to the client's `RetryPolicy` (if any), as the HSM may have
+/// already performed them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    /// Attempts to open a session (including the first) and the delay
+    /// between them
+    pub backoff: Backoff,
+
+    /// Kinds of connector errors which are retried
+    pub error_kinds: Vec<connector::ErrorKind>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: Backoff {
+                max_attempts: 5,
+                initial_backoff_ms: 100,
+                max_backoff_ms: 5000,
+                jitter: false,
+            },
+            error_kinds: vec![
+                connector::ErrorKind::ConnectionFailed,
+                connector::ErrorKind::IoError,
+            ],
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Should the given failed attempt (zero-indexed) be retried?
+    ///
+    /// This is the case if the error was caused by a connector error of one
+    /// of the configured kinds, and attempts remain.
+    pub fn should_retry(&self, attempt: u32, error: &(dyn StdError + 'static)) -> bool {
+        self.backoff.attempts_remain(attempt)
+            && connector_error_kind(error).is_some_and(|kind| self.error_kinds.contains(&kind))
+    }
+}
+
+/// Find the kind of the connector error which caused the given error, if any
+pub(crate) fn connector_error_kind(
+    error: &(dyn StdError + 'static),
+) -> Option<connector::ErrorKind> {
+    let mut error = Some(error);
+
+    while let Some(err) = error {
+        if let Some(err) = err.downcast_ref::<connector::Error>() {
+            return Some(*err.kind());
+        }
+
+        if let Some(context) = err.downcast_ref::<crate::error::Context<connector::ErrorKind>>() {
+            return Some(*context.kind());
+        }
+
+        error = err.source();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReconnectPolicy;
+    use crate::{
+        client,
+        connector::{self, Connector, ThrottleConfig},
+    };
+    use std::io;
+
+    #[test]
+    fn wrappers_keep_policy() {
+        let connector = || {
+            Connector::replay(io::empty())
+                .unwrap()
+                .with_reconnect_policy(Some(ReconnectPolicy::default()))
+        };
+
+        let throttled = Connector::throttle(connector(), &ThrottleConfig::default()).unwrap();
+        assert!(throttled.reconnect_policy().is_some());
+
+        let recording = Connector::record(connector(), io::sink());
+        assert!(recording.reconnect_policy().is_some());
+
+        let failover = Connector::failover([Connector::replay(io::empty()).unwrap(), connector()]);
+        assert!(failover.reconnect_policy().is_some());
+
+        let failover = Connector::failover([Connector::replay(io::empty()).unwrap()]);
+        assert!(failover.reconnect_policy().is_none());
+    }
+
+    #[test]
+    fn retries_configured_error_kinds() {
+        let policy = ReconnectPolicy::default();
+
+        let connection_failed: client::Error = client::ErrorKind::ConnectorError
+            .context(connector::Error::from(
+                connector::ErrorKind::ConnectionFailed,
+            ))
+            .into();
+
+        assert!(policy.should_retry(0, &connection_failed));
+        assert!(!policy.should_retry(4, &connection_failed));
+
+        let response_error: client::Error = client::ErrorKind::ConnectorError
+            .context(connector::Error::from(connector::ErrorKind::ResponseError))
+            .into();
+
+        assert!(!policy.should_retry(0, &response_error));
+    }
+}
@@ -0,0 +1,311 @@
+//! Connectors which record exchanges with the HSM and replay them, so
+//! protocol regressions can be reproduced without hardware.
+//!
+//! Recordings are text, with one exchange per line: the hex-encoded command
+//! message, a space, and either the hex-encoded response message or `!`
+//! followed by the error which occurred.
+
+use crate::{
+    connector::{
+        self, Connectable, Connection,
+        ErrorKind::{IoError, ResponseError},
+        Message,
+    },
+    journal::encode_hex,
+};
+use std::{
+    io::{BufRead, Write},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// Connector which records every exchange made over the connections of an
+/// underlying connector
+pub(crate) struct RecordingConnector {
+    /// Underlying connector
+    driver: Box<dyn Connectable>,
+
+    /// Destination for the recording
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl RecordingConnector {
+    /// Create a new `RecordingConnector` which records exchanges made using
+    /// the given connector to the given writer
+    pub fn create(
+        driver: Box<dyn Connectable>,
+        writer: Box<dyn Write + Send>,
+    ) -> Box<dyn Connectable> {
+        Box::new(RecordingConnector {
+            driver,
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+}
+
+impl Connectable for RecordingConnector {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn Connectable> {
+        Box::new(RecordingConnector {
+            driver: self.driver.box_clone(),
+            writer: Arc::clone(&self.writer),
+        })
+    }
+
+    /// Open a connection using the underlying connector
+    fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+        Ok(Box::new(RecordingConnection {
+            connection: self.driver.connect()?,
+            writer: Arc::clone(&self.writer),
+        }))
+    }
+}
+
+/// Connection which records exchanges made over an underlying connection
+struct RecordingConnection {
+    /// Underlying connection
+    connection: Box<dyn Connection>,
+
+    /// Destination for the recording
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl RecordingConnection {
+    /// Send a message using the underlying connection, recording the
+    /// command and its response (or error).
+    ///
+    /// Failing to record the exchange is logged rather than returned, as the
+    /// command has already been executed by the HSM.
+    fn send(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Option<Duration>,
+    ) -> Result<Message, connector::Error> {
+        let command = encode_hex(msg.as_ref());
+        let result = self.connection.send_message_with(uuid, msg, timeout);
+
+        let line = match &result {
+            Ok(response) => format!("{} {}\n", command, encode_hex(response.as_ref())),
+            Err(e) => format!("{} !{}\n", command, e.to_string().replace('\n', " ")),
+        };
+
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Err(e) = writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.flush())
+        {
+            error!("error recording exchange with HSM: {}", e);
+        }
+
+        result
+    }
+}
+
+impl Connection for RecordingConnection {
+    fn send_message(&self, uuid: Uuid, msg: Message) -> Result<Message, connector::Error> {
+        self.send(uuid, msg, None)
+    }
+
+    fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, connector::Error> {
+        self.send(uuid, msg, Some(timeout))
+    }
+}
+
+/// A recorded exchange
+struct Exchange {
+    /// Command message sent to the HSM
+    command: Vec<u8>,
+
+    /// Response message, or the error which occurred
+    response: Result<Vec<u8>, String>,
+}
+
+/// Connector which serves responses from a recording, in the order they were
+/// recorded
+pub(crate) struct ReplayConnector {
+    /// Recorded exchanges
+    exchanges: Arc<Vec<Exchange>>,
+
+    /// Index of the next exchange to replay (shared across connections)
+    position: Arc<Mutex<usize>>,
+}
+
+impl ReplayConnector {
+    /// Parse a recording from the given reader
+    pub fn create(reader: impl BufRead) -> Result<Box<dyn Connectable>, connector::Error> {
+        let mut exchanges = vec![];
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || -> connector::Error {
+                format_err!(ResponseError, "invalid recording at line {}", index + 1).into()
+            };
+
+            let (command, response) = line.split_once(' ').ok_or_else(invalid)?;
+            let command = decode_hex(command).ok_or_else(invalid)?;
+
+            let response = match response.strip_prefix('!') {
+                Some(error) => Err(error.to_owned()),
+                None => Ok(decode_hex(response).ok_or_else(invalid)?),
+            };
+
+            exchanges.push(Exchange { command, response });
+        }
+
+        Ok(Box::new(ReplayConnector {
+            exchanges: Arc::new(exchanges),
+            position: Arc::new(Mutex::new(0)),
+        }))
+    }
+}
+
+impl Connectable for ReplayConnector {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn Connectable> {
+        Box::new(ReplayConnector {
+            exchanges: Arc::clone(&self.exchanges),
+            position: Arc::clone(&self.position),
+        })
+    }
+
+    /// Open a connection which continues replaying from the current position
+    fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+        Ok(Box::new(ReplayConnector {
+            exchanges: Arc::clone(&self.exchanges),
+            position: Arc::clone(&self.position),
+        }))
+    }
+}
+
+impl Connection for ReplayConnector {
+    /// Serve the next recorded response, checking the command matches the
+    /// one which was recorded
+    fn send_message(&self, _uuid: Uuid, msg: Message) -> Result<Message, connector::Error> {
+        let mut position = self.position.lock().unwrap();
+
+        let exchange = self.exchanges.get(*position).ok_or_else(|| {
+            format_err!(
+                ResponseError,
+                "recording exhausted after {} exchanges",
+                self.exchanges.len()
+            )
+        })?;
+
+        ensure!(
+            exchange.command == msg.as_ref(),
+            ResponseError,
+            "command #{} doesn't match the recording (expected {}, got {})",
+            *position,
+            encode_hex(&exchange.command),
+            encode_hex(msg.as_ref())
+        );
+
+        *position += 1;
+
+        match &exchange.response {
+            Ok(response) => Ok(response.clone().into()),
+            Err(error) => fail!(IoError, "recorded error: {}", error),
+        }
+    }
+}
+
+/// Decode lower or upper-case hexadecimal
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordingConnector, ReplayConnector};
+    use crate::{
+        connector::{self, Connectable, Connection, Message},
+        test_util::{Buffer, FailingWriter},
+    };
+    use uuid::Uuid;
+
+    /// Connector which responds with the reversed command
+    #[derive(Clone)]
+    struct Reverse;
+
+    impl Connectable for Reverse {
+        fn box_clone(&self) -> Box<dyn Connectable> {
+            Box::new(Reverse)
+        }
+
+        fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+            Ok(Box::new(Reverse))
+        }
+    }
+
+    impl Connection for Reverse {
+        fn send_message(&self, _uuid: Uuid, msg: Message) -> Result<Message, connector::Error> {
+            Ok(msg
+                .as_ref()
+                .iter()
+                .rev()
+                .copied()
+                .collect::<Vec<_>>()
+                .into())
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let buffer = Buffer::default();
+        let recorder = RecordingConnector::create(Box::new(Reverse), Box::new(buffer.clone()));
+        let connection = recorder.connect().unwrap();
+
+        for command in [&[1, 2, 3][..], &[0xab, 0xcd]] {
+            connection
+                .send_message(Uuid::nil(), command.to_vec().into())
+                .unwrap();
+        }
+
+        let recording = buffer.contents();
+        assert_eq!(recording, b"010203 030201\nabcd cdab\n");
+
+        let replay = ReplayConnector::create(recording.as_slice())
+            .unwrap()
+            .connect()
+            .unwrap();
+
+        let response = replay
+            .send_message(Uuid::nil(), vec![1, 2, 3].into())
+            .unwrap();
+        assert_eq!(response.as_ref(), [3, 2, 1]);
+
+        // Commands which differ from the recording are rejected
+        assert!(replay.send_message(Uuid::nil(), vec![0xab].into()).is_err());
+    }
+
+    #[test]
+    fn recording_failures_preserve_response() {
+        let recorder = RecordingConnector::create(Box::new(Reverse), Box::new(FailingWriter));
+        let connection = recorder.connect().unwrap();
+
+        let response = connection
+            .send_message(Uuid::nil(), vec![1, 2, 3].into())
+            .unwrap();
+        assert_eq!(response.as_ref(), [3, 2, 1]);
+    }
+}
@@ -0,0 +1,332 @@
+//! Connector which limits the rate and concurrency of commands sent to the
+//! HSM, so one client can't starve others sharing it
+
+use crate::connector::{self, Connectable, Connection, ErrorKind, Message};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Limits enforced by a throttled connector (see
+/// [`Connector::throttle`][`super::Connector::throttle`])
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// Maximum number of commands sent per second, with bursts of up to a
+    /// second's worth of commands permitted (default unlimited). Must be
+    /// nonzero if set.
+    pub max_commands_per_sec: Option<u32>,
+
+    /// Maximum number of commands in flight at once (default unlimited).
+    /// Must be nonzero if set.
+    pub max_concurrent: Option<usize>,
+}
+
+/// Connector which waits until the configured limits permit each command
+/// before forwarding it to an underlying connector. The limits are shared by
+/// every connection made by the connector (and its clones).
+pub(crate) struct ThrottledConnector {
+    /// Underlying connector
+    driver: Box<dyn Connectable>,
+
+    /// Throttling state shared by all connections
+    throttle: Arc<Throttle>,
+}
+
+impl ThrottledConnector {
+    /// Create a new `ThrottledConnector` which throttles commands sent using
+    /// the given connector according to the given configuration
+    pub fn create(
+        driver: Box<dyn Connectable>,
+        config: &ThrottleConfig,
+    ) -> Result<Box<dyn Connectable>, connector::Error> {
+        ensure!(
+            config.max_commands_per_sec != Some(0),
+            ErrorKind::ConfigInvalid,
+            "max_commands_per_sec must be nonzero"
+        );
+
+        ensure!(
+            config.max_concurrent != Some(0),
+            ErrorKind::ConfigInvalid,
+            "max_concurrent must be nonzero"
+        );
+
+        let throttle = Throttle {
+            config: config.clone(),
+            state: Mutex::new(State {
+                tokens: f64::from(config.max_commands_per_sec.unwrap_or(0)),
+                refilled_at: Instant::now(),
+                in_flight: 0,
+            }),
+            released: Condvar::new(),
+        };
+
+        Ok(Box::new(ThrottledConnector {
+            driver,
+            throttle: Arc::new(throttle),
+        }))
+    }
+}
+
+impl Connectable for ThrottledConnector {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn Connectable> {
+        Box::new(ThrottledConnector {
+            driver: self.driver.box_clone(),
+            throttle: Arc::clone(&self.throttle),
+        })
+    }
+
+    /// Open a connection using the underlying connector
+    fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+        Ok(Box::new(ThrottledConnection {
+            connection: self.driver.connect()?,
+            throttle: Arc::clone(&self.throttle),
+        }))
+    }
+}
+
+/// Connection which throttles commands sent over an underlying connection
+struct ThrottledConnection {
+    /// Underlying connection
+    connection: Box<dyn Connection>,
+
+    /// Throttling state shared by all connections
+    throttle: Arc<Throttle>,
+}
+
+impl ThrottledConnection {
+    /// Wait until the limits permit sending a message, then send it using
+    /// the underlying connection
+    fn send(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Option<Duration>,
+    ) -> Result<Message, connector::Error> {
+        let _permit = self.throttle.acquire();
+        self.connection.send_message_with(uuid, msg, timeout)
+    }
+}
+
+impl Connection for ThrottledConnection {
+    fn send_message(&self, uuid: Uuid, msg: Message) -> Result<Message, connector::Error> {
+        self.send(uuid, msg, None)
+    }
+
+    fn send_message_with_timeout(
+        &self,
+        uuid: Uuid,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, connector::Error> {
+        self.send(uuid, msg, Some(timeout))
+    }
+}
+
+/// Throttling state shared by all connections
+struct Throttle {
+    /// Limits to enforce
+    config: ThrottleConfig,
+
+    /// Current state
+    state: Mutex<State>,
+
+    /// Signalled when a command completes
+    released: Condvar,
+}
+
+/// Current throttling state
+struct State {
+    /// Number of commands which can be sent before exceeding the rate limit
+    tokens: f64,
+
+    /// When `tokens` was last refilled
+    refilled_at: Instant,
+
+    /// Number of commands in flight
+    in_flight: usize,
+}
+
+impl Throttle {
+    /// Block until a command can be sent without exceeding the limits,
+    /// returning a permit which releases it when dropped
+    fn acquire(&self) -> Permit<'_> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if self
+                .config
+                .max_concurrent
+                .is_some_and(|max| state.in_flight >= max)
+            {
+                state = self.released.wait(state).unwrap();
+                continue;
+            }
+
+            let rate = match self.config.max_commands_per_sec {
+                Some(rate) => f64::from(rate),
+                None => break,
+            };
+
+            let now = Instant::now();
+            let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+            state.tokens = (state.tokens + elapsed * rate).min(rate);
+            state.refilled_at = now;
+
+            if state.tokens >= 1.0 {
+                state.tokens -= 1.0;
+                break;
+            }
+
+            let wait = Duration::from_secs_f64((1.0 - state.tokens) / rate);
+            state = self.released.wait_timeout(state, wait).unwrap().0;
+        }
+
+        state.in_flight += 1;
+        Permit(self)
+    }
+}
+
+/// Permit to send a command, which records that it has completed when
+/// dropped (even if sending it panics)
+struct Permit<'a>(&'a Throttle);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.0
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .in_flight -= 1;
+
+        self.0.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ThrottleConfig, ThrottledConnector};
+    use crate::connector::{self, Connectable, Connection, Message};
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+    use uuid::Uuid;
+
+    /// Connector which responds with an empty message
+    struct Empty;
+
+    impl Connectable for Empty {
+        fn box_clone(&self) -> Box<dyn Connectable> {
+            Box::new(Empty)
+        }
+
+        fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+            Ok(Box::new(Empty))
+        }
+    }
+
+    impl Connection for Empty {
+        fn send_message(&self, _uuid: Uuid, _msg: Message) -> Result<Message, connector::Error> {
+            Ok(vec![].into())
+        }
+    }
+
+    /// Connector whose connections panic when sending
+    struct Panicking;
+
+    impl Connectable for Panicking {
+        fn box_clone(&self) -> Box<dyn Connectable> {
+            Box::new(Panicking)
+        }
+
+        fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+            Ok(Box::new(Panicking))
+        }
+    }
+
+    impl Connection for Panicking {
+        fn send_message(&self, _uuid: Uuid, _msg: Message) -> Result<Message, connector::Error> {
+            panic!("connection failed");
+        }
+    }
+
+    #[test]
+    fn limits_commands_per_sec() {
+        let config = ThrottleConfig {
+            max_commands_per_sec: Some(10),
+            max_concurrent: Some(1),
+        };
+
+        let connection = ThrottledConnector::create(Box::new(Empty), &config)
+            .unwrap()
+            .connect()
+            .unwrap();
+
+        // The first second's worth of commands can be sent immediately, after
+        // which they're limited to one every 100ms
+        let started_at = Instant::now();
+
+        for _ in 0..12 {
+            connection.send_message(Uuid::nil(), vec![].into()).unwrap();
+        }
+
+        assert!(started_at.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn rejects_zero_limits() {
+        for config in [
+            ThrottleConfig {
+                max_commands_per_sec: Some(0),
+                max_concurrent: None,
+            },
+            ThrottleConfig {
+                max_commands_per_sec: None,
+                max_concurrent: Some(0),
+            },
+        ] {
+            let err = ThrottledConnector::create(Box::new(Empty), &config)
+                .err()
+                .unwrap();
+
+            assert_eq!(*err.kind(), connector::ErrorKind::ConfigInvalid);
+        }
+    }
+
+    #[test]
+    fn releases_permit_on_panic() {
+        let config = ThrottleConfig {
+            max_commands_per_sec: None,
+            max_concurrent: Some(1),
+        };
+
+        let connector = ThrottledConnector::create(Box::new(Panicking), &config).unwrap();
+        let connection = connector.connect().unwrap();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            connection.send_message(Uuid::nil(), vec![].into())
+        }));
+        assert!(result.is_err());
+
+        // The command which panicked no longer counts as in flight, so
+        // another can be sent rather than blocking forever
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let connection = connector.connect().unwrap();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                connection.send_message(Uuid::nil(), vec![].into())
+            }));
+            sender.send(result.is_err()).unwrap();
+        });
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+    }
+}
@@ -6,30 +6,52 @@
 //! To enumerate available USB devices (e.g. in the case there is more than
 //! one YubiHSM connected to the same computer), use [`Devices`].
 //!
+//! To be notified when a YubiHSM 2 is attached or detached, use
+//! [`Devices::watch`].
+//!
 //! [`Connector::usb`]: https://docs.rs/yubihsm/latest/yubihsm/connector/struct.Connector.html#method.usb
 
 #[macro_use]
 mod macros;
 
+#[cfg(feature = "usb-async")]
+mod async_connection;
+mod backend;
 mod config;
 mod connection;
+mod descriptor;
 mod device;
+mod metrics;
 mod timeout;
+mod variant;
+mod watch;
 
 pub use self::{
-    config::UsbConfig,
+    config::{ClaimOptions, UsbConfig},
     connection::UsbConnection,
-    device::{Device, Devices},
+    descriptor::{Descriptor, InterfaceDescriptor, ReleaseNumber},
+    device::{Device, Devices, OpenResult},
+    metrics::{LatencyHistogram, UsbMetrics, LATENCY_BUCKETS},
     timeout::UsbTimeout,
+    variant::Variant,
+    watch::{Event, Watcher},
 };
 use crate::connector::{self, Connectable, Connection};
 
+#[cfg(feature = "usb-async")]
+pub use self::async_connection::AsyncUsbConnection;
+#[cfg(feature = "usb-async")]
+use crate::connector::{AsyncConnectable, AsyncConnection, BoxFuture};
+
 /// USB vendor ID for Yubico
 pub const YUBICO_VENDOR_ID: u16 = 0x1050;
 
 /// USB product ID for the YubiHSM 2
 pub const YUBIHSM2_PRODUCT_ID: u16 = 0x0030;
 
+/// USB product ID for the YubiHSM 2 FIPS
+pub const YUBIHSM2_FIPS_PRODUCT_ID: u16 = 0x0031;
+
 /// YubiHSM 2 USB interface number
 pub const YUBIHSM2_INTERFACE_NUM: u8 = 0;
 
@@ -43,7 +65,9 @@ pub const YUBIHSM2_BULK_IN_ENDPOINT: u8 = 0x81;
 ///
 /// `UsbConnector` is available when the `usb` cargo feature is enabled.
 /// It requires `rusb` as a dependency, but does not otherwise need the
-/// [Yubico SDK].
+/// [Yubico SDK]. Alternatively the `usb-nusb` cargo feature provides the
+/// same functionality using a pure Rust USB implementation (`nusb`). If both
+/// features are enabled, libusb is used.
 ///
 /// [Yubico SDK]: https://developers.yubico.com/YubiHSM2/Releases/
 #[derive(Clone, Default, Debug)]
@@ -73,3 +97,35 @@ impl Into<Box<dyn Connectable>> for UsbConnector {
         Box::new(self)
     }
 }
+
+/// Connect to the HSM asynchronously via USB.
+///
+/// `AsyncUsbConnector` is available when the `usb-async` cargo feature is
+/// enabled.
+#[cfg(feature = "usb-async")]
+#[derive(Clone, Default, Debug)]
+pub(crate) struct AsyncUsbConnector(UsbConfig);
+
+#[cfg(feature = "usb-async")]
+impl AsyncUsbConnector {
+    /// Create a new `AsyncUsbConnector` with the given configuration
+    pub fn create(config: &UsbConfig) -> Box<dyn AsyncConnectable> {
+        Box::new(AsyncUsbConnector(config.clone()))
+    }
+}
+
+#[cfg(feature = "usb-async")]
+impl AsyncConnectable for AsyncUsbConnector {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn AsyncConnectable> {
+        Box::new(AsyncUsbConnector(self.0.clone()))
+    }
+
+    /// Open an asynchronous connection to the YubiHSM 2
+    fn connect(&self) -> BoxFuture<'_, Result<Box<dyn AsyncConnection>, connector::Error>> {
+        Box::pin(async move {
+            let connection: Box<dyn AsyncConnection> = Box::new(AsyncUsbConnection::open(&self.0)?);
+            Ok(connection)
+        })
+    }
+}
@@ -0,0 +1,151 @@
+//! Asynchronous connections to the YubiHSM 2 via USB
+
+use super::{
+    backend::{async_bulk_read, async_bulk_write, RawDevice, RawHandle, TransferError},
+    connection::{clear_halt, MAX_RECV_RETRIES, MAX_STALL_RETRIES},
+    Device, UsbConfig, UsbConnection, UsbTimeout, YUBIHSM2_BULK_IN_ENDPOINT,
+    YUBIHSM2_BULK_OUT_ENDPOINT,
+};
+use crate::{
+    command::MAX_MSG_SIZE,
+    connector::{self, AsyncConnection, BoxFuture, ErrorKind::UsbError, Message},
+};
+use std::future::Future;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Asynchronous connection to the HSM via USB.
+///
+/// This is the asynchronous counterpart of [`UsbConnection`], which performs
+/// bulk transfers using `nusb` without blocking the executor. Opening the
+/// device still performs (brief) blocking I/O.
+///
+/// Unlike `UsbConnection`, the device is not transparently reopened if it's
+/// disconnected: instead [`AsyncConnector`][`connector::AsyncConnector`]
+/// reconnects when the next message is sent.
+pub struct AsyncUsbConnection {
+    /// Handle to the underlying USB device
+    handle: Mutex<RawHandle>,
+
+    /// YubiHSM 2 USB device this connection is connected to
+    device: Device,
+
+    /// Timeout for reading from / writing to the YubiHSM 2
+    timeout: UsbTimeout,
+}
+
+impl AsyncUsbConnection {
+    /// Connect to a YubiHSM 2 using the given configuration
+    pub fn open(config: &UsbConfig) -> Result<Self, connector::Error> {
+        UsbConnection::open_from(RawDevice::enumerate_nusb()?, config).map(Self::from)
+    }
+
+    /// Borrow the `Device` for this connection
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+}
+
+/// Converts a connection opened with nusb. Connections opened with libusb
+/// (e.g. via `UsbConnection::open_with_context`) can be converted too, but
+/// sending messages over them fails: libusb transfers are only supported by
+/// the blocking `UsbConnection`.
+impl From<UsbConnection> for AsyncUsbConnection {
+    fn from(connection: UsbConnection) -> Self {
+        let (handle, device, timeout) = connection.into_parts();
+
+        Self {
+            handle: Mutex::new(handle),
+            device,
+            timeout,
+        }
+    }
+}
+
+impl AsyncConnection for AsyncUsbConnection {
+    /// Send a command to the YubiHSM and read its response
+    fn send_message(
+        &self,
+        _uuid: Uuid,
+        cmd: Message,
+    ) -> BoxFuture<'_, Result<Message, connector::Error>> {
+        Box::pin(async move {
+            let handle = self.handle.lock().await;
+            send_message(&handle, cmd.as_ref(), self.timeout).await?;
+            Ok(recv_message(&handle, self.timeout).await?)
+        })
+    }
+}
+
+/// Write a bulk message to the YubiHSM 2
+async fn send_message(
+    handle: &RawHandle,
+    data: &[u8],
+    timeout: UsbTimeout,
+) -> Result<usize, TransferError> {
+    let mut attempts_remaining = MAX_STALL_RETRIES;
+
+    let nbytes = loop {
+        let transfer = async_bulk_write(handle, YUBIHSM2_BULK_OUT_ENDPOINT, data);
+
+        match with_timeout(transfer, timeout).await {
+            Err(TransferError::Stall(err)) => {
+                clear_halt(handle, YUBIHSM2_BULK_OUT_ENDPOINT, err, attempts_remaining)?;
+                attempts_remaining -= 1;
+            }
+            result => break result?,
+        }
+    };
+
+    if data.len() == nbytes {
+        Ok(nbytes)
+    } else {
+        Err(TransferError::Other(
+            format_err!(
+                UsbError,
+                "incomplete bulk transfer: {} of {} bytes",
+                nbytes,
+                data.len()
+            )
+            .into(),
+        ))
+    }
+}
+
+/// Receive a message
+async fn recv_message(handle: &RawHandle, timeout: UsbTimeout) -> Result<Message, TransferError> {
+    let mut stalls_remaining = MAX_STALL_RETRIES;
+
+    for attempts_remaining in (0..MAX_RECV_RETRIES).rev() {
+        let transfer = async_bulk_read(handle, YUBIHSM2_BULK_IN_ENDPOINT, MAX_MSG_SIZE);
+
+        match with_timeout(transfer, timeout).await {
+            Ok(response) => return Ok(response.into()),
+            Err(TransferError::Io(_)) => {
+                debug!(
+                    "I/O error during USB bulk message receive, retrying ({} attempts remaining)",
+                    attempts_remaining
+                );
+            }
+            Err(TransferError::Stall(err)) => {
+                clear_halt(handle, YUBIHSM2_BULK_IN_ENDPOINT, err, stalls_remaining)?;
+                stalls_remaining -= 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(TransferError::Other(
+        format_err!(UsbError, "irrecoverable I/O error receiving bulk message").into(),
+    ))
+}
+
+/// Run a transfer, cancelling it if it doesn't complete within the timeout
+async fn with_timeout<T>(
+    transfer: impl Future<Output = Result<T, TransferError>>,
+    timeout: UsbTimeout,
+) -> Result<T, TransferError> {
+    tokio::time::timeout(timeout.duration(), transfer)
+        .await
+        .unwrap_or(Err(TransferError::Timeout))
+}
@@ -0,0 +1,151 @@
+//! USB backends.
+//!
+//! Two implementations of low-level USB access are available:
+//!
+//! - `usb` cargo feature: libusb (via `rusb`)
+//! - `usb-nusb` cargo feature: a pure Rust implementation (via `nusb`) which
+//!   doesn't need a C toolchain or libusb to build.
+//!
+//! If both features are enabled, both backends are compiled in: devices are
+//! enumerated with libusb by default, while asynchronous connections (and
+//! [`RawDevice::enumerate_nusb`]) use nusb. Each device keeps using the
+//! backend which enumerated it.
+
+mod dispatch;
+#[cfg(feature = "usb")]
+mod libusb;
+#[cfg(feature = "usb-nusb")]
+mod nusb;
+
+#[cfg(feature = "usb-async")]
+pub(super) use self::dispatch::{async_bulk_read, async_bulk_write};
+pub(super) use self::dispatch::{RawDevice, RawHandle};
+
+use super::{ClaimOptions, Descriptor, UsbTimeout, Variant};
+use crate::connector::{self, ErrorKind::UsbError};
+use std::{
+    hash::Hash,
+    sync::{atomic::AtomicBool, mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+/// A USB device as enumerated by a backend
+pub(super) trait UsbDevice: Clone + Send + Sized {
+    /// Handle to an opened device with the YubiHSM 2 interface claimed
+    type Handle: UsbHandle;
+
+    /// Identifier used to correlate hotplug events
+    type Id: Eq + Hash + Send;
+
+    /// Enumerate connected YubiHSM 2 devices
+    fn enumerate() -> Result<Vec<Self>, connector::Error>;
+
+    /// Enumerate connected YubiHSM 2 devices in the same way as this device
+    /// was enumerated (e.g. using the same libusb context)
+    fn rescan(&self) -> Result<Vec<Self>, connector::Error>;
+
+    /// Spawn a thread which forwards hotplug events for YubiHSM 2 devices
+    /// until `stop` is set. Devices which are already connected are
+    /// reported as arrivals.
+    fn watch(
+        events: mpsc::Sender<HotplugEvent<Self>>,
+        stop: Arc<AtomicBool>,
+    ) -> Result<thread::JoinHandle<()>, connector::Error>;
+
+    /// Get the identifier for this device
+    fn id(&self) -> Self::Id;
+
+    /// Get the bus number for this device
+    fn bus_number(&self) -> u8;
+
+    /// Get the address for this device
+    fn address(&self) -> u8;
+
+    /// Read the product name, serial number and descriptors of this device,
+    /// resetting it first if `reset` is set (and the backend needs to open
+    /// the device to read them)
+    fn read_identity(&self, reset: bool, timeout: UsbTimeout)
+        -> Result<Identity, connector::Error>;
+
+    /// Open this device and claim the YubiHSM 2 interface using the given
+    /// options
+    fn open_interface(&self, claim: &ClaimOptions) -> Result<Self::Handle, connector::Error>;
+}
+
+/// Handle to an opened YubiHSM 2
+pub(super) trait UsbHandle: Send {
+    /// Write a bulk message to the given endpoint
+    fn bulk_write(
+        &self,
+        endpoint: u8,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError>;
+
+    /// Read a bulk message from the given endpoint
+    fn bulk_read(
+        &self,
+        endpoint: u8,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError>;
+
+    /// Clear a halt (stall) condition on the given endpoint
+    fn clear_halt(&self, endpoint: u8) -> Result<(), connector::Error>;
+}
+
+/// Information identifying a device
+pub(super) struct Identity {
+    /// Product vendor and name
+    pub(super) product_name: String,
+
+    /// Serial number string
+    pub(super) serial_number: String,
+
+    /// Device variant (e.g. FIPS)
+    pub(super) variant: Variant,
+
+    /// Descriptor details
+    pub(super) descriptor: Descriptor,
+}
+
+/// Hotplug events reported by a backend
+pub(super) enum HotplugEvent<D: UsbDevice> {
+    /// A YubiHSM 2 was attached
+    Arrived(D),
+
+    /// The device with the given identifier was detached
+    Left(D::Id),
+}
+
+/// Errors which occur during bulk transfers
+pub(super) enum TransferError {
+    /// The transfer timed out
+    Timeout,
+
+    /// Sporadic I/O error (the transfer can be retried)
+    Io(connector::Error),
+
+    /// The endpoint is halted (the halt can be cleared and the transfer
+    /// retried)
+    Stall(connector::Error),
+
+    /// The device was disconnected (it may be possible to reopen it)
+    Disconnected(connector::Error),
+
+    /// Any other error
+    Other(connector::Error),
+}
+
+impl From<TransferError> for connector::Error {
+    fn from(err: TransferError) -> connector::Error {
+        match err {
+            TransferError::Timeout => format_err!(UsbError, "USB transfer timed out").into(),
+            TransferError::Io(err)
+            | TransferError::Stall(err)
+            | TransferError::Disconnected(err)
+            | TransferError::Other(err) => err,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Devices and handles from whichever USB backend enumerated them, so both
+//! backends can be used at runtime when both cargo features are enabled
+
+#[cfg(feature = "usb")]
+use super::libusb;
+#[cfg(feature = "usb-nusb")]
+use super::nusb;
+use super::{HotplugEvent, Identity, TransferError, UsbDevice, UsbHandle};
+use crate::connector::{
+    self,
+    usb::{ClaimOptions, UsbTimeout},
+};
+use std::{
+    sync::{atomic::AtomicBool, mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+#[cfg(all(feature = "usb", feature = "usb-async"))]
+use crate::connector::ErrorKind::UsbError;
+
+/// Evaluate an expression with the backend-specific value of an enum
+/// defined in this module
+macro_rules! dispatch {
+    ($value:expr, $inner:ident => $body:expr) => {
+        match $value {
+            #[cfg(feature = "usb")]
+            Self::Libusb($inner) => $body,
+            #[cfg(feature = "usb-nusb")]
+            Self::Nusb($inner) => $body,
+        }
+    };
+}
+
+/// USB devices as enumerated by one of the enabled backends
+#[derive(Clone)]
+pub(in crate::connector::usb) enum RawDevice {
+    /// Device enumerated by libusb
+    #[cfg(feature = "usb")]
+    Libusb(libusb::RawDevice),
+
+    /// Device enumerated by nusb
+    #[cfg(feature = "usb-nusb")]
+    Nusb(nusb::RawDevice),
+}
+
+impl RawDevice {
+    /// Enumerate connected YubiHSM 2 devices using the given libusb context
+    #[cfg(feature = "usb")]
+    pub(in crate::connector::usb) fn enumerate_with(
+        context: &rusb::Context,
+    ) -> Result<Vec<Self>, connector::Error> {
+        Ok(libusb::enumerate_with(context)?
+            .into_iter()
+            .map(Self::Libusb)
+            .collect())
+    }
+
+    /// Enumerate connected YubiHSM 2 devices using nusb
+    #[cfg(feature = "usb-nusb")]
+    pub(in crate::connector::usb) fn enumerate_nusb() -> Result<Vec<Self>, connector::Error> {
+        Ok(nusb::RawDevice::enumerate()?
+            .into_iter()
+            .map(Self::Nusb)
+            .collect())
+    }
+}
+
+impl UsbDevice for RawDevice {
+    type Handle = RawHandle;
+    type Id = DeviceId;
+
+    /// Enumerate devices using libusb if the `usb` feature is enabled, or
+    /// nusb otherwise
+    fn enumerate() -> Result<Vec<Self>, connector::Error> {
+        #[cfg(feature = "usb")]
+        let devices = libusb::RawDevice::enumerate()?
+            .into_iter()
+            .map(Self::Libusb)
+            .collect();
+
+        #[cfg(not(feature = "usb"))]
+        let devices = Self::enumerate_nusb()?;
+
+        Ok(devices)
+    }
+
+    fn rescan(&self) -> Result<Vec<Self>, connector::Error> {
+        Ok(match self {
+            #[cfg(feature = "usb")]
+            Self::Libusb(device) => UsbDevice::rescan(device)?
+                .into_iter()
+                .map(Self::Libusb)
+                .collect(),
+            #[cfg(feature = "usb-nusb")]
+            Self::Nusb(device) => UsbDevice::rescan(device)?
+                .into_iter()
+                .map(Self::Nusb)
+                .collect(),
+        })
+    }
+
+    /// Watch for hotplug events using the same backend as `enumerate`
+    fn watch(
+        events: mpsc::Sender<HotplugEvent<Self>>,
+        stop: Arc<AtomicBool>,
+    ) -> Result<thread::JoinHandle<()>, connector::Error> {
+        #[cfg(feature = "usb")]
+        return forward_events(events, stop, Self::Libusb, DeviceId::Libusb);
+
+        #[cfg(not(feature = "usb"))]
+        return forward_events(events, stop, Self::Nusb, DeviceId::Nusb);
+    }
+
+    fn id(&self) -> Self::Id {
+        match self {
+            #[cfg(feature = "usb")]
+            Self::Libusb(device) => DeviceId::Libusb(UsbDevice::id(device)),
+            #[cfg(feature = "usb-nusb")]
+            Self::Nusb(device) => DeviceId::Nusb(UsbDevice::id(device)),
+        }
+    }
+
+    fn bus_number(&self) -> u8 {
+        dispatch!(self, device => UsbDevice::bus_number(device))
+    }
+
+    fn address(&self) -> u8 {
+        dispatch!(self, device => UsbDevice::address(device))
+    }
+
+    fn read_identity(
+        &self,
+        reset: bool,
+        timeout: UsbTimeout,
+    ) -> Result<Identity, connector::Error> {
+        dispatch!(self, device => UsbDevice::read_identity(device, reset, timeout))
+    }
+
+    fn open_interface(&self, claim: &ClaimOptions) -> Result<Self::Handle, connector::Error> {
+        Ok(match self {
+            #[cfg(feature = "usb")]
+            Self::Libusb(device) => RawHandle::Libusb(UsbDevice::open_interface(device, claim)?),
+            #[cfg(feature = "usb-nusb")]
+            Self::Nusb(device) => RawHandle::Nusb(UsbDevice::open_interface(device, claim)?),
+        })
+    }
+}
+
+/// Handle to a YubiHSM 2 opened by one of the enabled backends
+pub(in crate::connector::usb) enum RawHandle {
+    /// Handle opened by libusb
+    #[cfg(feature = "usb")]
+    Libusb(<libusb::RawDevice as UsbDevice>::Handle),
+
+    /// Interface claimed by nusb
+    #[cfg(feature = "usb-nusb")]
+    Nusb(<nusb::RawDevice as UsbDevice>::Handle),
+}
+
+impl UsbHandle for RawHandle {
+    fn bulk_write(
+        &self,
+        endpoint: u8,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError> {
+        dispatch!(self, handle => UsbHandle::bulk_write(handle, endpoint, data, timeout))
+    }
+
+    fn bulk_read(
+        &self,
+        endpoint: u8,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError> {
+        dispatch!(self, handle => UsbHandle::bulk_read(handle, endpoint, buffer, timeout))
+    }
+
+    fn clear_halt(&self, endpoint: u8) -> Result<(), connector::Error> {
+        dispatch!(self, handle => UsbHandle::clear_halt(handle, endpoint))
+    }
+}
+
+/// Identifier used to correlate hotplug events from one of the backends
+#[derive(Eq, Hash, PartialEq)]
+pub(in crate::connector::usb) enum DeviceId {
+    /// Bus number and address of a device enumerated by libusb
+    #[cfg(feature = "usb")]
+    Libusb(<libusb::RawDevice as UsbDevice>::Id),
+
+    /// Identifier of a device enumerated by nusb
+    #[cfg(feature = "usb-nusb")]
+    Nusb(<nusb::RawDevice as UsbDevice>::Id),
+}
+
+/// Watch for hotplug events using the backend for `D`, forwarding them as
+/// events for [`RawDevice`] until the backend's event thread exits
+fn forward_events<D: UsbDevice + 'static>(
+    events: mpsc::Sender<HotplugEvent<RawDevice>>,
+    stop: Arc<AtomicBool>,
+    device: fn(D) -> RawDevice,
+    id: fn(D::Id) -> DeviceId,
+) -> Result<thread::JoinHandle<()>, connector::Error> {
+    let (sender, receiver) = mpsc::channel();
+    let watcher = D::watch(sender, stop)?;
+
+    Ok(thread::spawn(move || {
+        for event in receiver {
+            let event = match event {
+                HotplugEvent::Arrived(raw_device) => HotplugEvent::Arrived(device(raw_device)),
+                HotplugEvent::Left(raw_id) => HotplugEvent::Left(id(raw_id)),
+            };
+
+            if events.send(event).is_err() {
+                break;
+            }
+        }
+
+        let _ = watcher.join();
+    }))
+}
+
+/// Asynchronously write a bulk message to the given endpoint.
+///
+/// Asynchronous transfers need a handle opened with nusb.
+#[cfg(feature = "usb-async")]
+pub(in crate::connector::usb) async fn async_bulk_write(
+    handle: &RawHandle,
+    endpoint: u8,
+    data: &[u8],
+) -> Result<usize, TransferError> {
+    match handle {
+        #[cfg(feature = "usb")]
+        RawHandle::Libusb(_) => Err(async_unsupported()),
+        RawHandle::Nusb(interface) => nusb::async_bulk_write(interface, endpoint, data).await,
+    }
+}
+
+/// Asynchronously read a bulk message of up to `len` bytes from the given
+/// endpoint.
+///
+/// Asynchronous transfers need a handle opened with nusb.
+#[cfg(feature = "usb-async")]
+pub(in crate::connector::usb) async fn async_bulk_read(
+    handle: &RawHandle,
+    endpoint: u8,
+    len: usize,
+) -> Result<Vec<u8>, TransferError> {
+    match handle {
+        #[cfg(feature = "usb")]
+        RawHandle::Libusb(_) => Err(async_unsupported()),
+        RawHandle::Nusb(interface) => nusb::async_bulk_read(interface, endpoint, len).await,
+    }
+}
+
+/// Error for asynchronous transfers on a handle opened with libusb
+#[cfg(all(feature = "usb", feature = "usb-async"))]
+fn async_unsupported() -> TransferError {
+    TransferError::Other(
+        format_err!(
+            UsbError,
+            "asynchronous USB transfers require a device opened with nusb"
+        )
+        .into(),
+    )
+}
@@ -0,0 +1,310 @@
+//! USB backend using libusb (via `rusb`)
+
+use super::{HotplugEvent, Identity, TransferError, UsbDevice, UsbHandle};
+use crate::connector::{
+    self,
+    usb::{
+        descriptor::DEVICE_DESCRIPTOR_TYPE, ClaimOptions, Descriptor, InterfaceDescriptor,
+        ReleaseNumber, UsbTimeout, Variant, YUBICO_VENDOR_ID,
+    },
+    ErrorKind::{ConnectionFailed, DeviceBusyError, UsbError},
+};
+use rusb::UsbContext;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often the event thread checks whether it should stop
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Size of a USB device descriptor
+const DEVICE_DESCRIPTOR_SIZE: usize = 18;
+
+/// USB devices as enumerated by libusb
+pub(in crate::connector::usb) type RawDevice = rusb::Device<rusb::Context>;
+
+impl UsbDevice for RawDevice {
+    type Handle = rusb::DeviceHandle<rusb::Context>;
+    type Id = (u8, u8);
+
+    fn enumerate() -> Result<Vec<Self>, connector::Error> {
+        enumerate_with(&context()?)
+    }
+
+    fn rescan(&self) -> Result<Vec<Self>, connector::Error> {
+        enumerate_with(self.context())
+    }
+
+    fn watch(
+        events: mpsc::Sender<HotplugEvent<Self>>,
+        stop: Arc<AtomicBool>,
+    ) -> Result<thread::JoinHandle<()>, connector::Error> {
+        ensure!(
+            rusb::has_hotplug(),
+            UsbError,
+            "USB hotplug events are unsupported on this platform"
+        );
+
+        let context = context()?;
+        let (registered_tx, registered_rx) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            // The registration is kept on the event thread so it is
+            // deregistered when the thread exits
+            let registration = rusb::HotplugBuilder::new()
+                .vendor_id(YUBICO_VENDOR_ID)
+                .enumerate(true)
+                .register(&context, Box::new(Callback(events)));
+
+            let _registration = match registration {
+                Ok(registration) => {
+                    let _ = registered_tx.send(Ok(()));
+                    registration
+                }
+                Err(e) => {
+                    let _ = registered_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            while !stop.load(Ordering::Relaxed) {
+                if let Err(e) = context.handle_events(Some(EVENT_POLL_INTERVAL)) {
+                    debug!("USB: error handling hotplug events: {}", e);
+                    break;
+                }
+            }
+        });
+
+        match registered_rx.recv() {
+            Ok(Ok(())) => Ok(thread),
+            Ok(Err(e)) => fail!(UsbError, "error registering USB hotplug callback: {}", e),
+            Err(_) => fail!(UsbError, "USB hotplug event thread exited unexpectedly"),
+        }
+    }
+
+    fn id(&self) -> Self::Id {
+        (self.bus_number(), self.address())
+    }
+
+    fn bus_number(&self) -> u8 {
+        rusb::Device::bus_number(self)
+    }
+
+    fn address(&self) -> u8 {
+        rusb::Device::address(self)
+    }
+
+    fn read_identity(
+        &self,
+        reset: bool,
+        timeout: UsbTimeout,
+    ) -> Result<Identity, connector::Error> {
+        let desc = self.device_descriptor()?;
+
+        let handle =
+            rusb::Device::open(self).map_err(|e| usb_err!(self, "error opening device: {}", e))?;
+
+        if reset {
+            handle.reset().map_err(|error| match error {
+                rusb::Error::NoDevice => format_err!(
+                    DeviceBusyError,
+                    "USB(bus={},addr={}): couldn't reset device (already in use or disconnected)",
+                    self.bus_number(),
+                    self.address()
+                ),
+                other => usb_err!(self, "error resetting device: {}", other),
+            })?;
+        }
+
+        let language = *handle
+            .read_languages(timeout.duration())?
+            .first()
+            .ok_or_else(|| {
+                usb_err!(
+                    self,
+                    "couldn't read YubiHSM serial number (missing language info)"
+                )
+            })?;
+
+        let t = timeout.duration();
+        let manufacturer = handle.read_manufacturer_string(language, &desc, t)?;
+        let product = handle.read_product_string(language, &desc, t)?;
+        let serial_number = handle.read_serial_number_string(language, &desc, t)?;
+
+        let interfaces = self
+            .active_config_descriptor()?
+            .interfaces()
+            .filter_map(|interface| interface.descriptors().next())
+            .map(|interface| InterfaceDescriptor {
+                number: interface.interface_number(),
+                class: interface.class_code(),
+                subclass: interface.sub_class_code(),
+                protocol: interface.protocol_code(),
+            })
+            .collect();
+
+        let mut raw = vec![0u8; DEVICE_DESCRIPTOR_SIZE];
+        let raw_len = handle.read_control(
+            rusb::request_type(
+                rusb::Direction::In,
+                rusb::RequestType::Standard,
+                rusb::Recipient::Device,
+            ),
+            rusb::constants::LIBUSB_REQUEST_GET_DESCRIPTOR,
+            u16::from(DEVICE_DESCRIPTOR_TYPE) << 8,
+            0,
+            &mut raw,
+            t,
+        )?;
+        raw.truncate(raw_len);
+
+        let release_number = desc.device_version();
+
+        let variant = Variant::from_product_id(desc.product_id())
+            .ok_or_else(|| usb_err!(self, "not a YubiHSM 2"))?;
+
+        Ok(Identity {
+            product_name: format!("{manufacturer} {product}"),
+            serial_number,
+            variant,
+            descriptor: Descriptor {
+                release_number: ReleaseNumber {
+                    major: release_number.major(),
+                    minor: release_number.minor(),
+                    sub_minor: release_number.sub_minor(),
+                },
+                class: desc.class_code(),
+                subclass: desc.sub_class_code(),
+                protocol: desc.protocol_code(),
+                interfaces,
+                raw,
+            },
+        })
+    }
+
+    fn open_interface(&self, claim: &ClaimOptions) -> Result<Self::Handle, connector::Error> {
+        let handle = rusb::Device::open(self)?;
+        handle.reset()?;
+
+        if claim.detach_kernel_driver {
+            match handle.set_auto_detach_kernel_driver(true) {
+                // Platforms other than Linux don't bind kernel drivers in a
+                // way which prevents claiming the interface
+                Ok(()) | Err(rusb::Error::NotSupported) => (),
+                Err(e) => fail!(
+                    UsbError,
+                    "USB(bus={},addr={}): error enabling kernel driver detach: {}",
+                    self.bus_number(),
+                    self.address(),
+                    e
+                ),
+            }
+        }
+
+        handle.claim_interface(claim.interface)?;
+
+        if claim.alt_setting != 0 {
+            handle.set_alternate_setting(claim.interface, claim.alt_setting)?;
+        }
+
+        Ok(handle)
+    }
+}
+
+impl UsbHandle for rusb::DeviceHandle<rusb::Context> {
+    fn bulk_write(
+        &self,
+        endpoint: u8,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError> {
+        rusb::DeviceHandle::write_bulk(self, endpoint, data, timeout).map_err(transfer_error)
+    }
+
+    fn bulk_read(
+        &self,
+        endpoint: u8,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError> {
+        rusb::DeviceHandle::read_bulk(self, endpoint, buffer, timeout).map_err(transfer_error)
+    }
+
+    fn clear_halt(&self, endpoint: u8) -> Result<(), connector::Error> {
+        Ok(rusb::DeviceHandle::clear_halt(self, endpoint)?)
+    }
+}
+
+/// Enumerate connected YubiHSM 2 devices using the given libusb context
+pub(in crate::connector::usb) fn enumerate_with(
+    context: &rusb::Context,
+) -> Result<Vec<RawDevice>, connector::Error> {
+    let mut devices = vec![];
+
+    for device in context.devices()?.iter() {
+        let desc = device.device_descriptor()?;
+
+        if is_yubihsm2(&desc) {
+            devices.push(device);
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Does the given device descriptor belong to a YubiHSM 2?
+fn is_yubihsm2(desc: &rusb::DeviceDescriptor) -> bool {
+    desc.vendor_id() == YUBICO_VENDOR_ID && Variant::from_product_id(desc.product_id()).is_some()
+}
+
+/// Initialize a libusb context.
+///
+/// This fails if e.g. libusb can't access the USB subsystem, which is
+/// reported as an error rather than a panic so applications can decide
+/// how to handle it.
+fn context() -> Result<rusb::Context, connector::Error> {
+    rusb::Context::new().map_err(|e| {
+        format_err!(
+            ConnectionFailed,
+            "couldn't initialize libusb context: {}",
+            e
+        )
+        .into()
+    })
+}
+
+/// Convert a `rusb::Error` which occurred during a transfer
+fn transfer_error(err: rusb::Error) -> TransferError {
+    match err {
+        rusb::Error::Timeout => TransferError::Timeout,
+        rusb::Error::Io => TransferError::Io(err.into()),
+        rusb::Error::Pipe => TransferError::Stall(err.into()),
+        rusb::Error::NoDevice => TransferError::Disconnected(err.into()),
+        other => TransferError::Other(other.into()),
+    }
+}
+
+/// libusb hotplug callback which forwards events to the watcher
+struct Callback(mpsc::Sender<HotplugEvent<RawDevice>>);
+
+impl rusb::Hotplug<rusb::Context> for Callback {
+    /// Hotplug callbacks are registered for all Yubico devices, as libusb
+    /// can only filter on a single product ID
+    fn device_arrived(&mut self, device: RawDevice) {
+        if device
+            .device_descriptor()
+            .is_ok_and(|desc| is_yubihsm2(&desc))
+        {
+            let _ = self.0.send(HotplugEvent::Arrived(device));
+        }
+    }
+
+    fn device_left(&mut self, device: RawDevice) {
+        let _ = self.0.send(HotplugEvent::Left(device.id()));
+    }
+}
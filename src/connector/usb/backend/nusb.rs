@@ -0,0 +1,296 @@
+//! Pure Rust USB backend (via `nusb`)
+
+use super::{HotplugEvent, Identity, TransferError, UsbDevice, UsbHandle};
+use crate::connector::{
+    self,
+    usb::{
+        descriptor::DEVICE_DESCRIPTOR_TYPE, ClaimOptions, Descriptor, InterfaceDescriptor,
+        ReleaseNumber, UsbTimeout, Variant, YUBICO_VENDOR_ID,
+    },
+    ErrorKind::{AccessDenied, UsbError},
+};
+use futures_core::Stream;
+use nusb::transfer::{Completion, RequestBuffer};
+use std::{
+    future::{self, Future},
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// How often the event thread checks whether it should stop
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// USB devices as enumerated by nusb
+pub(in crate::connector::usb) type RawDevice = nusb::DeviceInfo;
+
+impl UsbDevice for RawDevice {
+    type Handle = nusb::Interface;
+    type Id = nusb::DeviceId;
+
+    fn enumerate() -> Result<Vec<Self>, connector::Error> {
+        Ok(nusb::list_devices()
+            .map_err(usb_error)?
+            .filter(is_yubihsm2)
+            .collect())
+    }
+
+    fn rescan(&self) -> Result<Vec<Self>, connector::Error> {
+        Self::enumerate()
+    }
+
+    fn watch(
+        events: mpsc::Sender<HotplugEvent<Self>>,
+        stop: Arc<AtomicBool>,
+    ) -> Result<thread::JoinHandle<()>, connector::Error> {
+        // Start watching before listing devices so none are missed
+        let mut watch = nusb::watch_devices().map_err(usb_error)?;
+        let attached = Self::enumerate()?;
+
+        Ok(thread::spawn(move || {
+            for device in attached {
+                if events.send(HotplugEvent::Arrived(device)).is_err() {
+                    return;
+                }
+            }
+
+            while !stop.load(Ordering::Relaxed) {
+                let event = match block_on_timeout(
+                    &mut future::poll_fn(|cx| Pin::new(&mut watch).poll_next(cx)),
+                    EVENT_POLL_INTERVAL,
+                ) {
+                    Some(Some(event)) => event,
+                    Some(None) => break,
+                    None => continue,
+                };
+
+                let event = match event {
+                    nusb::hotplug::HotplugEvent::Connected(device) if is_yubihsm2(&device) => {
+                        HotplugEvent::Arrived(device)
+                    }
+                    nusb::hotplug::HotplugEvent::Connected(_) => continue,
+                    nusb::hotplug::HotplugEvent::Disconnected(id) => HotplugEvent::Left(id),
+                };
+
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
+        }))
+    }
+
+    fn id(&self) -> Self::Id {
+        nusb::DeviceInfo::id(self)
+    }
+
+    fn bus_number(&self) -> u8 {
+        nusb::DeviceInfo::bus_number(self)
+    }
+
+    fn address(&self) -> u8 {
+        self.device_address()
+    }
+
+    /// nusb obtains string descriptors from the operating system, so unlike
+    /// libusb this doesn't require resetting the device
+    fn read_identity(
+        &self,
+        _reset: bool,
+        timeout: UsbTimeout,
+    ) -> Result<Identity, connector::Error> {
+        let product_name = match (self.manufacturer_string(), self.product_string()) {
+            (Some(manufacturer), Some(product)) => format!("{manufacturer} {product}"),
+            _ => fail!(
+                UsbError,
+                "USB(bus={},addr={}): couldn't read YubiHSM product name",
+                self.bus_number(),
+                self.address()
+            ),
+        };
+
+        let serial_number = self.serial_number().ok_or_else(|| {
+            usb_err!(
+                self,
+                "couldn't read YubiHSM serial number (missing descriptor)"
+            )
+        })?;
+
+        let interfaces = self
+            .interfaces()
+            .map(|interface| InterfaceDescriptor {
+                number: interface.interface_number(),
+                class: interface.class(),
+                subclass: interface.subclass(),
+                protocol: interface.protocol(),
+            })
+            .collect();
+
+        let raw = nusb::DeviceInfo::open(self)
+            .and_then(|device| {
+                device.get_descriptor(DEVICE_DESCRIPTOR_TYPE, 0, 0, timeout.duration())
+            })
+            .map_err(|e| usb_err!(self, "error reading device descriptor: {}", e))?;
+
+        let variant = Variant::from_product_id(self.product_id())
+            .ok_or_else(|| usb_err!(self, "not a YubiHSM 2"))?;
+
+        Ok(Identity {
+            product_name,
+            serial_number: serial_number.to_owned(),
+            variant,
+            descriptor: Descriptor {
+                release_number: ReleaseNumber::from_bcd(self.device_version()),
+                class: self.class(),
+                subclass: self.subclass(),
+                protocol: self.protocol(),
+                interfaces,
+                raw,
+            },
+        })
+    }
+
+    /// Resetting a device with nusb invalidates it, so unlike the libusb
+    /// backend the device is not reset before claiming its interface
+    fn open_interface(&self, claim: &ClaimOptions) -> Result<Self::Handle, connector::Error> {
+        let interface = nusb::DeviceInfo::open(self)
+            .and_then(|device| {
+                if claim.detach_kernel_driver {
+                    device.detach_and_claim_interface(claim.interface)
+                } else {
+                    device.claim_interface(claim.interface)
+                }
+            })
+            .map_err(|e| usb_err!(self, "error opening device: {}", e))?;
+
+        if claim.alt_setting != 0 {
+            interface
+                .set_alt_setting(claim.alt_setting)
+                .map_err(|e| usb_err!(self, "error selecting alternate setting: {}", e))?;
+        }
+
+        Ok(interface)
+    }
+}
+
+impl UsbHandle for nusb::Interface {
+    fn bulk_write(
+        &self,
+        endpoint: u8,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError> {
+        let mut transfer = self.bulk_out(endpoint, data.to_vec());
+        let completion = block_on_timeout(&mut transfer, timeout).ok_or(TransferError::Timeout)?;
+        completed(completion).map(|response| response.actual_length())
+    }
+
+    fn bulk_read(
+        &self,
+        endpoint: u8,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, TransferError> {
+        let mut transfer = self.bulk_in(endpoint, RequestBuffer::new(buffer.len()));
+        let completion = block_on_timeout(&mut transfer, timeout).ok_or(TransferError::Timeout)?;
+        let data = completed(completion)?;
+        buffer[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    fn clear_halt(&self, endpoint: u8) -> Result<(), connector::Error> {
+        nusb::Interface::clear_halt(self, endpoint).map_err(usb_error)
+    }
+}
+
+/// Write a bulk message to the given endpoint without blocking. Dropping the
+/// returned future before it completes cancels the transfer.
+#[cfg(feature = "usb-async")]
+pub(in crate::connector::usb) async fn async_bulk_write(
+    interface: &nusb::Interface,
+    endpoint: u8,
+    data: &[u8],
+) -> Result<usize, TransferError> {
+    let completion = interface.bulk_out(endpoint, data.to_vec()).await;
+    completed(completion).map(|response| response.actual_length())
+}
+
+/// Read a bulk message of up to `len` bytes from the given endpoint without
+/// blocking. Dropping the returned future before it completes cancels the
+/// transfer.
+#[cfg(feature = "usb-async")]
+pub(in crate::connector::usb) async fn async_bulk_read(
+    interface: &nusb::Interface,
+    endpoint: u8,
+    len: usize,
+) -> Result<Vec<u8>, TransferError> {
+    let completion = interface.bulk_in(endpoint, RequestBuffer::new(len)).await;
+    completed(completion)
+}
+
+/// Is the given device a YubiHSM 2?
+fn is_yubihsm2(device: &RawDevice) -> bool {
+    device.vendor_id() == YUBICO_VENDOR_ID
+        && Variant::from_product_id(device.product_id()).is_some()
+}
+
+/// Convert an error from nusb
+fn usb_error(err: nusb::Error) -> connector::Error {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => format_err!(AccessDenied, "{}", err),
+        _ => format_err!(UsbError, "{}", err),
+    }
+    .into()
+}
+
+/// Get the data from a completed transfer
+fn completed<T>(completion: Completion<T>) -> Result<T, TransferError> {
+    completion.into_result().map_err(|err| match err {
+        nusb::transfer::TransferError::Fault | nusb::transfer::TransferError::Unknown => {
+            TransferError::Io(format_err!(connector::ErrorKind::IoError, "{}", err).into())
+        }
+        nusb::transfer::TransferError::Stall => {
+            TransferError::Stall(format_err!(UsbError, "{}", err).into())
+        }
+        nusb::transfer::TransferError::Disconnected => TransferError::Disconnected(
+            format_err!(UsbError, "lost connection to USB device").into(),
+        ),
+        other => TransferError::Other(format_err!(UsbError, "{}", other).into()),
+    })
+}
+
+/// Drive a future to completion on the current thread, giving up after the
+/// given timeout. Dropping an unfinished nusb transfer cancels it.
+fn block_on_timeout<F: Future + Unpin>(future: &mut F, timeout: Duration) -> Option<F::Output> {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Poll::Ready(output) = Pin::new(&mut *future).poll(&mut cx) {
+            return Some(output);
+        }
+
+        let now = Instant::now();
+
+        if now >= deadline {
+            return None;
+        }
+
+        thread::park_timeout(deadline - now);
+    }
+}
+
+/// Waker which unparks the thread blocked on a future
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
@@ -1,6 +1,7 @@
 //! USB device configuration
 
-use crate::device::SerialNumber;
+use super::YUBIHSM2_INTERFACE_NUM;
+use crate::{connector::ReconnectPolicy, device::SerialNumber};
 use serde::{Deserialize, Serialize};
 
 /// Configuration for connecting to the YubiHSM via USB
@@ -9,8 +10,27 @@ pub struct UsbConfig {
     /// Serial number of the YubiHSM to connect to
     pub serial: Option<SerialNumber>,
 
+    /// USB bus number of the YubiHSM to connect to
+    pub bus: Option<u8>,
+
+    /// USB address of the YubiHSM to connect to
+    pub address: Option<u8>,
+
     /// Timeout for USB operations (default 1s)
     pub timeout_ms: u64,
+
+    /// Transparently reopen the YubiHSM (by serial number) if it's
+    /// disconnected, e.g. by being unplugged and replugged (default true)
+    #[serde(default = "default_reconnect")]
+    pub reconnect: bool,
+
+    /// Options for claiming the YubiHSM's USB interface
+    #[serde(default)]
+    pub claim: ClaimOptions,
+
+    /// Policy for reopening sessions when connecting to the HSM fails
+    #[serde(default)]
+    pub reconnect_policy: Option<ReconnectPolicy>,
 }
 
 impl UsbConfig {
@@ -22,7 +42,45 @@ impl Default for UsbConfig {
     fn default() -> UsbConfig {
         UsbConfig {
             serial: None,
+            bus: None,
+            address: None,
             timeout_ms: Self::DEFAULT_TIMEOUT_MILLIS,
+            reconnect: true,
+            claim: ClaimOptions::default(),
+            reconnect_policy: None,
         }
     }
 }
+
+/// Options for claiming the YubiHSM 2's USB interface when opening it
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ClaimOptions {
+    /// Detach any kernel driver bound to the interface before claiming it,
+    /// reattaching it when the device is closed (default false).
+    ///
+    /// This is only supported on Linux, where a kernel driver holding the
+    /// interface otherwise causes claiming it to fail.
+    pub detach_kernel_driver: bool,
+
+    /// Interface number to claim (default 0)
+    pub interface: u8,
+
+    /// Alternate setting to select on the claimed interface (default 0)
+    pub alt_setting: u8,
+}
+
+impl Default for ClaimOptions {
+    fn default() -> ClaimOptions {
+        ClaimOptions {
+            detach_kernel_driver: false,
+            interface: YUBIHSM2_INTERFACE_NUM,
+            alt_setting: 0,
+        }
+    }
+}
+
+/// Reconnect by default
+fn default_reconnect() -> bool {
+    true
+}
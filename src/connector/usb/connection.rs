@@ -1,66 +1,218 @@
 //! Connections to the YubiHSM 2 via USB
 
 use super::{
-    Device, Devices, UsbConfig, UsbTimeout, YUBIHSM2_BULK_IN_ENDPOINT, YUBIHSM2_BULK_OUT_ENDPOINT,
+    backend::{RawDevice, RawHandle, TransferError, UsbDevice, UsbHandle},
+    ClaimOptions, Device, Devices, UsbConfig, UsbMetrics, UsbTimeout, YUBIHSM2_BULK_IN_ENDPOINT,
+    YUBIHSM2_BULK_OUT_ENDPOINT,
 };
 use crate::{
     command::MAX_MSG_SIZE,
     connector::{self, Connection, ErrorKind::UsbError, Message},
 };
-use std::sync::Mutex;
+use std::{
+    sync::{Mutex, RwLock, RwLockReadGuard},
+    time::{Duration, Instant},
+};
 use uuid::Uuid;
 
 /// Number of times to retry a bulk message receive operation before giving up
-const MAX_RECV_RETRIES: usize = 3;
+pub(super) const MAX_RECV_RETRIES: usize = 3;
+
+/// Number of times to clear a halted endpoint and retry a bulk transfer
+pub(super) const MAX_STALL_RETRIES: usize = 2;
 
 /// Connection to HSM via USB
 pub struct UsbConnection {
     /// Handle to the underlying USB device
-    handle: Mutex<rusb::DeviceHandle<rusb::Context>>,
+    handle: Mutex<RawHandle>,
 
     /// YubiHSM 2 USB device this connection is connected to
-    device: Device,
+    device: RwLock<Device>,
 
     /// Timeout for reading from / writing to the YubiHSM 2
     timeout: UsbTimeout,
+
+    /// Reopen the device if it's disconnected (e.g. unplugged and replugged)
+    reconnect: bool,
+
+    /// Options used to claim the device's interface (including on reopen)
+    claim: ClaimOptions,
+
+    /// Transfer metrics for this connection
+    metrics: Mutex<UsbMetrics>,
 }
 
 impl UsbConnection {
     /// Connect to a YubiHSM 2 using the given configuration
     pub fn open(config: &UsbConfig) -> Result<Self, connector::Error> {
-        Devices::open(config.serial, UsbTimeout::from_millis(config.timeout_ms))
+        Self::open_from(RawDevice::enumerate()?, config)
+    }
+
+    /// Connect to a YubiHSM 2 using the given configuration and a
+    /// caller-owned libusb context rather than a newly created one
+    #[cfg(feature = "usb")]
+    pub fn open_with_context(
+        config: &UsbConfig,
+        context: &rusb::Context,
+    ) -> Result<Self, connector::Error> {
+        Self::open_from(RawDevice::enumerate_with(context)?, config)
     }
 
-    /// Create a new YubiHSM device from a rusb device
-    pub(super) fn create(device: Device, timeout: UsbTimeout) -> Result<Self, connector::Error> {
-        let handle = device.open_handle()?;
+    /// Connect to a YubiHSM 2 from the given devices using the given
+    /// configuration
+    pub(super) fn open_from(
+        devices: Vec<RawDevice>,
+        config: &UsbConfig,
+    ) -> Result<Self, connector::Error> {
+        let mut connection = Devices::open_matching(
+            devices,
+            config.serial,
+            config.bus,
+            config.address,
+            UsbTimeout::from_millis(config.timeout_ms),
+            &config.claim,
+        )?;
+
+        connection.set_reconnect(config.reconnect);
+        Ok(connection)
+    }
+
+    /// Create a new YubiHSM device from a USB device
+    pub(super) fn create(
+        device: Device,
+        timeout: UsbTimeout,
+        claim: &ClaimOptions,
+    ) -> Result<Self, connector::Error> {
+        let handle = device.open_handle(claim)?;
 
         // Clear any lingering messages
         for _ in 0..MAX_RECV_RETRIES {
-            if recv_message(&handle, UsbTimeout::from_millis(1)).is_err() {
+            let mut metrics = UsbMetrics::default();
+
+            if recv_message(&handle, UsbTimeout::from_millis(1), &mut metrics).is_err() {
                 break;
             }
         }
 
         Ok(Self {
-            device,
+            device: RwLock::new(device),
             timeout,
             handle: Mutex::new(handle),
+            reconnect: true,
+            claim: *claim,
+            metrics: Mutex::new(UsbMetrics::default()),
         })
     }
 
-    /// Borrow the `Device` for this connection
-    pub fn device(&self) -> &Device {
-        &self.device
+    /// Borrow the `Device` for this connection.
+    ///
+    /// If the device has been reconnected, this is the newly opened device.
+    pub fn device(&self) -> RwLockReadGuard<'_, Device> {
+        self.device.read().unwrap()
+    }
+
+    /// Enable or disable transparently reopening the device (by serial
+    /// number) if it's disconnected, e.g. due to being unplugged and
+    /// replugged or its hub being power-cycled (enabled by default)
+    pub fn set_reconnect(&mut self, reconnect: bool) {
+        self.reconnect = reconnect;
+    }
+
+    /// Get a snapshot of the transfer metrics for this connection
+    pub fn metrics(&self) -> UsbMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    /// Reset the transfer metrics for this connection
+    pub fn reset_metrics(&self) {
+        *self.metrics.lock().unwrap() = UsbMetrics::default();
+    }
+
+    /// Split this connection into its handle, device and timeout (for use by
+    /// `AsyncUsbConnection`)
+    #[cfg(feature = "usb-async")]
+    pub(super) fn into_parts(self) -> (RawHandle, Device, UsbTimeout) {
+        (
+            self.handle.into_inner().unwrap(),
+            self.device.into_inner().unwrap(),
+            self.timeout,
+        )
+    }
+
+    /// Re-enumerate devices and reopen the one with this connection's
+    /// serial number, replacing the given (disconnected) handle
+    fn reopen(&self, handle: &mut RawHandle) -> Result<(), connector::Error> {
+        let (devices, serial_number) = {
+            let device = self.device();
+            (device.device.rescan()?, device.serial_number)
+        };
+
+        debug!("USB: reconnecting to YubiHSM 2 (serial #{})", serial_number);
+        let reopened = Devices::open_matching(
+            devices,
+            Some(serial_number),
+            None,
+            None,
+            self.timeout,
+            &self.claim,
+        )?;
+        *handle = reopened.handle.into_inner().unwrap();
+        *self.device.write().unwrap() = reopened.device.into_inner().unwrap();
+        Ok(())
     }
 }
 
 impl Connection for UsbConnection {
     /// Send a command to the YubiHSM and read its response
     fn send_message(&self, _uuid: Uuid, cmd: Message) -> Result<Message, connector::Error> {
-        let handle = self.handle.lock().unwrap();
-        send_message(&handle, cmd.as_ref(), self.timeout)?;
-        recv_message(&handle, self.timeout)
+        self.send(cmd, self.timeout)
+    }
+
+    /// Send a command to the YubiHSM and read its response, using the given
+    /// timeout for the transfers
+    fn send_message_with_timeout(
+        &self,
+        _uuid: Uuid,
+        cmd: Message,
+        timeout: Duration,
+    ) -> Result<Message, connector::Error> {
+        self.send(cmd, UsbTimeout::new(timeout))
+    }
+}
+
+impl UsbConnection {
+    /// Send a command to the YubiHSM and read its response using the given
+    /// timeout
+    fn send(&self, cmd: Message, timeout: UsbTimeout) -> Result<Message, connector::Error> {
+        let mut handle = self.handle.lock().unwrap();
+
+        // Metrics for this command are merged in afterwards, so reading them
+        // doesn't wait for transfers to complete
+        let mut metrics = UsbMetrics {
+            commands_sent: 1,
+            ..UsbMetrics::default()
+        };
+
+        let result = match exchange(&handle, cmd.as_ref(), timeout, &mut metrics) {
+            // The device was unplugged and replugged (among other possible
+            // causes). Sessions don't survive this, so the message can't have
+            // been processed by the reopened device and is safe to resend.
+            Err(TransferError::Disconnected(err)) if self.reconnect => {
+                debug!("USB: lost connection to YubiHSM 2: {}", err);
+                self.reopen(&mut handle).and_then(|()| {
+                    metrics.reconnects += 1;
+                    Ok(exchange(&handle, cmd.as_ref(), timeout, &mut metrics)?)
+                })
+            }
+            result => result.map_err(Into::into),
+        };
+
+        if result.is_err() {
+            metrics.commands_failed += 1;
+        }
+
+        self.metrics.lock().unwrap().merge(&metrics);
+        result
     }
 }
 
@@ -70,52 +222,125 @@ impl Default for UsbConnection {
     }
 }
 
+/// Send a command message to the YubiHSM 2 and receive its response
+fn exchange(
+    handle: &RawHandle,
+    data: &[u8],
+    timeout: UsbTimeout,
+    metrics: &mut UsbMetrics,
+) -> Result<Message, TransferError> {
+    send_message(handle, data, timeout, metrics)?;
+    recv_message(handle, timeout, metrics)
+}
+
 /// Write a bulk message to the YubiHSM 2
 fn send_message(
-    handle: &rusb::DeviceHandle<rusb::Context>,
+    handle: &RawHandle,
     data: &[u8],
     timeout: UsbTimeout,
-) -> Result<usize, connector::Error> {
-    let nbytes = handle.write_bulk(YUBIHSM2_BULK_OUT_ENDPOINT, data, timeout.duration())?;
+    metrics: &mut UsbMetrics,
+) -> Result<usize, TransferError> {
+    let mut attempts_remaining = MAX_STALL_RETRIES;
+
+    let nbytes = loop {
+        let started_at = Instant::now();
+
+        match handle.bulk_write(YUBIHSM2_BULK_OUT_ENDPOINT, data, timeout.duration()) {
+            Ok(nbytes) => {
+                metrics.bulk_out.record(started_at.elapsed());
+                metrics.bytes_sent += nbytes as u64;
+                break nbytes;
+            }
+            // The endpoint is halted. Clear the halt and resend the message,
+            // which the YubiHSM 2 won't have accepted.
+            Err(TransferError::Stall(err)) => {
+                clear_halt(handle, YUBIHSM2_BULK_OUT_ENDPOINT, err, attempts_remaining)?;
+                metrics.stalls_cleared += 1;
+                attempts_remaining -= 1;
+            }
+            Err(err) => return Err(err),
+        }
+    };
 
     if data.len() == nbytes {
         Ok(nbytes)
     } else {
-        fail!(
-            UsbError,
-            "incomplete bulk transfer: {} of {} bytes",
-            nbytes,
-            data.len()
-        );
+        Err(TransferError::Other(
+            format_err!(
+                UsbError,
+                "incomplete bulk transfer: {} of {} bytes",
+                nbytes,
+                data.len()
+            )
+            .into(),
+        ))
     }
 }
 
 /// Receive a message
 fn recv_message(
-    handle: &rusb::DeviceHandle<rusb::Context>,
+    handle: &RawHandle,
     timeout: UsbTimeout,
-) -> Result<Message, connector::Error> {
+    metrics: &mut UsbMetrics,
+) -> Result<Message, TransferError> {
     // Allocate a buffer which is the maximum size we expect to receive
     let mut response = vec![0u8; MAX_MSG_SIZE];
+    let mut stalls_remaining = MAX_STALL_RETRIES;
 
     for attempts_remaining in (0..MAX_RECV_RETRIES).rev() {
-        match handle.read_bulk(YUBIHSM2_BULK_IN_ENDPOINT, &mut response, timeout.duration()) {
+        let started_at = Instant::now();
+
+        match handle.bulk_read(YUBIHSM2_BULK_IN_ENDPOINT, &mut response, timeout.duration()) {
             Ok(nbytes) => {
+                metrics.bulk_in.record(started_at.elapsed());
+                metrics.bytes_received += nbytes as u64;
                 response.truncate(nbytes);
                 return Ok(response.into());
             }
             // Sometimes I/O errors occur sporadically. When this happens,
             // retry the read for `MAX_RECV_RETRIES` attempts
-            Err(rusb::Error::Io) => {
+            Err(TransferError::Io(_)) => {
                 debug!(
                     "I/O error during USB bulk message receive, retrying ({} attempts remaining)",
                     attempts_remaining
                 );
+                metrics.retries += 1;
+            }
+            // The endpoint is halted. Clear the halt and retry the read.
+            Err(TransferError::Stall(err)) => {
+                clear_halt(handle, YUBIHSM2_BULK_IN_ENDPOINT, err, stalls_remaining)?;
+                metrics.stalls_cleared += 1;
+                stalls_remaining -= 1;
             }
             // All other errors we return immediately
-            Err(err) => return Err(err.into()),
+            Err(err) => return Err(err),
         }
     }
 
-    fail!(UsbError, "irrecoverable I/O error receiving bulk message")
+    Err(TransferError::Other(
+        format_err!(UsbError, "irrecoverable I/O error receiving bulk message").into(),
+    ))
+}
+
+/// Clear a halt (stall) condition on the given endpoint so the transfer
+/// which stalled can be retried.
+///
+/// If the endpoint has stalled too many times or can't be cleared, the
+/// device is treated as disconnected so `UsbConnection` can try to reopen it.
+pub(super) fn clear_halt(
+    handle: &RawHandle,
+    endpoint: u8,
+    err: connector::Error,
+    attempts_remaining: usize,
+) -> Result<(), TransferError> {
+    if attempts_remaining == 0 {
+        return Err(TransferError::Disconnected(err));
+    }
+
+    debug!(
+        "USB: endpoint {:#04x} stalled, clearing halt ({} attempts remaining): {}",
+        endpoint, attempts_remaining, err
+    );
+
+    UsbHandle::clear_halt(handle, endpoint).map_err(TransferError::Disconnected)
 }
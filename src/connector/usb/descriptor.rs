@@ -0,0 +1,93 @@
+//! USB descriptor information
+
+use std::fmt::{self, Display};
+
+/// USB descriptor type for device descriptors
+pub(super) const DEVICE_DESCRIPTOR_TYPE: u8 = 0x01;
+
+/// Details from the USB descriptors of a YubiHSM 2, which can be read without
+/// opening an authenticated session
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Descriptor {
+    /// Device release number (`bcdDevice`), which reflects the firmware
+    /// revision of the YubiHSM 2
+    pub release_number: ReleaseNumber,
+
+    /// Device class code
+    pub class: u8,
+
+    /// Device subclass code
+    pub subclass: u8,
+
+    /// Device protocol code
+    pub protocol: u8,
+
+    /// Interfaces provided by the device
+    pub interfaces: Vec<InterfaceDescriptor>,
+
+    /// Raw USB device descriptor, as read from the device
+    pub raw: Vec<u8>,
+}
+
+/// Details of a USB interface
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InterfaceDescriptor {
+    /// Interface number
+    pub number: u8,
+
+    /// Interface class code
+    pub class: u8,
+
+    /// Interface subclass code
+    pub subclass: u8,
+
+    /// Interface protocol code
+    pub protocol: u8,
+}
+
+/// Device release number, decoded from its binary-coded decimal form
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct ReleaseNumber {
+    /// Major version
+    pub major: u8,
+
+    /// Minor version
+    pub minor: u8,
+
+    /// Sub-minor version
+    pub sub_minor: u8,
+}
+
+impl ReleaseNumber {
+    /// Decode a binary-coded decimal release number (i.e. `0xJJMN` for
+    /// version `JJ.M.N`)
+    pub fn from_bcd(bcd: u16) -> Self {
+        let [hi, lo] = bcd.to_be_bytes();
+
+        Self {
+            major: (hi >> 4) * 10 + (hi & 0xf),
+            minor: lo >> 4,
+            sub_minor: lo & 0xf,
+        }
+    }
+}
+
+impl Display for ReleaseNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.sub_minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReleaseNumber;
+
+    #[test]
+    fn release_number_from_bcd() {
+        let release_number = ReleaseNumber::from_bcd(0x1234);
+        assert_eq!(release_number.major, 12);
+        assert_eq!(release_number.minor, 3);
+        assert_eq!(release_number.sub_minor, 4);
+        assert_eq!(release_number.to_string(), "12.3.4");
+    }
+}
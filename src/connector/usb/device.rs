@@ -1,24 +1,44 @@
-//! Support for connecting to the YubiHSM 2 USB device using rusb
+//! Support for connecting to the YubiHSM 2 USB device
 
 use super::{
-    UsbConnection, UsbTimeout, YUBICO_VENDOR_ID, YUBIHSM2_BULK_IN_ENDPOINT, YUBIHSM2_INTERFACE_NUM,
-    YUBIHSM2_PRODUCT_ID,
+    backend::{RawDevice, RawHandle, TransferError, UsbDevice, UsbHandle},
+    ClaimOptions, Descriptor, UsbConnection, UsbTimeout, Variant, Watcher,
+    YUBIHSM2_BULK_IN_ENDPOINT,
 };
 use crate::{
     command::MAX_MSG_SIZE,
     connector::{
         self,
-        ErrorKind::{AddrInvalid, DeviceBusyError, UsbError},
+        ErrorKind::{AddrInvalid, UsbError},
     },
     device::SerialNumber,
 };
 use std::{
     fmt::{self, Debug},
     slice::Iter,
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
     vec::IntoIter,
 };
 
+/// How often to check for a YubiHSM 2 which is being waited for
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Result of opening one of the YubiHSM 2s found by [`Devices::open_all`]
+pub struct OpenResult {
+    /// USB bus number the device is attached to
+    pub bus_number: u8,
+
+    /// Address of the device on its USB bus
+    pub address: u8,
+
+    /// Serial number of the device, or `None` if it couldn't be identified
+    pub serial_number: Option<SerialNumber>,
+
+    /// Connection to the device, or the error identifying or opening it
+    pub connection: Result<UsbConnection, connector::Error>,
+}
+
 /// A collection of detected YubiHSM 2 devices, represented as `Device`
 pub struct Devices(Vec<Device>);
 
@@ -36,12 +56,141 @@ impl Devices {
         serial_number: Option<SerialNumber>,
         timeout: UsbTimeout,
     ) -> Result<UsbConnection, connector::Error> {
-        let mut devices = Self::detect(timeout)?;
+        Self::open_matching(
+            RawDevice::enumerate()?,
+            serial_number,
+            None,
+            None,
+            timeout,
+            &ClaimOptions::default(),
+        )
+    }
+
+    /// Open a YubiHSM 2 (see [`Devices::open`]) using a caller-owned libusb
+    /// context rather than a newly created one
+    #[cfg(feature = "usb")]
+    pub fn open_with_context(
+        context: &rusb::Context,
+        serial_number: Option<SerialNumber>,
+        timeout: UsbTimeout,
+    ) -> Result<UsbConnection, connector::Error> {
+        let devices = RawDevice::enumerate_with(context)?;
+        Self::open_matching(
+            devices,
+            serial_number,
+            None,
+            None,
+            timeout,
+            &ClaimOptions::default(),
+        )
+    }
+
+    /// Open the YubiHSM 2 attached at the given USB bus number and address
+    pub fn open_by_location(
+        bus_number: u8,
+        address: u8,
+        timeout: UsbTimeout,
+    ) -> Result<UsbConnection, connector::Error> {
+        let devices = RawDevice::enumerate()?;
+        Self::open_matching(
+            devices,
+            None,
+            Some(bus_number),
+            Some(address),
+            timeout,
+            &ClaimOptions::default(),
+        )
+    }
+
+    /// Open every connected YubiHSM 2, returning each one's location and
+    /// serial number along with either a connection to it or the error
+    /// opening it.
+    ///
+    /// A failure to open one device doesn't prevent the others from being
+    /// opened. Devices which can't be identified (i.e. whose serial number
+    /// can't be read) are returned without a serial number, along with the
+    /// error identifying them.
+    pub fn open_all(timeout: UsbTimeout) -> Result<Vec<OpenResult>, connector::Error> {
+        let mut results = vec![];
+
+        for raw_device in RawDevice::enumerate()? {
+            let bus_number = raw_device.bus_number();
+            let address = raw_device.address();
+
+            let (serial_number, connection) = match Device::identify(raw_device, true, timeout) {
+                Ok(device) => (Some(device.serial_number), device.open(timeout)),
+                Err(e) => (None, Err(e)),
+            };
+
+            results.push(OpenResult {
+                bus_number,
+                address,
+                serial_number,
+                connection,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Wait up to the given duration for a YubiHSM 2 with the given serial
+    /// number to be attached, then open it, using the given timeout for USB
+    /// operations (both while polling and for the opened connection).
+    ///
+    /// Connected devices are polled without resetting them, so this is safe
+    /// to use e.g. in provisioning scripts immediately after plugging in a
+    /// device, before the operating system has finished enumerating it.
+    pub fn wait_for(
+        serial_number: SerialNumber,
+        wait: Duration,
+        usb_timeout: UsbTimeout,
+    ) -> Result<UsbConnection, connector::Error> {
+        let deadline = Instant::now() + wait;
+
+        loop {
+            for raw_device in RawDevice::enumerate()? {
+                // Devices which are still being enumerated may not be able to
+                // be identified yet, so errors are ignored until they can be
+                match Device::identify(raw_device, false, usb_timeout) {
+                    Ok(device) if device.serial_number == serial_number => {
+                        return device.open(usb_timeout);
+                    }
+                    Ok(_) => (),
+                    Err(e) => debug!("USB: couldn't identify device (will retry): {}", e),
+                }
+            }
+
+            let now = Instant::now();
+
+            ensure!(
+                now < deadline,
+                UsbError,
+                "timed out waiting for YubiHSM 2 with serial number: {}",
+                serial_number
+            );
+
+            thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Open a YubiHSM 2 from the given enumerated devices which matches the
+    /// given serial number and/or location, or the only one matching if
+    /// `serial_number` is `None`, claiming its interface with the given
+    /// options
+    pub(super) fn open_matching(
+        raw_devices: Vec<RawDevice>,
+        serial_number: Option<SerialNumber>,
+        bus_number: Option<u8>,
+        address: Option<u8>,
+        timeout: UsbTimeout,
+        claim: &ClaimOptions,
+    ) -> Result<UsbConnection, connector::Error> {
+        let mut devices = Self::scan(raw_devices, bus_number, address, true, timeout)?;
 
         if let Some(sn) = serial_number {
             while let Some(device) = devices.0.pop() {
                 if device.serial_number == sn {
-                    return device.open(timeout);
+                    return device.open_with_options(timeout, claim);
                 }
             }
 
@@ -52,8 +201,16 @@ impl Devices {
             )
         } else {
             match devices.0.len() {
-                1 => devices.0.remove(0).open(timeout),
-                0 => fail!(UsbError, "no YubiHSM 2 devices detected"),
+                1 => devices.0.remove(0).open_with_options(timeout, claim),
+                0 => match (bus_number, address) {
+                    (None, None) => fail!(UsbError, "no YubiHSM 2 devices detected"),
+                    _ => fail!(
+                        UsbError,
+                        "no YubiHSM 2 devices detected at USB(bus={},addr={})",
+                        display_location(bus_number),
+                        display_location(address)
+                    ),
+                },
                 _ => fail!(
                     UsbError,
                     "expected a single YubiHSM 2 device to be connected, found {}: {}",
@@ -71,63 +228,70 @@ impl Devices {
 
     /// Detect connected YubiHSM 2s, returning a collection of them
     pub fn detect(timeout: UsbTimeout) -> Result<Self, connector::Error> {
-        use rusb::UsbContext;
-        let device_list = rusb::Context::new()?.devices()?;
-        let mut devices = vec![];
+        Self::detect_by_location(None, None, timeout)
+    }
 
-        debug!("USB: enumerating devices...");
+    /// Detect connected YubiHSM 2s using a caller-owned libusb context
+    /// rather than a newly created one, e.g. to control its lifetime or
+    /// options. Devices opened from the result keep using the context.
+    #[cfg(feature = "usb")]
+    pub fn detect_with_context(
+        context: &rusb::Context,
+        timeout: UsbTimeout,
+    ) -> Result<Self, connector::Error> {
+        Self::scan(
+            RawDevice::enumerate_with(context)?,
+            None,
+            None,
+            true,
+            timeout,
+        )
+    }
 
-        for device in device_list.iter() {
-            let desc = device.device_descriptor()?;
+    /// Detect connected YubiHSM 2s on the given USB bus and/or at the given
+    /// address (`None` matches any).
+    ///
+    /// Only matching devices are opened to read their serial numbers.
+    pub fn detect_by_location(
+        bus_number: Option<u8>,
+        address: Option<u8>,
+        timeout: UsbTimeout,
+    ) -> Result<Self, connector::Error> {
+        Self::scan(RawDevice::enumerate()?, bus_number, address, true, timeout)
+    }
 
-            if desc.vendor_id() != YUBICO_VENDOR_ID || desc.product_id() != YUBIHSM2_PRODUCT_ID {
+    /// Detect connected YubiHSM 2s without resetting them.
+    ///
+    /// [`Devices::detect`] resets each YubiHSM 2 before reading its serial
+    /// number, which terminates any sessions other processes have open with
+    /// it. Passive detection only reads the device descriptors, so it's safe
+    /// to use from e.g. monitoring tools, but may fail to identify a device
+    /// which is in a bad state.
+    pub fn detect_passive(timeout: UsbTimeout) -> Result<Self, connector::Error> {
+        Self::scan(RawDevice::enumerate()?, None, None, false, timeout)
+    }
+
+    /// Identify the given enumerated YubiHSM 2s which match the given
+    /// location, optionally resetting them before reading their descriptors
+    fn scan(
+        raw_devices: Vec<RawDevice>,
+        bus_number: Option<u8>,
+        address: Option<u8>,
+        reset: bool,
+        timeout: UsbTimeout,
+    ) -> Result<Self, connector::Error> {
+        debug!("USB: enumerating devices...");
+        let mut devices = vec![];
+
+        for device in raw_devices {
+            if bus_number.is_some_and(|bus| bus != UsbDevice::bus_number(&device))
+                || address.is_some_and(|addr| addr != UsbDevice::address(&device))
+            {
                 continue;
             }
 
             usb_debug!(device, "found YubiHSM device");
-
-            let handle = device
-                .open()
-                .map_err(|e| usb_err!(device, "error opening device: {}", e))?;
-
-            handle.reset().map_err(|error| match error {
-                rusb::Error::NoDevice => format_err!(
-                    DeviceBusyError,
-                    "USB(bus={},addr={}): couldn't reset device (already in use or disconnected)",
-                    device.bus_number(),
-                    device.address()
-                ),
-                other => usb_err!(device, "error resetting device: {}", other),
-            })?;
-
-            let language = *handle
-                .read_languages(timeout.duration())?
-                .first()
-                .ok_or_else(|| {
-                    usb_err!(
-                        device,
-                        "couldn't read YubiHSM serial number (missing language info)"
-                    )
-                })?;
-
-            let t = timeout.duration();
-            let manufacturer = handle.read_manufacturer_string(language, &desc, t)?;
-            let product = handle.read_product_string(language, &desc, t)?;
-            let product_name = format!("{manufacturer} {product}");
-            let serial_number: SerialNumber = handle
-                .read_serial_number_string(language, &desc, t)?
-                .parse()
-                .map_err(|e| format_err!(AddrInvalid, "{}", e))?;
-
-            debug!(
-                "USB(bus={},addr={}): found {} (serial #{})",
-                device.bus_number(),
-                device.address(),
-                product_name,
-                serial_number,
-            );
-
-            devices.push(Device::new(device, product_name, serial_number));
+            devices.push(Device::identify(device, reset, timeout)?);
         }
 
         if devices.is_empty() {
@@ -137,6 +301,13 @@ impl Devices {
         Ok(Devices(devices))
     }
 
+    /// Watch for YubiHSM 2s being attached or detached.
+    ///
+    /// See [`Watcher`] for more information.
+    pub fn watch(timeout: UsbTimeout) -> Result<Watcher, connector::Error> {
+        Watcher::new(timeout)
+    }
+
     /// Number of detected devices
     pub fn len(&self) -> usize {
         self.0.len()
@@ -168,34 +339,70 @@ impl IntoIterator for Devices {
 }
 
 /// A USB device we've identified as a YubiHSM 2
+#[derive(Clone)]
 pub struct Device {
-    /// Underlying `rusb` device
-    pub(super) device: rusb::Device<rusb::Context>,
+    /// Underlying device from the USB backend
+    pub(super) device: RawDevice,
 
     /// Product vendor and name
     pub product_name: String,
 
     /// Serial number of the YubiHSM 2 device
     pub serial_number: SerialNumber,
+
+    /// Variant of the YubiHSM 2 (e.g. FIPS), based on its product ID
+    pub variant: Variant,
+
+    /// USB descriptor details (e.g. release number)
+    pub descriptor: Descriptor,
 }
 
 impl Device {
-    /// Create a new device
-    pub(super) fn new(
-        device: rusb::Device<rusb::Context>,
-        product_name: String,
-        serial_number: SerialNumber,
-    ) -> Self {
-        Self {
+    /// Read the product name and serial number of the given device to
+    /// identify it as a YubiHSM 2, optionally resetting it first
+    pub(super) fn identify(
+        device: RawDevice,
+        reset: bool,
+        timeout: UsbTimeout,
+    ) -> Result<Self, connector::Error> {
+        let identity = device.read_identity(reset, timeout)?;
+        let serial_number: SerialNumber = identity
+            .serial_number
+            .parse()
+            .map_err(|e| format_err!(AddrInvalid, "{}", e))?;
+
+        debug!(
+            "USB(bus={},addr={}): found {} ({}, serial #{}, release {})",
+            device.bus_number(),
+            device.address(),
+            identity.product_name,
+            identity.variant,
+            serial_number,
+            identity.descriptor.release_number,
+        );
+
+        Ok(Self {
             device,
-            product_name,
+            product_name: identity.product_name,
             serial_number,
-        }
+            variant: identity.variant,
+            descriptor: identity.descriptor,
+        })
     }
 
     /// Open this device, consuming it and creating a `UsbConnection`
     pub fn open(self, timeout: UsbTimeout) -> Result<UsbConnection, connector::Error> {
-        let connection = UsbConnection::create(self, timeout)?;
+        self.open_with_options(timeout, &ClaimOptions::default())
+    }
+
+    /// Open this device (see [`Device::open`]), claiming its interface using
+    /// the given options, e.g. to detach a kernel driver which holds it
+    pub fn open_with_options(
+        self,
+        timeout: UsbTimeout,
+        claim: &ClaimOptions,
+    ) -> Result<UsbConnection, connector::Error> {
+        let connection = UsbConnection::create(self, timeout, claim)?;
 
         debug!(
             "USB(bus={},addr={}): successfully opened {} (serial #{})",
@@ -210,24 +417,21 @@ impl Device {
 
     /// Get the bus number for this device
     pub fn bus_number(&self) -> u8 {
-        self.device.bus_number()
+        UsbDevice::bus_number(&self.device)
     }
 
     /// Get the address for this device
     pub fn address(&self) -> u8 {
-        self.device.address()
+        UsbDevice::address(&self.device)
     }
 
-    /// Open a handle to the underlying device (for use by `UsbConnection`)
-    pub(super) fn open_handle(
-        &self,
-    ) -> Result<rusb::DeviceHandle<rusb::Context>, connector::Error> {
-        let mut handle = self.device.open()?;
-        handle.reset()?;
-        handle.claim_interface(YUBIHSM2_INTERFACE_NUM)?;
+    /// Open a handle to the underlying device (for use by `UsbConnection`),
+    /// claiming its interface using the given options
+    pub(super) fn open_handle(&self, claim: &ClaimOptions) -> Result<RawHandle, connector::Error> {
+        let handle = self.device.open_interface(claim)?;
 
         // Flush any unconsumed messages still in the buffer
-        flush(&mut handle)?;
+        flush(&handle)?;
 
         Ok(handle)
     }
@@ -245,17 +449,22 @@ impl Debug for Device {
     }
 }
 
+/// Display part of a USB location, or `*` if it matches any
+fn display_location(value: Option<u8>) -> String {
+    value.map_or_else(|| "*".to_owned(), |v| v.to_string())
+}
+
 /// Flush any unconsumed messages still in the buffer to get the connection
 /// back into a clean state
-fn flush(handle: &mut rusb::DeviceHandle<rusb::Context>) -> Result<(), connector::Error> {
+fn flush(handle: &RawHandle) -> Result<(), connector::Error> {
     let mut buffer = [0u8; MAX_MSG_SIZE];
 
     // Use a near instantaneous (but non-zero) timeout to drain the buffer.
     // Zero is interpreted as wait forever.
     let timeout = Duration::from_millis(1);
 
-    match handle.read_bulk(YUBIHSM2_BULK_IN_ENDPOINT, &mut buffer, timeout) {
-        Ok(_) | Err(rusb::Error::Timeout) => Ok(()),
+    match handle.bulk_read(YUBIHSM2_BULK_IN_ENDPOINT, &mut buffer, timeout) {
+        Ok(_) | Err(TransferError::Timeout) => Ok(()),
         Err(e) => Err(e.into()),
     }
 }
@@ -0,0 +1,182 @@
+//! Metrics for USB connections, e.g. for detecting degrading devices
+
+use std::time::Duration;
+
+/// Upper bounds of the buckets used by [`LatencyHistogram`]. Transfers which
+/// take longer than the last bound are counted in an overflow bucket.
+pub const LATENCY_BUCKETS: [Duration; 10] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_millis(1000),
+];
+
+/// Snapshot of the metrics for a [`UsbConnection`][`super::UsbConnection`]
+#[derive(Clone, Debug, Default)]
+pub struct UsbMetrics {
+    /// Number of commands sent to the YubiHSM 2
+    pub commands_sent: u64,
+
+    /// Number of commands which failed (after any retries)
+    pub commands_failed: u64,
+
+    /// Number of bytes written to the YubiHSM 2
+    pub bytes_sent: u64,
+
+    /// Number of bytes read from the YubiHSM 2
+    pub bytes_received: u64,
+
+    /// Number of reads retried after a sporadic I/O error
+    pub retries: u64,
+
+    /// Number of times a halted endpoint was cleared
+    pub stalls_cleared: u64,
+
+    /// Number of times the YubiHSM 2 was reopened after being disconnected
+    pub reconnects: u64,
+
+    /// Latency of successful bulk out (write) transfers
+    pub bulk_out: LatencyHistogram,
+
+    /// Latency of successful bulk in (read) transfers
+    pub bulk_in: LatencyHistogram,
+}
+
+impl UsbMetrics {
+    /// Add the given metrics (e.g. for a single command) to these ones
+    pub(super) fn merge(&mut self, other: &UsbMetrics) {
+        self.commands_sent += other.commands_sent;
+        self.commands_failed += other.commands_failed;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.retries += other.retries;
+        self.stalls_cleared += other.stalls_cleared;
+        self.reconnects += other.reconnects;
+        self.bulk_out.merge(&other.bulk_out);
+        self.bulk_in.merge(&other.bulk_in);
+    }
+}
+
+/// Histogram of transfer latencies, bucketed by [`LATENCY_BUCKETS`]
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    /// Number of transfers in each bucket, followed by the overflow bucket
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+
+    /// Sum of all recorded latencies
+    total: Duration,
+
+    /// Highest recorded latency
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    /// Record the latency of a transfer
+    pub(super) fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.counts[bucket] += 1;
+        self.total = self.total.saturating_add(latency);
+        self.max = self.max.max(latency);
+    }
+
+    /// Add the transfers recorded in the given histogram to this one
+    fn merge(&mut self, other: &LatencyHistogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+
+        self.total = self.total.saturating_add(other.total);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Number of recorded transfers
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Mean latency of the recorded transfers, if any
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            count => {
+                let nanos = self.total.as_nanos() / u128::from(count);
+                Some(Duration::new(
+                    (nanos / 1_000_000_000) as u64,
+                    (nanos % 1_000_000_000) as u32,
+                ))
+            }
+        }
+    }
+
+    /// Highest recorded latency
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Iterate over the buckets, yielding each bucket's upper bound (or
+    /// `None` for the overflow bucket) and the number of transfers in it
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BUCKETS
+            .iter()
+            .map(|bound| Some(*bound))
+            .chain(Some(None))
+            .zip(self.counts.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LatencyHistogram, LATENCY_BUCKETS};
+    use std::time::Duration;
+
+    #[test]
+    fn latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.mean(), None);
+
+        histogram.record(Duration::from_micros(500));
+        histogram.record(Duration::from_millis(3));
+        histogram.record(Duration::from_secs(2));
+
+        let buckets: Vec<_> = histogram.buckets().collect();
+        assert_eq!(buckets.len(), LATENCY_BUCKETS.len() + 1);
+        assert_eq!(buckets[0], (Some(Duration::from_millis(1)), 1));
+        assert_eq!(buckets[2], (Some(Duration::from_millis(5)), 1));
+        assert_eq!(buckets[LATENCY_BUCKETS.len()], (None, 1));
+
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.max(), Duration::from_secs(2));
+        assert_eq!(histogram.mean(), Some(Duration::from_nanos(667_833_333)));
+
+        let mut merged = LatencyHistogram::default();
+        merged.record(Duration::from_secs(3));
+        merged.merge(&histogram);
+        assert_eq!(merged.count(), 4);
+        assert_eq!(merged.max(), Duration::from_secs(3));
+        assert_eq!(merged.buckets().last(), Some((None, 2)));
+    }
+
+    #[test]
+    fn latency_histogram_mean_of_many_transfers() {
+        let mut counts = [0; LATENCY_BUCKETS.len() + 1];
+        counts[0] = u64::from(u32::MAX) * 4;
+
+        let histogram = LatencyHistogram {
+            counts,
+            total: Duration::from_micros(u64::from(u32::MAX) * 2),
+            max: Duration::from_millis(1),
+        };
+
+        assert_eq!(histogram.mean(), Some(Duration::from_nanos(500)));
+    }
+}
@@ -0,0 +1,42 @@
+//! YubiHSM 2 device variants
+
+use super::{YUBIHSM2_FIPS_PRODUCT_ID, YUBIHSM2_PRODUCT_ID};
+use std::fmt::{self, Display};
+
+/// Variants of the YubiHSM 2, distinguished by their USB product ID
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Variant {
+    /// YubiHSM 2
+    Standard,
+
+    /// YubiHSM 2 FIPS
+    Fips,
+}
+
+impl Variant {
+    /// Get the variant with the given USB product ID, if it's a YubiHSM 2
+    pub fn from_product_id(product_id: u16) -> Option<Self> {
+        match product_id {
+            YUBIHSM2_PRODUCT_ID => Some(Variant::Standard),
+            YUBIHSM2_FIPS_PRODUCT_ID => Some(Variant::Fips),
+            _ => None,
+        }
+    }
+
+    /// Get the USB product ID for this variant
+    pub fn product_id(self) -> u16 {
+        match self {
+            Variant::Standard => YUBIHSM2_PRODUCT_ID,
+            Variant::Fips => YUBIHSM2_FIPS_PRODUCT_ID,
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Variant::Standard => "YubiHSM 2",
+            Variant::Fips => "YubiHSM 2 FIPS",
+        })
+    }
+}
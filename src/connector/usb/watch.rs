@@ -0,0 +1,147 @@
+//! Hotplug notifications for YubiHSM 2 devices being attached or detached
+
+use super::{
+    backend::{HotplugEvent, RawDevice, UsbDevice},
+    Device, UsbTimeout,
+};
+use crate::connector::{self, ErrorKind::UsbError};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Hotplug events for YubiHSM 2 devices
+#[derive(Debug)]
+pub enum Event {
+    /// A YubiHSM 2 was attached
+    Arrived(Device),
+
+    /// A previously attached YubiHSM 2 was detached
+    Left(Device),
+}
+
+/// Watches for YubiHSM 2 devices being attached or detached, yielding an
+/// [`Event`] for each.
+///
+/// Devices which are already connected when the watcher is created are
+/// reported as [`Event::Arrived`] first. Detach events are only reported for
+/// devices which were previously reported as attached.
+///
+/// Events are delivered by a background thread using the USB backend's
+/// hotplug support, which is stopped when the `Watcher` is dropped.
+pub struct Watcher {
+    /// Raw events received from the USB backend
+    events: mpsc::Receiver<HotplugEvent<RawDevice>>,
+
+    /// Flag used to stop the event thread
+    stop: Arc<AtomicBool>,
+
+    /// Thread which handles libusb events
+    thread: Option<thread::JoinHandle<()>>,
+
+    /// Devices which have been reported as attached
+    attached: HashMap<<RawDevice as UsbDevice>::Id, Device>,
+
+    /// Timeout used when identifying newly attached devices
+    timeout: UsbTimeout,
+}
+
+impl Watcher {
+    /// Create a new watcher, starting the backend's event thread
+    pub(super) fn new(timeout: UsbTimeout) -> Result<Self, connector::Error> {
+        let (sender, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = RawDevice::watch(sender, Arc::clone(&stop))?;
+
+        Ok(Self {
+            events,
+            stop,
+            thread: Some(thread),
+            attached: HashMap::new(),
+            timeout,
+        })
+    }
+
+    /// Wait up to the given duration for the next event, returning `None`
+    /// if no event occurred within the timeout.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<Result<Event, connector::Error>> {
+        loop {
+            let raw_event = match self.events.recv_timeout(timeout) {
+                Ok(raw_event) => raw_event,
+                Err(mpsc::RecvTimeoutError::Timeout) => return None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Some(Err(disconnected())),
+            };
+
+            if let Some(event) = self.process(raw_event) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Turn a raw hotplug event into an [`Event`].
+    ///
+    /// Newly attached devices are identified here rather than on the event
+    /// thread, as libusb doesn't permit synchronous I/O from hotplug
+    /// callbacks.
+    fn process(
+        &mut self,
+        raw_event: HotplugEvent<RawDevice>,
+    ) -> Option<Result<Event, connector::Error>> {
+        match raw_event {
+            HotplugEvent::Arrived(device) => {
+                let id = UsbDevice::id(&device);
+
+                // Never reset devices while identifying them: the watcher also
+                // reports devices which were already attached, and resetting
+                // them would close sessions held by other processes
+                let result = Device::identify(device, false, self.timeout).map(|device| {
+                    self.attached.insert(id, device.clone());
+                    Event::Arrived(device)
+                });
+
+                Some(result)
+            }
+            HotplugEvent::Left(id) => {
+                let device = self.attached.remove(&id)?;
+                usb_debug!(device, "detached (serial #{})", device.serial_number);
+                Some(Ok(Event::Left(device)))
+            }
+        }
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = Result<Event, connector::Error>;
+
+    /// Block until the next event occurs, returning `None` if the event
+    /// thread has stopped
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let raw_event = self.events.recv().ok()?;
+
+            if let Some(event) = self.process(raw_event) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Error returned when the event thread has stopped
+fn disconnected() -> connector::Error {
+    format_err!(UsbError, "USB hotplug event thread stopped").into()
+}
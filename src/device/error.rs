@@ -116,7 +116,8 @@ impl ErrorKind {
             0x0f => ErrorKind::CommandUnexecuted,
             0x10 => ErrorKind::GenericError,
             0x11 => ErrorKind::ObjectExists,
-            // TODO: determine correct value for YHR_DEVICE_SSH_CA_CONSTRAINT_VIOLATION
+            // 0x12 corresponds to `YHR_CONNECTOR_ERROR`, which isn't a device error
+            0x13 => ErrorKind::SshCaConstraintViolation,
             code => ErrorKind::Unknown { code },
         }
     }
@@ -142,13 +143,34 @@ impl ErrorKind {
             ErrorKind::CommandUnexecuted => 0x0f,
             ErrorKind::GenericError => 0x10,
             ErrorKind::ObjectExists => 0x11,
-            // TODO: determine correct value
-            ErrorKind::SshCaConstraintViolation => {
-                panic!("don't know device code for YHR_DEVICE_SSH_CA_CONSTRAINT_VIOLATION")
-            }
+            ErrorKind::SshCaConstraintViolation => 0x13,
         }
     }
 
+    /// Could sending the command again (possibly over a new session) succeed?
+    ///
+    /// This is the case for errors caused by the state of the HSM's sessions
+    /// rather than the command itself, e.g. all session slots being in use or
+    /// the HSM reporting that it didn't execute the command.
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::InvalidSession
+                | ErrorKind::SessionsFull
+                | ErrorKind::SessionFailed
+                | ErrorKind::CommandUnexecuted
+        )
+    }
+
+    /// Was the command rejected because the authentication key lacks the
+    /// required credentials, capabilities or domains?
+    pub fn is_authorization_error(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::AuthenticationFailed | ErrorKind::InsufficientPermissions
+        )
+    }
+
     /// Create an `Error` from the given `response::Code` (if applicable)
     pub fn from_response_code(code: response::Code) -> Option<ErrorKind> {
         Some(match code {
@@ -183,3 +205,17 @@ impl ErrorKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorKind;
+
+    #[test]
+    fn device_error_codes_round_trip() {
+        for code in 0x01..=0x13 {
+            let kind = ErrorKind::from_u8(code);
+            assert_eq!(kind.to_u8(), code);
+            assert_eq!(code == 0x12, kind == ErrorKind::Unknown { code });
+        }
+    }
+}
@@ -1,10 +1,5 @@
 //! Elliptic Curve Diffie Hellman Commands
 //!
-//! **WARNING**: This functionality has not been tested and has not yet been
-//! confirmed to actually work! USE AT YOUR OWN RISK!
-//!
-//! You will need to enable the `untested` cargo feature to use it.
-//!
 //! <https://developers.yubico.com/YubiHSM2/Commands/Derive_Ecdh.html>
 
 use crate::{
@@ -28,16 +23,16 @@ impl Command for DeriveEcdhCommand {
     type ResponseType = DeriveEcdhResponse;
 }
 
-/// Signed SSH certificates
+/// Shared secret derived via ECDH
 #[derive(Serialize, Deserialize, Debug)]
-pub struct DeriveEcdhResponse(ecdh::UncompressedPoint);
+pub struct DeriveEcdhResponse(pub(crate) ecdh::SharedSecret);
 
 impl Response for DeriveEcdhResponse {
     const COMMAND_CODE: command::Code = command::Code::DeriveEcdh;
 }
 
-impl From<DeriveEcdhResponse> for ecdh::UncompressedPoint {
-    fn from(response: DeriveEcdhResponse) -> ecdh::UncompressedPoint {
+impl From<DeriveEcdhResponse> for ecdh::SharedSecret {
+    fn from(response: DeriveEcdhResponse) -> ecdh::SharedSecret {
         response.0
     }
 }
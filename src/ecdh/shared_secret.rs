@@ -0,0 +1,36 @@
+//! ECDH shared secrets
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Shared secret computed by ECDH (i.e. the x-coordinate of the shared point)
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct SharedSecret(pub(crate) Vec<u8>);
+
+#[allow(clippy::len_without_is_empty)]
+impl SharedSecret {
+    /// Get length of the shared secret
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Borrow the shared secret as a byte slice
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SharedSecret {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid leaking secrets in debug messages
+        write!(f, "yubihsm::ecdh::SharedSecret(...)")
+    }
+}
@@ -3,11 +3,14 @@
 //! <https://developers.yubico.com/YubiHSM2/Commands/Sign_Ecdsa.html>
 
 use crate::{
+    asymmetric,
+    client::{self, ErrorKind::ResponseError},
     command::{self, Command},
     object,
     response::Response,
 };
 use serde::{Deserialize, Serialize};
+use spki::der::{asn1::UintRef, Decode, Reader, SliceReader};
 
 /// Sign ECDSA command parameters
 #[derive(Serialize, Deserialize, Debug)]
@@ -31,8 +34,73 @@ impl Response for SignEcdsaResponse {
     const COMMAND_CODE: command::Code = command::Code::SignEcdsa;
 }
 
+impl SignEcdsaResponse {
+    /// Convert the ASN.1 DER encoded signature into the fixed-size `r || s`
+    /// encoding for the given curve, with each component left-padded to the
+    /// curve's field size
+    pub(crate) fn to_fixed(&self, curve: asymmetric::Algorithm) -> Result<Vec<u8>, client::Error> {
+        let field_size = curve.key_len();
+
+        let (r, s) = SliceReader::new(&self.0)
+            .and_then(|mut reader| {
+                let components =
+                    reader.sequence(|seq| Ok((UintRef::decode(seq)?, UintRef::decode(seq)?)))?;
+                reader.finish(components)
+            })
+            .map_err(|e| ResponseError.context(e))?;
+
+        let mut signature = vec![0u8; field_size * 2];
+
+        for (component, output) in [r, s].iter().zip(signature.chunks_mut(field_size)) {
+            let bytes = component.as_bytes();
+
+            if bytes.len() > field_size {
+                fail!(
+                    ResponseError,
+                    "ECDSA signature component too long for {:?}: {} bytes",
+                    curve,
+                    bytes.len()
+                );
+            }
+
+            output[field_size - bytes.len()..].copy_from_slice(bytes);
+        }
+
+        Ok(signature)
+    }
+}
+
 impl From<SignEcdsaResponse> for Vec<u8> {
     fn from(response: SignEcdsaResponse) -> Vec<u8> {
         response.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SignEcdsaResponse;
+    use crate::asymmetric;
+
+    #[test]
+    fn converts_der_signature_to_fixed() {
+        let response =
+            SignEcdsaResponse(vec![0x30, 0x07, 0x02, 0x01, 0x01, 0x02, 0x02, 0x00, 0xff]);
+        let signature = response.to_fixed(asymmetric::Algorithm::EcP521).unwrap();
+
+        assert_eq!(signature.len(), 132);
+        assert_eq!(signature[65], 0x01);
+        assert_eq!(signature[131], 0xff);
+        assert!(signature[..65].iter().all(|&b| b == 0));
+        assert!(signature[66..131].iter().all(|&b| b == 0));
+
+        let oversized = SignEcdsaResponse(
+            [
+                &[0x30, 0x24, 0x02, 0x1f][..],
+                &[0x01; 31],
+                &[0x02, 0x01, 0x01],
+            ]
+            .concat(),
+        );
+        assert!(oversized.to_fixed(asymmetric::Algorithm::EcP224).is_err());
+    }
+}
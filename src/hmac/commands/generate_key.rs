@@ -15,6 +15,10 @@ pub(crate) struct GenHmacKeyCommand(pub(crate) generate::Params);
 
 impl Command for GenHmacKeyCommand {
     type ResponseType = GenHmacKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.0.key_id]
+    }
 }
 
 /// Response from `command::generate_hmac_key`
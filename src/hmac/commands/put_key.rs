@@ -24,6 +24,10 @@ pub(crate) struct PutHmacKeyCommand {
 
 impl Command for PutHmacKeyCommand {
     type ResponseType = PutHmacKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.id]
+    }
 }
 
 /// Response from `command::put_hmac_key`
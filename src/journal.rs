@@ -0,0 +1,497 @@
+//! Client-side journal of mutating HSM operations.
+//!
+//! The YubiHSM 2 keeps its own [audit log], however the device log is of
+//! limited size and only records what the device itself observed. A
+//! [`Journal`] complements it with a tamper-evident record kept by the
+//! client: every mutating command (object creation, deletion, option
+//! changes, etc) is appended to the journal along with its outcome, and
+//! each entry is hash-chained (SHA-256) to the one before it.
+//!
+//! When a [`Signer`] is configured, the current head of the hash chain is
+//! periodically signed by a designated key stored in the HSM, and the
+//! resulting signature is recorded in the journal as a checkpoint.
+//!
+//! The hash chain is unkeyed, so anyone able to rewrite the journal can
+//! recompute it: [`verify`] only detects accidental corruption and naive
+//! tampering. Use [`verify_with`] to also check checkpoint signatures, which
+//! protect the entries preceding the last checkpoint. Entries after it (or
+//! truncation back to it) can only be detected by comparing the verified
+//! sequence number and head against values stored elsewhere.
+//!
+//! Journals are enabled with [`Client::set_journal`].
+//!
+//! [audit log]: https://developers.yubico.com/YubiHSM2/Concepts/Logs.html
+//! [`Client::set_journal`]: crate::Client::set_journal
+
+mod entry;
+mod error;
+
+pub use self::{
+    entry::{Entry, Event, Outcome},
+    error::{Error, ErrorKind},
+};
+
+use crate::object;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+/// Size of the journal head (a SHA-256 digest)
+pub const HEAD_SIZE: usize = 32;
+
+/// Default number of entries between signed checkpoints
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 64;
+
+/// Head of the journal's hash chain
+pub type Head = [u8; HEAD_SIZE];
+
+/// Keys within the HSM which can be used to sign journal checkpoints
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Signer {
+    /// Sign checkpoints with an Ed25519 key
+    Ed25519(object::Id),
+
+    /// Sign checkpoints with an ECDSA key (the journal head is used as the
+    /// prehashed message digest)
+    Ecdsa(object::Id),
+}
+
+impl Signer {
+    /// Get the ID of the key used to sign checkpoints
+    pub fn key_id(self) -> object::Id {
+        match self {
+            Signer::Ed25519(key_id) | Signer::Ecdsa(key_id) => key_id,
+        }
+    }
+}
+
+/// Append-only, hash-chained journal of mutating client operations
+pub struct Journal {
+    /// Destination for journal entries
+    writer: Box<dyn Write + Send>,
+
+    /// Sequence number of the next entry
+    seq: u64,
+
+    /// Current head of the hash chain
+    head: Head,
+
+    /// Key used to sign checkpoints (if any)
+    signer: Option<Signer>,
+
+    /// Number of entries between checkpoints
+    checkpoint_interval: u64,
+
+    /// Number of entries appended since the last checkpoint
+    since_checkpoint: u64,
+
+    /// Number of operations which couldn't be recorded
+    failures: u64,
+}
+
+/// Result of verifying a journal with [`verify_with`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Verification {
+    /// Sequence number of the next entry
+    pub seq: u64,
+
+    /// Head of the hash chain
+    pub head: Head,
+
+    /// Number of checkpoints whose signatures were verified
+    pub checkpoints: u64,
+
+    /// Number of entries covered by the last verified checkpoint (i.e. the
+    /// sequence number of the first entry which isn't covered), if any
+    pub checkpointed_seq: Option<u64>,
+}
+
+impl Journal {
+    /// Create a new journal which writes entries to the given destination
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self::resume(writer, 0, [0u8; HEAD_SIZE])
+    }
+
+    /// Open a journal file, creating it if it does not exist.
+    ///
+    /// If the file already contains entries, the hash chain is verified and
+    /// the journal resumes from its last entry.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        let (seq, head) = if path.exists() {
+            verify(File::open(path)?)?
+        } else {
+            (0, [0u8; HEAD_SIZE])
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::resume(file, seq, head))
+    }
+
+    /// Create a journal which continues an existing hash chain
+    pub fn resume(writer: impl Write + Send + 'static, seq: u64, head: Head) -> Self {
+        Self {
+            writer: Box::new(writer),
+            seq,
+            head,
+            signer: None,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            since_checkpoint: 0,
+            failures: 0,
+        }
+    }
+
+    /// Sign the journal head with the given key every `interval` entries
+    pub fn signer(mut self, signer: Signer, interval: u64) -> Self {
+        self.signer = Some(signer);
+        self.checkpoint_interval = interval.max(1);
+        self
+    }
+
+    /// Sequence number of the next entry
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Current head of the hash chain
+    pub fn head(&self) -> Head {
+        self.head
+    }
+
+    /// Number of operations which couldn't be recorded (e.g. due to I/O
+    /// errors or checkpoint signing failures)
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// Count an operation which couldn't be recorded
+    pub(crate) fn record_failure(&mut self) {
+        self.failures += 1;
+    }
+
+    /// Append an event to the journal.
+    ///
+    /// Returns the signer and the head to sign if a checkpoint is due.
+    pub(crate) fn append(&mut self, event: Event) -> Result<Option<(Signer, Head)>, Error> {
+        self.write_entry(event)?;
+        self.since_checkpoint += 1;
+
+        match self.signer {
+            Some(signer) if self.since_checkpoint >= self.checkpoint_interval => {
+                Ok(Some((signer, self.head)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Record a signature over a previous journal head
+    pub(crate) fn checkpoint(
+        &mut self,
+        signer: Signer,
+        signed_head: Head,
+        signature: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.write_entry(Event::Checkpoint {
+            key_id: signer.key_id(),
+            signed_head,
+            signature,
+        })?;
+
+        self.since_checkpoint = 0;
+        Ok(())
+    }
+
+    /// Chain an entry onto the journal head and write it out
+    fn write_entry(&mut self, event: Event) -> Result<(), Error> {
+        let entry = Entry::new(self.seq, event);
+        let body = entry.to_string();
+        let head = chain(&self.head, &body);
+
+        // Only advance the chain once the entry has been written, so a failed
+        // write doesn't break the chain for subsequent entries
+        writeln!(self.writer, "{}\t{}", body, encode_hex(&head))?;
+        self.writer.flush()?;
+
+        self.head = head;
+        self.seq += 1;
+        Ok(())
+    }
+}
+
+/// Verify the hash chain and sequence numbers of a journal, returning the
+/// sequence number of the next entry and the head of the chain.
+///
+/// Checkpoints must sign a head which occurred earlier in the chain, but
+/// their signatures aren't checked (see [`verify_with`]).
+pub fn verify(journal: impl Read) -> Result<(u64, Head), Error> {
+    let verification = verify_with(journal, |_, _, _| true)?;
+    Ok((verification.seq, verification.head))
+}
+
+/// Verify the hash chain, sequence numbers and checkpoints of a journal,
+/// checking each checkpoint's signature with the given function.
+///
+/// The function is called with the ID of the key which signed the
+/// checkpoint, the signed head, and the signature, and should return
+/// whether the signature is valid (e.g. using the key's public key, as
+/// returned by [`Client::get_public_key`]).
+///
+/// [`Client::get_public_key`]: crate::Client::get_public_key
+pub fn verify_with<F>(journal: impl Read, mut verify_signature: F) -> Result<Verification, Error>
+where
+    F: FnMut(object::Id, &Head, &[u8]) -> bool,
+{
+    let mut verification = Verification {
+        seq: 0,
+        head: [0u8; HEAD_SIZE],
+        checkpoints: 0,
+        checkpointed_seq: None,
+    };
+
+    // Heads which checkpoints may sign, and the number of entries they cover
+    let mut heads = BTreeMap::new();
+
+    for line in BufReader::new(journal).lines() {
+        let line = line?;
+        let seq = verification.seq;
+
+        let (body, recorded_head) = line.rsplit_once('\t').ok_or_else(|| {
+            format_err!(ErrorKind::ChainInvalid, "malformed journal entry #{}", seq)
+        })?;
+
+        let mut fields = body.split('\t');
+
+        ensure!(
+            fields.next() == Some(seq.to_string().as_str()),
+            ErrorKind::ChainInvalid,
+            "sequence number mismatch at journal entry #{}",
+            seq
+        );
+
+        if fields.nth(1) == Some("Checkpoint") {
+            let (key_id, signed_head, signature) = parse_checkpoint(fields).ok_or_else(|| {
+                format_err!(
+                    ErrorKind::ChainInvalid,
+                    "malformed checkpoint at journal entry #{}",
+                    seq
+                )
+            })?;
+
+            let covered = heads.get(&signed_head).copied();
+
+            ensure!(
+                covered.is_some(),
+                ErrorKind::ChainInvalid,
+                "checkpoint at journal entry #{} signs an unknown head",
+                seq
+            );
+
+            ensure!(
+                verify_signature(key_id, &signed_head, &signature),
+                ErrorKind::ChainInvalid,
+                "invalid checkpoint signature at journal entry #{}",
+                seq
+            );
+
+            verification.checkpoints += 1;
+            verification.checkpointed_seq = covered;
+        }
+
+        verification.head = chain(&verification.head, body);
+
+        ensure!(
+            recorded_head == encode_hex(&verification.head),
+            ErrorKind::ChainInvalid,
+            "hash chain mismatch at journal entry #{}",
+            seq
+        );
+
+        verification.seq += 1;
+        heads.insert(verification.head, verification.seq);
+    }
+
+    Ok(verification)
+}
+
+/// Parse the key ID, signed head and signature fields of a checkpoint
+fn parse_checkpoint<'a>(
+    mut fields: impl Iterator<Item = &'a str>,
+) -> Option<(object::Id, Head, Vec<u8>)> {
+    let key_id = object::Id::from_str_radix(fields.next()?.strip_prefix("0x")?, 16).ok()?;
+    let signed_head = decode_hex(fields.next()?)?.try_into().ok()?;
+    let signature = decode_hex(fields.next()?)?;
+
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some((key_id, signed_head, signature))
+}
+
+/// Compute the next head of the hash chain
+fn chain(head: &Head, body: &str) -> Head {
+    let mut hasher = Sha256::new();
+    hasher.update(head);
+    hasher.update(body.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Encode bytes as lower-case hexadecimal
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+
+    hex
+}
+
+/// Decode lower-case hexadecimal
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command,
+        test_util::{Buffer, FailingWriter},
+    };
+
+    fn operation(object_ids: Vec<object::Id>) -> Event {
+        Event::Operation {
+            command: command::Code::DeleteObject,
+            object_ids,
+            outcome: Outcome::Success,
+        }
+    }
+
+    #[test]
+    fn chain_roundtrip() {
+        let buffer = Buffer::default();
+        let mut journal = Journal::new(buffer.clone()).signer(Signer::Ed25519(1), 2);
+
+        assert!(journal.append(operation(vec![100])).unwrap().is_none());
+        let (signer, head) = journal.append(operation(vec![101])).unwrap().unwrap();
+        assert_eq!(signer, Signer::Ed25519(1));
+        journal.checkpoint(signer, head, vec![0u8; 64]).unwrap();
+
+        let output = buffer.contents();
+        assert_eq!(verify(output.as_slice()).unwrap(), (3, journal.head()));
+    }
+
+    /// Recompute the hash chain of a (rewritten) journal
+    fn rechain(journal: &str) -> String {
+        let mut head = [0u8; HEAD_SIZE];
+        let mut output = String::new();
+
+        for line in journal.lines() {
+            let body = line.rsplit_once('\t').unwrap().0;
+            head = chain(&head, body);
+            writeln!(output, "{}\t{}", body, encode_hex(&head)).unwrap();
+        }
+
+        output
+    }
+
+    #[test]
+    fn checkpoint_signatures_verified() {
+        use ed25519_dalek::{Signer as _, SigningKey, Verifier as _};
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let verify_signature = |key_id: object::Id, head: &Head, signature: &[u8]| {
+            key_id == 1
+                && ed25519_dalek::Signature::from_slice(signature)
+                    .is_ok_and(|sig| key.verifying_key().verify(head, &sig).is_ok())
+        };
+
+        let buffer = Buffer::default();
+        let mut journal = Journal::new(buffer.clone()).signer(Signer::Ed25519(1), 2);
+        journal.append(operation(vec![100])).unwrap();
+        let (signer, head) = journal.append(operation(vec![101])).unwrap().unwrap();
+        journal
+            .checkpoint(signer, head, key.sign(&head).to_vec())
+            .unwrap();
+        journal.append(operation(vec![102])).unwrap();
+
+        let output = String::from_utf8(buffer.contents()).unwrap();
+        let verification = verify_with(output.as_bytes(), verify_signature).unwrap();
+        assert_eq!(verification.seq, 4);
+        assert_eq!(verification.checkpoints, 1);
+        assert_eq!(verification.checkpointed_seq, Some(2));
+
+        // Rewrite an entry and recompute the chain, including the head signed
+        // by the checkpoint
+        let rewritten = rechain(&output.replacen("0x0064", "0x0066", 1));
+        let rewritten_head = rewritten
+            .lines()
+            .nth(1)
+            .unwrap()
+            .rsplit_once('\t')
+            .unwrap()
+            .1;
+        let rewritten = rechain(&rewritten.replacen(&encode_hex(&head), rewritten_head, 1));
+
+        assert!(verify(rewritten.as_bytes()).is_ok());
+        assert_eq!(
+            *verify_with(rewritten.as_bytes(), verify_signature)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ChainInvalid
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_verified() {
+        let buffer = Buffer::default();
+        let mut journal = Journal::new(buffer.clone());
+        journal.append(operation(vec![100])).unwrap();
+        journal.append(operation(vec![101])).unwrap();
+
+        // Drop the first entry and recompute the chain
+        let output = String::from_utf8(buffer.contents()).unwrap();
+        let truncated = rechain(output.split_once('\n').unwrap().1);
+
+        assert_eq!(
+            *verify(truncated.as_bytes()).unwrap_err().kind(),
+            ErrorKind::ChainInvalid
+        );
+    }
+
+    #[test]
+    fn failed_write_preserves_chain() {
+        let mut journal = Journal::new(FailingWriter);
+        assert!(journal.append(operation(vec![100])).is_err());
+        assert_eq!((journal.seq(), journal.head()), (0, [0u8; HEAD_SIZE]));
+    }
+
+    #[test]
+    fn tampering_detected() {
+        let buffer = Buffer::default();
+        let mut journal = Journal::new(buffer.clone());
+        journal.append(operation(vec![100])).unwrap();
+        journal.append(operation(vec![101])).unwrap();
+
+        let output = String::from_utf8(buffer.contents()).unwrap();
+        let tampered = output.replacen("0x0064", "0x0066", 1);
+        assert_eq!(
+            *verify(tampered.as_bytes()).unwrap_err().kind(),
+            ErrorKind::ChainInvalid
+        );
+    }
+}
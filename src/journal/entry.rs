@@ -0,0 +1,122 @@
+//! Journal entries
+
+use super::{encode_hex, Head};
+use crate::{command, object};
+use std::{
+    fmt::{self, Display},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Entries in the journal
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    /// Sequence number of this entry
+    pub seq: u64,
+
+    /// Time the entry was recorded (seconds since the UNIX epoch)
+    pub timestamp: u64,
+
+    /// Event recorded by this entry
+    pub event: Event,
+}
+
+impl Entry {
+    /// Create a new entry timestamped with the current time
+    pub(super) fn new(seq: u64, event: Event) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            seq,
+            timestamp,
+            event,
+        }
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.seq, self.timestamp, self.event)
+    }
+}
+
+/// Events recorded in the journal
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// A mutating command was sent to the HSM
+    Operation {
+        /// Command which was sent
+        command: command::Code,
+
+        /// Objects the command operated on
+        object_ids: Vec<object::Id>,
+
+        /// Result of the command
+        outcome: Outcome,
+    },
+
+    /// The journal head was signed by a key in the HSM
+    Checkpoint {
+        /// ID of the key which produced the signature
+        key_id: object::Id,
+
+        /// Journal head which was signed
+        signed_head: Head,
+
+        /// Signature over the journal head
+        signature: Vec<u8>,
+    },
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Operation {
+                command,
+                object_ids,
+                outcome,
+            } => {
+                let ids = object_ids
+                    .iter()
+                    .map(|id| format!("0x{id:04x}"))
+                    .collect::<Vec<_>>()
+                    .join(",");
+
+                write!(f, "{command:?}\t{ids}\t{outcome}")
+            }
+            Event::Checkpoint {
+                key_id,
+                signed_head,
+                signature,
+            } => write!(
+                f,
+                "Checkpoint\t0x{:04x}\t{}\t{}",
+                key_id,
+                encode_hex(signed_head),
+                encode_hex(signature)
+            ),
+        }
+    }
+}
+
+/// Outcome of a journaled operation
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// The command succeeded
+    Success,
+
+    /// The command failed with the given error
+    Failure(String),
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Success => f.write_str("ok"),
+            // Keep the entry on a single line with tab-separated fields
+            Outcome::Failure(msg) => write!(f, "error: {}", msg.replace(['\t', '\n'], " ")),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Journal errors
+
+use crate::error::{BoxError, Context};
+use std::io;
+use thiserror::Error;
+
+/// Journal-related errors
+pub type Error = crate::Error<ErrorKind>;
+
+/// Kinds of journal-related errors
+#[derive(Copy, Clone, Debug, Eq, Error, PartialEq)]
+pub enum ErrorKind {
+    /// Hash chain is broken (i.e. the journal has been tampered with)
+    #[error("invalid hash chain")]
+    ChainInvalid,
+
+    /// Input/output error
+    #[error("I/O error")]
+    IoError,
+
+    /// Error signing a checkpoint
+    #[error("checkpoint signing failed")]
+    SigningFailed,
+}
+
+impl ErrorKind {
+    /// Create an error context from this error
+    pub fn context(self, source: impl Into<BoxError>) -> Context<ErrorKind> {
+        Context::new(self, Some(source.into()))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        ErrorKind::IoError.context(err).into()
+    }
+}
@@ -57,6 +57,8 @@ mod serialization;
 
 pub mod algorithm;
 pub mod asymmetric;
+#[cfg(feature = "async")]
+pub mod asynch;
 pub mod attestation;
 pub mod audit;
 pub mod authentication;
@@ -70,6 +72,7 @@ pub mod ecdh;
 pub mod ecdsa;
 pub mod ed25519;
 pub mod hmac;
+pub mod journal;
 #[cfg(feature = "mockhsm")]
 pub(crate) mod mockhsm;
 pub mod object;
@@ -82,12 +85,14 @@ pub mod session;
 pub mod setup;
 pub mod ssh;
 pub mod template;
+#[cfg(test)]
+mod test_util;
 mod uuid;
 pub mod wrap;
 
 #[cfg(feature = "http")]
 pub use crate::connector::HttpConfig;
-#[cfg(feature = "usb")]
+#[cfg(any(feature = "usb", feature = "usb-nusb"))]
 pub use crate::connector::UsbConfig;
 
 pub use crate::{
@@ -11,7 +11,9 @@ mod command;
 mod connection;
 mod digest;
 mod error;
+mod oaep;
 mod object;
+mod otp;
 mod session;
 mod state;
 
@@ -42,6 +44,12 @@ impl MockHsm {
     pub fn new() -> Self {
         MockHsm(Arc::new(Mutex::new(State::new())))
     }
+
+    /// Expire all open sessions, as if they had been idle for longer than
+    /// the HSM's inactivity timeout
+    pub fn expire_sessions(&self) {
+        self.0.lock().unwrap().expire_sessions();
+    }
 }
 
 impl Connectable for MockHsm {
@@ -56,6 +64,26 @@ impl Connectable for MockHsm {
     }
 }
 
+#[cfg(feature = "async")]
+impl connector::AsyncConnectable for MockHsm {
+    /// Make a clone of this connectable as boxed trait object
+    fn box_clone(&self) -> Box<dyn connector::AsyncConnectable> {
+        Box::new(MockHsm(self.0.clone()))
+    }
+
+    /// Create a new connection with a clone of the MockHsm state
+    fn connect(
+        &self,
+    ) -> connector::BoxFuture<'_, Result<Box<dyn connector::AsyncConnection>, connector::Error>>
+    {
+        Box::pin(async move {
+            let connection: Box<dyn connector::AsyncConnection> =
+                Box::new(MockConnection::new(self));
+            Ok(connection)
+        })
+    }
+}
+
 impl Default for MockHsm {
     fn default() -> Self {
         Self::new()
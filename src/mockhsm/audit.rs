@@ -1,9 +1,18 @@
 //! (Partial) support for audit logging within the MockHsm
 //!
-//! No logging is performed and these settings are not yet enforced
+//! Commands sent over sessions are logged if auditing is enabled for them.
+//! When the log is full, the oldest entries are overwritten unless forced
+//! auditing is enabled, in which case audited commands fail until the log
+//! has been consumed.
 
-use crate::{audit::*, command, serialization::serialize};
-use std::collections::BTreeMap;
+use crate::{audit::*, command, object, response, serialization::serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Maximum number of entries in the audit log
+const LOG_CAPACITY: usize = 62;
+
+/// Key ID logged when no key was involved
+const NO_KEY: object::Id = 0xffff;
 
 /// Default per-command auditing options
 pub const DEFAULT_COMMAND_AUDIT_OPTIONS: &[AuditCommand] = &[
@@ -81,6 +90,82 @@ impl CommandAuditOptions {
     pub fn put(&mut self, command_type: command::Code, audit_option: AuditOption) {
         self.0.insert(command_type, audit_option);
     }
+
+    /// Should the given command be logged?
+    pub fn is_audited(&self, command_type: command::Code) -> bool {
+        self.0.get(&command_type) != Some(&AuditOption::Off)
+    }
+}
+
+/// Audit log of the commands performed by the MockHsm
+#[derive(Debug, Default)]
+pub(crate) struct AuditLog {
+    /// Entries which haven't been consumed via `SetLogIndex`
+    entries: VecDeque<LogEntry>,
+
+    /// Digest of the most recent entry
+    last_digest: LogDigest,
+
+    /// Item number of the most recent entry
+    last_item: u16,
+
+    /// Simulated tick count of the HSM's internal clock
+    tick: u32,
+}
+
+impl AuditLog {
+    /// Record a command performed using the given authentication key
+    pub fn record(
+        &mut self,
+        cmd: command::Code,
+        length: usize,
+        session_key: object::Id,
+        result: response::Code,
+    ) {
+        self.last_item = self.last_item.wrapping_add(1);
+        self.tick = self.tick.wrapping_add(1);
+
+        let mut entry = LogEntry {
+            item: self.last_item,
+            cmd,
+            length: length as u16,
+            session_key,
+            target_key: NO_KEY,
+            second_key: NO_KEY,
+            result,
+            tick: self.tick,
+            digest: LogDigest::default(),
+        };
+
+        entry.digest = entry.compute_digest(&self.last_digest);
+        self.last_digest = entry.digest.clone();
+
+        if self.entries.len() == LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+
+    /// Is the log full of unconsumed entries?
+    pub fn is_full(&self) -> bool {
+        self.entries.len() == LOG_CAPACITY
+    }
+
+    /// Get the unconsumed log entries
+    pub fn entries(&self) -> LogEntries {
+        LogEntries {
+            unlogged_boot_events: 0,
+            unlogged_auth_events: 0,
+            num_entries: self.entries.len() as u8,
+            entries: self.entries.iter().cloned().collect(),
+        }
+    }
+
+    /// Mark entries up to and including the given item number as consumed
+    pub fn set_index(&mut self, index: u16) {
+        self.entries.retain(|entry| entry.item > index);
+    }
 }
 
 impl Default for CommandAuditOptions {
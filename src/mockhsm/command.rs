@@ -1,23 +1,24 @@
 //! Commands supported by the `MockHsm`
 
-use super::{object::Payload, state::State, MOCK_SERIAL_NUMBER};
+use super::{oaep, object::Payload, otp::Token, state::State, MOCK_SERIAL_NUMBER};
 use crate::{
     algorithm::*,
     asymmetric::{self, commands::*, PublicKey},
+    attestation::{self, commands::*},
     audit::{commands::*, AuditCommand, AuditOption, AuditTag},
     authentication::{self, commands::*},
     command::{Code, Message},
     connector,
     device::{self, commands::*, SerialNumber, StorageInfo},
-    ecdh,
+    ecdh::{self, commands::*},
     ecdsa::{self, commands::*},
     ed25519::commands::*,
     hmac::{self, commands::*},
     object::{self, commands::*},
     opaque::{self, commands::*},
-    otp,
+    otp::{self, commands::*},
     response::{self, Response},
-    rsa::{self, pkcs1::commands::*, pss::commands::*},
+    rsa::{self, oaep::commands::*, pkcs1::commands::*, pss::commands::*},
     serialization::deserialize,
     session::{self, commands::*},
     template,
@@ -25,11 +26,18 @@ use crate::{
     Capability,
 };
 use ::ecdsa::{
-    elliptic_curve::{bigint::U256, generic_array::GenericArray, ops::Reduce, Field},
+    elliptic_curve::{
+        bigint::U256,
+        generic_array::GenericArray,
+        group::Curve,
+        ops::Reduce,
+        sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint},
+        AffinePoint, CurveArithmetic, Field, FieldBytesSize, SecretKey,
+    },
     hazmat::SignPrimitive,
 };
-use ::hmac::{Hmac, Mac};
-use ::rsa::{pkcs1v15, pss, RsaPrivateKey};
+use ::hmac::{digest::KeyInit, Hmac, Mac};
+use ::rsa::{pkcs1v15, pss, traits::PublicKeyParts, Pkcs1v15Encrypt, RsaPrivateKey};
 use digest::{
     const_oid::AssociatedOid, crypto_common::OutputSizeUser, typenum::Unsigned, Digest,
     FixedOutputReset,
@@ -41,8 +49,22 @@ use signature::{
     hazmat::{PrehashSigner, RandomizedPrehashSigner},
     Signer,
 };
-use std::{io::Cursor, str::FromStr};
+use std::{io::Cursor, str::FromStr, time::Duration};
 use subtle::ConstantTimeEq;
+use x509_cert::{
+    builder::{Builder, CertificateBuilder, Profile},
+    der::{asn1::BitString, Encode},
+    name::Name,
+    serial_number,
+    spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned},
+    time::Validity,
+};
+
+/// Object identifier for Ed25519 public keys (RFC 8410)
+const ED25519_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// Validity period of attestation certificates
+const ATTESTATION_VALIDITY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
 
 /// Create a new HSM session
 pub(crate) fn create_session(
@@ -93,21 +115,57 @@ pub(crate) fn session_message(
         )
     });
 
+    if state.get_session(session_id).is_err() {
+        debug!("no such session: {:?}", session_id);
+        return Ok(response::Message::new(response::Code::DeviceInvalidSession, vec![]).into());
+    }
+
     let command = state
         .get_session(session_id)?
         .decrypt_command(encrypted_command);
 
+    // With forced auditing, audited commands (other than those needed to
+    // consume the log) fail rather than overwriting unconsumed log entries
+    let audited = state.command_audit_options.is_audited(command.command_type);
+
+    if audited
+        && state.force_audit != AuditOption::Off
+        && state.audit_log.is_full()
+        && !matches!(
+            command.command_type,
+            Code::GetLogEntries | Code::SetLogIndex
+        )
+    {
+        return Ok(state
+            .get_session(session_id)?
+            .encrypt_response(device::ErrorKind::LogFull.into())
+            .into());
+    }
+
     let response = match command.command_type {
         Code::BlinkDevice => BlinkDeviceResponse {}.serialize(),
+        Code::ChangeAuthenticationKey => {
+            let session_key_id = state.get_session(session_id)?.authentication_key_id;
+            change_authentication_key(state, session_key_id, &command.data)
+        }
         Code::CloseSession => return close_session(state, session_id),
-        Code::DeleteObject => delete_object(state, &command.data),
+        Code::CreateOtpAead => create_otp_aead(state, &command.data),
+        Code::DecryptOaep => decrypt_oaep(state, &command.data),
+        Code::DecryptOtp => decrypt_otp(state, &command.data),
+        Code::DecryptPkcs1 => decrypt_pkcs1(state, &command.data),
+        Code::DeleteObject => {
+            let session_key_id = state.get_session(session_id)?.authentication_key_id;
+            delete_object(state, session_key_id, &command.data)
+        }
+        Code::DeriveEcdh => derive_ecdh(state, &command.data),
         Code::DeviceInfo => device_info(),
         Code::Echo => echo(&command.data),
         Code::ExportWrapped => export_wrapped(state, &command.data),
         Code::GenerateAsymmetricKey => gen_asymmetric_key(state, &command.data),
         Code::GenerateHmacKey => gen_hmac_key(state, &command.data),
+        Code::GenerateOtpAead => gen_otp_aead_key(state, &command.data),
         Code::GenerateWrapKey => gen_wrap_key(state, &command.data),
-        Code::GetLogEntries => get_log_entries(),
+        Code::GetLogEntries => get_log_entries(state),
         Code::GetObjectInfo => get_object_info(state, &command.data),
         Code::GetOpaqueObject => get_opaque(state, &command.data),
         Code::GetOption => get_option(state, &command.data),
@@ -120,10 +178,14 @@ pub(crate) fn session_message(
         Code::PutAuthenticationKey => put_authentication_key(state, &command.data),
         Code::PutHmacKey => put_hmac_key(state, &command.data),
         Code::PutOpaqueObject => put_opaque(state, &command.data),
+        Code::PutOtpAead => put_otp_aead_key(state, &command.data),
         Code::SetOption => put_option(state, &command.data),
         Code::PutWrapKey => put_wrap_key(state, &command.data),
+        Code::RandomizeOtpAead => randomize_otp_aead(state, &command.data),
         Code::ResetDevice => return Ok(reset_device(state, session_id)),
-        Code::SetLogIndex => SetLogIndexResponse {}.serialize(),
+        Code::RewrapOtpAead => rewrap_otp_aead(state, &command.data),
+        Code::SetLogIndex => set_log_index(state, &command.data),
+        Code::SignAttestationCertificate => sign_attestation_certificate(state, &command.data),
         Code::SignEcdsa => sign_ecdsa(state, &command.data),
         Code::SignEddsa => sign_eddsa(state, &command.data),
         Code::GetStorageInfo => get_storage_info(),
@@ -133,12 +195,66 @@ pub(crate) fn session_message(
         unsupported => panic!("unsupported command type: {unsupported:?}"),
     };
 
+    if audited {
+        let session_key = state.get_session(session_id)?.authentication_key_id;
+        state.audit_log.record(
+            command.command_type,
+            command.data.len(),
+            session_key,
+            response.code,
+        );
+    }
+
     Ok(state
         .get_session(session_id)?
         .encrypt_response(response)
         .into())
 }
 
+/// Replace the authentication key used to open the current session
+fn change_authentication_key(
+    state: &mut State,
+    session_key_id: object::Id,
+    cmd_data: &[u8],
+) -> response::Message {
+    let ChangeAuthenticationKeyCommand {
+        key_id,
+        algorithm,
+        authentication_key,
+    } = deserialize(cmd_data)
+        .unwrap_or_else(|e| panic!("error parsing Code::ChangeAuthenticationKey: {e:?}"));
+
+    if key_id != session_key_id {
+        debug!("not the session's authentication key: {:?}", key_id);
+        return device::ErrorKind::InvalidId.into();
+    }
+
+    if let Some(obj) = state
+        .objects
+        .get_mut(key_id, object::Type::AuthenticationKey)
+    {
+        if !obj
+            .object_info
+            .capabilities
+            .contains(Capability::CHANGE_AUTHENTICATION_KEY)
+        {
+            debug!("authentication key can't be changed: {:?}", key_id);
+            return device::ErrorKind::InsufficientPermissions.into();
+        }
+
+        if obj.algorithm() != Algorithm::Authentication(algorithm) {
+            debug!("wrong authentication key algorithm: {:?}", algorithm);
+            return device::ErrorKind::InvalidData.into();
+        }
+
+        obj.payload = Payload::AuthenticationKey(authentication_key);
+        ChangeAuthenticationKeyResponse { key_id }.serialize()
+    } else {
+        debug!("no such authentication key ID: {:?}", key_id);
+        device::ErrorKind::ObjectNotFound.into()
+    }
+}
+
 /// Close an active session
 fn close_session(state: &mut State, session_id: session::Id) -> Result<Vec<u8>, connector::Error> {
     let response = state
@@ -149,11 +265,185 @@ fn close_session(state: &mut State, session_id: session::Id) -> Result<Vec<u8>,
     Ok(response.into())
 }
 
+/// Create an OTP AEAD from a Yubico OTP token's key and private ID
+fn create_otp_aead(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: CreateOtpAeadCommand = deserialize(cmd_data)
+        .unwrap_or_else(|e| panic!("error parsing Code::CreateOtpAead: {e:?}"));
+
+    if let Some((algorithm, key_data)) = otp_aead_key(state, command.key_id) {
+        let token = Token {
+            key: command.key,
+            private_id: command.private_id,
+        };
+
+        CreateOtpAeadResponse(token.seal(algorithm, key_data)).serialize()
+    } else {
+        debug!("no such OTP AEAD key ID: {:?}", command.key_id);
+        device::ErrorKind::ObjectNotFound.into()
+    }
+}
+
+/// Decrypt data encrypted with RSA-OAEP
+fn decrypt_oaep(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: DecryptOaepCommand =
+        deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::DecryptOaep: {e:?}"));
+
+    if let Some(obj) = state
+        .objects
+        .get(command.key_id, object::Type::AsymmetricKey)
+    {
+        if let Payload::RsaKey(private_key) = &obj.payload {
+            // The label hash follows the ciphertext, which is as long as the
+            // modulus (and its hash algorithm may differ from MGF1's)
+            let mut ciphertext = command.data;
+            ciphertext.extend_from_slice(&command.label_hash);
+            let label_hash = ciphertext.split_off(private_key.size().min(ciphertext.len()));
+
+            match oaep::decrypt(private_key, command.mgf1_hash_alg, &label_hash, &ciphertext) {
+                Some(plaintext) => {
+                    DecryptOaepResponse(rsa::oaep::DecryptedData(plaintext)).serialize()
+                }
+                None => {
+                    debug!("RSA-OAEP decryption failed");
+                    device::ErrorKind::InvalidData.into()
+                }
+            }
+        } else {
+            debug!("not an Rsa key: {:?}", obj.algorithm());
+            device::ErrorKind::InvalidCommand.into()
+        }
+    } else {
+        debug!("no such object ID: {:?}", command.key_id);
+        device::ErrorKind::ObjectNotFound.into()
+    }
+}
+
+/// Decrypt a Yubico OTP using the AEAD of the token which generated it
+fn decrypt_otp(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: DecryptOtpCommand =
+        deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::DecryptOtp: {e:?}"));
+
+    let (algorithm, key_data) = match otp_aead_key(state, command.key_id) {
+        Some(key) => key,
+        None => {
+            debug!("no such OTP AEAD key ID: {:?}", command.key_id);
+            return device::ErrorKind::ObjectNotFound.into();
+        }
+    };
+
+    let token = match Token::open(algorithm, key_data, &command.aead) {
+        Some(token) => token,
+        None => {
+            debug!("AEAD not encrypted under key ID: {:?}", command.key_id);
+            return device::ErrorKind::InvalidData.into();
+        }
+    };
+
+    match token.decrypt_otp(&command.otp) {
+        Some(counters) => DecryptOtpResponse(counters).serialize(),
+        None => device::ErrorKind::InvalidOtp.into(),
+    }
+}
+
+/// Decrypt data encrypted with RSAES-PKCS#1v1.5
+fn decrypt_pkcs1(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: DecryptPkcs1Command =
+        deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::DecryptPkcs1: {e:?}"));
+
+    if let Some(obj) = state
+        .objects
+        .get(command.key_id, object::Type::AsymmetricKey)
+    {
+        if let Payload::RsaKey(private_key) = &obj.payload {
+            match private_key.decrypt(Pkcs1v15Encrypt, &command.data) {
+                Ok(plaintext) => {
+                    DecryptPkcs1Response(rsa::pkcs1::DecryptedData(plaintext)).serialize()
+                }
+                Err(e) => {
+                    debug!("RSA PKCS#1v1.5 decryption failed: {}", e);
+                    device::ErrorKind::InvalidData.into()
+                }
+            }
+        } else {
+            debug!("not an Rsa key: {:?}", obj.algorithm());
+            device::ErrorKind::InvalidCommand.into()
+        }
+    } else {
+        debug!("no such object ID: {:?}", command.key_id);
+        device::ErrorKind::ObjectNotFound.into()
+    }
+}
+
+/// Derive a shared secret via ECDH
+fn derive_ecdh(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: DeriveEcdhCommand =
+        deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::DeriveEcdh: {e:?}"));
+
+    if let Some(obj) = state
+        .objects
+        .get(command.key_id, object::Type::AsymmetricKey)
+    {
+        let public_key = command.public_key.as_slice();
+
+        let shared_secret = match &obj.payload {
+            Payload::EcdsaNistP256(secret_key) => ecdh_shared_secret(secret_key, public_key),
+            Payload::EcdsaNistP384(secret_key) => ecdh_shared_secret(secret_key, public_key),
+            Payload::EcdsaSecp256k1(secret_key) => ecdh_shared_secret(secret_key, public_key),
+            _ => {
+                debug!("not an EC key: {:?}", obj.algorithm());
+                return device::ErrorKind::InvalidCommand.into();
+            }
+        };
+
+        match shared_secret {
+            Some(secret) => DeriveEcdhResponse(ecdh::SharedSecret(secret)).serialize(),
+            None => {
+                debug!("invalid ECDH public key");
+                device::ErrorKind::InvalidData.into()
+            }
+        }
+    } else {
+        debug!("no such object ID: {:?}", command.key_id);
+        device::ErrorKind::ObjectNotFound.into()
+    }
+}
+
+/// Compute the x-coordinate of the ECDH shared point of the given secret key
+/// and SEC1-encoded public key (if it is a valid point on the curve)
+fn ecdh_shared_secret<C>(secret_key: &SecretKey<C>, public_key: &[u8]) -> Option<Vec<u8>>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let public_key = ::ecdsa::elliptic_curve::PublicKey::<C>::from_sec1_bytes(public_key).ok()?;
+    let shared_point = (public_key.to_projective() * *secret_key.to_nonzero_scalar()).to_affine();
+    Some(shared_point.to_encoded_point(false).x()?.to_vec())
+}
+
 /// Delete an object
-fn delete_object(state: &mut State, cmd_data: &[u8]) -> response::Message {
+fn delete_object(
+    state: &mut State,
+    session_key_id: object::Id,
+    cmd_data: &[u8],
+) -> response::Message {
     let command: DeleteObjectCommand =
         deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::DeleteObject: {e:?}"));
 
+    let permitted = state
+        .objects
+        .get(session_key_id, object::Type::AuthenticationKey)
+        .is_some_and(|key| {
+            key.object_info
+                .capabilities
+                .contains(command.object_type.delete_capability())
+        });
+
+    if !permitted {
+        debug!("can't delete {} objects", command.object_type);
+        return device::ErrorKind::InsufficientPermissions.into();
+    }
+
     if state
         .objects
         .remove(command.object_id, command.object_type)
@@ -299,6 +589,35 @@ fn gen_hmac_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
     .serialize()
 }
 
+/// Generate a new random OTP AEAD key
+fn gen_otp_aead_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
+    let GenOtpAeadKeyCommand { params, nonce_id } = deserialize(cmd_data)
+        .unwrap_or_else(|e| panic!("error parsing Code::GenerateOtpAead: {e:?}"));
+
+    state.objects.generate(
+        params.key_id,
+        object::Type::OtpAeadKey,
+        params.algorithm,
+        params.label,
+        params.capabilities,
+        Capability::default(),
+        params.domains,
+    );
+
+    if let Some(Payload::OtpAeadKey(_, data)) = state
+        .objects
+        .get_mut(params.key_id, object::Type::OtpAeadKey)
+        .map(|obj| &mut obj.payload)
+    {
+        data[..4].copy_from_slice(&nonce_id.to_be_bytes());
+    }
+
+    GenOtpAeadKeyResponse {
+        key_id: params.key_id,
+    }
+    .serialize()
+}
+
 /// Generate a new random wrap (i.e. AES-CCM) key
 fn gen_wrap_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
     let GenWrapKeyCommand {
@@ -322,16 +641,18 @@ fn gen_wrap_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
     .serialize()
 }
 
-/// Get mock log information
-fn get_log_entries() -> response::Message {
-    // TODO: mimic the YubiHSM's actual audit log
-    LogEntries {
-        unlogged_boot_events: 0,
-        unlogged_auth_events: 0,
-        num_entries: 0,
-        entries: vec![],
-    }
-    .serialize()
+/// Get the unconsumed entries in the audit log
+fn get_log_entries(state: &State) -> response::Message {
+    state.audit_log.entries().serialize()
+}
+
+/// Mark audit log entries as consumed
+fn set_log_index(state: &mut State, cmd_data: &[u8]) -> response::Message {
+    let command: SetLogIndexCommand =
+        deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::SetLogIndex: {e:?}"));
+
+    state.audit_log.set_index(command.log_index);
+    SetLogIndexResponse {}.serialize()
 }
 
 /// Get detailed info about a specific object
@@ -484,18 +805,19 @@ fn list_objects(state: &State, cmd_data: &[u8]) -> response::Message {
 
 /// Put an existing asymmetric key into the HSM
 fn put_asymmetric_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
-    let PutAsymmetricKeyCommand { params, data } = deserialize(cmd_data)
+    let command: PutAsymmetricKeyCommand = deserialize(cmd_data)
         .unwrap_or_else(|e| panic!("error parsing Code::PutAsymmetricKey: {e:?}"));
+    let params = &command.params;
 
     state.objects.put(
         params.id,
         object::Type::AsymmetricKey,
         params.algorithm,
-        params.label,
+        params.label.clone(),
         params.capabilities,
         Capability::default(),
         params.domains,
-        &data,
+        &command.data,
     );
 
     PutAsymmetricKeyResponse { key_id: params.id }.serialize()
@@ -518,7 +840,9 @@ fn put_authentication_key(state: &mut State, cmd_data: &[u8]) -> response::Messa
         params.capabilities,
         delegated_capabilities,
         params.domains,
-        &authentication_key.0,
+        authentication_key
+            .as_secret_slice()
+            .expect("deserialized keys are held in memory"),
     );
 
     PutAuthenticationKeyResponse { key_id: params.id }.serialize()
@@ -548,6 +872,11 @@ fn put_opaque(state: &mut State, cmd_data: &[u8]) -> response::Message {
     let PutOpaqueCommand { params, data } = deserialize(cmd_data)
         .unwrap_or_else(|e| panic!("error parsing Code::PutOpaqueObject: {e:?}"));
 
+    if params.algorithm.opaque().is_none() {
+        debug!("not an opaque algorithm: {:?}", params.algorithm);
+        return device::ErrorKind::InvalidData.into();
+    }
+
     state.objects.put(
         params.id,
         object::Type::Opaque,
@@ -593,6 +922,28 @@ fn put_option(state: &mut State, cmd_data: &[u8]) -> response::Message {
     PutOptionResponse {}.serialize()
 }
 
+/// Put an existing OTP AEAD key into the HSM
+fn put_otp_aead_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
+    let PutOtpAeadKeyCommand {
+        params,
+        nonce_id,
+        data,
+    } = deserialize(cmd_data).unwrap_or_else(|e| panic!("error parsing Code::PutOtpAead: {e:?}"));
+
+    state.objects.put(
+        params.id,
+        object::Type::OtpAeadKey,
+        params.algorithm,
+        params.label,
+        params.capabilities,
+        Capability::default(),
+        params.domains,
+        &[&nonce_id.to_be_bytes(), data.as_slice()].concat(),
+    );
+
+    PutOtpAeadKeyResponse { key_id: params.id }.serialize()
+}
+
 /// Put an existing wrap (i.e. AES-CCM) key into the HSM
 fn put_wrap_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
     let PutWrapKeyCommand {
@@ -615,6 +966,55 @@ fn put_wrap_key(state: &mut State, cmd_data: &[u8]) -> response::Message {
     PutWrapKeyResponse { key_id: params.id }.serialize()
 }
 
+/// Create an OTP AEAD from a random key and private ID
+fn randomize_otp_aead(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: RandomizeOtpAeadCommand = deserialize(cmd_data)
+        .unwrap_or_else(|e| panic!("error parsing Code::RandomizeOtpAead: {e:?}"));
+
+    if let Some((algorithm, key_data)) = otp_aead_key(state, command.key_id) {
+        RandomizeOtpAeadResponse(Token::generate().seal(algorithm, key_data)).serialize()
+    } else {
+        debug!("no such OTP AEAD key ID: {:?}", command.key_id);
+        device::ErrorKind::ObjectNotFound.into()
+    }
+}
+
+/// Re-encrypt an OTP AEAD from one OTP AEAD key to another
+fn rewrap_otp_aead(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: RewrapOtpAeadCommand = deserialize(cmd_data)
+        .unwrap_or_else(|e| panic!("error parsing Code::RewrapOtpAead: {e:?}"));
+
+    let (from_keys, to_keys) = match (
+        otp_aead_key(state, command.from_key_id),
+        otp_aead_key(state, command.to_key_id),
+    ) {
+        (Some(from_keys), Some(to_keys)) => (from_keys, to_keys),
+        _ => {
+            debug!(
+                "no such OTP AEAD key IDs: {:?}, {:?}",
+                command.from_key_id, command.to_key_id
+            );
+            return device::ErrorKind::ObjectNotFound.into();
+        }
+    };
+
+    match Token::open(from_keys.0, from_keys.1, &command.aead) {
+        Some(token) => RewrapOtpAeadResponse(token.seal(to_keys.0, to_keys.1)).serialize(),
+        None => {
+            debug!("AEAD not encrypted under key ID: {:?}", command.from_key_id);
+            device::ErrorKind::InvalidData.into()
+        }
+    }
+}
+
+/// Get the algorithm and data (nonce ID followed by the key) of an OTP AEAD key
+fn otp_aead_key(state: &State, key_id: object::Id) -> Option<(otp::Algorithm, &[u8])> {
+    match &state.objects.get(key_id, object::Type::OtpAeadKey)?.payload {
+        Payload::OtpAeadKey(algorithm, data) => Some((*algorithm, data)),
+        _ => None,
+    }
+}
+
 /// Reset the MockHsm back to its default state
 fn reset_device(state: &mut State, session_id: session::Id) -> Vec<u8> {
     let response = state
@@ -627,6 +1027,106 @@ fn reset_device(state: &mut State, session_id: session::Id) -> Vec<u8> {
     response
 }
 
+/// Sign an X.509 certificate attesting to an asymmetric key
+fn sign_attestation_certificate(state: &State, cmd_data: &[u8]) -> response::Message {
+    let command: SignAttestationCertificateCommand = deserialize(cmd_data)
+        .unwrap_or_else(|e| panic!("error parsing Code::SignAttestationCertificate: {e:?}"));
+
+    let subject_public_key = match state
+        .objects
+        .get(command.key_id, object::Type::AsymmetricKey)
+    {
+        Some(obj) => match subject_public_key_info(&obj.payload) {
+            Some(spki) => spki,
+            None => {
+                debug!("can't attest key: {:?}", obj.algorithm());
+                return device::ErrorKind::InvalidData.into();
+            }
+        },
+        None => {
+            debug!("no such object ID: {:?}", command.key_id);
+            return device::ErrorKind::ObjectNotFound.into();
+        }
+    };
+
+    let attestation_key = if command.attestation_key_id == 0 {
+        state.attestation_key.clone()
+    } else {
+        match state
+            .objects
+            .get(command.attestation_key_id, object::Type::AsymmetricKey)
+        {
+            Some(obj) => match &obj.payload {
+                Payload::EcdsaNistP256(secret_key) => p256::ecdsa::SigningKey::from(secret_key),
+                _ => {
+                    debug!("unsupported attestation key: {:?}", obj.algorithm());
+                    return device::ErrorKind::InvalidCommand.into();
+                }
+            },
+            None => {
+                debug!("no such object ID: {:?}", command.attestation_key_id);
+                return device::ErrorKind::ObjectNotFound.into();
+            }
+        }
+    };
+
+    let issuer = Name::from_str("CN=MockHsm Attestation").unwrap();
+    let subject = Name::from_str(&format!(
+        "CN=YubiHSM Attestation id:0x{:04x}",
+        command.key_id
+    ))
+    .unwrap();
+
+    // Positive random serial number
+    let mut serial_number = [0u8; 16];
+    OsRng.fill_bytes(&mut serial_number);
+    serial_number[0] &= 0x7f;
+
+    let certificate = CertificateBuilder::new(
+        Profile::Leaf {
+            issuer,
+            enable_key_agreement: false,
+            enable_key_encipherment: false,
+        },
+        serial_number::SerialNumber::new(&serial_number).unwrap(),
+        Validity::from_now(ATTESTATION_VALIDITY).unwrap(),
+        subject,
+        subject_public_key,
+        &attestation_key,
+    )
+    .and_then(|builder| builder.build::<p256::ecdsa::DerSignature>())
+    .expect("error building attestation certificate");
+
+    attestation::Certificate(certificate.to_der().unwrap()).serialize()
+}
+
+/// Get the X.509 `SubjectPublicKeyInfo` for an asymmetric key
+fn subject_public_key_info(payload: &Payload) -> Option<SubjectPublicKeyInfoOwned> {
+    match payload {
+        Payload::EcdsaNistP256(secret_key) => {
+            SubjectPublicKeyInfoOwned::from_key(secret_key.public_key()).ok()
+        }
+        Payload::EcdsaNistP384(secret_key) => {
+            SubjectPublicKeyInfoOwned::from_key(secret_key.public_key()).ok()
+        }
+        Payload::EcdsaSecp256k1(secret_key) => {
+            SubjectPublicKeyInfoOwned::from_key(secret_key.public_key()).ok()
+        }
+        Payload::RsaKey(private_key) => {
+            SubjectPublicKeyInfoOwned::from_key(private_key.to_public_key()).ok()
+        }
+        Payload::Ed25519Key(signing_key) => Some(SubjectPublicKeyInfoOwned {
+            algorithm: AlgorithmIdentifierOwned {
+                oid: ED25519_OID,
+                parameters: None,
+            },
+            subject_public_key: BitString::from_bytes(signing_key.verifying_key().as_bytes())
+                .ok()?,
+        }),
+        _ => None,
+    }
+}
+
 /// Sign a message using the ECDSA signature algorithm
 fn sign_ecdsa(state: &State, cmd_data: &[u8]) -> response::Message {
     let command: SignEcdsaCommand =
@@ -649,6 +1149,18 @@ fn sign_ecdsa(state: &State, cmd_data: &[u8]) -> response::Message {
 
                 SignEcdsaResponse(signature.to_der().as_ref().into()).serialize()
             }
+            Payload::EcdsaNistP384(secret_key) => {
+                let k = p384::Scalar::random(&mut OsRng);
+                let z = ::ecdsa::hazmat::bits2field::<p384::NistP384>(&command.digest)
+                    .expect("invalid digest length");
+                let signature = secret_key
+                    .to_nonzero_scalar()
+                    .try_sign_prehashed(k, &z)
+                    .expect("ECDSA failure!")
+                    .0;
+
+                SignEcdsaResponse(signature.to_der().as_ref().into()).serialize()
+            }
             Payload::EcdsaSecp256k1(secret_key) => {
                 let k = k256::Scalar::random(&mut OsRng);
                 let z = <k256::Scalar as Reduce<U256>>::reduce_bytes(GenericArray::from_slice(
@@ -703,11 +1215,7 @@ fn sign_hmac(state: &State, cmd_data: &[u8]) -> response::Message {
 
     if let Some(obj) = state.objects.get(command.key_id, object::Type::HmacKey) {
         if let Payload::HmacKey(alg, ref key) = obj.payload {
-            assert_eq!(alg, hmac::Algorithm::Sha256);
-            let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
-            mac.update(&command.data);
-            let tag = mac.finalize();
-            SignHmacResponse(hmac::Tag(tag.into_bytes().as_slice().into())).serialize()
+            SignHmacResponse(hmac::Tag(hmac_tag(alg, key, &command.data))).serialize()
         } else {
             debug!("not an HMAC key: {:?}", obj.algorithm());
             device::ErrorKind::InvalidCommand.into()
@@ -718,14 +1226,35 @@ fn sign_hmac(state: &State, cmd_data: &[u8]) -> response::Message {
     }
 }
 
+/// Compute the HMAC tag of a message using the given algorithm
+fn hmac_tag(algorithm: hmac::Algorithm, key: &[u8], msg: &[u8]) -> Vec<u8> {
+    fn compute<M: Mac + KeyInit>(key: &[u8], msg: &[u8]) -> Vec<u8> {
+        <M as Mac>::new_from_slice(key)
+            .unwrap()
+            .chain_update(msg)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+
+    match algorithm {
+        hmac::Algorithm::Sha1 => compute::<Hmac<Sha1>>(key, msg),
+        hmac::Algorithm::Sha256 => compute::<Hmac<Sha256>>(key, msg),
+        hmac::Algorithm::Sha384 => compute::<Hmac<Sha384>>(key, msg),
+        hmac::Algorithm::Sha512 => compute::<Hmac<Sha512>>(key, msg),
+    }
+}
+
 /// Sign a message using the RSASSA-PSS signature algorithm
 fn sign_pss(state: &State, cmd_data: &[u8]) -> response::Message {
     #[inline]
     fn sign_pss_digest<D: Digest + FixedOutputReset>(
         private_key: &RsaPrivateKey,
+        salt_len: u16,
         msg: &[u8],
     ) -> pss::Signature {
-        let signing_key = pss::SigningKey::<D>::new(private_key.clone());
+        let signing_key =
+            pss::SigningKey::<D>::new_with_salt_len(private_key.clone(), salt_len.into());
         signing_key
             .sign_prehash_with_rng(&mut OsRng, msg)
             .expect("unable to sign with prehash, wrong payload length?")
@@ -741,16 +1270,16 @@ fn sign_pss(state: &State, cmd_data: &[u8]) -> response::Message {
         if let Payload::RsaKey(private_key) = &obj.payload {
             let signature = match command.mgf1_hash_alg {
                 rsa::mgf::Algorithm::Sha1 => {
-                    sign_pss_digest::<Sha1>(private_key, command.digest.as_ref())
+                    sign_pss_digest::<Sha1>(private_key, command.salt_len, &command.digest)
                 }
                 rsa::mgf::Algorithm::Sha256 => {
-                    sign_pss_digest::<Sha256>(private_key, command.digest.as_ref())
+                    sign_pss_digest::<Sha256>(private_key, command.salt_len, &command.digest)
                 }
                 rsa::mgf::Algorithm::Sha384 => {
-                    sign_pss_digest::<Sha384>(private_key, command.digest.as_ref())
+                    sign_pss_digest::<Sha384>(private_key, command.salt_len, &command.digest)
                 }
                 rsa::mgf::Algorithm::Sha512 => {
-                    sign_pss_digest::<Sha512>(private_key, command.digest.as_ref())
+                    sign_pss_digest::<Sha512>(private_key, command.salt_len, &command.digest)
                 }
             };
 
@@ -823,15 +1352,13 @@ fn verify_hmac(state: &State, cmd_data: &[u8]) -> response::Message {
 
     if let Some(obj) = state.objects.get(command.key_id, object::Type::HmacKey) {
         if let Payload::HmacKey(alg, ref key) = obj.payload {
-            assert_eq!(alg, hmac::Algorithm::Sha256);
-
             // Because of a quirk of our serde parser everything winds up in the tag field
             let data = command.tag.into_vec();
 
-            let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
-            mac.update(&data[32..]);
-            let tag = mac.finalize().into_bytes();
-            let is_ok = tag.as_slice().ct_eq(&data[..32]).unwrap_u8();
+            // Tags are the size of the digest, which is also the key length
+            let (expected_tag, msg) = data.split_at(alg.key_len().min(data.len()));
+            let tag = hmac_tag(alg, key, msg);
+            let is_ok = tag.as_slice().ct_eq(expected_tag).unwrap_u8();
 
             VerifyHmacResponse(is_ok).serialize()
         } else {
@@ -39,3 +39,16 @@ impl Connection for MockConnection {
         .map(Message::from)
     }
 }
+
+/// The MockHsm runs in-process, so its responses are always ready
+#[cfg(feature = "async")]
+impl connector::AsyncConnection for MockConnection {
+    fn send_message(
+        &self,
+        uuid: Uuid,
+        message: Message,
+    ) -> connector::BoxFuture<'_, Result<Message, connector::Error>> {
+        let response = Connection::send_message(self, uuid, message);
+        Box::pin(async move { response })
+    }
+}
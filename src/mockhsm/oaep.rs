@@ -0,0 +1,81 @@
+//! RSA-OAEP decryption in the `MockHsm`
+//!
+//! The HSM is only given the hash of the OAEP label, which the `rsa` crate's
+//! OAEP implementation doesn't support, so the `MockHsm` decodes the OAEP
+//! padding itself. This implementation is not constant time and is only
+//! suitable for testing.
+
+use crate::rsa::mgf;
+use ::rsa::{hazmat::rsa_decrypt_and_check, traits::PublicKeyParts, BigUint, RsaPrivateKey};
+use digest::Digest;
+use rand_core::OsRng;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+
+/// Decrypt an RSA-OAEP ciphertext, returning `None` if decryption fails or
+/// the label hash doesn't match
+pub(crate) fn decrypt(
+    private_key: &RsaPrivateKey,
+    mgf1_algorithm: mgf::Algorithm,
+    label_hash: &[u8],
+    ciphertext: &[u8],
+) -> Option<Vec<u8>> {
+    let key_len = private_key.size();
+    let hash_len = label_hash.len();
+
+    if ciphertext.len() != key_len || key_len < 2 * hash_len + 2 {
+        return None;
+    }
+
+    let m = rsa_decrypt_and_check(
+        private_key,
+        Some(&mut OsRng),
+        &BigUint::from_bytes_be(ciphertext),
+    )
+    .ok()?;
+
+    let mut em = vec![0u8; key_len];
+    let m_bytes = m.to_bytes_be();
+    em[key_len - m_bytes.len()..].copy_from_slice(&m_bytes);
+
+    let (masked_seed, masked_db) = em[1..].split_at_mut(hash_len);
+    xor_mask(mgf1_algorithm, masked_db, masked_seed);
+    xor_mask(mgf1_algorithm, masked_seed, masked_db);
+
+    if em[0] != 0 || em[1 + hash_len..1 + 2 * hash_len] != *label_hash {
+        return None;
+    }
+
+    let db = &em[1 + 2 * hash_len..];
+    let separator = db.iter().position(|&b| b != 0)?;
+
+    if db[separator] != 0x01 {
+        return None;
+    }
+
+    Some(db[separator + 1..].to_vec())
+}
+
+/// XOR the MGF1 mask generated from `seed` into `output`
+fn xor_mask(algorithm: mgf::Algorithm, seed: &[u8], output: &mut [u8]) {
+    match algorithm {
+        mgf::Algorithm::Sha1 => mgf1_xor::<Sha1>(seed, output),
+        mgf::Algorithm::Sha256 => mgf1_xor::<Sha256>(seed, output),
+        mgf::Algorithm::Sha384 => mgf1_xor::<Sha384>(seed, output),
+        mgf::Algorithm::Sha512 => mgf1_xor::<Sha512>(seed, output),
+    }
+}
+
+/// MGF1 (RFC 8017 B.2.1) using the hash function `D`
+fn mgf1_xor<D: Digest>(seed: &[u8], output: &mut [u8]) {
+    for (counter, chunk) in output.chunks_mut(<D as Digest>::output_size()).enumerate() {
+        let mask = D::new()
+            .chain_update(seed)
+            .chain_update((counter as u32).to_be_bytes())
+            .finalize();
+
+        for (byte, mask_byte) in chunk.iter_mut().zip(mask) {
+            *byte ^= mask_byte;
+        }
+    }
+}
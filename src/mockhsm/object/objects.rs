@@ -170,6 +170,11 @@ impl Objects {
         self.0.get(&Handle::new(object_id, object_type))
     }
 
+    /// Get a mutable reference to an object
+    pub fn get_mut(&mut self, object_id: Id, object_type: Type) -> Option<&mut Object> {
+        self.0.get_mut(&Handle::new(object_id, object_type))
+    }
+
     /// Put a new object in the MockHsm
     pub fn put(
         &mut self,
@@ -246,6 +251,8 @@ impl Objects {
             );
         }
 
+        self.check_delegated_capabilities(wrap_key_id, object_to_wrap.object_info.capabilities)?;
+
         let mut object_info = object_to_wrap.object_info.clone();
 
         match object_info.origin {
@@ -280,6 +287,7 @@ impl Objects {
         wrap_key.decrypt_in_place(nonce, b"", &mut wrapped_data)?;
 
         let unwrapped_object: WrappedObject = deserialize(&wrapped_data).unwrap();
+        self.check_delegated_capabilities(wrap_key_id, unwrapped_object.object_info.capabilities)?;
 
         let payload = match unwrapped_object.object_info.algorithm {
             Algorithm::Asymmetric(alg) if alg.is_rsa() => Payload::new(
@@ -320,6 +328,30 @@ impl Objects {
         self.0.iter()
     }
 
+    /// Ensure objects with the given capabilities can be wrapped (or
+    /// unwrapped) under a wrap key, i.e. their capabilities are a subset of
+    /// the wrap key's delegated capabilities
+    fn check_delegated_capabilities(
+        &self,
+        wrap_key_id: Id,
+        capabilities: Capability,
+    ) -> Result<(), Error> {
+        let delegated_capabilities = self
+            .get(wrap_key_id, Type::WrapKey)
+            .map(|wrap_key| wrap_key.object_info.delegated_capabilities)
+            .unwrap_or_default();
+
+        ensure!(
+            delegated_capabilities.contains(capabilities),
+            ErrorKind::AccessDenied,
+            "capabilities {:?} not delegated by wrap key {:?}",
+            capabilities,
+            wrap_key_id
+        );
+
+        Ok(())
+    }
+
     /// Get a wrapping key
     fn get_wrap_key(&self, wrap_key_id: Id) -> Result<AesCcmKey, Error> {
         let wrap_key = match self.get(wrap_key_id, Type::WrapKey) {
@@ -1,7 +1,10 @@
 //! Object "payloads" in the MockHsm are instances of software implementations
 //! of supported cryptographic primitives, already initialized with a private key
 
-use crate::{algorithm::Algorithm, asymmetric, authentication, hmac, opaque, wrap};
+use crate::{
+    algorithm::Algorithm, asymmetric, authentication, hmac, mockhsm::otp::NONCE_ID_SIZE, opaque,
+    otp, wrap,
+};
 use ecdsa::elliptic_curve::sec1::ToEncodedPoint;
 use ed25519_dalek as ed25519;
 use num_traits::cast::FromPrimitive;
@@ -17,6 +20,9 @@ pub(crate) enum Payload {
     /// ECDSA/P-256 signing key
     EcdsaNistP256(p256::SecretKey),
 
+    /// ECDSA/P-384 signing key
+    EcdsaNistP384(p384::SecretKey),
+
     /// ECDSA/secp256k1 signing key,
     EcdsaSecp256k1(k256::SecretKey),
 
@@ -32,6 +38,9 @@ pub(crate) enum Payload {
     /// Opaque data
     Opaque(opaque::Algorithm, Vec<u8>),
 
+    /// OTP AEAD key (nonce ID followed by the key itself)
+    OtpAeadKey(otp::Algorithm, Vec<u8>),
+
     /// Wrapping (i.e. symmetric encryption keys)
     WrapKey(wrap::Algorithm, Vec<u8>),
 }
@@ -46,6 +55,10 @@ impl Payload {
                     assert_eq!(data.len(), 32);
                     Payload::EcdsaNistP256(p256::SecretKey::from_slice(data).unwrap())
                 }
+                asymmetric::Algorithm::EcP384 => {
+                    assert_eq!(data.len(), 48);
+                    Payload::EcdsaNistP384(p384::SecretKey::from_slice(data).unwrap())
+                }
                 asymmetric::Algorithm::EcK256 => {
                     assert_eq!(data.len(), 32);
                     Payload::EcdsaSecp256k1(k256::SecretKey::from_slice(data).unwrap())
@@ -71,6 +84,10 @@ impl Payload {
             },
             Algorithm::Hmac(alg) => Payload::HmacKey(alg, data.into()),
             Algorithm::Opaque(alg) => Payload::Opaque(alg, data.into()),
+            Algorithm::YubicoOtp(alg) => {
+                assert_eq!(data.len(), NONCE_ID_SIZE + alg.key_len());
+                Payload::OtpAeadKey(alg, data.into())
+            }
             Algorithm::Authentication(_) => {
                 Payload::AuthenticationKey(authentication::Key::from_slice(data).unwrap())
             }
@@ -97,6 +114,9 @@ impl Payload {
                 asymmetric::Algorithm::EcP256 => {
                     Payload::EcdsaNistP256(p256::SecretKey::random(&mut OsRng))
                 }
+                asymmetric::Algorithm::EcP384 => {
+                    Payload::EcdsaNistP384(p384::SecretKey::random(&mut OsRng))
+                }
                 asymmetric::Algorithm::EcK256 => {
                     Payload::EcdsaSecp256k1(k256::SecretKey::random(&mut OsRng))
                 }
@@ -115,6 +135,11 @@ impl Payload {
                 OsRng.fill_bytes(&mut bytes);
                 Payload::HmacKey(hmac_alg, bytes)
             }
+            Algorithm::YubicoOtp(otp_alg) => {
+                let mut bytes = vec![0u8; NONCE_ID_SIZE + otp_alg.key_len()];
+                OsRng.fill_bytes(&mut bytes);
+                Payload::OtpAeadKey(otp_alg, bytes)
+            }
             _ => panic!("MockHsm does not support generating {algorithm:?} objects"),
         }
     }
@@ -126,6 +151,7 @@ impl Payload {
                 Algorithm::Authentication(authentication::Algorithm::YubicoAes)
             }
             Payload::EcdsaNistP256(_) => Algorithm::Asymmetric(asymmetric::Algorithm::EcP256),
+            Payload::EcdsaNistP384(_) => Algorithm::Asymmetric(asymmetric::Algorithm::EcP384),
             Payload::EcdsaSecp256k1(_) => Algorithm::Asymmetric(asymmetric::Algorithm::EcK256),
             Payload::Ed25519Key(_) => Algorithm::Asymmetric(asymmetric::Algorithm::Ed25519),
             Payload::RsaKey(ref k) => match k.size() {
@@ -136,6 +162,7 @@ impl Payload {
             },
             Payload::HmacKey(alg, _) => alg.into(),
             Payload::Opaque(alg, _) => alg.into(),
+            Payload::OtpAeadKey(alg, _) => alg.into(),
             Payload::WrapKey(alg, _) => alg.into(),
         }
     }
@@ -143,12 +170,14 @@ impl Payload {
     /// Get the length of the object
     pub fn len(&self) -> u16 {
         let l = match self {
-            Payload::AuthenticationKey(_) => authentication::key::SIZE,
+            Payload::AuthenticationKey(k) => k.size(),
             Payload::EcdsaNistP256(_) | Payload::EcdsaSecp256k1(_) => 32,
+            Payload::EcdsaNistP384(_) => 48,
             Payload::Ed25519Key(_) => ed25519::SECRET_KEY_LENGTH,
             Payload::RsaKey(k) => k.size(),
             Payload::HmacKey(_, ref data) => data.len(),
             Payload::Opaque(_, ref data) => data.len(),
+            Payload::OtpAeadKey(_, ref data) => data.len(),
             Payload::WrapKey(_, ref data) => data.len(),
         };
         l as u16
@@ -160,6 +189,9 @@ impl Payload {
             Payload::EcdsaNistP256(secret_key) => {
                 Some(secret_key.public_key().to_encoded_point(false).as_bytes()[1..].into())
             }
+            Payload::EcdsaNistP384(secret_key) => {
+                Some(secret_key.public_key().to_encoded_point(false).as_bytes()[1..].into())
+            }
             Payload::EcdsaSecp256k1(secret_key) => {
                 Some(secret_key.public_key().to_encoded_point(false).as_bytes()[1..].into())
             }
@@ -180,10 +212,14 @@ impl Payload {
     /// Serialize this payload as a byte vector
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
-            Payload::AuthenticationKey(k) => k.0.as_ref().into(),
+            Payload::AuthenticationKey(k) => k
+                .as_secret_slice()
+                .expect("mock HSM keys are held in memory")
+                .to_vec(),
             Payload::EcdsaNistP256(k) => k.to_bytes().to_vec(),
+            Payload::EcdsaNistP384(k) => k.to_bytes().to_vec(),
             Payload::EcdsaSecp256k1(k) => k.to_bytes().to_vec(),
-            Payload::Ed25519Key(k) => k.verifying_key().to_bytes().into(),
+            Payload::Ed25519Key(k) => k.to_bytes().into(),
             Payload::RsaKey(k) => {
                 use rsa::traits::PrivateKeyParts;
                 let mut out = Vec::new();
@@ -214,6 +250,7 @@ impl Payload {
             }
             Payload::HmacKey(_, data) => data.clone(),
             Payload::Opaque(_, data) => data.clone(),
+            Payload::OtpAeadKey(_, data) => data.clone(),
             Payload::WrapKey(_, data) => data.clone(),
         }
     }
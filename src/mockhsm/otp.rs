@@ -0,0 +1,186 @@
+//! Yubico OTP AEADs and OTP decryption in the `MockHsm`
+//!
+//! The `MockHsm`'s AEADs consist of a 6-byte nonce (the OTP AEAD key's nonce
+//! ID followed by 2 random bytes), the token's key and private ID encrypted
+//! with AES-CCM, and an 8-byte MAC. They're only meant to round trip through
+//! the `MockHsm`, and aren't compatible with AEADs created by a real device.
+
+use crate::otp;
+use aes::cipher::{
+    consts::{U13, U8},
+    BlockDecrypt,
+};
+use ccm::aead::{AeadCore, AeadInPlace, KeyInit};
+use rand_core::{OsRng, RngCore};
+
+/// Size of the nonce ID of an OTP AEAD key (stored before the key itself)
+pub(crate) const NONCE_ID_SIZE: usize = 4;
+
+/// Size of the nonce of an AEAD
+const NONCE_SIZE: usize = 6;
+
+/// Size of the MAC of an AEAD
+const TAG_SIZE: usize = 8;
+
+/// AES-CCM with a 128-bit key and 8-byte MAC
+type Aes128Ccm = ccm::Ccm<aes::Aes128, U8, U13>;
+
+/// AES-CCM with a 192-bit key and 8-byte MAC
+type Aes192Ccm = ccm::Ccm<aes::Aes192, U8, U13>;
+
+/// AES-CCM with a 256-bit key and 8-byte MAC
+type Aes256Ccm = ccm::Ccm<aes::Aes256, U8, U13>;
+
+/// CRC16 of a valid OTP (including its own checksum)
+const CRC_RESIDUAL: u16 = 0xf0b8;
+
+/// Key and private ID of a Yubico OTP token (i.e. the plaintext of an AEAD)
+pub(crate) struct Token {
+    /// AES key of the token
+    pub key: [u8; otp::KEY_SIZE],
+
+    /// Private ID of the token
+    pub private_id: [u8; otp::PRIVATE_ID_SIZE],
+}
+
+impl Token {
+    /// Generate a token with a random key and private ID
+    pub fn generate() -> Self {
+        let mut token = Token {
+            key: [0u8; otp::KEY_SIZE],
+            private_id: [0u8; otp::PRIVATE_ID_SIZE],
+        };
+
+        OsRng.fill_bytes(&mut token.key);
+        OsRng.fill_bytes(&mut token.private_id);
+        token
+    }
+
+    /// Encrypt this token as an AEAD under the given OTP AEAD key (i.e. the
+    /// key's nonce ID followed by the key itself)
+    pub fn seal(&self, algorithm: otp::Algorithm, key_data: &[u8]) -> otp::Aead {
+        let (nonce_id, key) = key_data.split_at(NONCE_ID_SIZE);
+
+        let mut aead = [0u8; otp::AEAD_SIZE];
+        let (nonce, rest) = aead.split_at_mut(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at_mut(otp::KEY_SIZE + otp::PRIVATE_ID_SIZE);
+
+        nonce[..NONCE_ID_SIZE].copy_from_slice(nonce_id);
+        OsRng.fill_bytes(&mut nonce[NONCE_ID_SIZE..]);
+        ciphertext[..otp::KEY_SIZE].copy_from_slice(&self.key);
+        ciphertext[otp::KEY_SIZE..].copy_from_slice(&self.private_id);
+
+        let ccm_nonce = ccm_nonce(nonce);
+        let result = match algorithm {
+            otp::Algorithm::Aes128 => encrypt::<Aes128Ccm>(key, &ccm_nonce, ciphertext),
+            otp::Algorithm::Aes192 => encrypt::<Aes192Ccm>(key, &ccm_nonce, ciphertext),
+            otp::Algorithm::Aes256 => encrypt::<Aes256Ccm>(key, &ccm_nonce, ciphertext),
+        };
+
+        tag.copy_from_slice(&result);
+        otp::Aead(aead)
+    }
+
+    /// Decrypt an AEAD encrypted under the given OTP AEAD key, returning
+    /// `None` if it wasn't encrypted under this key
+    pub fn open(algorithm: otp::Algorithm, key_data: &[u8], aead: &otp::Aead) -> Option<Self> {
+        let key = &key_data[NONCE_ID_SIZE..];
+        let (nonce, rest) = aead.0.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(otp::KEY_SIZE + otp::PRIVATE_ID_SIZE);
+
+        let mut plaintext = [0u8; otp::KEY_SIZE + otp::PRIVATE_ID_SIZE];
+        plaintext.copy_from_slice(ciphertext);
+
+        let ccm_nonce = ccm_nonce(nonce);
+        let valid = match algorithm {
+            otp::Algorithm::Aes128 => decrypt::<Aes128Ccm>(key, &ccm_nonce, &mut plaintext, tag),
+            otp::Algorithm::Aes192 => decrypt::<Aes192Ccm>(key, &ccm_nonce, &mut plaintext, tag),
+            otp::Algorithm::Aes256 => decrypt::<Aes256Ccm>(key, &ccm_nonce, &mut plaintext, tag),
+        };
+
+        if !valid {
+            return None;
+        }
+
+        let mut token = Token {
+            key: [0u8; otp::KEY_SIZE],
+            private_id: [0u8; otp::PRIVATE_ID_SIZE],
+        };
+
+        token.key.copy_from_slice(&plaintext[..otp::KEY_SIZE]);
+        token
+            .private_id
+            .copy_from_slice(&plaintext[otp::KEY_SIZE..]);
+        Some(token)
+    }
+
+    /// Decrypt an OTP generated by this token, returning `None` if it's
+    /// invalid (i.e. wasn't generated by this token)
+    pub fn decrypt_otp(&self, otp: &[u8; otp::OTP_SIZE]) -> Option<otp::Counters> {
+        let cipher = aes::Aes128::new(&self.key.into());
+        let mut block = (*otp).into();
+        cipher.decrypt_block(&mut block);
+
+        if block[..otp::PRIVATE_ID_SIZE] != self.private_id || crc16(&block) != CRC_RESIDUAL {
+            return None;
+        }
+
+        Some(otp::Counters {
+            use_counter: u16::from_le_bytes([block[6], block[7]]),
+            timestamp_low: u16::from_le_bytes([block[8], block[9]]),
+            timestamp_high: block[10],
+            session_counter: block[11],
+        })
+    }
+}
+
+/// Expand an AEAD's nonce into an AES-CCM nonce
+fn ccm_nonce(nonce: &[u8]) -> [u8; 13] {
+    let mut ccm_nonce = [0u8; 13];
+    ccm_nonce[..NONCE_SIZE].copy_from_slice(nonce);
+    ccm_nonce
+}
+
+/// Encrypt the given buffer in place with AES-CCM, returning the MAC
+fn encrypt<C>(key: &[u8], nonce: &[u8; 13], buffer: &mut [u8]) -> [u8; TAG_SIZE]
+where
+    C: KeyInit + AeadInPlace + AeadCore<NonceSize = U13, TagSize = U8>,
+{
+    C::new_from_slice(key)
+        .expect("invalid OTP AEAD key")
+        .encrypt_in_place_detached(nonce.into(), &[], buffer)
+        .expect("error encrypting OTP AEAD")
+        .into()
+}
+
+/// Decrypt the given buffer in place with AES-CCM, returning whether the MAC
+/// was valid
+fn decrypt<C>(key: &[u8], nonce: &[u8; 13], buffer: &mut [u8], tag: &[u8]) -> bool
+where
+    C: KeyInit + AeadInPlace + AeadCore<NonceSize = U13, TagSize = U8>,
+{
+    C::new_from_slice(key)
+        .expect("invalid OTP AEAD key")
+        .decrypt_in_place_detached(nonce.into(), &[], buffer, tag.into())
+        .is_ok()
+}
+
+/// CRC16 (ISO 13239) as used by Yubico OTPs
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+
+    for &byte in data {
+        crc ^= u16::from(byte);
+
+        for _ in 0..8 {
+            let carry = crc & 1 != 0;
+            crc >>= 1;
+
+            if carry {
+                crc ^= 0x8408;
+            }
+        }
+    }
+
+    crc
+}
@@ -3,7 +3,7 @@
 use std::fmt::{self, Debug};
 
 use crate::{
-    command, response,
+    command, object, response,
     session::{
         securechannel::{Challenge, Cryptogram, SecureChannel},
         Id,
@@ -18,16 +18,25 @@ pub(crate) struct HsmSession {
     /// Card challenge for this session
     pub card_challenge: Challenge,
 
+    /// Authentication key this session was opened with
+    pub authentication_key_id: object::Id,
+
     /// Encrypted channel
     pub channel: SecureChannel,
 }
 
 impl HsmSession {
     /// Create a new session
-    pub fn new(id: Id, card_challenge: Challenge, channel: SecureChannel) -> Self {
+    pub fn new(
+        id: Id,
+        card_challenge: Challenge,
+        authentication_key_id: object::Id,
+        channel: SecureChannel,
+    ) -> Self {
         Self {
             id,
             card_challenge,
+            authentication_key_id,
             channel,
         }
     }
@@ -1,16 +1,22 @@
 //! `MockHsm` presents a thread-safe API by locking interior mutable state,
 //! contained in the `State` struct defined in this module.
 
-use super::{audit::CommandAuditOptions, object::Objects, session::HsmSession};
+use super::{
+    audit::{AuditLog, CommandAuditOptions},
+    object::Objects,
+    session::HsmSession,
+};
 use crate::{
     audit::AuditOption,
     connector, object,
     session::{
         self,
         securechannel::{Challenge, SecureChannel},
+        RustCryptoBackend,
     },
 };
-use std::collections::BTreeMap;
+use rand_core::OsRng;
+use std::{collections::BTreeMap, sync::Arc};
 
 /// Mutable interior state of the `MockHsm`
 #[derive(Debug)]
@@ -25,6 +31,13 @@ pub(crate) struct State {
     /// Fips mode
     pub(super) fips: AuditOption,
 
+    /// Audit log of commands performed over sessions
+    pub(super) audit_log: AuditLog,
+
+    /// Device attestation key, used to sign attestation certificates when no
+    /// other attestation key is given (survives resets, like on a real device)
+    pub(super) attestation_key: p256::ecdsa::SigningKey,
+
     /// Active sessions with the MockHsm
     sessions: BTreeMap<session::Id, HsmSession>,
 
@@ -39,6 +52,8 @@ impl State {
             command_audit_options: CommandAuditOptions::default(),
             force_audit: AuditOption::Off,
             fips: AuditOption::Off,
+            audit_log: AuditLog::default(),
+            attestation_key: p256::ecdsa::SigningKey::random(&mut OsRng),
             sessions: BTreeMap::new(),
             objects: Objects::default(),
         }
@@ -76,10 +91,11 @@ impl State {
                     .expect("auth key payload"),
                 host_challenge,
                 card_challenge,
+                Arc::new(RustCryptoBackend),
             )
         };
 
-        let session = HsmSession::new(session_id, card_challenge, channel);
+        let session = HsmSession::new(session_id, card_challenge, authentication_key_id, channel);
         assert!(self.sessions.insert(session_id, session).is_none());
 
         self.get_session(session_id).unwrap()
@@ -102,9 +118,15 @@ impl State {
         assert!(self.sessions.remove(&id).is_some());
     }
 
+    /// Forget all active sessions, as if they had timed out
+    pub fn expire_sessions(&mut self) {
+        self.sessions = BTreeMap::new();
+    }
+
     /// Reset the internal HSM state, closing all connections
     pub fn reset(&mut self) {
         self.command_audit_options = CommandAuditOptions::default();
+        self.audit_log = AuditLog::default();
         self.sessions = BTreeMap::new();
         self.objects = Objects::default();
     }
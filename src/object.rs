@@ -4,6 +4,7 @@
 //! <https://developers.yubico.com/YubiHSM2/Concepts/Object.html>
 
 pub(crate) mod commands;
+mod deletion;
 mod entry;
 mod error;
 mod filter;
@@ -16,6 +17,7 @@ pub mod put;
 mod types;
 
 pub use self::{
+    deletion::Deletion,
     entry::Entry,
     error::{Error, ErrorKind},
     filter::Filter,
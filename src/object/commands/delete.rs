@@ -21,6 +21,10 @@ pub(crate) struct DeleteObjectCommand {
 
 impl Command for DeleteObjectCommand {
     type ResponseType = DeleteObjectResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.object_id]
+    }
 }
 
 /// Response from `command::delete_object`
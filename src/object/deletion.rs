@@ -0,0 +1,24 @@
+//! Outcomes of deleting objects
+
+/// Outcome of deleting an object with [`Client::delete_object`].
+///
+/// Other failures, such as the authentication key lacking the capability to
+/// delete objects of the given type, are returned as errors.
+///
+/// [`Client::delete_object`]: crate::Client::delete_object
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Deletion {
+    /// The object was deleted
+    Deleted,
+
+    /// No object with the given ID and type exists (e.g. it was already
+    /// deleted), or it isn't in any of the session's domains
+    NotFound,
+}
+
+impl Deletion {
+    /// Was the object deleted?
+    pub fn is_deleted(self) -> bool {
+        self == Deletion::Deleted
+    }
+}
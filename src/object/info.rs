@@ -5,7 +5,7 @@ use crate::{object, Algorithm, Capability, Domain};
 use serde::{Deserialize, Serialize};
 
 /// Information about an object
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Info {
     /// Capabilities (bitfield)
     pub capabilities: Capability,
@@ -38,3 +38,10 @@ pub struct Info {
     /// Delegated Capabilities (bitfield)
     pub delegated_capabilities: Capability,
 }
+
+impl Info {
+    /// Get the handle (ID and type) identifying this object
+    pub fn handle(&self) -> object::Handle {
+        object::Handle::new(self.object_id, self.object_type)
+    }
+}
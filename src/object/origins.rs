@@ -36,6 +36,28 @@ impl Origin {
     pub fn to_u8(self) -> u8 {
         self as u8
     }
+
+    /// Was the object originally generated on a device (as opposed to
+    /// being imported from the host)?
+    pub fn is_generated(self) -> bool {
+        matches!(self, Origin::Generated | Origin::WrappedGenerated)
+    }
+
+    /// Was the object imported under wrap (i.e. with Import Wrapped)?
+    pub fn is_wrapped(self) -> bool {
+        matches!(self, Origin::WrappedGenerated | Origin::WrappedImported)
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Origin::Generated => "generated",
+            Origin::Imported => "imported",
+            Origin::WrappedGenerated => "generated:imported_wrapped",
+            Origin::WrappedImported => "imported:imported_wrapped",
+        })
+    }
 }
 
 impl Serialize for Origin {
@@ -58,7 +80,7 @@ impl<'de> Deserialize<'de> for Origin {
             type Value = Origin;
 
             fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-                formatter.write_str("an unsigned byte between 0x01 and 0x07")
+                formatter.write_str("an object origin byte (0x01, 0x02, 0x11 or 0x12)")
             }
 
             fn visit_u8<E>(self, value: u8) -> Result<Origin, E>
@@ -1,6 +1,7 @@
 //! Types of objects
 
 use super::{Error, ErrorKind};
+use crate::Capability;
 use serde::{de, ser, Deserialize, Serialize};
 use std::{fmt, str::FromStr};
 
@@ -48,6 +49,19 @@ impl Type {
     pub fn to_u8(self) -> u8 {
         self as u8
     }
+
+    /// Capability needed to delete objects of this type
+    pub fn delete_capability(self) -> Capability {
+        match self {
+            Type::Opaque => Capability::DELETE_OPAQUE,
+            Type::AuthenticationKey => Capability::DELETE_AUTHENTICATION_KEY,
+            Type::AsymmetricKey => Capability::DELETE_ASYMMETRIC_KEY,
+            Type::WrapKey => Capability::DELETE_WRAP_KEY,
+            Type::HmacKey => Capability::DELETE_HMAC_KEY,
+            Type::Template => Capability::DELETE_TEMPLATE,
+            Type::OtpAeadKey => Capability::DELETE_OTP_AEAD_KEY,
+        }
+    }
 }
 
 impl fmt::Display for Type {
@@ -21,6 +21,10 @@ pub(crate) struct PutOpaqueCommand {
 
 impl Command for PutOpaqueCommand {
     type ResponseType = PutOpaqueResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.id]
+    }
 }
 
 /// Response from `command::put_opaque`
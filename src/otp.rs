@@ -1,6 +1,26 @@
 //! Yubico One Time Password (OTP) functionality
+//!
+//! OTP AEAD keys encrypt the AES keys and private IDs of Yubico OTP tokens
+//! into AEADs which can be stored outside the HSM, e.g. in the database of a
+//! Yubico OTP validation (KSM) service. OTPs can then be decrypted within the
+//! HSM given the AEAD for the token which generated them.
 
+mod aead;
 mod algorithm;
 pub(crate) mod commands;
+mod counters;
 
-pub use self::algorithm::Algorithm;
+pub use self::{
+    aead::{Aead, AEAD_SIZE},
+    algorithm::Algorithm,
+    counters::Counters,
+};
+
+/// Size of the AES-128 key of a Yubico OTP token
+pub const KEY_SIZE: usize = 16;
+
+/// Size of the private ID of a Yubico OTP token
+pub const PRIVATE_ID_SIZE: usize = 6;
+
+/// Size of a (modhex decoded) Yubico OTP
+pub const OTP_SIZE: usize = 16;
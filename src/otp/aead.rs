@@ -0,0 +1,46 @@
+//! AEADs containing the AES key and private ID of a Yubico OTP token,
+//! encrypted under an OTP AEAD key
+
+/// Size of an OTP AEAD: a 6-byte nonce, the encrypted key and private ID,
+/// and an 8-byte MAC
+pub const AEAD_SIZE: usize = 36;
+
+/// OTP AEAD (i.e. an encrypted Yubico OTP token key and private ID)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Aead(pub [u8; AEAD_SIZE]);
+
+impl Aead {
+    /// Get the AEAD as a byte slice
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Aead {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; AEAD_SIZE]> for Aead {
+    fn from(bytes: [u8; AEAD_SIZE]) -> Aead {
+        Aead(bytes)
+    }
+}
+
+impl From<&[u8]> for Aead {
+    fn from(bytes: &[u8]) -> Aead {
+        assert_eq!(
+            bytes.len(),
+            AEAD_SIZE,
+            "OTP AEAD must be exactly {} bytes (got {})",
+            AEAD_SIZE,
+            bytes.len()
+        );
+        let mut aead = [0u8; AEAD_SIZE];
+        aead.copy_from_slice(bytes);
+        Aead(aead)
+    }
+}
+
+impl_array_serializers!(Aead, AEAD_SIZE);
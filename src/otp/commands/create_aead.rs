@@ -0,0 +1,35 @@
+//! Create an OTP AEAD from a Yubico OTP token's key and private ID
+//!
+//! <https://developers.yubico.com/YubiHSM2/Commands/Create_Otp_Aead.html>
+
+use crate::{
+    command::{self, Command},
+    object, otp,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+/// Request parameters for `command::create_otp_aead`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CreateOtpAeadCommand {
+    /// ID of the OTP AEAD key to encrypt the AEAD with
+    pub key_id: object::Id,
+
+    /// AES key of the Yubico OTP token
+    pub key: [u8; otp::KEY_SIZE],
+
+    /// Private ID of the Yubico OTP token
+    pub private_id: [u8; otp::PRIVATE_ID_SIZE],
+}
+
+impl Command for CreateOtpAeadCommand {
+    type ResponseType = CreateOtpAeadResponse;
+}
+
+/// Response from `command::create_otp_aead`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CreateOtpAeadResponse(pub(crate) otp::Aead);
+
+impl Response for CreateOtpAeadResponse {
+    const COMMAND_CODE: command::Code = command::Code::CreateOtpAead;
+}
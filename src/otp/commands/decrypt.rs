@@ -0,0 +1,35 @@
+//! Decrypt a Yubico OTP using the AEAD of the token which generated it
+//!
+//! <https://developers.yubico.com/YubiHSM2/Commands/Decrypt_Otp.html>
+
+use crate::{
+    command::{self, Command},
+    object, otp,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+/// Request parameters for `command::decrypt_otp`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct DecryptOtpCommand {
+    /// ID of the OTP AEAD key the AEAD is encrypted with
+    pub key_id: object::Id,
+
+    /// AEAD of the token which generated the OTP
+    pub aead: otp::Aead,
+
+    /// OTP to decrypt
+    pub otp: [u8; otp::OTP_SIZE],
+}
+
+impl Command for DecryptOtpCommand {
+    type ResponseType = DecryptOtpResponse;
+}
+
+/// Response from `command::decrypt_otp`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct DecryptOtpResponse(pub(crate) otp::Counters);
+
+impl Response for DecryptOtpResponse {
+    const COMMAND_CODE: command::Code = command::Code::DecryptOtp;
+}
@@ -15,12 +15,19 @@ pub(crate) struct PutOtpAeadKeyCommand {
     /// Common parameters to all put object commands
     pub params: object::put::Params,
 
+    /// Nonce ID included in the nonces of AEADs encrypted with the key
+    pub nonce_id: u32,
+
     /// Serialized object
     pub data: Vec<u8>,
 }
 
 impl Command for PutOtpAeadKeyCommand {
     type ResponseType = PutOtpAeadKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.id]
+    }
 }
 
 /// Response from `command::put_otp_aead_key`
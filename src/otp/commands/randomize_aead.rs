@@ -0,0 +1,29 @@
+//! Create an OTP AEAD from a random key and private ID
+//!
+//! <https://developers.yubico.com/YubiHSM2/Commands/Randomize_Otp_Aead.html>
+
+use crate::{
+    command::{self, Command},
+    object, otp,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+/// Request parameters for `command::randomize_otp_aead`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct RandomizeOtpAeadCommand {
+    /// ID of the OTP AEAD key to encrypt the AEAD with
+    pub key_id: object::Id,
+}
+
+impl Command for RandomizeOtpAeadCommand {
+    type ResponseType = RandomizeOtpAeadResponse;
+}
+
+/// Response from `command::randomize_otp_aead`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct RandomizeOtpAeadResponse(pub(crate) otp::Aead);
+
+impl Response for RandomizeOtpAeadResponse {
+    const COMMAND_CODE: command::Code = command::Code::RandomizeOtpAead;
+}
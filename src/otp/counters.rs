@@ -0,0 +1,28 @@
+//! Counters and timestamp of a decrypted Yubico OTP
+
+use serde::{Deserialize, Serialize};
+
+/// Counters and timestamp of a decrypted Yubico OTP, which a validation
+/// service compares against the last ones it saw for the token to detect
+/// replayed OTPs
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Counters {
+    /// Usage counter (incremented each time the token is powered up)
+    pub use_counter: u16,
+
+    /// Session counter (incremented for each OTP generated while powered up)
+    pub session_counter: u8,
+
+    /// High byte of the token's timestamp
+    pub timestamp_high: u8,
+
+    /// Low bytes of the token's timestamp
+    pub timestamp_low: u16,
+}
+
+impl Counters {
+    /// Get the token's 24-bit timestamp (8 Hz ticks since it was powered up)
+    pub fn timestamp(&self) -> u32 {
+        (u32::from(self.timestamp_high) << 16) | u32::from(self.timestamp_low)
+    }
+}
@@ -104,7 +104,7 @@ pub enum Code {
 impl Code {
     /// Convert an unsigned byte into a Code (if valid)
     pub fn from_u8(byte: u8) -> Result<Self, Error> {
-        let code = i16::from(byte).checked_sub(0x80).unwrap() as i8;
+        let code = (i16::from(byte) - 0x80) as i8;
 
         Ok(match code {
             0..=0x7F => Code::Success(
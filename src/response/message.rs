@@ -48,11 +48,11 @@ impl Message {
         length_bytes.copy_from_slice(&bytes[1..3]);
         let length = u16::from_be_bytes(length_bytes) as usize;
 
-        if length.checked_add(3).unwrap() != bytes.len() {
+        if length + 3 != bytes.len() {
             fail!(
                 ProtocolError,
                 "unexpected response length {} (expecting {})",
-                bytes.len().checked_sub(3).unwrap(),
+                bytes.len() - 3,
                 length
             );
         }
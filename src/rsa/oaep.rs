@@ -3,6 +3,8 @@
 mod algorithm;
 pub(crate) mod commands;
 mod decrypted_data;
+mod params;
 
 pub use self::algorithm::Algorithm;
 pub use self::decrypted_data::DecryptedData;
+pub use self::params::Params;
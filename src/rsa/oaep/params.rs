@@ -0,0 +1,46 @@
+//! RSA-OAEP decryption parameters
+
+use super::Algorithm;
+use crate::rsa::mgf;
+use sha2::Digest;
+
+/// Parameters for decrypting RSA-OAEP ciphertexts
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Params {
+    /// Hash algorithm to use for the MGF1 mask generation function
+    pub mgf1_algorithm: mgf::Algorithm,
+
+    /// Hash of the OAEP label (computed with the OAEP hash algorithm)
+    pub label_hash: Vec<u8>,
+}
+
+impl Params {
+    /// Create new RSA-OAEP parameters, hashing the given label with the hash
+    /// function of the given OAEP algorithm
+    pub fn new(algorithm: Algorithm, mgf1_algorithm: mgf::Algorithm, label: &[u8]) -> Self {
+        let label_hash = match algorithm {
+            Algorithm::Sha1 => sha1::Sha1::digest(label).to_vec(),
+            Algorithm::Sha256 => sha2::Sha256::digest(label).to_vec(),
+            Algorithm::Sha384 => sha2::Sha384::digest(label).to_vec(),
+            Algorithm::Sha512 => sha2::Sha512::digest(label).to_vec(),
+        };
+
+        Self {
+            mgf1_algorithm,
+            label_hash,
+        }
+    }
+
+    /// Parameters for the given OAEP algorithm, using its hash function for
+    /// MGF1 and an empty label
+    pub fn for_algorithm(algorithm: Algorithm) -> Self {
+        let mgf1_algorithm = match algorithm {
+            Algorithm::Sha1 => mgf::Algorithm::Sha1,
+            Algorithm::Sha256 => mgf::Algorithm::Sha256,
+            Algorithm::Sha384 => mgf::Algorithm::Sha384,
+            Algorithm::Sha512 => mgf::Algorithm::Sha512,
+        };
+
+        Self::new(algorithm, mgf1_algorithm, &[])
+    }
+}
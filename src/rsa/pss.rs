@@ -3,6 +3,7 @@
 
 mod algorithm;
 pub(crate) mod commands;
+mod params;
 mod signature;
 mod signer;
 
@@ -10,5 +11,6 @@ mod signer;
 pub const MAX_MESSAGE_SIZE: usize = 0xFFFF;
 
 pub use self::algorithm::Algorithm;
+pub use self::params::Params;
 pub use self::signature::Signature;
 pub use self::signer::Signer;
@@ -0,0 +1,32 @@
+//! RSASSA-PSS signature parameters
+
+use crate::rsa::{mgf, SignatureAlgorithm};
+
+/// Parameters for computing RSASSA-PSS signatures of precomputed digests
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Params {
+    /// Hash algorithm to use for the MGF1 mask generation function
+    pub mgf1_algorithm: mgf::Algorithm,
+
+    /// Length of the random salt in bytes
+    pub salt_len: u16,
+}
+
+impl Params {
+    /// Create new RSASSA-PSS parameters
+    pub fn new(mgf1_algorithm: mgf::Algorithm, salt_len: u16) -> Self {
+        Self {
+            mgf1_algorithm,
+            salt_len,
+        }
+    }
+
+    /// Parameters for the given digest algorithm, using it for MGF1 and a
+    /// salt the length of its output (as recommended by RFC 8017)
+    pub fn for_digest<S: SignatureAlgorithm>() -> Self {
+        Self::new(
+            S::MGF_ALGORITHM,
+            <S as digest::Digest>::output_size() as u16,
+        )
+    }
+}
@@ -5,11 +5,14 @@ mod error;
 mod ser;
 
 pub use self::error::Error;
+use crate::command::MAX_MSG_SIZE;
 use std::io::Cursor;
 
 /// Serialize a message into a byte vector
 pub fn serialize<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, Error> {
-    let mut result = vec![];
+    // Reserve space for the largest message up front so sensitive data (e.g.
+    // key material) isn't copied around and left behind as the buffer grows
+    let mut result = Vec::with_capacity(MAX_MSG_SIZE);
     serde::Serialize::serialize(value, &mut ser::Serializer::new(&mut result))?;
     Ok(result)
 }
@@ -6,42 +6,66 @@
 #[macro_use]
 mod macros;
 
+mod channel;
 pub(crate) mod commands;
 mod error;
+mod event;
 mod guard;
 mod id;
+mod middleware;
+#[cfg(feature = "scp03")]
+pub mod securechannel;
+#[cfg(not(feature = "scp03"))]
 pub(crate) mod securechannel;
 mod timeout;
 
 pub use self::{
+    channel::{Channel, Handshake},
     error::{Error, ErrorKind},
+    event::Event,
     guard::Guard,
     id::Id,
+    middleware::{CommandInfo, CommandOutcome, Middleware},
+    securechannel::{ChallengeRng, CryptoBackend, RustCryptoBackend},
     timeout::Timeout,
 };
 
-use self::{commands::CloseSessionCommand, securechannel::SecureChannel};
+pub(crate) use self::{event::EventHandler, middleware::MiddlewareStack};
+
+use self::{
+    commands::CloseSessionCommand,
+    securechannel::{SecureChannel, MAX_COMMANDS_PER_SESSION},
+};
 use crate::{
     authentication::Credentials,
     command::{self, Command},
     connector::Connector,
     device, response,
-    serialization::deserialize,
+    serialization::{deserialize, serialize},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use std::time::{Duration, Instant};
 
 /// Timeout fuzz factor: to avoid races/skew with the YubiHSM's clock,
 /// we consider sessions to be timed out slightly earlier than the actual
 /// timeout. This should (hopefully) ensure we always time out first,
 /// and therefore generate appropriate timeout-related errors rather
 /// than opaque "lost connection to HSM"-style errors.
-const TIMEOUT_FUZZ_FACTOR: Duration = Duration::from_secs(1);
+pub(crate) const TIMEOUT_FUZZ_FACTOR: Duration = Duration::from_secs(1);
+
+/// Number of messages short of the per-session limit at which a session is
+/// due to be rekeyed, leaving room to close the old session cleanly.
+pub(crate) const REKEY_MARGIN: usize = 16;
 
 /// Authenticated and encrypted (SCP03) `Session` with the HSM. A `Session` is
 /// needed to perform any command.
 ///
-/// `Session`s are automatically closed on `Drop`, releasing HSM session
-/// resources and wiping the ephemeral keys used to encrypt the session.
+/// `Session`s are automatically closed on `Drop` (on a best-effort basis,
+/// ignoring errors), releasing HSM session resources and wiping the
+/// ephemeral keys used to encrypt the session. Use [`Session::close`] to
+/// find out whether closing the session succeeded.
 pub struct Session {
     /// ID for this session
     id: Id,
@@ -60,14 +84,31 @@ pub struct Session {
 
     /// Inactivity timeout for this session
     timeout: Timeout,
+
+    /// How long to wait for responses to commands (if overriding the
+    /// connector's timeout)
+    command_timeout: Option<Duration>,
+
+    /// Handler notified when this session is closed or terminated
+    event_handler: Option<EventHandler>,
+
+    /// Middleware called for each command sent over this session
+    middleware: MiddlewareStack,
+
+    /// Was a command sent without its response being processed?
+    exchange_incomplete: bool,
 }
 
 impl Session {
-    /// Connect to the HSM using the given configuration and credentials
+    /// Connect to the HSM using the given configuration and credentials,
+    /// using the given crypto backend and host challenge RNG for the secure
+    /// channel
     pub(super) fn open(
         connector: Connector,
         credentials: &Credentials,
         timeout: Timeout,
+        backend: Arc<dyn CryptoBackend>,
+        challenge_rng: &dyn ChallengeRng,
     ) -> Result<Self, Error> {
         ensure!(
             timeout.duration() > TIMEOUT_FUZZ_FACTOR,
@@ -76,7 +117,7 @@ impl Session {
             TIMEOUT_FUZZ_FACTOR
         );
 
-        let channel = SecureChannel::open(&connector, credentials)?;
+        let channel = SecureChannel::open(&connector, credentials, backend, challenge_rng)?;
         let now = Instant::now();
 
         let mut session = Session {
@@ -86,9 +127,17 @@ impl Session {
             created_at: now,
             last_active: now,
             timeout,
+            command_timeout: None,
+            event_handler: None,
+            middleware: Arc::new([]),
+            exchange_incomplete: false,
         };
 
-        session.authenticate(credentials)?;
+        // Don't try to close sessions which failed to authenticate on drop
+        session.authenticate(credentials).map_err(|e| {
+            session.abort();
+            e
+        })?;
 
         Ok(session)
     }
@@ -116,6 +165,54 @@ impl Session {
             .map(SecureChannel::counter)
     }
 
+    /// Is this session close to the limit on the number of messages which
+    /// can be sent under one set of session keys, meaning it should be closed
+    /// and a new session opened before sending more commands?
+    pub fn needs_rekey(&self) -> bool {
+        self.messages_sent()
+            .is_ok_and(|n| n + REKEY_MARGIN >= MAX_COMMANDS_PER_SESSION as usize)
+    }
+
+    /// Number of commands which can be sent before this session is due to be
+    /// rekeyed
+    pub(crate) fn commands_until_rekey(&self) -> usize {
+        self.messages_sent().map_or(0, |n| {
+            (MAX_COMMANDS_PER_SESSION as usize).saturating_sub(n + REKEY_MARGIN)
+        })
+    }
+
+    /// Wait up to the given duration for responses to subsequent commands,
+    /// or the connector's configured timeout if `None`
+    pub(crate) fn set_command_timeout(&mut self, timeout: Option<Duration>) {
+        self.command_timeout = timeout;
+    }
+
+    /// Was a command sent over this session without its response being
+    /// processed (e.g. because a panic interrupted the exchange)? If so,
+    /// the secure channel state is out of sync with the HSM.
+    pub(crate) fn is_interrupted(&self) -> bool {
+        self.secure_channel.is_some() && self.exchange_incomplete
+    }
+
+    /// Notify the given handler when this session is closed or terminated
+    pub(crate) fn set_event_handler(&mut self, handler: Option<EventHandler>) {
+        self.event_handler = handler;
+    }
+
+    /// Call the given middleware for subsequent commands (set each time a
+    /// client locks the session, as it may be shared by clients with
+    /// different middleware)
+    pub(crate) fn set_middleware(&mut self, middleware: MiddlewareStack) {
+        self.middleware = middleware;
+    }
+
+    /// Report an event to the event handler (if any)
+    fn emit(&self, event: Event) {
+        if let Some(handler) = &self.event_handler {
+            handler(&event);
+        }
+    }
+
     /// Has this session timed out?
     pub fn is_timed_out(&self) -> bool {
         let idle_time = Instant::now().duration_since(self.last_active);
@@ -125,20 +222,65 @@ impl Session {
 
     /// Close this session, consuming it in the process.
     pub fn close(mut self) -> Result<(), Error> {
+        self.close_channel()
+    }
+
+    /// Discard this session without closing it, reporting it as closed if
+    /// it timed out (rather than having been terminated)
+    pub(crate) fn discard(mut self) {
+        if self.secure_channel.take().is_some() {
+            self.emit(Event::Closed {
+                session_id: self.id,
+            });
+        }
+    }
+
+    /// Send a `CloseSession` command if the session is still open, wiping
+    /// the secure channel regardless of whether it succeeds
+    fn close_channel(&mut self) -> Result<(), Error> {
+        // The secure channel is out of sync with the HSM, so the session
+        // can't be closed cleanly
+        if self.is_interrupted() {
+            self.abort();
+            return Ok(());
+        }
+
         // Only attempt to close the session if we have an active secure
         // channel and our session hasn't already timed out
         if self.secure_channel.is_none() || self.is_timed_out() {
+            if self.secure_channel.take().is_some() {
+                self.emit(Event::Closed {
+                    session_id: self.id,
+                });
+            }
+
             return Ok(());
         }
 
         session_debug!(self, "closing session");
-        self.send_command(&CloseSessionCommand {})?;
-        Ok(())
+
+        match self.send_command(&CloseSessionCommand {}) {
+            Ok(_) => {
+                self.secure_channel = None;
+                self.emit(Event::Closed {
+                    session_id: self.id,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                self.abort();
+                Err(e)
+            }
+        }
     }
 
     /// Abort this session, terminating it without closing it
     pub(crate) fn abort(&mut self) {
-        self.secure_channel = None;
+        if self.secure_channel.take().is_some() {
+            self.emit(Event::Terminated {
+                session_id: self.id,
+            });
+        }
     }
 
     /// Encrypt a command, send it to the HSM, then read and decrypt the response
@@ -146,7 +288,128 @@ impl Session {
         &mut self,
         command: &C,
     ) -> Result<C::ResponseType, Error> {
-        let plaintext_cmd = command::Message::from(command);
+        let plaintext_cmd = prepare_command(command)?;
+        self.send_instrumented_command(command, plaintext_cmd)
+    }
+
+    /// Send several commands of the same type, appending their responses to
+    /// `responses` and stopping at the first error.
+    ///
+    /// The secure channel requires each response to be verified before the
+    /// next command can be encrypted, so commands are sent one at a time,
+    /// but the session only needs to be checked out once for the whole batch.
+    pub(crate) fn send_commands<C: Command>(
+        &mut self,
+        commands: &[C],
+        responses: &mut Vec<C::ResponseType>,
+    ) -> Result<(), Error> {
+        for command in commands {
+            responses.push(self.send_command(command)?);
+        }
+
+        Ok(())
+    }
+
+    /// Send a serialized command (see `send_prepared_command`), logging the
+    /// command type, the IDs of the objects it operates on, its latency, and
+    /// whether it succeeded
+    fn send_instrumented_command<C: Command>(
+        &mut self,
+        command: &C,
+        plaintext_cmd: command::Message,
+    ) -> Result<C::ResponseType, Error> {
+        let started_at = Instant::now();
+        let result = self.send_prepared_command::<C>(plaintext_cmd);
+
+        session_debug!(
+            self,
+            "cmd={:?} objects={:?} latency={:?} ok={}",
+            C::COMMAND_CODE,
+            command.object_ids(),
+            started_at.elapsed(),
+            result.is_ok()
+        );
+
+        result
+    }
+
+    /// Send a command with the given code and serialized data, returning the
+    /// data of the (decrypted) response
+    pub(crate) fn send_raw_command(
+        &mut self,
+        cmd_type: command::Code,
+        command_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        SecureChannel::ensure_command_fits(cmd_type, command_data)?;
+        let plaintext_cmd = command::Message::create(cmd_type, command_data.to_vec())?;
+
+        let started_at = Instant::now();
+        let result = self.exchange(plaintext_cmd);
+
+        session_debug!(
+            self,
+            "cmd={:?} raw=true latency={:?} ok={}",
+            cmd_type,
+            started_at.elapsed(),
+            result.is_ok()
+        );
+
+        Ok(result?.data)
+    }
+
+    /// Encrypt a serialized command, send it to the HSM, then read, decrypt
+    /// and deserialize the response
+    fn send_prepared_command<C: Command>(
+        &mut self,
+        plaintext_cmd: command::Message,
+    ) -> Result<C::ResponseType, Error> {
+        let response = self.exchange(plaintext_cmd)?;
+        deserialize(response.data.as_ref()).map_err(Into::into)
+    }
+
+    /// Send a serialized command (see `exchange_unhooked`), calling the
+    /// middleware (if any) before and after
+    fn exchange(&mut self, plaintext_cmd: command::Message) -> Result<response::Message, Error> {
+        if self.middleware.is_empty() {
+            return self.exchange_unhooked(plaintext_cmd);
+        }
+
+        let middleware = Arc::clone(&self.middleware);
+        let info = CommandInfo {
+            session_id: self.id,
+            command_type: plaintext_cmd.command_type,
+            length: plaintext_cmd.data.len(),
+        };
+
+        for hook in middleware.iter() {
+            hook.before_command(&info).map_err(|e| {
+                session_debug!(self, "cmd={:?} rejected: {}", info.command_type, e);
+                ErrorKind::RejectedError.context(e)
+            })?;
+        }
+
+        let started_at = Instant::now();
+        let result = self.exchange_unhooked(plaintext_cmd);
+        let outcome = CommandOutcome {
+            latency: started_at.elapsed(),
+            response_length: result.as_ref().ok().map(|response| response.data.len()),
+            error: result.as_ref().err().map(|e| *e.kind()),
+        };
+
+        for hook in middleware.iter() {
+            hook.after_command(&info, &outcome);
+        }
+
+        result
+    }
+
+    /// Encrypt a serialized command, send it to the HSM, then read and
+    /// decrypt the response, checking it's a successful response to the
+    /// command
+    fn exchange_unhooked(
+        &mut self,
+        plaintext_cmd: command::Message,
+    ) -> Result<response::Message, Error> {
         let cmd_type = plaintext_cmd.command_type;
 
         let encrypted_cmd = self
@@ -164,9 +427,10 @@ impl Session {
             "n={} uuid={} cmd={:?}",
             self.messages_sent()?,
             uuid,
-            C::COMMAND_CODE
+            cmd_type
         );
 
+        self.exchange_incomplete = true;
         let encrypted_response = self.send_message(encrypted_cmd)?;
 
         let response = self
@@ -178,6 +442,8 @@ impl Session {
                 e
             })?;
 
+        self.exchange_incomplete = false;
+
         if response.is_err() {
             if let Some(kind) = device::ErrorKind::from_response_message(&response) {
                 session_debug!(self, "uuid={} failed={:?} error={:?}", uuid, cmd_type, kind);
@@ -188,16 +454,16 @@ impl Session {
             }
         }
 
-        if response.command() != Some(C::COMMAND_CODE) {
+        if response.command() != Some(cmd_type) {
             fail!(
                 ErrorKind::ResponseError,
                 "bad command type in response: {:?} (expected {:?})",
                 response.command(),
-                C::COMMAND_CODE,
+                cmd_type,
             );
         }
 
-        deserialize(response.data.as_ref()).map_err(Into::into)
+        Ok(response)
     }
 
     /// Send a command message to the HSM and parse the response
@@ -217,17 +483,34 @@ impl Session {
             );
         }
 
-        let response = match self.connector.send_message(uuid, cmd.into()) {
-            Ok(response_bytes) => response::Message::parse(response_bytes)?,
-            Err(e) => {
-                // Abort the session in the event of errors
-                self.abort();
-                return Err(e.into());
-            }
-        };
+        let response =
+            match self
+                .connector
+                .send_message_with(uuid, cmd.into(), self.command_timeout)
+            {
+                Ok(response_bytes) => response::Message::parse(response_bytes)?,
+                Err(e) => {
+                    // Abort the session in the event of errors
+                    self.abort();
+                    return Err(e.into());
+                }
+            };
 
         if response.is_err() {
             session_error!(self, "uuid={} error={:?}", &uuid, response.code);
+
+            // The HSM no longer recognizes this session, so don't use it again
+            if device::ErrorKind::from_response_code(response.code)
+                == Some(device::ErrorKind::InvalidSession)
+            {
+                self.abort();
+                fail!(
+                    ErrorKind::ExpiredError,
+                    "session {} expired on HSM",
+                    self.id().to_u8()
+                );
+            }
+
             fail!(
                 ErrorKind::ResponseError,
                 "HSM error (session: {})",
@@ -276,3 +559,19 @@ impl Session {
             .ok_or_else(|| format_err!(ErrorKind::ClosedError, "session is already closed").into())
     }
 }
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Err(e) = self.close_channel() {
+            session_debug!(self, "error closing session on drop: {}", e);
+        }
+    }
+}
+
+/// Serialize a command into a plaintext message to be sent over a session
+fn prepare_command<C: Command>(command: &C) -> Result<command::Message, Error> {
+    let cmd_type = C::COMMAND_CODE;
+    let command_data = serialize(command)?;
+    SecureChannel::ensure_command_fits(cmd_type, &command_data)?;
+    command::Message::create(cmd_type, command_data)
+}
@@ -0,0 +1,467 @@
+//! SCP03 channels driven by the caller, for transporting messages to the HSM
+//! without a [`Connector`][crate::connector::Connector] (e.g. over a relay to
+//! an air-gapped host).
+//!
+//! A [`Handshake`] produces the raw `CreateSession` command to send to the
+//! HSM. Its response yields a [`Channel`] along with the raw
+//! `AuthenticateSession` command, and once the response to that has been
+//! processed the channel can encrypt commands and decrypt responses:
+//!
+//! ```ignore
+//! let handshake = Handshake::new(&credentials);
+//! let response = relay(handshake.create_session_command())?;
+//!
+//! let (mut channel, authenticate_command) = handshake.finish(response)?;
+//! channel.finish_authenticate_session(relay(authenticate_command)?)?;
+//!
+//! let command = channel.encrypt_command(command::Code::Echo, b"hello")?;
+//! let (code, data) = channel.decrypt_response(relay(command)?)?;
+//! ```
+//!
+//! Channels in strict mode (see [`Channel::set_strict`]) treat any response
+//! which is malformed, fails verification or doesn't answer the last command
+//! sent as a security event and close the channel.
+
+use super::{
+    securechannel::{Challenge, SecureChannel, SecurityLevel},
+    ChallengeRng, CryptoBackend, Error, ErrorKind, Id, RustCryptoBackend,
+};
+use crate::{authentication::Credentials, command, connector, device, response};
+use rand_core::OsRng;
+use std::sync::Arc;
+
+/// First step in opening a [`Channel`]: the `CreateSession` exchange
+pub struct Handshake {
+    /// Credentials to authenticate with
+    credentials: Credentials,
+
+    /// Challenge sent in the `CreateSession` command
+    host_challenge: Challenge,
+
+    /// AES and AES-CMAC implementation for the channel
+    backend: Arc<dyn CryptoBackend>,
+}
+
+impl Handshake {
+    /// Begin opening a channel with the given credentials
+    pub fn new(credentials: &Credentials) -> Self {
+        Self::with_backend(credentials, Arc::new(RustCryptoBackend))
+    }
+
+    /// Begin opening a channel with the given credentials, using the given
+    /// crypto backend
+    pub fn with_backend(credentials: &Credentials, backend: Arc<dyn CryptoBackend>) -> Self {
+        Self::with_challenge_rng(credentials, backend, &OsRng)
+    }
+
+    /// Begin opening a channel with the given credentials, using the given
+    /// crypto backend and RNG for the host challenge
+    pub fn with_challenge_rng(
+        credentials: &Credentials,
+        backend: Arc<dyn CryptoBackend>,
+        challenge_rng: &dyn ChallengeRng,
+    ) -> Self {
+        Self {
+            credentials: credentials.clone(),
+            host_challenge: Challenge::from_rng(challenge_rng),
+            backend,
+        }
+    }
+
+    /// Serialized `CreateSession` command to send to the HSM
+    pub fn create_session_command(&self) -> Vec<u8> {
+        SecureChannel::create_session_command(&self.credentials, self.host_challenge).serialize()
+    }
+
+    /// Process the HSM's response to the `CreateSession` command, verifying
+    /// the HSM's cryptogram. Returns the new channel along with the serialized
+    /// `AuthenticateSession` command to send to the HSM, whose response must
+    /// be passed to [`Channel::finish_authenticate_session`].
+    pub fn finish(self, response: Vec<u8>) -> Result<(Channel, Vec<u8>), Error> {
+        let response_message = response::Message::parse(connector::Message(response))?;
+
+        let mut channel = SecureChannel::from_create_session_response(
+            &self.credentials,
+            self.host_challenge,
+            response_message,
+            self.backend,
+        )?;
+
+        let command = channel.authenticate_session()?.serialize();
+
+        let channel = Channel {
+            channel,
+            strict: false,
+            pending_command: None,
+        };
+
+        Ok((channel, command))
+    }
+}
+
+/// SCP03 channel which encrypts commands and decrypts responses as raw
+/// messages, leaving it to the caller to transport them to and from the HSM
+pub struct Channel {
+    /// Underlying secure channel
+    channel: SecureChannel,
+
+    /// Close the channel on any response which is out of sync
+    strict: bool,
+
+    /// Command awaiting a response
+    pending_command: Option<command::Code>,
+}
+
+impl Channel {
+    /// Get the channel (i.e. session) ID
+    pub fn id(&self) -> Id {
+        self.channel.id()
+    }
+
+    /// Number of messages sent over this channel
+    pub fn messages_sent(&self) -> usize {
+        self.channel.counter()
+    }
+
+    /// Enable or disable strict mode (disabled by default).
+    ///
+    /// In strict mode every response must be a well-formed answer to the
+    /// last command encrypted, with the expected session ID, declared
+    /// lengths and R-MAC (chained from that command's C-MAC), and only one
+    /// command may await a response at a time. Any mismatch closes the
+    /// channel and returns an error of kind [`ErrorKind::DesyncError`].
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Process the HSM's response to the `AuthenticateSession` command,
+    /// after which the channel can be used to send commands
+    pub fn finish_authenticate_session(&mut self, response: Vec<u8>) -> Result<(), Error> {
+        self.ensure_security_level(SecurityLevel::None)?;
+        let response_message = response::Message::parse(connector::Message(response))?;
+
+        if response_message.is_err() {
+            return Err(
+                match device::ErrorKind::from_response_message(&response_message) {
+                    Some(kind) => kind.into(),
+                    None => format_err!(
+                        ErrorKind::AuthenticationError,
+                        "HSM error: {:?}",
+                        response_message.code
+                    )
+                    .into(),
+                },
+            );
+        }
+
+        ensure!(
+            response_message.command() == Some(command::Code::AuthenticateSession),
+            ErrorKind::ProtocolError,
+            "command type mismatch: expected {:?}, got {:?}",
+            command::Code::AuthenticateSession,
+            response_message.code
+        );
+
+        self.channel.finish_authenticate_session(&response_message)
+    }
+
+    /// Encrypt a command, returning the serialized `SessionMessage` to send
+    /// to the HSM
+    pub fn encrypt_command(
+        &mut self,
+        command_type: command::Code,
+        command_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.ensure_security_level(SecurityLevel::Authenticated)?;
+
+        if self.strict {
+            if let Some(pending) = self.pending_command {
+                return Err(self.desync(format_err!(
+                    ErrorKind::DesyncError,
+                    "{:?} command sent while awaiting a response to {:?}",
+                    command_type,
+                    pending
+                )));
+            }
+        }
+
+        SecureChannel::ensure_command_fits(command_type, command_data)?;
+        let command_message = command::Message::create(command_type, command_data)?;
+        let encrypted_command = self.channel.encrypt_command(command_message)?.serialize();
+
+        self.pending_command = Some(command_type);
+        Ok(encrypted_command)
+    }
+
+    /// Verify and decrypt the HSM's response to a command sent with
+    /// [`Channel::encrypt_command`], returning its response code and data
+    pub fn decrypt_response(
+        &mut self,
+        response: Vec<u8>,
+    ) -> Result<(response::Code, Vec<u8>), Error> {
+        self.ensure_security_level(SecurityLevel::Authenticated)?;
+        let pending_command = self.pending_command.take();
+
+        if self.strict && pending_command.is_none() {
+            return Err(self.desync(format_err!(
+                ErrorKind::DesyncError,
+                "response received without a command awaiting one"
+            )));
+        }
+
+        let response_message = response::Message::parse(connector::Message(response));
+        let response_message = self.strict_result(response_message)?;
+
+        ensure!(
+            !response_message.is_err(),
+            ErrorKind::ResponseError,
+            "HSM error (session: {}): {:?}",
+            self.id().to_u8(),
+            response_message.code
+        );
+
+        // Only session messages carry an R-MAC and encrypted response
+        if response_message.code != response::Code::Success(command::Code::SessionMessage) {
+            self.channel.terminate();
+
+            return self.strict_result(Err(format_err!(
+                ErrorKind::ProtocolError,
+                "expected SessionMessage response, got {:?}",
+                response_message.code
+            )
+            .into()));
+        }
+
+        let decrypted_response = self.channel.decrypt_response(response_message);
+        let decrypted_response = self.strict_result(decrypted_response)?;
+
+        if self.strict && !decrypted_response.is_err() {
+            if let Some(expected) = pending_command {
+                if decrypted_response.command() != Some(expected) {
+                    return Err(self.desync(format_err!(
+                        ErrorKind::DesyncError,
+                        "expected response to {:?}, got {:?}",
+                        expected,
+                        decrypted_response.code
+                    )));
+                }
+            }
+        }
+
+        Ok((decrypted_response.code, decrypted_response.data))
+    }
+
+    /// In strict mode, close the channel if the given result is an error
+    fn strict_result<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        match result {
+            Err(err) if self.strict => Err(self.desync(err)),
+            other => other,
+        }
+    }
+
+    /// Close the channel, returning a desync error caused by the given error
+    fn desync(&mut self, err: impl Into<Error>) -> Error {
+        self.channel.terminate();
+        let err = err.into();
+
+        if *err.kind() == ErrorKind::DesyncError {
+            err
+        } else {
+            ErrorKind::DesyncError.context(err).into()
+        }
+    }
+
+    /// Return an error unless the channel is in the given state
+    fn ensure_security_level(&self, expected: SecurityLevel) -> Result<(), Error> {
+        match self.channel.security_level() {
+            level if level == expected => Ok(()),
+            SecurityLevel::Terminated => fail!(ErrorKind::ClosedError, "channel is terminated"),
+            SecurityLevel::None => fail!(
+                ErrorKind::ProtocolError,
+                "channel has not been authenticated"
+            ),
+            SecurityLevel::Authenticated => {
+                fail!(ErrorKind::ProtocolError, "channel is already authenticated")
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mockhsm"))]
+mod tests {
+    use super::*;
+    use crate::{connector::Connector, test_util::FixedRng, uuid};
+
+    /// Send a raw message to the connector
+    fn relay(connector: &Connector, message: Vec<u8>) -> Vec<u8> {
+        connector
+            .send_message(uuid::new_v4(), message.into())
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn echo_test() {
+        let connector = Connector::mockhsm();
+        let handshake = Handshake::new(&Credentials::default());
+        let response = relay(&connector, handshake.create_session_command());
+
+        let (mut channel, authenticate_command) = handshake.finish(response).unwrap();
+
+        // Commands can't be sent until the channel is authenticated
+        let err = channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ProtocolError);
+
+        let response = relay(&connector, authenticate_command);
+        channel.finish_authenticate_session(response).unwrap();
+
+        let command = channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap();
+        let (code, data) = channel
+            .decrypt_response(relay(&connector, command))
+            .unwrap();
+
+        assert_eq!(code, response::Code::Success(command::Code::Echo));
+        assert_eq!(data, b"hello");
+    }
+
+    /// Open an authenticated channel in strict mode
+    fn open_strict_channel(connector: &Connector) -> Channel {
+        let handshake = Handshake::new(&Credentials::default());
+        let response = relay(connector, handshake.create_session_command());
+
+        let (mut channel, authenticate_command) = handshake.finish(response).unwrap();
+        let response = relay(connector, authenticate_command);
+        channel.finish_authenticate_session(response).unwrap();
+        channel.set_strict(true);
+        channel
+    }
+
+    #[test]
+    fn strict_replayed_response_test() {
+        let connector = Connector::mockhsm();
+        let mut channel = open_strict_channel(&connector);
+
+        let command = channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap();
+        let response = relay(&connector, command);
+        channel.decrypt_response(response.clone()).unwrap();
+
+        let err = channel.decrypt_response(response).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DesyncError);
+
+        let err = channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ClosedError);
+    }
+
+    #[test]
+    fn strict_tampered_response_test() {
+        let connector = Connector::mockhsm();
+        let mut channel = open_strict_channel(&connector);
+
+        let command = channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap();
+        let mut response = relay(&connector, command);
+        *response.last_mut().unwrap() ^= 1;
+
+        let err = channel.decrypt_response(response).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DesyncError);
+        assert_eq!(channel.channel.security_level(), SecurityLevel::Terminated);
+    }
+
+    #[test]
+    fn create_session_response_rejected_test() {
+        let connector = Connector::mockhsm();
+
+        for strict in [false, true] {
+            let mut channel = open_strict_channel(&connector);
+            channel.set_strict(strict);
+
+            channel
+                .encrypt_command(command::Code::Echo, b"hello")
+                .unwrap();
+
+            // A successful CreateSession response has a session ID but no
+            // R-MAC
+            let response = vec![0x83, 0x00, 0x01, channel.id().to_u8()];
+            assert!(channel.decrypt_response(response).is_err());
+            assert_eq!(channel.channel.security_level(), SecurityLevel::Terminated);
+        }
+    }
+
+    #[test]
+    fn malformed_response_test() {
+        let connector = Connector::mockhsm();
+
+        for strict in [false, true] {
+            let id = open_strict_channel(&connector).id().to_u8();
+
+            let responses = [
+                // Too short to contain a code and length
+                vec![0x85],
+                // Length doesn't match the message
+                vec![0x85, 0x00, 0x10, id],
+                // SessionMessage without an R-MAC
+                vec![0x85, 0x00, 0x01, id],
+                // Unencrypted Echo response
+                vec![0x81, 0x00, 0x02, b'h', b'i'],
+                // Unknown response code
+                vec![0x7F, 0x00, 0x00],
+            ];
+
+            for response in responses {
+                let mut channel = open_strict_channel(&connector);
+                channel.set_strict(strict);
+
+                channel
+                    .encrypt_command(command::Code::Echo, b"hello")
+                    .unwrap();
+
+                assert!(channel.decrypt_response(response).is_err());
+
+                // Further responses are rejected rather than panicking
+                if channel.channel.security_level() == SecurityLevel::Terminated {
+                    assert!(channel
+                        .decrypt_response(vec![0x85, 0x00, 0x01, id])
+                        .is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn strict_unanswered_command_test() {
+        let connector = Connector::mockhsm();
+        let mut channel = open_strict_channel(&connector);
+
+        channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap();
+
+        let err = channel
+            .encrypt_command(command::Code::Echo, b"hello")
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::DesyncError);
+        assert_eq!(channel.channel.security_level(), SecurityLevel::Terminated);
+    }
+
+    #[test]
+    fn challenge_rng_test() {
+        let handshake = Handshake::with_challenge_rng(
+            &Credentials::default(),
+            Arc::new(RustCryptoBackend),
+            &FixedRng,
+        );
+
+        let command = handshake.create_session_command();
+        assert_eq!(command[..5], [0x03, 0x00, 0x0a, 0x00, 0x01]);
+        assert_eq!(command[5..], [0x42; 8]);
+    }
+}
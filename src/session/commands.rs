@@ -42,7 +42,7 @@ impl Response for CreateSessionResponse {
 ///
 /// <https://developers.yubico.com/YubiHSM2/Commands/Close_Session.html>
 #[derive(Serialize, Deserialize, Debug)]
-pub(super) struct CloseSessionCommand {}
+pub(crate) struct CloseSessionCommand {}
 
 impl Command for CloseSessionCommand {
     type ResponseType = CloseSessionResponse;
@@ -30,14 +30,31 @@ pub enum ErrorKind {
     #[error("HSM error")]
     DeviceError,
 
+    /// Session has expired on the HSM (e.g. due to inactivity) and a new
+    /// session should be created
+    #[error("session expired")]
+    ExpiredError,
+
     /// Message was intended for a different session than the current one
     #[error("session ID mismatch")]
     MismatchError,
 
+    /// Command is too long to be sent to the HSM
+    #[error("message too long")]
+    MessageTooLong,
+
     /// Protocol error occurred
     #[error("protocol error")]
     ProtocolError,
 
+    /// Command was rejected by middleware before being sent
+    #[error("command rejected")]
+    RejectedError,
+
+    /// Responses are out of sync with commands sent over a strict channel
+    #[error("channel desynchronized")]
+    DesyncError,
+
     /// Error response from HSM we can't further specify
     #[error("HSM response error")]
     ResponseError,
@@ -0,0 +1,46 @@
+//! Session lifecycle events
+
+use super::Id;
+use crate::object;
+use std::sync::Arc;
+
+/// Changes in the state of a client's sessions with the HSM, reported to the
+/// handler given to [`Client::set_session_event_handler`] so applications
+/// can e.g. emit metrics or alerts.
+///
+/// [`Client::set_session_event_handler`]: crate::Client::set_session_event_handler
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// A session was opened and authenticated
+    Opened {
+        /// ID of the new session
+        session_id: Id,
+
+        /// Authentication key the session was opened with
+        authentication_key_id: object::Id,
+    },
+
+    /// Authenticating a new session failed (e.g. due to invalid credentials)
+    AuthenticationFailed {
+        /// Authentication key the session was being opened with
+        authentication_key_id: object::Id,
+    },
+
+    /// A session was closed by the client, or by the HSM after being idle
+    /// longer than its inactivity timeout
+    Closed {
+        /// ID of the closed session
+        session_id: Id,
+    },
+
+    /// A session's secure channel was terminated due to an error (e.g. a
+    /// lost connection, failed response verification, or the HSM no longer
+    /// recognizing the session)
+    Terminated {
+        /// ID of the terminated session
+        session_id: Id,
+    },
+}
+
+/// Handler for session events
+pub(crate) type EventHandler = Arc<dyn Fn(&Event) + Send + Sync>;
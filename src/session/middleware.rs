@@ -0,0 +1,59 @@
+//! Middleware which observes (and can veto) commands sent over sessions
+
+use super::{ErrorKind, Id};
+use crate::{command, error::BoxError};
+use std::{sync::Arc, time::Duration};
+
+/// Hooks called for each command sent over a client's sessions, registered
+/// with [`Client::add_middleware`] to implement e.g. custom auditing, policy
+/// checks or metrics.
+///
+/// Middleware is called while the session is locked, so it shouldn't block
+/// or use the client.
+///
+/// [`Client::add_middleware`]: crate::Client::add_middleware
+pub trait Middleware: Send + Sync {
+    /// Called before a command is encrypted and sent. Returning an error
+    /// rejects the command, which fails with an error of kind
+    /// [`ErrorKind::RejectedError`] without being sent.
+    fn before_command(&self, command: &CommandInfo) -> Result<(), BoxError> {
+        let _ = command;
+        Ok(())
+    }
+
+    /// Called after the response to a command has been received and
+    /// decrypted (or sending the command failed)
+    fn after_command(&self, command: &CommandInfo, outcome: &CommandOutcome) {
+        let _ = (command, outcome);
+    }
+}
+
+/// Command about to be sent over a session
+#[derive(Clone, Debug)]
+pub struct CommandInfo {
+    /// Session the command is sent over
+    pub session_id: Id,
+
+    /// Type of command
+    pub command_type: command::Code,
+
+    /// Length of the serialized (plaintext) command data in bytes
+    pub length: usize,
+}
+
+/// Outcome of sending a command over a session
+#[derive(Clone, Debug)]
+pub struct CommandOutcome {
+    /// Time taken to send the command and receive and decrypt its response
+    pub latency: Duration,
+
+    /// Length of the serialized (plaintext) response data in bytes, if the
+    /// command succeeded
+    pub response_length: Option<usize>,
+
+    /// Kind of error the command failed with (if it failed)
+    pub error: Option<ErrorKind>,
+}
+
+/// Middleware registered with a client
+pub(crate) type MiddlewareStack = Arc<[Arc<dyn Middleware>]>;
@@ -0,0 +1,180 @@
+//! Pluggable AES and AES-CMAC implementations used by the secure channel.
+//!
+//! By default the [RustCrypto] `aes`, `cbc` and `cmac` crates are used. The
+//! `aes` crate detects AES-NI (x86/x86_64) or the ARMv8 cryptography
+//! extensions at runtime, falling back to a constant-time bitsliced software
+//! implementation on other CPUs. The software implementation can be forced
+//! by building with `RUSTFLAGS="--cfg aes_force_soft"`.
+//!
+//! Environments which need to use other implementations (e.g. a validated
+//! cryptographic module) can provide their own [`CryptoBackend`].
+//!
+//! [RustCrypto]: https://github.com/RustCrypto
+
+use aes::{
+    cipher::{
+        block_padding::NoPadding, generic_array::GenericArray, BlockDecryptMut, BlockEncrypt,
+        BlockEncryptMut, KeyInit, KeyIvInit,
+    },
+    Aes128, Aes256,
+};
+use cmac::{digest::Mac as _, Cmac};
+use std::fmt::Debug;
+
+/// Size of an AES-128 key in bytes
+pub const AES128_KEY_SIZE: usize = 16;
+
+/// Size of an AES-256 key in bytes
+pub const AES256_KEY_SIZE: usize = 32;
+
+/// Size of an AES block (128-bits)
+pub const AES_BLOCK_SIZE: usize = 16;
+
+/// AES block
+pub type Block = [u8; AES_BLOCK_SIZE];
+
+/// AES and AES-CMAC primitives used to implement SCP03.
+///
+/// Keys are either 16 bytes (AES-128) or 32 bytes (AES-256). Implementations
+/// may panic if given a key of any other size. Padding is handled by the
+/// secure channel, so buffers passed to the CBC methods are always a multiple
+/// of the AES block size (16 bytes).
+pub trait CryptoBackend: Debug + Send + Sync {
+    /// Encrypt a single block in place (i.e. AES-ECB)
+    fn encrypt_block(&self, key: &[u8], block: &mut Block);
+
+    /// Encrypt the given buffer in place using AES-CBC
+    fn cbc_encrypt(&self, key: &[u8], iv: &Block, buffer: &mut [u8]);
+
+    /// Decrypt the given buffer in place using AES-CBC
+    fn cbc_decrypt(&self, key: &[u8], iv: &Block, buffer: &mut [u8]);
+
+    /// Compute the (untruncated) AES-CMAC tag for the given message
+    fn cmac(&self, key: &[u8], message: &[u8]) -> Block;
+}
+
+/// [`CryptoBackend`] implemented using the RustCrypto `aes`, `cbc` and
+/// `cmac` crates (the default)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RustCryptoBackend;
+
+impl CryptoBackend for RustCryptoBackend {
+    fn encrypt_block(&self, key: &[u8], block: &mut Block) {
+        let block = GenericArray::from_mut_slice(block);
+
+        match key.len() {
+            AES128_KEY_SIZE => Aes128::new_from_slice(key).unwrap().encrypt_block(block),
+            AES256_KEY_SIZE => Aes256::new_from_slice(key).unwrap().encrypt_block(block),
+            other => unsupported_key_size(other),
+        }
+    }
+
+    fn cbc_encrypt(&self, key: &[u8], iv: &Block, buffer: &mut [u8]) {
+        let len = buffer.len();
+
+        match key.len() {
+            AES128_KEY_SIZE => cbc::Encryptor::<Aes128>::new_from_slices(key, iv)
+                .unwrap()
+                .encrypt_padded_mut::<NoPadding>(buffer, len)
+                .map(|_| ()),
+            AES256_KEY_SIZE => cbc::Encryptor::<Aes256>::new_from_slices(key, iv)
+                .unwrap()
+                .encrypt_padded_mut::<NoPadding>(buffer, len)
+                .map(|_| ()),
+            other => unsupported_key_size(other),
+        }
+        .expect("buffer not a multiple of the AES block size")
+    }
+
+    fn cbc_decrypt(&self, key: &[u8], iv: &Block, buffer: &mut [u8]) {
+        match key.len() {
+            AES128_KEY_SIZE => cbc::Decryptor::<Aes128>::new_from_slices(key, iv)
+                .unwrap()
+                .decrypt_padded_mut::<NoPadding>(buffer)
+                .map(|_| ()),
+            AES256_KEY_SIZE => cbc::Decryptor::<Aes256>::new_from_slices(key, iv)
+                .unwrap()
+                .decrypt_padded_mut::<NoPadding>(buffer)
+                .map(|_| ()),
+            other => unsupported_key_size(other),
+        }
+        .expect("buffer not a multiple of the AES block size")
+    }
+
+    fn cmac(&self, key: &[u8], message: &[u8]) -> Block {
+        match key.len() {
+            AES128_KEY_SIZE => {
+                let mut mac = <Cmac<Aes128> as KeyInit>::new_from_slice(key).unwrap();
+                mac.update(message);
+                mac.finalize().into_bytes().into()
+            }
+            AES256_KEY_SIZE => {
+                let mut mac = <Cmac<Aes256> as KeyInit>::new_from_slice(key).unwrap();
+                mac.update(message);
+                mac.finalize().into_bytes().into()
+            }
+            other => unsupported_key_size(other),
+        }
+    }
+}
+
+/// Panic on an unsupported key size
+fn unsupported_key_size(size: usize) -> ! {
+    panic!("unsupported AES key size: {size}-bytes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// AES-CMAC examples from NIST SP 800-38B (Appendix D)
+    const AES128_KEY: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    const AES256_KEY: [u8; 32] = [
+        0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d, 0x77,
+        0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3, 0x09, 0x14,
+        0xdf, 0xf4,
+    ];
+    const MESSAGE: [u8; 16] = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a,
+    ];
+
+    #[test]
+    fn cmac_aes128_test() {
+        assert_eq!(
+            RustCryptoBackend.cmac(&AES128_KEY, &[]),
+            [
+                0xbb, 0x1d, 0x69, 0x29, 0xe9, 0x59, 0x37, 0x28, 0x7f, 0xa3, 0x7d, 0x12, 0x9b, 0x75,
+                0x67, 0x46
+            ]
+        );
+        assert_eq!(
+            RustCryptoBackend.cmac(&AES128_KEY, &MESSAGE),
+            [
+                0x07, 0x0a, 0x16, 0xb4, 0x6b, 0x4d, 0x41, 0x44, 0xf7, 0x9b, 0xdd, 0x9d, 0xd0, 0x4a,
+                0x28, 0x7c
+            ]
+        );
+    }
+
+    #[test]
+    fn cmac_aes256_test() {
+        assert_eq!(
+            RustCryptoBackend.cmac(&AES256_KEY, &[]),
+            [
+                0x02, 0x89, 0x62, 0xf6, 0x1b, 0x7b, 0xf8, 0x9e, 0xfc, 0x6b, 0x55, 0x1f, 0x46, 0x67,
+                0xd9, 0x83
+            ]
+        );
+        assert_eq!(
+            RustCryptoBackend.cmac(&AES256_KEY, &MESSAGE),
+            [
+                0x28, 0xa7, 0x02, 0x3f, 0x45, 0x2e, 0x8f, 0x82, 0xbd, 0x4b, 0xf2, 0x8d, 0x8c, 0x37,
+                0xc3, 0x5c
+            ]
+        );
+    }
+}
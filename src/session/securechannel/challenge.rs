@@ -1,11 +1,34 @@
 //! Challenge messages used as part of SCP03's challenge/response protocol.
 
-use rand_core::{OsRng, RngCore};
+use rand_core::{CryptoRng, OsRng, RngCore};
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
 
 /// Size of a challenge message
 pub const CHALLENGE_SIZE: usize = 8;
 
+/// Source of randomness for host challenges, e.g. an approved entropy source
+/// on SGX/embedded platforms, or a seeded RNG for deterministic tests.
+///
+/// Implemented for [`OsRng`] (the default), and for any [`CryptoRng`] in a
+/// [`Mutex`].
+pub trait ChallengeRng: Send + Sync {
+    /// Fill the given buffer with random bytes
+    fn fill(&self, dest: &mut [u8]);
+}
+
+impl ChallengeRng for OsRng {
+    fn fill(&self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest)
+    }
+}
+
+impl<R: CryptoRng + RngCore + Send> ChallengeRng for Mutex<R> {
+    fn fill(&self, dest: &mut [u8]) {
+        self.lock().unwrap().fill_bytes(dest)
+    }
+}
+
 /// A challenge message, sent by either host or the card
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct Challenge([u8; CHALLENGE_SIZE]);
@@ -13,15 +36,20 @@ pub struct Challenge([u8; CHALLENGE_SIZE]);
 impl Challenge {
     /// Create a new random `Challenge`
     pub fn new() -> Self {
+        Self::from_rng(&OsRng)
+    }
+
+    /// Create a new random `Challenge` using the given RNG
+    pub fn from_rng(rng: &dyn ChallengeRng) -> Self {
         let mut challenge = [0u8; CHALLENGE_SIZE];
-        OsRng.fill_bytes(&mut challenge);
+        rng.fill(&mut challenge);
         Challenge(challenge)
     }
 
     /// Create a new challenge from a slice
     ///
     /// Panics if the slice is not 8-bytes
-    #[cfg(all(test, feature = "mockhsm"))]
+    #[cfg(any(feature = "scp03", all(test, feature = "mockhsm")))]
     pub fn from_slice(slice: &[u8]) -> Self {
         assert_eq!(slice.len(), 8, "challenge must be 8-bytes long");
 
@@ -36,3 +64,9 @@ impl Challenge {
         &self.0
     }
 }
+
+impl Default for Challenge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
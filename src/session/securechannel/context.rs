@@ -1,11 +1,14 @@
 //! Derivation context (i.e. concatenated challenges)
 
 use super::{Challenge, CHALLENGE_SIZE};
+use zeroize::Zeroize;
 
 /// Size of a session context
 const CONTEXT_SIZE: usize = CHALLENGE_SIZE * 2;
 
 /// Derivation context (i.e. concatenated challenges)
+#[derive(Zeroize)]
+#[zeroize(drop)]
 pub struct Context([u8; CONTEXT_SIZE]);
 
 impl Context {
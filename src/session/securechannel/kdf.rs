@@ -2,18 +2,51 @@
 //! counter mode KDF as described in NIST SP 800-108 (NIST 800-108)
 //! with "fixed input data" specific to the SCP03 protocol
 
-use super::{Context, KEY_SIZE};
-use aes::Aes128;
-use cmac::{Cmac, Mac};
+use super::{
+    backend::{Block, CryptoBackend, AES128_KEY_SIZE, AES256_KEY_SIZE, AES_BLOCK_SIZE},
+    Context,
+};
+use zeroize::Zeroize;
 
-/// Derive a slice of output data using SCP03's KDF
-pub fn derive(mac_key: &[u8], derivation_constant: u8, context: &Context, output: &mut [u8]) {
-    assert_eq!(mac_key.len(), KEY_SIZE, "16-byte MAC key expected");
+/// Derive a slice of output data using SCP03's KDF, where the derivation
+/// constant is one of those listed in Table 4-1 of GPC_SPE_014.
+///
+/// Panics if the MAC key isn't 16 or 32 bytes, or if more than 32 bytes of
+/// output are requested.
+pub fn derive(
+    backend: &dyn CryptoBackend,
+    mac_key: &[u8],
+    derivation_constant: u8,
+    context: &Context,
+    output: &mut [u8],
+) {
+    assert!(
+        mac_key.len() == AES128_KEY_SIZE || mac_key.len() == AES256_KEY_SIZE,
+        "16-byte or 32-byte MAC key expected"
+    );
 
+    derive_with(
+        |message| backend.cmac(mac_key, message),
+        derivation_constant,
+        context,
+        output,
+    );
+}
+
+/// Derive a slice of output data using SCP03's KDF with the given AES-CMAC
+/// PRF (i.e. keyed with a static key which may be held externally).
+///
+/// Panics if more than 32 bytes of output are requested.
+pub(crate) fn derive_with(
+    prf: impl Fn(&[u8]) -> Block,
+    derivation_constant: u8,
+    context: &Context,
+    output: &mut [u8],
+) {
     let output_len = output.len();
     assert!(
-        output_len <= 16,
-        "up to 16-bytes of data supported ({output_len} requested)"
+        output_len <= AES256_KEY_SIZE,
+        "up to 32-bytes of data supported ({output_len} requested)"
     );
 
     let mut derivation_data = [0u8; 32];
@@ -29,14 +62,15 @@ pub fn derive(mac_key: &[u8], derivation_constant: u8, context: &Context, output
     let length = (output_len * 8) as u16;
     derivation_data[13..15].copy_from_slice(&length.to_be_bytes());
 
-    // "i": KDF counter for deriving more than one block-length of data
-    // Hardcoded to 1 as we don't support deriving more than 128-bits
-    derivation_data[15] = 0x01;
-
     // Derivation context (i.e. challenges concatenated)
     derivation_data[16..].copy_from_slice(context.as_slice());
 
-    let mut mac = Cmac::<Aes128>::new_from_slice(mac_key).unwrap();
-    mac.update(&derivation_data);
-    output.copy_from_slice(&mac.finalize().into_bytes().as_slice()[..output_len]);
+    // "i": KDF counter, incremented for each block-length of derived data
+    for (i, chunk) in output.chunks_mut(AES_BLOCK_SIZE).enumerate() {
+        derivation_data[15] = (i + 1) as u8;
+
+        let mut block = prf(&derivation_data);
+        chunk.copy_from_slice(&block[..chunk.len()]);
+        block.zeroize();
+    }
 }
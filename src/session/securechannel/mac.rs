@@ -6,8 +6,8 @@
 //! increases the chance of collisions since the birthday bound is much
 //! lower (~2^32 messages).
 
+use super::backend::Block;
 use crate::session;
-use cmac::digest::generic_array::{typenum::U16, GenericArray};
 use std::fmt;
 use subtle::{Choice, ConstantTimeEq};
 use zeroize::Zeroize;
@@ -37,7 +37,7 @@ impl Mac {
         &self.0
     }
 
-    /// Verify a 16-byte GenericArray against this MAC tag
+    /// Verify a 16-byte CMAC tag (truncated to 8-bytes) against this MAC tag
     pub fn verify<M>(&self, other: M) -> Result<(), session::Error>
     where
         M: Into<Mac>,
@@ -63,8 +63,8 @@ impl fmt::Debug for Mac {
     }
 }
 
-impl<'a> From<&'a GenericArray<u8, U16>> for Mac {
-    fn from(array: &'a GenericArray<u8, U16>) -> Self {
-        Self::from_slice(&array.as_slice()[..Self::BYTE_SIZE])
+impl<'a> From<&'a Block> for Mac {
+    fn from(block: &'a Block) -> Self {
+        Self::from_slice(&block[..Self::BYTE_SIZE])
     }
 }
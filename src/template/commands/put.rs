@@ -21,6 +21,10 @@ pub(crate) struct PutTemplateCommand {
 
 impl Command for PutTemplateCommand {
     type ResponseType = PutTemplateResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.id]
+    }
 }
 
 /// Response from `command::put_template`
@@ -0,0 +1,106 @@
+//! Fixtures shared by unit tests
+
+use crate::connector::{
+    self, Connectable, Connection,
+    ErrorKind::{ConnectionFailed, IoError},
+    Message,
+};
+#[cfg(feature = "mockhsm")]
+use crate::session::ChallengeRng;
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use uuid::Uuid;
+
+/// Shared in-memory buffer for capturing output (e.g. journals and
+/// recordings) written through a clone of it
+#[derive(Clone, Default)]
+pub(crate) struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    /// Copy of everything written to the buffer so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer which fails every write
+pub(crate) struct FailingWriter;
+
+impl Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Other.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// RNG which always produces the same output, e.g. to generate the same
+/// host challenges every time a session is opened
+#[cfg(feature = "mockhsm")]
+pub(crate) struct FixedRng;
+
+#[cfg(feature = "mockhsm")]
+impl ChallengeRng for FixedRng {
+    fn fill(&self, dest: &mut [u8]) {
+        dest.fill(0x42);
+    }
+}
+
+/// Connector which echoes messages back with its ID until it's broken
+#[derive(Clone, Default)]
+pub(crate) struct TestConnector {
+    /// ID sent back in responses
+    pub id: u8,
+
+    /// Do connections fail to send messages?
+    pub broken: Arc<AtomicBool>,
+
+    /// Does connecting fail?
+    pub down: Arc<AtomicBool>,
+
+    /// Number of connection attempts
+    pub connects: Arc<AtomicUsize>,
+}
+
+impl Connectable for TestConnector {
+    fn box_clone(&self) -> Box<dyn Connectable> {
+        Box::new(self.clone())
+    }
+
+    fn connect(&self) -> Result<Box<dyn Connection>, connector::Error> {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+
+        if self.down.load(Ordering::Relaxed) {
+            Err(format_err!(ConnectionFailed, "down").into())
+        } else {
+            Ok(Box::new(self.clone()))
+        }
+    }
+}
+
+impl Connection for TestConnector {
+    fn send_message(&self, _uuid: Uuid, _msg: Message) -> Result<Message, connector::Error> {
+        if self.broken.load(Ordering::Relaxed) {
+            Err(format_err!(IoError, "broken").into())
+        } else {
+            Ok(vec![self.id].into())
+        }
+    }
+}
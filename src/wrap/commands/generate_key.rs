@@ -22,6 +22,10 @@ pub(crate) struct GenWrapKeyCommand {
 
 impl Command for GenWrapKeyCommand {
     type ResponseType = GenWrapKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.key_id]
+    }
 }
 
 /// Response from `command::generate_wrap_key`
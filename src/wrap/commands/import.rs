@@ -25,6 +25,10 @@ pub(crate) struct ImportWrappedCommand {
 
 impl Command for ImportWrappedCommand {
     type ResponseType = ImportWrappedResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.wrap_key_id]
+    }
 }
 
 /// Response from `command::import_wrapped`
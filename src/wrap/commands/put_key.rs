@@ -25,6 +25,10 @@ pub(crate) struct PutWrapKeyCommand {
 
 impl Command for PutWrapKeyCommand {
     type ResponseType = PutWrapKeyResponse;
+
+    fn object_ids(&self) -> Vec<object::Id> {
+        vec![self.params.id]
+    }
 }
 
 /// Response from `command::put_wrap_key`
@@ -0,0 +1,67 @@
+use yubihsm::{authentication, client, device, object, Capability, Client, Credentials};
+
+use crate::{clear_test_key_slot, TEST_DOMAINS, TEST_KEY_ID, TEST_KEY_LABEL, TEST_MESSAGE};
+
+/// Change the authentication key used to open a session
+#[test]
+fn change_authentication_key_test() {
+    let client = crate::get_hsm_client();
+    let algorithm = authentication::Algorithm::YubicoAes;
+    let old_authentication_key = authentication::Key::derive_from_password(TEST_MESSAGE);
+    let new_authentication_key = authentication::Key::derive_from_password(b"new password");
+
+    clear_test_key_slot(&client, object::Type::AuthenticationKey);
+
+    client
+        .put_authentication_key(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::CHANGE_AUTHENTICATION_KEY,
+            Capability::default(),
+            algorithm,
+            old_authentication_key.clone(),
+        )
+        .unwrap_or_else(|err| panic!("error putting auth key: {err}"));
+
+    let test_client = Client::open(
+        client.connector().clone(),
+        Credentials::new(TEST_KEY_ID, old_authentication_key),
+        true,
+    )
+    .unwrap_or_else(|err| panic!("error opening session: {err}"));
+
+    // Only the key used to open the session can be changed
+    let err = test_client
+        .change_authentication_key(1, algorithm, new_authentication_key.clone())
+        .unwrap_err();
+
+    assert_eq!(err.device_error(), Some(device::ErrorKind::InvalidId));
+
+    // Keys must match the size expected for the algorithm
+    let err = test_client
+        .change_authentication_key(TEST_KEY_ID, algorithm, authentication::Key::random_aes256())
+        .unwrap_err();
+
+    assert_eq!(*err.kind(), client::ErrorKind::ProtocolError);
+
+    let key_id = test_client
+        .change_authentication_key(TEST_KEY_ID, algorithm, new_authentication_key.clone())
+        .unwrap_or_else(|err| panic!("error changing auth key: {err}"));
+
+    assert_eq!(key_id, TEST_KEY_ID);
+
+    // The client reopens its session using the new key
+    test_client.close().unwrap();
+    test_client.connect().unwrap();
+
+    let new_client = Client::open(
+        client.connector().clone(),
+        Credentials::new(TEST_KEY_ID, new_authentication_key),
+        true,
+    )
+    .unwrap_or_else(|err| panic!("error opening session with new key: {err}"));
+
+    new_client.close().unwrap();
+    test_client.close().unwrap();
+}
@@ -47,3 +47,48 @@ fn rsa_decrypt_oaep_test() {
 
     assert_eq!(decrypted_data.as_slice(), plaintext);
 }
+
+/// Test RSA OAEP decryption with a label and an MGF1 hash which differs from
+/// the OAEP hash
+#[test]
+fn rsa_decrypt_oaep_with_params_test() {
+    let client = crate::get_hsm_client();
+
+    generate_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::Rsa2048,
+        Capability::DECRYPT_OAEP,
+    );
+
+    let rsa_public_key = client.get_public_key(TEST_KEY_ID).unwrap().rsa().unwrap();
+
+    let plaintext = b"Secret message!";
+    let label = "yubihsm.rs label";
+
+    let mut rng = rand_core::OsRng;
+    let ciphertext = rsa_public_key
+        .encrypt(
+            &mut rng,
+            rsa::Oaep::new_with_mgf_hash_and_label::<sha2::Sha384, sha2::Sha256, _>(label),
+            plaintext,
+        )
+        .expect("Failed to encrypt");
+
+    let params = yubihsm::rsa::oaep::Params::new(
+        yubihsm::rsa::oaep::Algorithm::Sha384,
+        yubihsm::rsa::mgf::Algorithm::Sha256,
+        label.as_bytes(),
+    );
+
+    let decrypted_data = client
+        .decrypt_oaep_with_params(TEST_KEY_ID, &params, ciphertext.clone())
+        .unwrap();
+
+    assert_eq!(decrypted_data.as_slice(), plaintext);
+
+    let wrong_label =
+        yubihsm::rsa::oaep::Params::for_algorithm(yubihsm::rsa::oaep::Algorithm::Sha384);
+    assert!(client
+        .decrypt_oaep_with_params(TEST_KEY_ID, &wrong_label, ciphertext)
+        .is_err());
+}
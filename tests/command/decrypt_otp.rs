@@ -0,0 +1,87 @@
+use crate::generate_otp_aead_key;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use yubihsm::{device, otp, Capability};
+
+/// AES key of the test Yubico OTP token
+pub const TEST_OTP_KEY: [u8; otp::KEY_SIZE] = [0x42; otp::KEY_SIZE];
+
+/// Private ID of the test Yubico OTP token
+pub const TEST_OTP_PRIVATE_ID: [u8; otp::PRIVATE_ID_SIZE] = [1, 2, 3, 4, 5, 6];
+
+/// Counters and timestamp for the test OTP
+pub const TEST_OTP_COUNTERS: otp::Counters = otp::Counters {
+    use_counter: 0x0107,
+    session_counter: 3,
+    timestamp_high: 0x12,
+    timestamp_low: 0x3456,
+};
+
+/// Generate an OTP as the test Yubico OTP token would
+pub fn generate_otp(counters: &otp::Counters) -> [u8; otp::OTP_SIZE] {
+    let mut otp = [0u8; otp::OTP_SIZE];
+    otp[..6].copy_from_slice(&TEST_OTP_PRIVATE_ID);
+    otp[6..8].copy_from_slice(&counters.use_counter.to_le_bytes());
+    otp[8..10].copy_from_slice(&counters.timestamp_low.to_le_bytes());
+    otp[10] = counters.timestamp_high;
+    otp[11] = counters.session_counter;
+    otp[12..14].copy_from_slice(&[0xde, 0xad]);
+
+    let crc = !crc16(&otp[..14]);
+    otp[14..].copy_from_slice(&crc.to_le_bytes());
+
+    let mut block = otp.into();
+    aes::Aes128::new(&TEST_OTP_KEY.into()).encrypt_block(&mut block);
+    block.into()
+}
+
+/// CRC16 (ISO 13239) as used by Yubico OTPs
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+
+    for &byte in data {
+        crc ^= u16::from(byte);
+
+        for _ in 0..8 {
+            let carry = crc & 1 != 0;
+            crc >>= 1;
+
+            if carry {
+                crc ^= 0x8408;
+            }
+        }
+    }
+
+    crc
+}
+
+/// Create an AEAD for a token and decrypt OTPs with it
+#[test]
+fn decrypt_otp_test() {
+    let client = crate::get_hsm_client();
+    generate_otp_aead_key(
+        &client,
+        Capability::CREATE_OTP_AEAD | Capability::DECRYPT_OTP,
+    );
+
+    let aead = client
+        .create_otp_aead(crate::TEST_KEY_ID, TEST_OTP_KEY, TEST_OTP_PRIVATE_ID)
+        .unwrap_or_else(|err| panic!("error creating OTP AEAD: {err}"));
+
+    let otp = generate_otp(&TEST_OTP_COUNTERS);
+
+    let counters = client
+        .decrypt_otp(crate::TEST_KEY_ID, &aead, otp)
+        .unwrap_or_else(|err| panic!("error decrypting OTP: {err}"));
+
+    assert_eq!(counters, TEST_OTP_COUNTERS);
+    assert_eq!(counters.timestamp(), 0x12_3456);
+
+    let mut bad_otp = otp;
+    bad_otp[0] ^= 0xff;
+
+    let err = client
+        .decrypt_otp(crate::TEST_KEY_ID, &aead, bad_otp)
+        .expect_err("invalid OTP decrypted");
+
+    assert_eq!(err.device_error(), Some(device::ErrorKind::InvalidOtp));
+}
@@ -0,0 +1,30 @@
+use crate::{generate_asymmetric_key, TEST_KEY_ID};
+use yubihsm::{asymmetric, Capability};
+
+/// Test RSA PKCS#1v1.5 decryption
+#[test]
+fn rsa_decrypt_pkcs1_test() {
+    let client = crate::get_hsm_client();
+
+    generate_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::Rsa2048,
+        Capability::DECRYPT_PKCS,
+    );
+
+    let rsa_public_key = client
+        .get_public_key(TEST_KEY_ID)
+        .unwrap_or_else(|err| panic!("error getting public key: {}", err))
+        .rsa()
+        .unwrap();
+
+    let plaintext = b"Secret message!";
+
+    let mut rng = rand_core::OsRng;
+    let ciphertext = rsa_public_key
+        .encrypt(&mut rng, rsa::Pkcs1v15Encrypt, plaintext)
+        .expect("Failed to encrypt");
+
+    let decrypted_data = client.decrypt_pkcs1(TEST_KEY_ID, ciphertext).unwrap();
+    assert_eq!(decrypted_data.as_slice(), plaintext);
+}
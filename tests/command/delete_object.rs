@@ -1,5 +1,8 @@
-use crate::{generate_asymmetric_key, TEST_KEY_ID};
-use yubihsm::{asymmetric, object, Capability};
+use crate::{generate_asymmetric_key, TEST_DOMAINS, TEST_KEY_ID, TEST_KEY_LABEL, TEST_MESSAGE};
+use yubihsm::{asymmetric, authentication, device, object, Capability, Client, Credentials};
+
+/// Key ID of the authentication key used to test deletion permissions
+const TEST_DELETE_AUTHENTICATION_KEY_ID: object::Id = 102;
 
 /// Delete an object in the YubiHSM 2
 #[test]
@@ -13,12 +16,79 @@ fn delete_object_test() {
     );
 
     // The first request to delete should succeed because the object exists
-    assert!(client
-        .delete_object(TEST_KEY_ID, object::Type::AsymmetricKey)
-        .is_ok());
+    assert_eq!(
+        client
+            .delete_object(TEST_KEY_ID, object::Type::AsymmetricKey)
+            .unwrap(),
+        object::Deletion::Deleted
+    );
+
+    // The second request should report the object as already deleted
+    assert_eq!(
+        client
+            .delete_object(TEST_KEY_ID, object::Type::AsymmetricKey)
+            .unwrap(),
+        object::Deletion::NotFound
+    );
+}
 
-    // The second request to delete should fail because it's already deleted
-    assert!(client
+/// Deleting objects without the capability to do so fails
+#[test]
+fn delete_object_permission_test() {
+    let client = crate::get_hsm_client();
+    let authentication_key = authentication::Key::derive_from_password(TEST_MESSAGE);
+
+    generate_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::Ed25519,
+        Capability::SIGN_EDDSA,
+    );
+
+    client
+        .delete_object(
+            TEST_DELETE_AUTHENTICATION_KEY_ID,
+            object::Type::AuthenticationKey,
+        )
+        .unwrap();
+
+    client
+        .put_authentication_key(
+            TEST_DELETE_AUTHENTICATION_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::DELETE_OPAQUE,
+            Capability::default(),
+            authentication::Algorithm::YubicoAes,
+            authentication_key.clone(),
+        )
+        .unwrap();
+
+    let limited_client = Client::open(
+        client.connector().clone(),
+        Credentials::new(TEST_DELETE_AUTHENTICATION_KEY_ID, authentication_key),
+        true,
+    )
+    .unwrap();
+
+    let err = limited_client
         .delete_object(TEST_KEY_ID, object::Type::AsymmetricKey)
-        .is_err());
+        .unwrap_err();
+
+    assert_eq!(
+        err.device_error(),
+        Some(device::ErrorKind::InsufficientPermissions)
+    );
+
+    limited_client.close().unwrap();
+    drop(limited_client);
+
+    // The key has not been deleted
+    assert!(client.get_public_key(TEST_KEY_ID).is_ok());
+
+    client
+        .delete_object(
+            TEST_DELETE_AUTHENTICATION_KEY_ID,
+            object::Type::AuthenticationKey,
+        )
+        .unwrap();
 }
@@ -0,0 +1,54 @@
+//! ECDH key agreement test
+
+use crate::{generate_asymmetric_key, TEST_KEY_ID};
+use p256::{elliptic_curve::sec1::ToEncodedPoint, NistP256, PublicKey, SecretKey};
+use rand_core::OsRng;
+use yubihsm::{asymmetric, ecdh, Capability};
+
+/// Test ECDH (using NIST P-256)
+#[test]
+fn derive_ecdh_nistp256_test() {
+    let client = crate::get_hsm_client();
+
+    generate_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::EcP256,
+        Capability::DERIVE_ECDH,
+    );
+
+    let hsm_public_key = PublicKey::from_sec1_bytes(
+        client
+            .get_public_key(TEST_KEY_ID)
+            .unwrap()
+            .ecdsa::<NistP256>()
+            .unwrap()
+            .as_bytes(),
+    )
+    .unwrap();
+
+    let peer_secret_key = SecretKey::random(&mut OsRng);
+    let peer_public_key = ecdh::UncompressedPoint::from_bytes(
+        peer_secret_key
+            .public_key()
+            .to_encoded_point(false)
+            .as_bytes(),
+    )
+    .unwrap();
+
+    let shared_secret = client
+        .derive_ecdh(TEST_KEY_ID, peer_public_key)
+        .unwrap_or_else(|err| panic!("error deriving ECDH shared secret: {err}"));
+
+    let expected_point =
+        (hsm_public_key.to_projective() * *peer_secret_key.to_nonzero_scalar()).to_affine();
+
+    assert_eq!(shared_secret.len(), 32);
+    assert_eq!(
+        shared_secret.as_slice(),
+        expected_point
+            .to_encoded_point(false)
+            .x()
+            .unwrap()
+            .as_slice()
+    );
+}
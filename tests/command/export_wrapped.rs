@@ -1,6 +1,6 @@
 use crate::{
     clear_test_key_slot, test_vectors::AESCCM_TEST_VECTORS, TEST_DOMAINS, TEST_EXPORTED_KEY_ID,
-    TEST_EXPORTED_KEY_LABEL, TEST_KEY_ID, TEST_KEY_LABEL,
+    TEST_EXPORTED_KEY_LABEL, TEST_KEY_ID, TEST_KEY_LABEL, TEST_MESSAGE,
 };
 use yubihsm::{asymmetric, object, wrap, Capability};
 
@@ -46,10 +46,18 @@ fn wrap_key_test() {
         )
         .unwrap_or_else(|err| panic!("error generating asymmetric key: {err}"));
 
+    let signature = client
+        .sign_ed25519(TEST_EXPORTED_KEY_ID, TEST_MESSAGE)
+        .unwrap_or_else(|err| panic!("error signing message: {err}"));
+
     let wrap_data = client
         .export_wrapped(TEST_KEY_ID, exported_key_type, TEST_EXPORTED_KEY_ID)
         .unwrap_or_else(|err| panic!("error exporting key: {err}"));
 
+    // Back the wrapped key up as a blob of bytes, and restore it from the blob
+    let wrap_data = wrap::Message::from_vec(wrap_data.into_vec())
+        .unwrap_or_else(|err| panic!("error parsing wrapped key: {err}"));
+
     // Delete the object from the HSM prior to re-importing it
     assert!(client
         .delete_object(TEST_EXPORTED_KEY_ID, exported_key_type)
@@ -77,6 +85,14 @@ fn wrap_key_test() {
         &imported_key_info.label.to_string(),
         TEST_EXPORTED_KEY_LABEL
     );
+
+    // The re-imported key must be the same key (Ed25519 is deterministic)
+    assert_eq!(
+        client
+            .sign_ed25519(TEST_EXPORTED_KEY_ID, TEST_MESSAGE)
+            .unwrap_or_else(|err| panic!("error signing message: {err}")),
+        signature
+    );
 }
 
 #[test]
@@ -49,12 +49,11 @@ fn rsa2048_key_test() {
 }
 
 /// Generate a NIST P-256 key
-#[cfg(not(feature = "mockhsm"))]
 #[test]
 fn nistp256_key_test() {
     let client = crate::get_hsm_client();
     let algorithm = asymmetric::Algorithm::EcP256;
-    let capabilities = Capability::SIGN_EDDSA;
+    let capabilities = Capability::SIGN_ECDSA | Capability::DERIVE_ECDH;
 
     generate_asymmetric_key(&client, algorithm, capabilities);
 
@@ -70,3 +69,27 @@ fn nistp256_key_test() {
     assert_eq!(object_info.origin, object::Origin::Generated);
     assert_eq!(&object_info.label.to_string(), TEST_KEY_LABEL);
 }
+
+/// Capabilities for operations other keys types perform are rejected
+#[test]
+fn unsupported_capabilities_test() {
+    let client = crate::get_hsm_client();
+
+    for (algorithm, capabilities) in [
+        (asymmetric::Algorithm::Ed25519, Capability::SIGN_ECDSA),
+        (asymmetric::Algorithm::EcP256, Capability::SIGN_PSS),
+        (asymmetric::Algorithm::Rsa2048, Capability::SIGN_EDDSA),
+    ] {
+        let err = client
+            .generate_asymmetric_key(
+                TEST_KEY_ID,
+                TEST_KEY_LABEL.into(),
+                TEST_DOMAINS,
+                capabilities | Capability::EXPORTABLE_UNDER_WRAP,
+                algorithm,
+            )
+            .unwrap_err();
+
+        assert_eq!(err.kind(), &yubihsm::client::ErrorKind::ProtocolError);
+    }
+}
@@ -33,6 +33,7 @@ fn wrap_key_test() {
         .unwrap_or_else(|err| panic!("error getting object info: {err}"));
 
     assert_eq!(object_info.capabilities, capabilities);
+    assert_eq!(object_info.delegated_capabilities, delegated_capabilities);
     assert_eq!(object_info.object_id, TEST_KEY_ID);
     assert_eq!(object_info.domains, TEST_DOMAINS);
     assert_eq!(object_info.object_type, object::Type::WrapKey);
@@ -3,8 +3,27 @@
 fn get_audit_logs_test() {
     let client = crate::get_hsm_client();
 
-    // TODO: test audit logging functionality
     client
         .get_log_entries()
         .unwrap_or_else(|err| panic!("error getting logs: {err}"));
 }
+
+/// Drain the audit log, verifying the digest chain across calls
+#[test]
+fn consume_audit_logs_test() {
+    let client = crate::get_hsm_client();
+
+    client.blink_device(1).unwrap();
+
+    let first = client
+        .consume_log_entries()
+        .unwrap_or_else(|err| panic!("error consuming logs: {err}"));
+    let last = first.entries.last().expect("no audit log entries");
+    assert!(first.verify(None).is_ok());
+
+    client.blink_device(1).unwrap();
+
+    let second = client.consume_log_entries().unwrap();
+    assert!(second.entries.iter().all(|entry| entry.item > last.item));
+    assert!(second.verify(Some(&last.digest)).is_ok());
+}
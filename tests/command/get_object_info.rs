@@ -1,5 +1,9 @@
-use crate::DEFAULT_AUTHENTICATION_KEY_LABEL;
+use crate::{
+    generate_asymmetric_key, DEFAULT_AUTHENTICATION_KEY_LABEL, TEST_DOMAINS, TEST_KEY_ID,
+    TEST_KEY_LABEL,
+};
 use yubihsm::{
+    asymmetric,
     authentication::{self, DEFAULT_AUTHENTICATION_KEY_ID},
     object, Capability, Domain,
 };
@@ -30,3 +34,36 @@ fn default_authkey_test() {
         DEFAULT_AUTHENTICATION_KEY_LABEL
     );
 }
+
+/// Get object info on a generated key
+#[test]
+fn generated_key_test() {
+    let client = crate::get_hsm_client();
+
+    generate_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::Ed25519,
+        Capability::SIGN_EDDSA,
+    );
+
+    let object_info = client
+        .get_object_info(TEST_KEY_ID, object::Type::AsymmetricKey)
+        .unwrap_or_else(|err| panic!("error getting object info: {err}"));
+
+    assert_eq!(
+        object_info.handle(),
+        object::Handle::new(TEST_KEY_ID, object::Type::AsymmetricKey)
+    );
+    assert_eq!(object_info.capabilities, Capability::SIGN_EDDSA);
+    assert_eq!(object_info.delegated_capabilities, Capability::default());
+    assert_eq!(object_info.domains, TEST_DOMAINS);
+    assert_eq!(
+        object_info.algorithm.asymmetric(),
+        Some(asymmetric::Algorithm::Ed25519)
+    );
+    assert_eq!(object_info.origin, object::Origin::Generated);
+    assert!(object_info.origin.is_generated());
+    assert!(!object_info.origin.is_wrapped());
+    assert_eq!(object_info.origin.to_string(), "generated");
+    assert_eq!(&object_info.label.to_string(), TEST_KEY_LABEL);
+}
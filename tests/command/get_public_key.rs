@@ -0,0 +1,36 @@
+use crate::{generate_asymmetric_key, TEST_KEY_ID};
+use yubihsm::{asymmetric, Capability};
+
+/// Get typed public keys for each kind of asymmetric key
+#[test]
+fn typed_public_key_test() {
+    let client = crate::get_hsm_client();
+
+    for (algorithm, capabilities) in [
+        (asymmetric::Algorithm::Ed25519, Capability::SIGN_EDDSA),
+        (asymmetric::Algorithm::EcP256, Capability::SIGN_ECDSA),
+        (asymmetric::Algorithm::Rsa2048, Capability::SIGN_PKCS),
+    ] {
+        generate_asymmetric_key(&client, algorithm, capabilities);
+
+        let public_key = client.get_public_key(TEST_KEY_ID).unwrap();
+        let typed_public_key = client
+            .get_typed_public_key(TEST_KEY_ID)
+            .unwrap_or_else(|err| panic!("error getting typed public key: {err}"));
+
+        assert_eq!(typed_public_key.algorithm(), algorithm);
+
+        match typed_public_key {
+            asymmetric::TypedPublicKey::Rsa { key, .. } => {
+                assert_eq!(Some(key), public_key.rsa());
+            }
+            asymmetric::TypedPublicKey::Ec { point, .. } => {
+                let expected = public_key.ecdsa::<p256::NistP256>().unwrap();
+                assert_eq!(point, expected.as_bytes());
+            }
+            asymmetric::TypedPublicKey::Ed25519(key) => {
+                assert_eq!(Some(key), public_key.ed25519());
+            }
+        }
+    }
+}
@@ -1,9 +1,12 @@
 //! Integration tests for YubiHSM 2 commands
 
 pub mod blink_device;
-#[cfg(not(feature = "mockhsm"))]
+pub mod change_authentication_key;
 pub mod decrypt_oaep;
+pub mod decrypt_otp;
+pub mod decrypt_pkcs1;
 pub mod delete_object;
+pub mod derive_ecdh;
 pub mod device_info;
 pub mod export_wrapped;
 pub mod generate_asymmetric_key;
@@ -13,15 +16,17 @@ pub mod get_log_entries;
 pub mod get_object_info;
 pub mod get_option;
 pub mod get_pseudo_random;
+pub mod get_public_key;
 pub mod get_storage_info;
 pub mod list_objects;
 pub mod put_asymmetric_key;
 pub mod put_authentication_key;
 pub mod put_opaque;
+pub mod put_wrap_key;
 #[cfg(feature = "mockhsm")]
 pub mod reset_device;
+pub mod rewrap_otp_aead;
 pub mod set_option;
-#[cfg(not(feature = "mockhsm"))]
 pub mod sign_attestation_certificate;
 #[cfg(not(feature = "mockhsm"))]
 pub mod sign_ecdsa;
@@ -1,3 +1,4 @@
+use p256::elliptic_curve::sec1::ToEncodedPoint;
 use yubihsm::{asymmetric, object, Capability};
 
 use crate::test_vectors::ED25519_TEST_VECTORS;
@@ -25,3 +26,73 @@ fn ed25519_key_test() {
     assert_eq!(object_info.origin, object::Origin::Imported);
     assert_eq!(&object_info.label.to_string(), TEST_KEY_LABEL);
 }
+
+/// Put a NIST P-256 key and check the HSM derives the same public key
+#[test]
+fn nistp256_key_test() {
+    let client = crate::get_hsm_client();
+    let secret_key = p256::SecretKey::random(&mut rand_core::OsRng);
+
+    put_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::EcP256,
+        Capability::SIGN_ECDSA,
+        secret_key.to_bytes().as_slice(),
+    );
+
+    let public_key = client
+        .get_public_key(TEST_KEY_ID)
+        .unwrap()
+        .ecdsa::<p256::NistP256>()
+        .unwrap();
+
+    assert_eq!(
+        public_key,
+        secret_key.public_key().as_affine().to_encoded_point(false)
+    );
+}
+
+/// Malformed keys are rejected before being sent to the HSM
+#[test]
+fn invalid_key_test() {
+    let client = crate::get_hsm_client();
+
+    let mut rsa_key = [0xffu8; 256];
+    rsa_key[255] = 0xfe;
+
+    for (algorithm, capabilities, key) in [
+        (
+            asymmetric::Algorithm::EcP256,
+            Capability::SIGN_ECDSA,
+            &[0u8; 32][..],
+        ),
+        (
+            asymmetric::Algorithm::EcP256,
+            Capability::SIGN_ECDSA,
+            &[1u8; 31][..],
+        ),
+        (
+            asymmetric::Algorithm::Rsa2048,
+            Capability::SIGN_PKCS,
+            &rsa_key[..],
+        ),
+        (
+            asymmetric::Algorithm::Ed25519,
+            Capability::SIGN_PSS,
+            ED25519_TEST_VECTORS[0].sk,
+        ),
+    ] {
+        let err = client
+            .put_asymmetric_key(
+                TEST_KEY_ID,
+                TEST_KEY_LABEL.into(),
+                TEST_DOMAINS,
+                capabilities,
+                algorithm,
+                key,
+            )
+            .unwrap_err();
+
+        assert_eq!(err.kind(), &yubihsm::client::ErrorKind::ProtocolError);
+    }
+}
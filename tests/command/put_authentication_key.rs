@@ -1,4 +1,4 @@
-use yubihsm::{authentication, object, Capability};
+use yubihsm::{authentication, client, object, Capability};
 
 use crate::{clear_test_key_slot, TEST_DOMAINS, TEST_KEY_ID, TEST_KEY_LABEL, TEST_MESSAGE};
 
@@ -40,3 +40,29 @@ fn put_authentication_key() {
     assert_eq!(object_info.origin, object::Origin::Imported);
     assert_eq!(&object_info.label.to_string(), TEST_KEY_LABEL);
 }
+
+/// Reject authentication keys whose size doesn't match the algorithm
+#[test]
+fn put_authentication_key_with_wrong_size() {
+    let client = crate::get_hsm_client();
+
+    clear_test_key_slot(&client, object::Type::AuthenticationKey);
+
+    let err = client
+        .put_authentication_key(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::all(),
+            Capability::all(),
+            authentication::Algorithm::YubicoAes,
+            authentication::Key::random_aes256(),
+        )
+        .unwrap_err();
+
+    assert_eq!(*err.kind(), client::ErrorKind::ProtocolError);
+
+    assert!(client
+        .get_object_info(TEST_KEY_ID, object::Type::AuthenticationKey)
+        .is_err());
+}
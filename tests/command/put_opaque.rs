@@ -1,6 +1,9 @@
-use yubihsm::{object, opaque, Capability};
+use yubihsm::{asymmetric, client, command, object, opaque, Capability};
 
-use crate::{clear_test_key_slot, TEST_DOMAINS, TEST_KEY_ID, TEST_KEY_LABEL, TEST_MESSAGE};
+use crate::{
+    clear_test_key_slot, generate_asymmetric_key, TEST_DOMAINS, TEST_KEY_ID, TEST_KEY_LABEL,
+    TEST_MESSAGE,
+};
 
 /// Put an opaque object and read it back
 #[test]
@@ -28,3 +31,104 @@ fn opaque_object_test() {
 
     assert_eq!(opaque_data, TEST_MESSAGE);
 }
+
+/// Store the certificate for a key alongside it and read it back
+#[test]
+fn certificate_test() {
+    let client = crate::get_hsm_client();
+
+    generate_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::EcP256,
+        Capability::SIGN_ECDSA,
+    );
+
+    let certificate = client
+        .sign_attestation_certificate(TEST_KEY_ID, None)
+        .unwrap_or_else(|err| panic!("error getting attestation certificate: {err}"))
+        .into_vec();
+
+    clear_test_key_slot(&client, object::Type::Opaque);
+
+    client
+        .put_certificate(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::default(),
+            certificate.clone(),
+        )
+        .unwrap_or_else(|err| panic!("error putting certificate: {err}"));
+
+    let info = client
+        .get_object_info(TEST_KEY_ID, object::Type::Opaque)
+        .unwrap();
+
+    assert_eq!(
+        info.algorithm.opaque(),
+        Some(opaque::Algorithm::X509Certificate)
+    );
+
+    assert_eq!(client.get_certificate(TEST_KEY_ID).unwrap(), certificate);
+
+    // The key remains usable under the same object ID
+    assert!(client.get_public_key(TEST_KEY_ID).is_ok());
+
+    let err = client
+        .put_certificate(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::default(),
+            TEST_MESSAGE,
+        )
+        .unwrap_err();
+
+    assert_eq!(*err.kind(), client::ErrorKind::ProtocolError);
+}
+
+/// Opaque data which isn't a certificate can't be read as one
+#[test]
+fn certificate_wrong_algorithm_test() {
+    let client = crate::get_hsm_client();
+
+    clear_test_key_slot(&client, object::Type::Opaque);
+
+    client
+        .put_opaque(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::default(),
+            opaque::Algorithm::Data,
+            TEST_MESSAGE,
+        )
+        .unwrap();
+
+    let err = client.get_certificate(TEST_KEY_ID).unwrap_err();
+    assert_eq!(*err.kind(), client::ErrorKind::ProtocolError);
+}
+
+/// Opaque objects too large to fit in a single message are rejected before
+/// being sent to the HSM
+#[test]
+fn oversized_opaque_object_test() {
+    let client = crate::get_hsm_client();
+
+    let err = client
+        .put_opaque(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            Capability::default(),
+            opaque::Algorithm::Data,
+            vec![0u8; command::MAX_SESSION_COMMAND_DATA_SIZE],
+        )
+        .unwrap_err();
+
+    assert_eq!(*err.kind(), client::ErrorKind::MessageTooLong);
+
+    // The session remains usable
+    let data = vec![0x42u8; command::MAX_SESSION_COMMAND_DATA_SIZE];
+    assert_eq!(client.echo(data.clone()).unwrap(), data);
+}
@@ -0,0 +1,70 @@
+use crate::{
+    clear_test_key_slot, test_vectors::AESCCM_TEST_VECTORS, TEST_DOMAINS, TEST_EXPORTED_KEY_ID,
+    TEST_EXPORTED_KEY_LABEL, TEST_KEY_ID, TEST_KEY_LABEL,
+};
+use yubihsm::{asymmetric, object, wrap, Capability};
+
+/// Put a known wrap key into the HSM, and ensure only objects with delegated
+/// capabilities can be exported under it
+#[test]
+fn put_wrap_key_test() {
+    let client = crate::get_hsm_client();
+    let algorithm = wrap::Algorithm::Aes128Ccm;
+    let capabilities = Capability::EXPORT_WRAPPED | Capability::IMPORT_WRAPPED;
+    let delegated_capabilities = Capability::SIGN_EDDSA | Capability::EXPORTABLE_UNDER_WRAP;
+
+    clear_test_key_slot(&client, object::Type::WrapKey);
+
+    let key_id = client
+        .put_wrap_key(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            capabilities,
+            delegated_capabilities,
+            algorithm,
+            AESCCM_TEST_VECTORS[0].key,
+        )
+        .unwrap_or_else(|err| panic!("error putting wrap key: {err}"));
+
+    assert_eq!(key_id, TEST_KEY_ID);
+
+    let object_info = client
+        .get_object_info(TEST_KEY_ID, object::Type::WrapKey)
+        .unwrap_or_else(|err| panic!("error getting object info: {err}"));
+
+    assert_eq!(object_info.capabilities, capabilities);
+    assert_eq!(object_info.delegated_capabilities, delegated_capabilities);
+    assert_eq!(object_info.algorithm, algorithm.into());
+    assert_eq!(object_info.origin, object::Origin::Imported);
+
+    for (exported_key_capabilities, exportable) in [
+        (delegated_capabilities, true),
+        (
+            Capability::SIGN_EDDSA
+                | Capability::EXPORTABLE_UNDER_WRAP
+                | Capability::SIGN_ATTESTATION_CERTIFICATE,
+            false,
+        ),
+    ] {
+        let _ = client.delete_object(TEST_EXPORTED_KEY_ID, object::Type::AsymmetricKey);
+
+        client
+            .generate_asymmetric_key(
+                TEST_EXPORTED_KEY_ID,
+                TEST_EXPORTED_KEY_LABEL.into(),
+                TEST_DOMAINS,
+                exported_key_capabilities,
+                asymmetric::Algorithm::Ed25519,
+            )
+            .unwrap_or_else(|err| panic!("error generating asymmetric key: {err}"));
+
+        let result = client.export_wrapped(
+            TEST_KEY_ID,
+            object::Type::AsymmetricKey,
+            TEST_EXPORTED_KEY_ID,
+        );
+
+        assert_eq!(result.is_ok(), exportable, "{exported_key_capabilities:?}");
+    }
+}
@@ -0,0 +1,59 @@
+use super::decrypt_otp::{generate_otp, TEST_OTP_COUNTERS, TEST_OTP_KEY, TEST_OTP_PRIVATE_ID};
+use crate::{
+    generate_otp_aead_key, TEST_DOMAINS, TEST_EXPORTED_KEY_ID, TEST_KEY_ID, TEST_KEY_LABEL,
+};
+use yubihsm::{object, otp, Capability};
+
+/// Rewrap AEADs from one OTP AEAD key to another
+#[test]
+fn rewrap_otp_aead_test() {
+    let client = crate::get_hsm_client();
+    let capabilities = Capability::CREATE_OTP_AEAD
+        | Capability::RANDOMIZE_OTP_AEAD
+        | Capability::REWRAP_FROM_OTP_AEAD_KEY
+        | Capability::REWRAP_TO_OTP_AEAD_KEY
+        | Capability::DECRYPT_OTP;
+
+    generate_otp_aead_key(&client, capabilities);
+
+    let _ = client.delete_object(TEST_EXPORTED_KEY_ID, object::Type::OtpAeadKey);
+
+    client
+        .put_otp_aead_key(
+            TEST_EXPORTED_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            capabilities,
+            otp::Algorithm::Aes256,
+            0x0506_0708,
+            [0x24; 32],
+        )
+        .unwrap_or_else(|err| panic!("error putting OTP AEAD key: {err}"));
+
+    let aead = client
+        .create_otp_aead(TEST_KEY_ID, TEST_OTP_KEY, TEST_OTP_PRIVATE_ID)
+        .unwrap_or_else(|err| panic!("error creating OTP AEAD: {err}"));
+
+    let rewrapped = client
+        .rewrap_otp_aead(TEST_KEY_ID, TEST_EXPORTED_KEY_ID, &aead)
+        .unwrap_or_else(|err| panic!("error rewrapping OTP AEAD: {err}"));
+
+    assert_ne!(aead, rewrapped);
+
+    let otp = generate_otp(&TEST_OTP_COUNTERS);
+
+    let counters = client
+        .decrypt_otp(TEST_EXPORTED_KEY_ID, &rewrapped, otp)
+        .unwrap_or_else(|err| panic!("error decrypting OTP: {err}"));
+
+    assert_eq!(counters, TEST_OTP_COUNTERS);
+    assert!(client.decrypt_otp(TEST_KEY_ID, &rewrapped, otp).is_err());
+
+    let randomized = client
+        .randomize_otp_aead(TEST_KEY_ID)
+        .unwrap_or_else(|err| panic!("error randomizing OTP AEAD: {err}"));
+
+    assert!(client
+        .rewrap_otp_aead(TEST_KEY_ID, TEST_EXPORTED_KEY_ID, &randomized)
+        .is_ok());
+}
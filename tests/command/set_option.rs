@@ -20,23 +20,15 @@ fn command_audit_options_test() {
 }
 
 /// Configure the "force audit" option setting
-// TODO(tarcieri): clear audit log when tests start. See notes on PR#185
 #[test]
-#[ignore]
 fn force_audit_option_test() {
     let client = crate::get_hsm_client();
 
     // Make sure we've consumed the latest log data or else forced auditing
     // will prevent the tests from completing
-    let audit_logs = client
-        .get_log_entries()
-        .unwrap_or_else(|err| panic!("error getting audit logs: {err}"));
-
-    if let Some(last_entry) = audit_logs.entries.last() {
-        client
-            .set_log_index(last_entry.item)
-            .unwrap_or_else(|err| panic!("error setting audit log position: {err}"));
-    }
+    client
+        .consume_log_entries()
+        .unwrap_or_else(|err| panic!("error consuming audit logs: {err}"));
 
     for audit_option in &[AuditOption::On, AuditOption::Off] {
         client
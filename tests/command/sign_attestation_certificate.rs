@@ -1,4 +1,5 @@
 use crate::{generate_asymmetric_key, EC_P256_PUBLIC_KEY_SIZE, TEST_KEY_ID};
+use x509_cert::der::Decode;
 use yubihsm::{asymmetric, Capability};
 
 /// Generate an attestation about a key in the HSM
@@ -16,6 +17,22 @@ fn attest_asymmetric_test() {
         .sign_attestation_certificate(TEST_KEY_ID, None)
         .unwrap_or_else(|err| panic!("error getting attestation certificate: {}", err));
 
-    // TODO: more tests, e.g. test that the certificate validates
     assert!(certificate.len() > EC_P256_PUBLIC_KEY_SIZE);
+
+    let certificate = x509_cert::Certificate::from_der(certificate.as_slice())
+        .unwrap_or_else(|err| panic!("error parsing attestation certificate: {}", err));
+
+    let public_key = client
+        .get_public_key(TEST_KEY_ID)
+        .unwrap_or_else(|err| panic!("error getting public key: {}", err));
+
+    // SEC1 uncompressed point: 0x04 tag followed by the coordinates
+    let subject_public_key = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+        .raw_bytes();
+
+    assert_eq!(subject_public_key[0], 0x04);
+    assert_eq!(&subject_public_key[1..], public_key.as_ref());
 }
@@ -63,3 +63,27 @@ fn generated_key_test() {
             .is_ok()
     );
 }
+
+/// Test batched Ed25519 signing against RFC 8032 test vectors
+#[test]
+fn batch_test() {
+    let client = crate::get_hsm_client();
+    let vector = &ED25519_TEST_VECTORS[2];
+
+    put_asymmetric_key(
+        &client,
+        asymmetric::Algorithm::Ed25519,
+        Capability::SIGN_EDDSA,
+        vector.sk,
+    );
+
+    let signatures = client
+        .sign_ed25519_batch(TEST_KEY_ID, vec![vector.msg; 20])
+        .unwrap_or_else(|err| panic!("error performing Ed25519 signatures: {err}"));
+
+    assert_eq!(signatures.len(), 20);
+
+    for signature in signatures {
+        assert_eq!(vector.sig, &signature.to_bytes());
+    }
+}
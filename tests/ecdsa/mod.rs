@@ -7,7 +7,7 @@ use ::ecdsa::{
         sec1::{self, FromEncodedPoint, ToEncodedPoint},
         AffinePoint, CurveArithmetic, FieldBytesSize, PrimeCurve,
     },
-    signature::{Keypair, Verifier},
+    signature::{digest::Digest, Keypair, Verifier},
 };
 use spki::SubjectPublicKeyInfoOwned;
 use std::{str::FromStr, time::Duration};
@@ -19,13 +19,13 @@ use x509_cert::{
 };
 use yubihsm::{
     asymmetric::signature::Signer as _,
-    ecdsa::{self, algorithm::CurveAlgorithm, NistP256},
+    ecdsa::{self, algorithm::CurveAlgorithm, NistP256, NistP384},
     object, Client,
 };
 
 #[cfg(feature = "secp256k1")]
 use {
-    ::ecdsa::signature::{digest::Digest, DigestSigner, DigestVerifier},
+    ::ecdsa::signature::{DigestSigner, DigestVerifier},
     yubihsm::ecdsa::Secp256k1,
 };
 
@@ -82,6 +82,41 @@ fn ecdsa_nistp256_sign_test() {
     assert!(verify_key.verify(TEST_MESSAGE, &signature).is_ok());
 }
 
+#[test]
+fn ecdsa_nistp384_sign_test() {
+    let signer = create_signer::<NistP384>(205);
+    let verify_key = p384::ecdsa::VerifyingKey::from_encoded_point(signer.public_key()).unwrap();
+
+    let signature: ecdsa::Signature<NistP384> = signer.sign(TEST_MESSAGE);
+    assert!(verify_key.verify(TEST_MESSAGE, &signature).is_ok());
+}
+
+#[test]
+fn ecdsa_nistp384_sign_fixed_test() {
+    let key_id = 206;
+    let client = crate::get_hsm_client();
+    create_yubihsm_key(&client, key_id, yubihsm::asymmetric::Algorithm::EcP384);
+
+    let public_key = client.get_public_key(key_id).unwrap();
+    let verify_key =
+        p384::ecdsa::VerifyingKey::from_encoded_point(&public_key.ecdsa::<NistP384>().unwrap())
+            .unwrap();
+
+    let digest = sha2::Sha384::digest(TEST_MESSAGE);
+    let signature = client
+        .sign_ecdsa_prehash_fixed(
+            key_id,
+            yubihsm::asymmetric::Algorithm::EcP384,
+            digest.as_slice(),
+        )
+        .unwrap();
+
+    assert_eq!(signature.len(), 96);
+
+    let signature = ecdsa::Signature::<NistP384>::from_slice(&signature).unwrap();
+    assert!(verify_key.verify(TEST_MESSAGE, &signature).is_ok());
+}
+
 #[cfg(feature = "secp256k1")]
 #[test]
 fn ecdsa_secp256k1_sign_test() {
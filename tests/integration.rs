@@ -2,7 +2,7 @@
 
 use once_cell::sync::Lazy;
 use std::sync::{Mutex, MutexGuard};
-use yubihsm::{asymmetric, device, object, Capability, Client, Connector, Domain};
+use yubihsm::{asymmetric, device, object, otp, Capability, Client, Connector, Domain};
 
 /// Integration tests for individual YubiHSM 2 commands
 mod command;
@@ -34,6 +34,9 @@ const DEFAULT_AUTHENTICATION_KEY_LABEL: &str = "DEFAULT AUTHKEY CHANGE THIS ASAP
 /// Label to use for the exported test
 const TEST_EXPORTED_KEY_LABEL: &str = "yubihsm.rs exported test key";
 
+/// Nonce ID to use for the test OTP AEAD key
+const TEST_OTP_NONCE_ID: u32 = 0x0102_0304;
+
 /// Domain to use for all tests
 const TEST_DOMAINS: Domain = Domain::DOM1;
 
@@ -71,7 +74,7 @@ pub fn create_hsm_connector() -> Connector {
     return create_mockhsm_connector();
 
     // USB has second highest priority when testing
-    #[cfg(feature = "usb")]
+    #[cfg(any(feature = "usb", feature = "usb-nusb"))]
     return create_usb_connector();
 
     // HTTP has lowest priority when testing
@@ -91,7 +94,7 @@ pub fn create_http_connector() -> Connector {
 }
 
 /// Connect to the HSM via USB
-#[cfg(feature = "usb")]
+#[cfg(any(feature = "usb", feature = "usb-nusb"))]
 pub fn create_usb_connector() -> Connector {
     Connector::usb(&Default::default())
 }
@@ -107,14 +110,12 @@ pub fn create_mockhsm_connector() -> Connector {
 pub fn clear_test_key_slot(client: &Client, object_type: object::Type) {
     println!("clearing test key slot: {object_type:?} {TEST_KEY_ID}");
 
-    // Delete the key in TEST_KEY_ID slot it exists (we use it for testing)
-    if let Err(e) = client.delete_object(TEST_KEY_ID, object_type) {
-        // Ignore errors for nonexistent objects. We're here to make sure the
-        // slot is clear, so it's irrelevant if there was no obj to begin with
-        if e.device_error() != Some(device::ErrorKind::ObjectNotFound) {
-            panic!("error clearing test key: {e}");
-        }
-    }
+    // Delete the key in TEST_KEY_ID slot it exists (we use it for testing).
+    // We're here to make sure the slot is clear, so it's irrelevant if there
+    // was no obj to begin with
+    client
+        .delete_object(TEST_KEY_ID, object_type)
+        .unwrap_or_else(|e| panic!("error clearing test key: {e}"));
 
     // Ensure the object does not exist
     match client.get_object_info(TEST_KEY_ID, object_type) {
@@ -147,6 +148,24 @@ pub fn generate_asymmetric_key(
     }
 }
 
+/// Create an OTP AEAD key for use in a test
+pub fn generate_otp_aead_key(client: &Client, capabilities: Capability) {
+    clear_test_key_slot(client, object::Type::OtpAeadKey);
+
+    let key_id = client
+        .generate_otp_aead_key(
+            TEST_KEY_ID,
+            TEST_KEY_LABEL.into(),
+            TEST_DOMAINS,
+            capabilities,
+            otp::Algorithm::Aes128,
+            TEST_OTP_NONCE_ID,
+        )
+        .unwrap_or_else(|err| panic!("error generating OTP AEAD key: {err}"));
+
+    assert_eq!(key_id, TEST_KEY_ID);
+}
+
 /// Put an asymmetric private key into the HSM
 pub fn put_asymmetric_key<T: Into<Vec<u8>>>(
     client: &Client,
@@ -256,6 +256,35 @@ fn rsa_raw_pkcs1_sha256_sign_test() {
         .is_ok());
 }
 
+#[test]
+fn rsa_raw_pkcs1_sha384_sha512_sign_test() {
+    let client = crate::get_hsm_client();
+    create_yubihsm_key(&client, 228, yubihsm::asymmetric::Algorithm::Rsa2048);
+    let public_key = client.get_public_key(228).unwrap().rsa().unwrap();
+
+    let signature = client
+        .sign_rsa_pkcs1v15_sha384(228, TEST_MESSAGE)
+        .expect("sign message");
+    let verifying_key = ::rsa::pkcs1v15::VerifyingKey::<sha2::Sha384>::new(public_key.clone());
+    assert!(verifying_key
+        .verify(
+            TEST_MESSAGE,
+            &::rsa::pkcs1v15::Signature::try_from(signature.as_slice()).unwrap()
+        )
+        .is_ok());
+
+    let signature = client
+        .sign_rsa_pkcs1v15_sha512(228, TEST_MESSAGE)
+        .expect("sign message");
+    let verifying_key = ::rsa::pkcs1v15::VerifyingKey::<sha2::Sha512>::new(public_key);
+    assert!(verifying_key
+        .verify(
+            TEST_MESSAGE,
+            &::rsa::pkcs1v15::Signature::try_from(signature.as_slice()).unwrap()
+        )
+        .is_ok());
+}
+
 #[test]
 fn rsa_raw_pss_sha256_sign_test() {
     let client = crate::get_hsm_client();
@@ -273,3 +302,28 @@ fn rsa_raw_pss_sha256_sign_test() {
         )
         .is_ok());
 }
+
+#[test]
+fn rsa_raw_pss_params_sign_test() {
+    use sha2::Digest;
+
+    let client = crate::get_hsm_client();
+    create_yubihsm_key(&client, 229, yubihsm::asymmetric::Algorithm::Rsa2048);
+
+    let salt_len = 20;
+    let params = pss::Params::new(yubihsm::rsa::mgf::Algorithm::Sha384, salt_len);
+    let digest = sha2::Sha384::digest(TEST_MESSAGE);
+
+    let signature = client
+        .sign_rsa_pss_prehash(229, params, &digest)
+        .expect("sign digest");
+    let public_key = client.get_public_key(229).unwrap().rsa().unwrap();
+    let verifying_key =
+        ::rsa::pss::VerifyingKey::<sha2::Sha384>::new_with_salt_len(public_key, salt_len.into());
+    assert!(verifying_key
+        .verify(
+            TEST_MESSAGE,
+            &::rsa::pss::Signature::try_from(signature.as_slice()).unwrap()
+        )
+        .is_ok());
+}
@@ -18,3 +18,60 @@ pub const HMAC_SHA256_TEST_VECTORS: &[HMACTestVector] = &[
         tag: b"\x82\x55\x8A\x38\x9A\x44\x3C\x0E\xA4\xCC\x81\x98\x99\xF2\x08\x3A\x85\xF0\xFA\xA3\xE5\x78\xF8\x07\x7A\x2E\x3F\xF4\x67\x29\x66\x5B"
     },
 ];
+
+/// HMAC-SHA-1 test vectors (from RFC 2202, converted to Rust bytestring literals)
+pub const HMAC_SHA1_TEST_VECTORS: &[HMACTestVector] = &[
+    HMACTestVector {
+        key: b"\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B",
+        msg: b"\x48\x69\x20\x54\x68\x65\x72\x65",
+        tag: b"\xB6\x17\x31\x86\x55\x05\x72\x64\xE2\x8B\xC0\xB6\xFB\x37\x8C\x8E\xF1\x46\xBE\x00"
+    },
+    HMACTestVector {
+        key: b"\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA",
+        msg: b"\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD",
+        tag: b"\x12\x5D\x73\x42\xB9\xAC\x11\xCD\x91\xA3\x9A\xF4\x8A\xA1\x7B\x4F\x63\xF1\x75\xD3"
+    },
+    HMACTestVector {
+        key: b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19",
+        msg: b"\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD",
+        tag: b"\x4C\x90\x07\xF4\x02\x62\x50\xC6\xBC\x84\x14\xF9\xBF\x50\xC8\x6C\x2D\x72\x35\xDA"
+    },
+];
+
+/// HMAC-SHA-384 test vectors (from RFC 4231, converted to Rust bytestring literals)
+pub const HMAC_SHA384_TEST_VECTORS: &[HMACTestVector] = &[
+    HMACTestVector {
+        key: b"\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B",
+        msg: b"\x48\x69\x20\x54\x68\x65\x72\x65",
+        tag: b"\xAF\xD0\x39\x44\xD8\x48\x95\x62\x6B\x08\x25\xF4\xAB\x46\x90\x7F\x15\xF9\xDA\xDB\xE4\x10\x1E\xC6\x82\xAA\x03\x4C\x7C\xEB\xC5\x9C\xFA\xEA\x9E\xA9\x07\x6E\xDE\x7F\x4A\xF1\x52\xE8\xB2\xFA\x9C\xB6"
+    },
+    HMACTestVector {
+        key: b"\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA",
+        msg: b"\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD",
+        tag: b"\x88\x06\x26\x08\xD3\xE6\xAD\x8A\x0A\xA2\xAC\xE0\x14\xC8\xA8\x6F\x0A\xA6\x35\xD9\x47\xAC\x9F\xEB\xE8\x3E\xF4\xE5\x59\x66\x14\x4B\x2A\x5A\xB3\x9D\xC1\x38\x14\xB9\x4E\x3A\xB6\xE1\x01\xA3\x4F\x27"
+    },
+    HMACTestVector {
+        key: b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19",
+        msg: b"\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD",
+        tag: b"\x3E\x8A\x69\xB7\x78\x3C\x25\x85\x19\x33\xAB\x62\x90\xAF\x6C\xA7\x7A\x99\x81\x48\x08\x50\x00\x9C\xC5\x57\x7C\x6E\x1F\x57\x3B\x4E\x68\x01\xDD\x23\xC4\xA7\xD6\x79\xCC\xF8\xA3\x86\xC6\x74\xCF\xFB"
+    },
+];
+
+/// HMAC-SHA-512 test vectors (from RFC 4231, converted to Rust bytestring literals)
+pub const HMAC_SHA512_TEST_VECTORS: &[HMACTestVector] = &[
+    HMACTestVector {
+        key: b"\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B\x0B",
+        msg: b"\x48\x69\x20\x54\x68\x65\x72\x65",
+        tag: b"\x87\xAA\x7C\xDE\xA5\xEF\x61\x9D\x4F\xF0\xB4\x24\x1A\x1D\x6C\xB0\x23\x79\xF4\xE2\xCE\x4E\xC2\x78\x7A\xD0\xB3\x05\x45\xE1\x7C\xDE\xDA\xA8\x33\xB7\xD6\xB8\xA7\x02\x03\x8B\x27\x4E\xAE\xA3\xF4\xE4\xBE\x9D\x91\x4E\xEB\x61\xF1\x70\x2E\x69\x6C\x20\x3A\x12\x68\x54"
+    },
+    HMACTestVector {
+        key: b"\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA",
+        msg: b"\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD\xDD",
+        tag: b"\xFA\x73\xB0\x08\x9D\x56\xA2\x84\xEF\xB0\xF0\x75\x6C\x89\x0B\xE9\xB1\xB5\xDB\xDD\x8E\xE8\x1A\x36\x55\xF8\x3E\x33\xB2\x27\x9D\x39\xBF\x3E\x84\x82\x79\xA7\x22\xC8\x06\xB4\x85\xA4\x7E\x67\xC8\x07\xB9\x46\xA3\x37\xBE\xE8\x94\x26\x74\x27\x88\x59\xE1\x32\x92\xFB"
+    },
+    HMACTestVector {
+        key: b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19",
+        msg: b"\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD\xCD",
+        tag: b"\xB0\xBA\x46\x56\x37\x45\x8C\x69\x90\xE5\xA8\xC5\xF6\x1D\x4A\xF7\xE5\x76\xD9\x7F\xF9\x4B\x87\x2D\xE7\x6F\x80\x50\x36\x1E\xE3\xDB\xA9\x1C\xA5\xC1\x1A\xA2\x5E\xB4\xD6\x79\x27\x5C\xC5\x78\x80\x63\xA5\xF1\x97\x41\x12\x0C\x4F\x2D\xE2\xAD\xEB\xEB\x10\xA2\x98\xDD"
+    },
+];
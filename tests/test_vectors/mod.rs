@@ -11,7 +11,10 @@ mod hmac;
 
 pub use self::aesccm::AESCCM_TEST_VECTORS;
 pub use self::ed25519::ED25519_TEST_VECTORS;
-pub use self::hmac::HMAC_SHA256_TEST_VECTORS;
+pub use self::hmac::{
+    HMAC_SHA1_TEST_VECTORS, HMAC_SHA256_TEST_VECTORS, HMAC_SHA384_TEST_VECTORS,
+    HMAC_SHA512_TEST_VECTORS,
+};
 
 /// Authenticated encryption test vector (presently specialized for AES-CCM)
 #[allow(dead_code)]